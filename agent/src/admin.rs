@@ -0,0 +1,97 @@
+// admin接口：只读地导出各服务当前的配置、拓扑、后端状态以及dns缓存
+use context::Quadruple;
+use discovery::TopologyReadGuard;
+use endpoint::{Inspect, Topology as _};
+use serde_json::{Value, json};
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::service::Topology;
+
+// key为quadruple的name
+type Services = HashMap<String, (Quadruple, TopologyReadGuard<Topology>)>;
+static SERVICES: Mutex<Option<Services>> = Mutex::new(None);
+
+// 服务初始化完成后注册，退出时取消注册
+pub(crate) fn register(quard: &Quadruple, top: &TopologyReadGuard<Topology>) {
+    let mut services = SERVICES.lock().expect("admin services");
+    let services = services.get_or_insert_with(Default::default);
+    services.insert(quard.name(), (quard.clone(), top.clone()));
+}
+pub(crate) fn unregister(quard: &Quadruple) {
+    if let Some(services) = SERVICES.lock().expect("admin services").as_mut() {
+        services.remove(&quard.name());
+    }
+}
+
+pub(crate) fn services() -> Value {
+    let services = SERVICES.lock().expect("admin services");
+    let mut all: Vec<_> = services.iter().flat_map(|s| s.values()).collect();
+    all.sort_by(|a, b| a.0.cmp(&b.0));
+    let all: Vec<Value> = all
+        .into_iter()
+        .map(|(quard, top)| {
+            json!({
+                "name": quard.name(),
+                "service": quard.service(),
+                "family": quard.family(),
+                "protocol": quard.protocol(),
+                "endpoint": quard.endpoint(),
+                "address": quard.address(),
+                "tls": quard.tls(),
+                "signature": discovery::signature(quard.service()),
+                "inited": top.inited(),
+                "version": top.version(),
+                "topology": top.get().inspect(),
+                "rejected": rejected(quard.service()),
+            })
+        })
+        .collect();
+    Value::Array(all)
+}
+
+// 最后一次被拒绝的配置，之后有配置校验通过时为null
+fn rejected(service: &str) -> Value {
+    match discovery::rejected(service) {
+        Some(r) => json!({ "error": r.error, "cfg": r.cfg, "at": r.at }),
+        None => Value::Null,
+    }
+}
+
+// key在服务当前拓扑中的路由信息，service可以是quadruple的name或者service
+pub(crate) fn route(service: &str, key: &str) -> Option<Value> {
+    let services = SERVICES.lock().expect("admin services");
+    let services = services.as_ref()?;
+    let (_, top) = services
+        .get(service)
+        .or_else(|| services.values().find(|(q, _)| q.service() == service))?;
+    Some(top.get().route(key.as_bytes()))
+}
+
+fn snapshot() -> discovery::Snapshot {
+    let ctx = context::get();
+    discovery::Snapshot::new(ctx.snapshot_path.to_string(), ctx.snapshot_history)
+}
+
+pub(crate) async fn snapshots(service: &str) -> Value {
+    json!({ "versions": snapshot().versions(service).await })
+}
+
+// 历史版本不存在时返回false
+pub(crate) async fn rollback(service: &str, version: u64) -> bool {
+    let exists = snapshot().versions(service).await.contains(&version);
+    if exists {
+        log::info!("rollback {} to snapshot {}", service, version);
+        discovery::rollback(service, version);
+    }
+    exists
+}
+
+pub(crate) fn dns() -> Value {
+    let hosts: serde_json::Map<String, Value> = discovery::dns::hosts()
+        .into_iter()
+        .map(|(host, ips)| (host, json!(ips)))
+        .collect();
+    Value::Object(hosts)
+}
@@ -1,14 +1,27 @@
 #![cfg(feature = "http")]
 
 use super::prometheus::prometheus_metrics;
+use hyper::header::CONTENT_TYPE;
+use hyper::server::conn::AddrStream;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Method, Request, Response, Server, StatusCode};
 use rt::spawn;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 
 pub(crate) fn start(ctx: &context::Context) {
+    // whitelist_host中的域名由dns缓存定期解析，请求时只查缓存，不阻塞
+    let updated = Arc::new(AtomicBool::new(false));
+    whitelist(ctx)
+        .filter(|host| host.parse::<IpAddr>().is_err())
+        .for_each(|host| discovery::dns::register(host, updated.clone()));
     let addr = ([0, 0, 0, 0], ctx.port).into();
 
-    let service = make_service_fn(|_| async { Ok::<_, hyper::Error>(service_fn(route)) });
+    let service = make_service_fn(|conn: &AddrStream| {
+        let remote = conn.remote_addr().ip();
+        async move { Ok::<_, hyper::Error>(service_fn(move |req| route(req, remote))) }
+    });
     let server = Server::bind(&addr).serve(service);
     spawn(async {
         if let Err(_e) = server.await {
@@ -17,13 +30,111 @@ pub(crate) fn start(ctx: &context::Context) {
     });
 }
 
-async fn route(req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
-    match (req.method(), req.uri().path()) {
+async fn route(req: Request<Body>, remote: IpAddr) -> Result<Response<Body>, hyper::Error> {
+    let path = req.uri().path();
+    // admin接口只对whitelist_host开放
+    if path.starts_with("/admin/") && !whitelisted(remote) {
+        return Ok(status(StatusCode::FORBIDDEN));
+    }
+    match (req.method(), path) {
         (&Method::GET, "/metrics") => prometheus_metrics().await,
-        _ => {
-            let mut not_found = Response::default();
-            *not_found.status_mut() = StatusCode::NOT_FOUND;
-            Ok(not_found)
+        (&Method::GET, "/admin/services") => Ok(json(super::admin::services())),
+        (&Method::GET, "/admin/dns") => Ok(json(super::admin::dns())),
+        (&Method::GET, "/admin/route") => Ok(key_route(&req)),
+        (&Method::GET, "/admin/snapshots") => Ok(snapshots(&req).await),
+        (&Method::POST, "/admin/rollback") => Ok(rollback(&req).await),
+        (&Method::POST, "/admin/unpin") => Ok(unpin(&req)),
+        _ => Ok(status(StatusCode::NOT_FOUND)),
+    }
+}
+
+// whitelist_host为逗号分隔的host列表
+fn whitelist(ctx: &context::Context) -> impl Iterator<Item = &str> {
+    ctx.whitelist_host
+        .split(',')
+        .map(str::trim)
+        .filter(|host| !host.is_empty())
+}
+
+// host为ip时直接比较，域名使用dns缓存中的解析结果，ip变化后随缓存刷新生效
+fn whitelisted(remote: IpAddr) -> bool {
+    let remote = remote.to_canonical();
+    if remote.is_loopback() {
+        return true;
+    }
+    whitelist(context::get()).any(|host| match (host.parse::<IpAddr>(), remote) {
+        (Ok(ip), _) => ip == remote,
+        // dns缓存只解析ipv4
+        (Err(_), IpAddr::V4(v4)) => {
+            let mut found = false;
+            discovery::dns::lookup_ips(host, |ips| found = ips.contains(&v4));
+            found
         }
+        (Err(_), IpAddr::V6(_)) => false,
+    })
+}
+
+// url中query的参数
+fn param(req: &Request<Body>, name: &str) -> Option<String> {
+    let query = req.uri().query().unwrap_or_default();
+    url::form_urlencoded::parse(query.as_bytes())
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v.into_owned())
+}
+
+// /admin/route?service=xxx&key=xxx
+fn key_route(req: &Request<Body>) -> Response<Body> {
+    let (Some(service), Some(key)) = (param(req, "service"), param(req, "key")) else {
+        return status(StatusCode::BAD_REQUEST);
+    };
+    match super::admin::route(&service, &key) {
+        Some(route) => json(route),
+        None => status(StatusCode::NOT_FOUND),
     }
 }
+
+// /admin/snapshots?service=xxx 服务所在group的历史快照版本
+async fn snapshots(req: &Request<Body>) -> Response<Body> {
+    let Some(service) = param(req, "service") else {
+        return status(StatusCode::BAD_REQUEST);
+    };
+    json(super::admin::snapshots(&service).await)
+}
+
+// /admin/rollback?service=xxx&version=xxx 回滚到历史快照，并且不再从discovery更新，直到unpin
+async fn rollback(req: &Request<Body>) -> Response<Body> {
+    let service = param(req, "service");
+    let version = param(req, "version").and_then(|v| v.parse().ok());
+    let (Some(service), Some(version)) = (service, version) else {
+        return status(StatusCode::BAD_REQUEST);
+    };
+    if super::admin::rollback(&service, version).await {
+        status(StatusCode::OK)
+    } else {
+        status(StatusCode::NOT_FOUND)
+    }
+}
+
+// /admin/unpin?service=xxx
+fn unpin(req: &Request<Body>) -> Response<Body> {
+    let Some(service) = param(req, "service") else {
+        return status(StatusCode::BAD_REQUEST);
+    };
+    discovery::unpin(&service);
+    status(StatusCode::OK)
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    let mut rsp = Response::default();
+    *rsp.status_mut() = code;
+    rsp
+}
+
+fn json(v: serde_json::Value) -> Response<Body> {
+    let mut rsp = Response::new(Body::from(v.to_string()));
+    rsp.headers_mut().insert(
+        CONTENT_TYPE,
+        "application/json".parse().expect("content type"),
+    );
+    rsp
+}
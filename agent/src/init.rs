@@ -1,4 +1,5 @@
 use context::Context;
+use std::os::unix::net::UnixStream;
 pub(super) fn init(ctx: &Context) {
     #[cfg(feature = "panic-hook")]
     init_panic_hook();
@@ -6,14 +7,18 @@ pub(super) fn init(ctx: &Context) {
     init_limit(&ctx);
     init_log(&ctx);
     init_local_ip(&ctx);
+    init_upgrade(ctx);
     start_metrics_register_task(ctx);
 
+    rt::spawn(discovery::dns::start_dns_resolver_refresher());
+    // whitelist_host依赖dns缓存，在dns refresher之后启动
     #[cfg(feature = "http")]
     crate::http::start(ctx);
-    rt::spawn(discovery::dns::start_dns_resolver_refresher());
+    crate::prometheus::start_snapshot(crate::otlp::init(ctx));
     crate::prometheus::register_target(ctx);
 
     endpoint::cacheservice::init_not_update_master_l1();
+    endpoint::hedge::set_budget(ctx.hedge_budget);
 }
 pub(crate) fn init_limit(ctx: &Context) {
     set_rlimit(ctx.no_file);
@@ -64,25 +69,75 @@ pub(crate) fn init_local_ip(ctx: &Context) {
     metrics::init_local_ip(&ctx.metrics_probe, &ctx.host_ip);
 }
 
+// 热升级：先从旧进程继承侦听的fd，再侦听控制socket，供下一次升级使用。
+// 收到新进程的升级请求后，把侦听的fd交给新进程，然后进入退出流程。
+pub(crate) fn init_upgrade(ctx: &Context) {
+    let inherited = ctx.upgrade
+        && match UnixStream::connect(&ctx.upgrade_path).and_then(net::upgrade::inherit) {
+            Ok(n) => {
+                log::info!("upgrade: {} listeners inherited", n);
+                true
+            }
+            Err(e) => {
+                log::warn!("upgrade: inherit listeners failed. {:?}", e);
+                false
+            }
+        };
+    // 未从旧进程继承时，控制socket仍可能由其他进程侦听，只清理已失效的socket文件
+    if !inherited && UnixStream::connect(&ctx.upgrade_path).is_ok() {
+        log::warn!(
+            "upgrade: {} is in use, hot upgrade disabled",
+            ctx.upgrade_path
+        );
+        return;
+    }
+    let _ = std::fs::remove_file(&ctx.upgrade_path);
+    let l = match tokio::net::UnixListener::bind(&ctx.upgrade_path) {
+        Ok(l) => l,
+        Err(e) => {
+            log::warn!("upgrade: listen {} failed. {:?}", ctx.upgrade_path, e);
+            return;
+        }
+    };
+    rt::spawn(async move {
+        while let Ok((stream, _)) = l.accept().await {
+            let handover = rt::spawn_blocking(move || {
+                let stream = stream.into_std()?;
+                stream.set_nonblocking(false)?;
+                net::upgrade::handover(stream)
+            });
+            match handover.await {
+                Ok(Ok(n)) => {
+                    log::info!("upgrade: {} listeners handed over, shutting down", n);
+                    rt::shutdown();
+                    break;
+                }
+                _e => log::warn!("upgrade: hand over listeners failed. {:?}", _e),
+            }
+        }
+    });
+}
+
 pub(crate) fn start_metrics_register_task(_ctx: &Context) {
     rt::spawn(metrics::MetricRegister::default());
 }
 
 use tokio::signal::unix::{signal, SignalKind};
+// SIGTERM、SIGINT触发优雅退出
 fn init_signal() {
-    let stream = signal(SignalKind::terminate());
-    match stream {
-        Ok(mut stream) => {
-            rt::spawn(async move {
-                loop {
+    for kind in [SignalKind::terminate(), SignalKind::interrupt()] {
+        match signal(kind) {
+            Ok(mut stream) => {
+                rt::spawn(async move {
                     stream.recv().await;
-                    println!("got signal terminate");
-                }
-            });
-        }
-        Err(e) => {
-            println!("init signal failed: {:?}", e);
-            return;
+                    log::info!("got signal {:?}, shutting down", kind);
+                    rt::shutdown();
+                    // 再次收到信号时不再等待，立即退出
+                    stream.recv().await;
+                    std::process::exit(1);
+                });
+            }
+            Err(e) => log::warn!("init signal failed: {:?}", e),
         }
     }
 }
@@ -2,28 +2,51 @@ use ds::BrzMalloc;
 #[global_allocator]
 static GLOBAL: BrzMalloc = BrzMalloc {};
 
+mod admin;
 mod http;
+mod otlp;
 mod prometheus;
 mod service;
+mod validate;
 use context::Context;
 use discovery::*;
 mod init;
 
 use ds::time::{sleep, Duration};
+use std::collections::HashMap;
 use rt::spawn;
 
 use protocol::Result;
 
 // 默认支持
 fn main() -> Result<()> {
-    tokio::runtime::Builder::new_multi_thread()
+    if let Some(context::Command::Validate {
+        endpoint,
+        protocol,
+        file,
+    }) = &context::get().command
+    {
+        let code = match validate::run(endpoint, protocol, file) {
+            Ok(0) => 0,
+            Ok(_) => 1,
+            Err(e) => {
+                println!("validate {} failed: {}", file, e);
+                2
+            }
+        };
+        std::process::exit(code);
+    }
+    let runtime = tokio::runtime::Builder::new_multi_thread()
         .worker_threads(context::get().thread_num as usize)
         .thread_name("breeze-w")
         .thread_stack_size(2 * 1024 * 1024)
         .enable_all()
         .build()
-        .unwrap()
-        .block_on(run())
+        .unwrap();
+    let ret = runtime.block_on(run());
+    // 后台任务（discovery、dns等）不再等待
+    runtime.shutdown_timeout(Duration::from_secs(1));
+    ret
 }
 
 async fn run() -> Result<()> {
@@ -36,15 +59,22 @@ async fn run() -> Result<()> {
     log::info!("server inited {:?}", ctx);
 
     let mut listeners = ctx.listeners();
-    loop {
-        let (quards, failed) = listeners.scan().await;
+    // 运行中的服务，drop掉对应的sender即通知服务下线
+    let mut running = HashMap::new();
+    while !rt::shutting_down() {
+        let (quards, removed, failed) = listeners.scan().await;
         if failed > 0 {
             metrics::set_sockfile_failed(failed);
         }
+        for quard in removed {
+            running.remove(quard.service());
+        }
         for quard in quards {
             let discovery = tx.clone();
+            let (offline_tx, offline) = tokio::sync::watch::channel(());
+            running.insert(quard.service().to_string(), offline_tx);
             spawn(async move {
-                match service::process_one(&quard, discovery).await {
+                match service::process_one(&quard, discovery, offline).await {
                     Ok(_) => log::info!("service complete:{}", quard),
                     Err(_e) => log::warn!("service failed. {} err:{:?}", quard, _e),
                 }
@@ -52,12 +82,29 @@ async fn run() -> Result<()> {
         }
         sleep(Duration::from_secs(1)).await;
     }
+
+    drain(ctx).await;
+    Ok(())
+}
+
+// 侦听已在service中关闭，等待所有连接处理完pending的请求，超过截止时间后直接退出
+async fn drain(ctx: &Context) {
+    let deadline = ds::time::Instant::now() + ctx.drain_timeout();
+    log::info!("draining {} connections", rt::conns());
+    while rt::conns() > 0 && ds::time::Instant::now() < deadline {
+        sleep(Duration::from_millis(100)).await;
+    }
+    if rt::conns() > 0 {
+        log::warn!("drain timeout, {} connections left", rt::conns());
+    }
+    metrics::flush().await;
+    log::info!("server shutdown");
 }
 
 async fn discovery_init(ctx: &Context, rx: service::Receiver) -> Result<()> {
     // 将dns resolver的初始化放到外层，提前进行，避免并发场景下顺序错乱 fishermen
     let discovery = discovery::Discovery::from_url(&ctx.discovery);
-    let snapshot = ctx.snapshot_path.to_string();
+    let snapshot = discovery::Snapshot::new(ctx.snapshot_path.to_string(), ctx.snapshot_history);
     let tick = ctx.tick();
     let mut fix = discovery::Fixed::default();
 
@@ -68,10 +115,13 @@ async fn discovery_init(ctx: &Context, rx: service::Receiver) -> Result<()> {
     let mut dir = tokio::fs::read_dir(&ctx.service_path).await?;
     while let Some(child) = dir.next_entry().await? {
         let path = child.path();
+        let is_unix_sock = path.to_str().map(|s| s.ends_with(".sock")).unwrap_or(false);
+        // 热升级时，unix socket由旧进程传递过来，继续使用
+        if is_unix_sock && net::upgrade::inherited() {
+            continue;
+        }
         //从vintage获取socklist，或者存在unixsock，需要事先清理
-        if service_pool_socks_url.len() > 1
-            || path.to_str().map(|s| s.ends_with(".sock")).unwrap_or(false)
-        {
+        if service_pool_socks_url.len() > 1 || is_unix_sock {
             log::info!("{:?} exists. deleting", path);
             let _ = tokio::fs::remove_file(path).await;
         }
@@ -0,0 +1,62 @@
+use ds::time::{Duration, timeout};
+use hyper::{Body, Client, Request, client::HttpConnector, header::CONTENT_TYPE};
+use metrics::snapshot::Records;
+
+// 把每个周期的snapshot及采样的span推送到otlp collector
+pub(crate) struct Exporter {
+    client: Client<HttpConnector>,
+    metrics_url: String,
+    traces_url: String,
+}
+
+// 没有配置otlp collector时返回None
+pub(crate) fn init(ctx: &context::Context) -> Option<Exporter> {
+    if ctx.otlp_endpoint.is_empty() {
+        return None;
+    }
+    let endpoint = ctx.otlp_endpoint.trim_end_matches('/');
+    let endpoint = if endpoint.starts_with("http") {
+        endpoint.to_string()
+    } else {
+        format!("http://{}", endpoint)
+    };
+    let region = ctx.region().unwrap_or("");
+    let resource = [
+        ("service.name", "breeze"),
+        ("service.version", ctx.version.as_str()),
+        ("pool", ctx.service_pool.as_str()),
+        ("idc", ctx.idc.as_str()),
+        ("region", region),
+    ];
+    metrics::otlp::init(&resource, ctx.otlp_trace_ratio);
+    log::info!("otlp exporter started: {}", endpoint);
+    Some(Exporter {
+        client: Client::new(),
+        metrics_url: format!("{}/v1/metrics", endpoint),
+        traces_url: format!("{}/v1/traces", endpoint),
+    })
+}
+
+impl Exporter {
+    pub(crate) async fn export(&self, records: &Records) {
+        if let Some(body) = metrics::otlp::export_metrics(records) {
+            post(&self.client, &self.metrics_url, body).await;
+        }
+        if let Some(body) = metrics::otlp::export_spans() {
+            post(&self.client, &self.traces_url, body).await;
+        }
+    }
+}
+
+async fn post(client: &Client<HttpConnector>, url: &str, body: Vec<u8>) {
+    let req = Request::builder()
+        .method("POST")
+        .uri(url)
+        .header(CONTENT_TYPE, "application/x-protobuf")
+        .body(Body::from(body))
+        .expect("build request");
+    match timeout(Duration::from_secs(2), client.request(req)).await {
+        Ok(Ok(rsp)) if rsp.status().is_success() => {}
+        _r => log::warn!("otlp export to {} failed: {:?}", url, _r),
+    }
+}
@@ -1,31 +1,42 @@
 use metrics::prometheus::Prometheus;
 
-use ds::lock::Lock;
 use ds::time::{interval, timeout, Duration, Instant};
 use hyper::{header::CONTENT_TYPE, Body, Client, Request, Response, StatusCode};
-use lazy_static::lazy_static;
 use tokio_util::io::ReaderStream;
 
-lazy_static! {
-    static ref LAST: Lock<Instant> = Instant::now().into();
-}
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(10);
 
+// 返回最近一次snapshot的结果，周期内重复拉取得到相同的数据
 pub async fn prometheus_metrics() -> Result<Response<Body>, hyper::Error> {
     let mut rsp = Response::default();
-    if let Ok(mut last) = LAST.try_lock() {
-        let secs = last.elapsed().as_secs_f64();
-        if secs >= 8f64 {
-            *last = Instant::now();
-            *rsp.body_mut() = Body::wrap_stream(ReaderStream::new(Prometheus::new(secs)));
-        } else {
-            *rsp.status_mut() = StatusCode::NOT_MODIFIED;
+    match metrics::snapshot::latest() {
+        Some(records) => {
+            *rsp.body_mut() = Body::wrap_stream(ReaderStream::new(Prometheus::new(records)));
         }
-    } else {
-        *rsp.status_mut() = StatusCode::PROCESSING;
+        None => *rsp.status_mut() = StatusCode::NOT_MODIFIED,
     }
     Ok(rsp)
 }
 
+// 每个周期对metrics做一次snapshot，/metrics与otlp共用
+pub(crate) fn start_snapshot(otlp: Option<crate::otlp::Exporter>) {
+    rt::spawn(async move {
+        let mut interval = interval(SNAPSHOT_INTERVAL);
+        // 第一次tick立即返回，跳过
+        interval.tick().await;
+        let mut last = Instant::now();
+        loop {
+            interval.tick().await;
+            let secs = last.elapsed().as_secs_f64();
+            last = Instant::now();
+            let records = metrics::snapshot::take(secs);
+            if let Some(otlp) = otlp.as_ref() {
+                otlp.export(&records).await;
+            }
+        }
+    });
+}
+
 // 定期发心跳
 pub(crate) fn register_target(ctx: &context::Context) {
     if ctx.metrics_url.is_empty() {
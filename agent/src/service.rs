@@ -1,5 +1,5 @@
 use context::Quadruple;
-use ds::time::{sleep, Duration};
+use ds::time::{sleep, timeout, Duration};
 use net::Listener;
 use rt::spawn;
 use std::sync::Arc;
@@ -11,15 +11,20 @@ use stream::pipeline::copy_bidirectional;
 use stream::{Backend, CheckedTopology, Request, StreamMetrics};
 
 type Endpoint = Backend<Request>;
-type Topology = endpoint::TopologyProtocol<Endpoint, Parser>;
+// client的TLS握手超时时间
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(3);
+pub(super) type Topology = endpoint::TopologyProtocol<Endpoint, Parser>;
 use metrics::Status;
-// 一直侦听，直到成功侦听或者取消侦听（当前尚未支持取消侦听）
+// 一直侦听，直到成功侦听或者服务下线
 // 1. 尝试侦听之前，先确保服务配置信息已经更新完成
+// 2. 服务下线（offline的sender被drop）后，停止侦听，topology及metrics随之清理
 type Sender = ds::chan::Sender<(String, TopologyWriteGuard<Topology>)>;
 pub(super) type Receiver = ds::chan::Receiver<(String, TopologyWriteGuard<Topology>)>;
+type Offline = tokio::sync::watch::Receiver<()>;
 pub(super) async fn process_one(
     quard: &Quadruple,
     discovery: Sender,
+    mut offline: Offline,
 ) -> std::result::Result<(), Box<dyn std::error::Error>> {
     let p = Parser::try_from(&quard.protocol())?;
     let top = endpoint::TopologyProtocol::try_from(p.clone(), quard.endpoint())?;
@@ -34,6 +39,13 @@ pub(super) async fn process_one(
     // 等待初始化完成
     let mut tries = 0usize;
     while !rx.inited() || !metrics.check_registered() {
+        if rt::shutting_down() {
+            return Ok(());
+        }
+        if offline.has_changed().is_err() {
+            remove(quard, &rx, &path);
+            return Ok(());
+        }
         tries += 1;
         let s = if tries <= 10 {
             Duration::from_secs(1)
@@ -50,43 +62,91 @@ pub(super) async fn process_one(
     }
 
     log::info!("service inited. {} ", quard);
+    crate::admin::register(quard, &rx);
     let switcher = ds::Switcher::from(true);
 
     let metrics = Arc::new(metrics);
 
     // 服务注册完成，侦听端口直到成功。
-    while let Err(_e) = _process_one(quard, &p, &rx, metrics.clone()).await {
+    while let Err(_e) = _process_one(quard, &p, &rx, metrics.clone(), &mut offline).await {
         // 监听失败或accept连接失败，对监听失败数+1
         unsafe { *metrics.listen_failed.as_mut() += Status::ERROR };
         log::warn!("service process failed. {}, err:{:?}", quard, _e);
+        if rt::shutting_down() || offline.has_changed().is_err() {
+            break;
+        }
         sleep(Duration::from_secs(6)).await;
     }
     switcher.off();
+    crate::admin::unregister(quard);
+    if !rt::shutting_down() && offline.has_changed().is_err() {
+        remove(quard, &rx, &path);
+    }
 
     // 因为回调，有可能在连接释放的时候，还在引用top。
     sleep(Duration::from_secs(3)).await;
     Ok(())
 }
 
+// 服务下线：不再从discovery更新topology，已有的连接在refresh时关闭，metrics不再输出
+fn remove(quard: &Quadruple, top: &TopologyReadGuard<Topology>, path: &Path) {
+    log::info!("service removed. {}", quard);
+    top.remove();
+    metrics::unregister(path);
+}
+
 async fn _process_one(
     quard: &Quadruple,
     p: &Parser,
     top: &TopologyReadGuard<Topology>,
     metrics: Arc<StreamMetrics>,
+    offline: &mut Offline,
 ) -> Result<()> {
     let l = Listener::bind(&quard.family(), &quard.address()).await?;
+    let ctx = context::get();
+    let tls = if quard.tls() {
+        Some(net::tls::Acceptor::new(&ctx.tls_cert, &ctx.tls_key)?)
+    } else {
+        None
+    };
     log::info!("started. {}", quard);
     unsafe { *metrics.listen_failed.as_mut() += Status::OK };
 
     loop {
-        // 等待初始化成功
-        let (client, _addr) = l.accept().await?;
-        let client = rt::Stream::from(client);
+        // 进入退出流程后，drop掉listener，不再接收新的连接
+        let (client, _addr) = tokio::select! {
+            accepted = l.accept() => accepted?,
+            _ = rt::wait_shutdown() => {
+                log::info!("listener closed. {}", quard);
+                return Ok(());
+            }
+            _ = offline.changed() => {
+                log::info!("listener closed by offline. {}", quard);
+                return Ok(());
+            }
+        };
         let p = p.clone();
         log::debug!("connection established:{:?}", metrics.biz());
         let ctop = CheckedTopology::from(top.clone());
         let metrics = metrics.clone();
+        let tls = tls.clone();
         spawn(async move {
+            // TLS握手在连接自己的task中进行，不阻塞accept
+            let client = match tls {
+                Some(tls) => match timeout(TLS_HANDSHAKE_TIMEOUT, tls.accept(client)).await {
+                    Ok(Ok(client)) => client,
+                    Ok(Err(_e)) => {
+                        log::warn!("{:?} tls handshake failed. {:?}", metrics.biz(), _e);
+                        return;
+                    }
+                    Err(_) => {
+                        log::warn!("{:?} tls handshake timeout", metrics.biz());
+                        return;
+                    }
+                },
+                None => client,
+            };
+            let client = rt::Stream::from(client);
             if let Err(e) = copy_bidirectional(ctop, metrics.clone(), client, p).await {
                 use protocol::Error::*;
                 match e {
@@ -0,0 +1,29 @@
+// validate子命令：离线校验namespace的配置文件，不启动服务。
+// 配置文件的格式与从discovery拉取到的一致，一个文件包含多个namespace时（如mc），逐个校验。
+use discovery::TopologyWrite;
+use endpoint::Validate;
+use protocol::Parser;
+
+use super::service::Topology;
+
+// 返回校验失败的namespace数量
+pub(super) fn run(
+    endpoint: &str,
+    protocol: &str,
+    file: &str,
+) -> std::result::Result<usize, Box<dyn std::error::Error>> {
+    let p = Parser::try_from(protocol)?;
+    let top: Topology = endpoint::TopologyProtocol::try_from(p, endpoint)?;
+    let cfg = std::fs::read_to_string(file)?;
+    let mut failed = 0;
+    for (namespace, cfg) in top.disgroup(file, &cfg) {
+        match top.validate(namespace, cfg) {
+            Ok(_) => println!("{}: ok", namespace),
+            Err(e) => {
+                println!("{}: {}", namespace, e);
+                failed += 1;
+            }
+        }
+    }
+    Ok(failed)
+}
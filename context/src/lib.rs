@@ -1,8 +1,8 @@
 extern crate lazy_static;
-use clap::{CommandFactory, FromArgMatches, Parser};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use lazy_static::lazy_static;
 use std::path::Path;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::SystemTime;
 use std::{
     io::{Error, ErrorKind, Result},
     vec,
@@ -27,7 +27,7 @@ pub struct ContextOption {
     #[clap(
         short,
         long,
-        help("service registry url. e.g. vintage://127.0.0.1:8080"),
+        help("service registry url. e.g. vintage://127.0.0.1:8080, file:///path/to/dir, consul://127.0.0.1:8500/prefix, etcd://127.0.0.1:2379/prefix"),
         default_value("vintage://127.0.0.1:8080")
     )]
     pub discovery: Url,
@@ -58,6 +58,13 @@ pub struct ContextOption {
     )]
     tick_sec: usize,
 
+    #[clap(
+        long,
+        help("max seconds to wait for connections draining after SIGTERM/SIGINT"),
+        default_value("10")
+    )]
+    drain_sec: usize,
+
     #[clap(
         short,
         long("snapshot"),
@@ -65,6 +72,12 @@ pub struct ContextOption {
         default_value("/tmp/breeze/snapshot")
     )]
     pub snapshot_path: String,
+    #[clap(
+        long,
+        help("number of historical snapshots retained per service group for rollback"),
+        default_value("5")
+    )]
+    pub snapshot_history: usize,
     #[clap(
         short('p'),
         long,
@@ -79,7 +92,14 @@ pub struct ContextOption {
         default_value("false")
     )]
     #[clap(short, long, help("starting in upgrade mode"))]
-    upgrade: bool,
+    pub upgrade: bool,
+
+    #[clap(
+        long,
+        help("unix socket for handing over listeners in upgrade mode"),
+        default_value("/tmp/breeze/upgrade.sock")
+    )]
+    pub upgrade_path: String,
 
     #[clap(short, long, help("log path"), default_value("/tmp/breeze/logs"))]
     pub log_dir: String,
@@ -125,6 +145,55 @@ pub struct ContextOption {
 
     #[clap(long, help("host ip"), default_value(""))]
     pub host_ip: String,
+
+    // 证书文件变更后自动重新加载，不需要重启
+    #[clap(
+        long,
+        help("certificate for tls listeners"),
+        default_value("/var/breeze/tls/server.crt")
+    )]
+    pub tls_cert: String,
+    #[clap(
+        long,
+        help("private key for tls listeners"),
+        default_value("/var/breeze/tls/server.key")
+    )]
+    pub tls_key: String,
+
+    // 所有namespace共享的对冲读预算
+    #[clap(
+        long,
+        help("max percent of hedged reads to all reads"),
+        default_value("10")
+    )]
+    pub hedge_budget: u8,
+
+    // 为空时不开启。指标与/metrics共享区间内的计数，只使用其中一种方式
+    #[clap(
+        long,
+        help("otlp http collector, push metrics and sampled spans. e.g. http://127.0.0.1:4318"),
+        default_value("")
+    )]
+    pub otlp_endpoint: String,
+    #[clap(long, help("ratio of requests traced to otlp"), default_value("0.001"))]
+    pub otlp_trace_ratio: f64,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    // 离线校验namespace的配置文件，不启动服务。校验失败时以非0退出
+    #[clap(about("validate config file of namespaces offline"))]
+    Validate {
+        #[clap(long, help("endpoint of the service. e.g. cs, rs, kv"))]
+        endpoint: String,
+        #[clap(long, help("protocol of the service. e.g. mc, redis, kv"))]
+        protocol: String,
+        #[clap(help("config file, same as the config fetched from discovery"))]
+        file: String,
+    },
 }
 
 lazy_static! {
@@ -154,8 +223,11 @@ impl ContextOption {
         let matches = app.get_matches();
         <Self as FromArgMatches>::from_arg_matches(&matches).expect("parse args failed")
     }
-    // service_path目录要存在
+    // service_path目录要存在，子命令不启动服务，不需要检查
     pub fn check(&self) -> Result<()> {
+        if self.command.is_some() {
+            return Ok(());
+        }
         let path = Path::new(&self.service_path);
         if !path.is_dir() {
             let msg = format!("{} is not a valid dir", self.service_path);
@@ -171,12 +243,16 @@ impl ContextOption {
         assert!(self.tick_sec >= 1 && self.tick_sec <= 60);
         ds::time::Duration::from_secs(self.tick_sec as u64)
     }
+    // 收到退出信号后，等待连接处理完pending请求的最长时间
+    pub fn drain_timeout(&self) -> ds::time::Duration {
+        ds::time::Duration::from_secs(self.drain_sec as u64)
+    }
     // 如果是以升级模式启动，则会将原有的端口先关闭。
     pub fn listeners(&self) -> ListenerIter {
         ListenerIter {
             path: self.service_path.to_string(),
             processed: Default::default(),
-            last_read: UNIX_EPOCH, //初始值设为0时
+            modified: None,
         }
     }
 
@@ -206,9 +282,9 @@ impl ContextOption {
 
 use std::collections::HashMap;
 pub struct ListenerIter {
-    processed: HashMap<String, String>,
+    processed: HashMap<String, Quadruple>,
     path: String,
-    last_read: SystemTime, //上次扫描到socks目录有更新时间
+    modified: Option<SystemTime>, //上次扫描时socks目录的修改时间
 }
 
 impl ListenerIter {
@@ -216,7 +292,7 @@ impl ListenerIter {
         Self {
             processed: Default::default(),
             path,
-            last_read: UNIX_EPOCH,
+            modified: None,
         }
     }
 
@@ -224,31 +300,40 @@ impl ListenerIter {
     // 不以.sock结尾，由'@'字符分隔成一个Quard的配置。一个标准的服务配置文件名为
     // 如果对应的文件已经存在 $name.sock。那说明有其他进程侦听了该服务，如果协议或端口不同则说明冲突；
     // unix的配置放在前面
-    // 返回解析成功的Quadruple和解析失败的数量
-    pub async fn scan(&mut self) -> (Vec<Quadruple>, usize) {
+    // 已处理的配置文件被删除或者改名后，对应的服务需要下线
+    // 返回新增的Quadruple、下线的Quadruple和解析失败的数量
+    pub async fn scan(&mut self) -> (Vec<Quadruple>, Vec<Quadruple>, usize) {
         let mut failed = 0;
         let mut listeners = vec![];
-        // 本次循环开始时间
-        let start = SystemTime::now();
+        let mut removed = vec![];
         match self.read_all().await {
-            Ok(names) => {
-                for name in names {
-                    if let Some(one) = Quadruple::parse(&self.path, &name) {
-                        if !self.processed.contains_key(one.service()) {
-                            listeners.push(one);
-                        } else {
-                            // 包含了service，但端口、协议等任何其他发生变化，则立即汇报
-                            let empty = "default-empty".to_string();
-                            let old = self.processed.get(one.service()).unwrap_or(&empty);
-                            if old.ne(&one.name()) {
-                                log::warn!(
-                                    "sock scan found conflict, old:{}, new:{}",
-                                    old,
-                                    one.name()
-                                );
-                                failed += 1;
-                            }
+            Ok(None) => {}
+            Ok(Some(names)) => {
+                let quards: Vec<Quadruple> = names
+                    .iter()
+                    .filter_map(|name| Quadruple::parse(&self.path, name))
+                    .collect();
+                self.processed.retain(|_, old| {
+                    let exists = quards.contains(old);
+                    if !exists {
+                        log::info!("sock scan found removed:{}", old.name());
+                        removed.push(old.clone());
+                    }
+                    exists
+                });
+                for one in quards {
+                    match self.processed.get(one.service()) {
+                        None => listeners.push(one),
+                        // 包含了service，但端口、协议等任何其他发生变化，则立即汇报
+                        Some(old) if old.ne(&one) => {
+                            log::warn!(
+                                "sock scan found conflict, old:{}, new:{}",
+                                old.name(),
+                                one.name()
+                            );
+                            failed += 1;
                         }
+                        Some(_) => {}
                     }
                 }
             }
@@ -262,16 +347,16 @@ impl ListenerIter {
             listeners.sort();
             listeners.retain(|item| {
                 let retain = !self.processed.contains_key(item.service());
-                self.processed
-                    .insert(item.service().to_string(), item.name());
-                if !retain {
+                if retain {
+                    self.processed
+                        .insert(item.service().to_string(), item.clone());
+                } else {
                     log::warn!("{} register in multiple family", item.service());
                 }
                 retain
             });
-            self.last_read = start;
         }
-        (listeners, failed)
+        (listeners, removed, failed)
     }
 
     pub async fn remove_unix_sock(&mut self) -> Result<()> {
@@ -286,16 +371,21 @@ impl ListenerIter {
         }
         Ok(())
     }
-    async fn read_all(&self) -> Result<Vec<String>> {
+    // 目录没有更新时返回None
+    async fn read_all(&mut self) -> Result<Option<Vec<String>>> {
         let mut found = vec![];
         let dir_meta = tokio::fs::metadata(&self.path).await?;
-        let last_update = dir_meta.modified();
-        match last_update {
+        match dir_meta.modified() {
             Ok(t) => {
-                //上次扫描到sock文件后后续再未更新
-                if &self.last_read > &t {
-                    return Ok(found);
+                // 目录mtime的精度有限，最近1秒内更新过的目录在同一个时间单位内可能还有变更，
+                // 需要重新读取；否则修改时间与上次扫描时相同说明没有更新
+                let settled = SystemTime::now()
+                    .duration_since(t)
+                    .is_ok_and(|d| d >= std::time::Duration::from_secs(1));
+                if settled && self.modified == Some(t) {
+                    return Ok(None);
                 }
+                self.modified = Some(t);
             }
             Err(_err) => log::warn!("get socks dir metadata err:{:?}", _err),
         }
@@ -311,7 +401,7 @@ impl ListenerIter {
                 }
             }
         }
-        Ok(found)
+        Ok(Some(found))
     }
     pub async fn files(&self) -> Result<Vec<String>> {
         let mut found = vec![];
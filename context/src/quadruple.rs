@@ -9,6 +9,7 @@ pub struct Quadruple {
     protocol: String,
     endpoint: String,
     addr: String,
+    tls: bool,
 }
 
 // feed.content#yf@unix@memcache@cacheservice
@@ -16,6 +17,7 @@ impl Quadruple {
     // service@protocol@backend_type
     // service: 服务名称
     // protocol: 处理client连接的协议。memcache、redis等支持的协议.  格式为 mc:port.
+    //           tcp侦听需要终结TLS时，格式为 mc:port:tls
     // backend_type: 后端资源的类型。是cacheservice、redis_dns等等
     pub(super) fn parse(path: &str, name: &str) -> Option<Self> {
         let name = Path::new(name).file_name().map(std::ffi::OsStr::to_str)??;
@@ -46,6 +48,11 @@ impl Quadruple {
                 path.to_string() + "/" + protocol_fields.get(1).unwrap_or(&service) + ".sock",
             )
         };
+        // 只有tcp侦听支持tls
+        let tls = protocol_fields.get(2) == Some(&"tls");
+        if tls && !is_tcp {
+            log::warn!("tls is only supported by tcp listener:{}", name);
+        }
         let mut protocol = protocol_fields[0];
         let mut backend = fields[2];
         // TODO 增加mysql的兼容逻辑，将mysql改为kv，待client修改上线后清理，预计2023.6.15后清理没问题
@@ -63,6 +70,7 @@ impl Quadruple {
             protocol: protocol.to_owned(),
             endpoint: backend.to_string(),
             addr: addr.to_string(),
+            tls: tls && is_tcp,
             parsed_at: Instant::now(),
         })
     }
@@ -90,6 +98,10 @@ impl Quadruple {
     pub fn endpoint(&self) -> &str {
         &self.endpoint
     }
+    // 侦听是否终结TLS
+    pub fn tls(&self) -> bool {
+        self.tls
+    }
     // 从discovery同步数据的间隔周期
     pub fn tick(&self) -> Duration {
         Duration::from_secs(15)
@@ -0,0 +1,100 @@
+use std::io::{Error, ErrorKind::Other};
+
+use ds::time::{Duration, timeout};
+use hyper::{Body, Client, Request, Uri, client::HttpConnector};
+use url::Url;
+
+use super::Config;
+
+// 从consul的KV获取配置：GET /v1/kv/{prefix}/{name}?raw，
+// 以响应头中blocking query使用的X-Consul-Index作为签名，没有该响应头时（如经过代理）以内容的md5作为签名。
+// 所有group在同一个task中依次刷新，所以请求时不带index，避免blocking query阻塞其他group。
+pub struct Consul {
+    client: Client<HttpConnector>,
+    addr: String,
+    prefix: String,
+    token: Option<String>,
+}
+
+impl Consul {
+    // consul://host:port/prefix?token=xxx
+    pub fn from_url(url: &Url) -> Self {
+        let host = url.host_str().unwrap_or("127.0.0.1");
+        let port = url.port().unwrap_or(8500);
+        let token = url
+            .query_pairs()
+            .find(|(k, _)| k == "token")
+            .map(|(_, v)| v.to_string());
+        Self {
+            client: Client::new(),
+            addr: format!("{host}:{port}"),
+            prefix: url.path().trim_matches('/').to_string(),
+            token,
+        }
+    }
+
+    async fn lookup<C>(
+        &self,
+        name: &str,
+        index: &str,
+    ) -> Result<Config<C>, Box<dyn std::error::Error>>
+    where
+        C: From<String>,
+    {
+        let name = name.trim_start_matches('/');
+        let key = match self.prefix.len() {
+            0 => name.to_string(),
+            _ => format!("{}/{}", self.prefix, name),
+        };
+        let uri: Uri = format!("http://{}/v1/kv/{}?raw", self.addr, key).parse()?;
+        log::debug!("lookup: {}", uri);
+        let mut req = Request::get(uri);
+        if let Some(token) = &self.token {
+            req = req.header("X-Consul-Token", token);
+        }
+
+        let resp = timeout(
+            Duration::from_secs(3),
+            self.client.request(req.body(Body::empty())?),
+        )
+        .await??;
+        match resp.status().as_u16() {
+            404 => Ok(Config::NotFound),
+            200 => {
+                let t_index = resp
+                    .headers()
+                    .get("X-Consul-Index")
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string();
+                if !t_index.is_empty() && t_index == index {
+                    return Ok(Config::NotChanged);
+                }
+                let b = hyper::body::to_bytes(resp.into_body()).await?;
+                let data = String::from_utf8(b.to_vec())?;
+                let t_index = match t_index.is_empty() {
+                    true => format!("md5-{:x}", md5::compute(&data)),
+                    false => t_index,
+                };
+                if t_index == index {
+                    return Ok(Config::NotChanged);
+                }
+                log::info!("{} '{}' => '{}' len:{}", key, index, t_index, data.len());
+                Ok(Config::Config(t_index, C::from(data)))
+            }
+            status => Err(Box::new(Error::new(Other, status.to_string()))),
+        }
+    }
+}
+
+impl super::Discover for Consul {
+    #[inline]
+    async fn get_service<C>(&self, name: &str, sig: &str) -> std::io::Result<Config<C>>
+    where
+        C: Unpin + Send + From<String>,
+    {
+        self.lookup(name, sig)
+            .await
+            .map_err(|e| Error::new(Other, e.to_string()))
+    }
+}
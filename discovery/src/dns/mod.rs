@@ -34,6 +34,15 @@ pub fn lookup_ips<'a>(host: &str, mut f: impl FnMut(&[IpAddr])) {
         f(ips.as_slice())
     }
 }
+// 当前缓存的所有host及解析出的ip，用于admin接口
+pub fn hosts() -> Vec<(String, Vec<IpAddr>)> {
+    let dns = get_dns();
+    dns.hosts
+        .hosts
+        .iter()
+        .map(|(host, record)| (host.clone(), record.ips.as_slice().to_vec()))
+        .collect()
+}
 
 #[derive(Clone, Debug)]
 struct Record {
@@ -0,0 +1,104 @@
+use std::io::{Error, ErrorKind::Other};
+
+use base64::{Engine as _, engine::general_purpose};
+use ds::time::{Duration, timeout};
+use hyper::{Body, Client, Request, Uri, client::HttpConnector};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::Config;
+
+// 通过etcd v3的json gateway获取配置：POST /v3/kv/range，key、value均为base64编码，
+// 以key的mod_revision作为签名。
+pub struct Etcd {
+    client: Client<HttpConnector>,
+    addr: String,
+    prefix: String,
+}
+
+#[derive(Serialize)]
+struct RangeRequest {
+    key: String,
+}
+
+#[derive(Deserialize)]
+struct RangeResponse {
+    #[serde(default)]
+    kvs: Vec<Kv>,
+}
+
+// int64在json gateway中被编码为字符串，value为空时不返回
+#[derive(Deserialize)]
+struct Kv {
+    #[serde(default)]
+    value: String,
+    mod_revision: String,
+}
+
+impl Etcd {
+    // etcd://host:port/prefix
+    pub fn from_url(url: &Url) -> Self {
+        let host = url.host_str().unwrap_or("127.0.0.1");
+        let port = url.port().unwrap_or(2379);
+        Self {
+            client: Client::new(),
+            addr: format!("{host}:{port}"),
+            prefix: url.path().trim_end_matches('/').to_string(),
+        }
+    }
+
+    async fn lookup<C>(
+        &self,
+        name: &str,
+        revision: &str,
+    ) -> Result<Config<C>, Box<dyn std::error::Error>>
+    where
+        C: From<String>,
+    {
+        let key = format!("{}/{}", self.prefix, name.trim_start_matches('/'));
+        let uri: Uri = format!("http://{}/v3/kv/range", self.addr).parse()?;
+        log::debug!("lookup: {} {}", uri, key);
+        let body = serde_json::to_vec(&RangeRequest {
+            key: general_purpose::STANDARD.encode(&key),
+        })?;
+        let req = Request::post(uri)
+            .header("Content-Type", "application/json")
+            .body(Body::from(body))?;
+
+        let resp = timeout(Duration::from_secs(3), self.client.request(req)).await??;
+        match resp.status().as_u16() {
+            200 => {
+                let b = hyper::body::to_bytes(resp.into_body()).await?;
+                let resp: RangeResponse = serde_json::from_slice(&b)?;
+                let Some(kv) = resp.kvs.into_iter().next() else {
+                    return Ok(Config::NotFound);
+                };
+                if kv.mod_revision == revision {
+                    return Ok(Config::NotChanged);
+                }
+                let data = String::from_utf8(general_purpose::STANDARD.decode(&kv.value)?)?;
+                log::info!(
+                    "{} '{}' => '{}' len:{}",
+                    key,
+                    revision,
+                    kv.mod_revision,
+                    data.len()
+                );
+                Ok(Config::Config(kv.mod_revision, C::from(data)))
+            }
+            status => Err(Box::new(Error::new(Other, status.to_string()))),
+        }
+    }
+}
+
+impl super::Discover for Etcd {
+    #[inline]
+    async fn get_service<C>(&self, name: &str, sig: &str) -> std::io::Result<Config<C>>
+    where
+        C: Unpin + Send + From<String>,
+    {
+        self.lookup(name, sig)
+            .await
+            .map_err(|e| Error::new(Other, e.to_string()))
+    }
+}
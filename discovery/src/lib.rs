@@ -3,6 +3,11 @@
 pub mod distance;
 pub mod socks;
 
+mod consul;
+mod etcd;
+mod local;
+pub mod snapshot;
+pub use snapshot::Snapshot;
 mod topology;
 mod update;
 mod vintage;
@@ -14,14 +19,15 @@ mod fixed;
 
 pub use fixed::Fixed;
 pub use topology::*;
+use consul::Consul;
+use etcd::Etcd;
+use local::Local;
 use vintage::Vintage;
 
 use std::io::Result;
 
 use url::Url;
 
-use enum_dispatch::enum_dispatch;
-
 #[derive(Debug)]
 pub enum Config<C> {
     NotFound,
@@ -29,7 +35,6 @@ pub enum Config<C> {
     Config(String, C), // 第一个元素是签名，第二个是数据
 }
 
-#[enum_dispatch]
 pub trait Discover {
     ///name 格式为domain/path/to/path
     fn get_service<C>(
@@ -41,9 +46,12 @@ pub trait Discover {
         C: Unpin + Send + From<String>;
 }
 
-#[enum_dispatch(Discover)]
+// 按url的scheme选择配置中心
 pub enum Discovery {
     Vintage(Vintage),
+    Local(Local),
+    Consul(Consul),
+    Etcd(Etcd),
 }
 impl Discovery {
     pub fn from_url(url: &Url) -> Self {
@@ -51,11 +59,30 @@ impl Discovery {
         // let http = Self::copy_url_to_http(&url);
         match schem {
             "vintage" => Self::Vintage(Vintage::default()),
+            "file" => Self::Local(Local::from_url(url)),
+            "consul" => Self::Consul(Consul::from_url(url)),
+            "etcd" => Self::Etcd(Etcd::from_url(url)),
             _ => panic!("not supported endpoint name"),
         }
     }
 }
 
+// 各实现返回的future类型不同，不能通过enum_dispatch分发
+impl Discover for Discovery {
+    #[inline]
+    async fn get_service<C>(&self, name: &str, sig: &str) -> std::io::Result<Config<C>>
+    where
+        C: Unpin + Send + From<String>,
+    {
+        match self {
+            Self::Vintage(d) => d.get_service(name, sig).await,
+            Self::Local(d) => d.get_service(name, sig).await,
+            Self::Consul(d) => d.get_service(name, sig).await,
+            Self::Etcd(d) => d.get_service(name, sig).await,
+        }
+    }
+}
+
 impl<T: Discover + Send + Unpin + Sync> Discover for std::sync::Arc<T> {
     #[inline]
     async fn get_service<C>(&self, name: &str, sig: &str) -> std::io::Result<Config<C>>
@@ -0,0 +1,58 @@
+use std::io::{ErrorKind, Result};
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use url::Url;
+
+use super::Config;
+
+// 从本地目录读取配置，文件路径为 {dir}/{name}，主要用于开发、测试环境。
+// 签名格式为 mtime-md5，mtime未变化时不读取文件。
+pub struct Local {
+    dir: PathBuf,
+}
+
+impl Local {
+    pub fn from_url(url: &Url) -> Self {
+        Self {
+            dir: PathBuf::from(url.path()),
+        }
+    }
+
+    async fn lookup<C>(&self, name: &str, sig: &str) -> Result<Config<C>>
+    where
+        C: From<String>,
+    {
+        let path = self.dir.join(name.trim_start_matches('/'));
+        let meta = match tokio::fs::metadata(&path).await {
+            Ok(meta) if meta.is_file() => meta,
+            Ok(_) => return Ok(Config::NotFound),
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Config::NotFound),
+            Err(e) => return Err(e),
+        };
+        let mtime = meta
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        let prefix = format!("{}-", mtime);
+        if sig.starts_with(&prefix) {
+            return Ok(Config::NotChanged);
+        }
+
+        let data = tokio::fs::read_to_string(&path).await?;
+        let t_sig = format!("{}{:x}", prefix, md5::compute(&data));
+        log::info!("{:?} '{}' => '{}' len:{}", path, sig, t_sig, data.len());
+        Ok(Config::Config(t_sig, C::from(data)))
+    }
+}
+
+impl super::Discover for Local {
+    #[inline]
+    async fn get_service<C>(&self, name: &str, sig: &str) -> Result<Config<C>>
+    where
+        C: Unpin + Send + From<String>,
+    {
+        self.lookup(name, sig).await
+    }
+}
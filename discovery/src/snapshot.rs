@@ -0,0 +1,117 @@
+// 服务配置的快照，每个group一个文件：
+// 第一行是sig
+// 第二行是group name
+// 后面是cfg
+// 1. 先写临时文件，fsync后再rename，rename后fsync目录，避免进程异常退出时留下不完整的快照；
+// 2. 每次dump同时在.history目录下保留一个版本，最多保留history个，用于回滚。
+//    版本号为纳秒时间戳，且比已有的最新版本大，同一秒内多次dump也不会覆盖。
+use std::io::Result;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+
+const HISTORY_DIR: &str = ".history";
+
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    path: String,
+    history: usize,
+}
+
+pub struct Content {
+    pub sig: String,
+    pub group: String,
+    pub cfg: String,
+}
+
+impl Snapshot {
+    pub fn new(path: String, history: usize) -> Self {
+        Self { path, history }
+    }
+    // version为None时，读取最新的快照，否则读取历史版本
+    pub async fn load(&self, group: &str, version: Option<u64>) -> Option<Content> {
+        let path = match version {
+            Some(v) => self.history_path(group, v),
+            None => self.file(group),
+        };
+        // 返回第一行与剩余的内容
+        fn take_line(mut s: String) -> Option<(String, String)> {
+            let idx = s.find("\n")?;
+            let left = s.split_off(idx + 1);
+            s.pop();
+            if !s.is_empty() && s.as_bytes()[s.len() - 1] == b'\r' {
+                s.pop();
+            }
+            Some((s, left))
+        }
+        let content = fs::read_to_string(&path).await.ok()?;
+        let (sig, group_cfg) = take_line(content)?;
+        let (group, cfg) = take_line(group_cfg)?;
+        Some(Content { sig, group, cfg })
+    }
+    pub async fn dump(&self, group: &str, c: &Content) -> Result<()> {
+        let path = self.file(group);
+        log::info!("dump to snapshot: {}", path);
+        Self::write(&path, c).await?;
+        if self.history > 0 {
+            fs::create_dir_all(self.history_dir()).await?;
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let latest = self.versions_of(group).await.first().copied();
+            let version = (now.as_nanos() as u64).max(latest.map_or(0, |v| v + 1));
+            Self::write(&self.history_path(group, version), c).await?;
+            // 超过history个的旧版本删除
+            for v in self.versions_of(group).await.into_iter().skip(self.history) {
+                let _ = fs::remove_file(self.history_path(group, v)).await;
+            }
+        }
+        Ok(())
+    }
+    async fn write(path: &str, c: &Content) -> Result<()> {
+        let tmp = format!("{}.tmp", path);
+        let mut file = File::create(&tmp).await?;
+        file.write_all(c.sig.as_bytes()).await?;
+        file.write_all(b"\n").await?;
+        file.write_all(c.group.as_bytes()).await?;
+        file.write_all(b"\n").await?;
+        file.write_all(c.cfg.as_bytes()).await?;
+        file.sync_all().await?;
+        fs::rename(&tmp, path).await?;
+        // rename只有在目录fsync后才能保证持久化
+        match std::path::Path::new(path).parent() {
+            Some(dir) => File::open(dir).await?.sync_all().await,
+            None => Ok(()),
+        }
+    }
+    // 服务所在group的历史版本，按时间从新到旧排列。服务名的格式见split_service
+    pub async fn versions(&self, service: &str) -> Vec<u64> {
+        let (group, _, _) = super::update::split_service(service);
+        self.versions_of(group).await
+    }
+    async fn versions_of(&self, group: &str) -> Vec<u64> {
+        let mut versions: Vec<u64> = Vec::new();
+        let prefix = format!("{}.", group);
+        if let Ok(mut dir) = fs::read_dir(self.history_dir()).await {
+            while let Ok(Some(child)) = dir.next_entry().await {
+                let name = child.file_name();
+                let v = name.to_str().and_then(|n| n.strip_prefix(&prefix));
+                if let Some(v) = v.and_then(|v| v.parse().ok()) {
+                    versions.push(v);
+                }
+            }
+        }
+        versions.sort_unstable_by(|a, b| b.cmp(a));
+        versions
+    }
+    fn file(&self, group: &str) -> String {
+        format!("{}/{}", self.path, group)
+    }
+    fn history_dir(&self) -> String {
+        format!("{}/{}", self.path, HISTORY_DIR)
+    }
+    fn history_path(&self, group: &str, version: u64) -> String {
+        format!("{}/{}.{}", self.history_dir(), group, version)
+    }
+}
@@ -1,15 +1,20 @@
 use ds::{cow, CowReadHandle, CowWriteHandle};
 
 use std::{
+    collections::HashMap,
     ops::Deref,
     sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
     },
+    time::{SystemTime, UNIX_EPOCH},
 };
 
+// 配置校验失败的原因。校验失败时保留原有的topology
+pub type UpdateError = Box<dyn std::error::Error>;
+
 pub trait TopologyWrite {
-    fn update(&mut self, name: &str, cfg: &str);
+    fn update(&mut self, name: &str, cfg: &str) -> Result<(), UpdateError>;
     #[inline]
     fn disgroup<'a>(&self, path: &'a str, cfg: &'a str) -> Vec<(&'a str, &'a str)> {
         vec![(path, cfg)]
@@ -24,6 +29,11 @@ pub trait TopologyWrite {
     fn load(&mut self) -> bool {
         true
     }
+    // 服务已下线，不再需要更新
+    #[inline]
+    fn removed(&self) -> bool {
+        false
+    }
 }
 
 pub fn topology<T>(t: T, service: &str) -> (TopologyWriteGuard<T>, TopologyReadGuard<T>)
@@ -32,19 +42,59 @@ where
 {
     let (tx, rx) = cow(t);
 
-    let updates = Arc::new(AtomicUsize::new(0));
+    let state = Arc::new(State::default());
 
     (
         TopologyWriteGuard {
             updating: None,
             inner: tx,
             service: service.to_string(),
-            updates: updates.clone(),
+            state: state.clone(),
         },
-        TopologyReadGuard { inner: rx, updates },
+        TopologyReadGuard { inner: rx, state },
     )
 }
 
+#[derive(Debug, Clone)]
+pub struct Rejected {
+    pub error: String,
+    pub cfg: String,
+    // 被拒绝的时间，unix秒
+    pub at: u64,
+}
+
+// 每个服务最后一次被拒绝的配置，key为topology(t, service)中的service，与admin中的服务名一致
+static REJECTED: Mutex<Option<HashMap<String, Rejected>>> = Mutex::new(None);
+
+// 被拒绝时通过any/{group:namespace}/config_rejected上报，直到新的配置校验通过。
+fn reject(service: &str, r: Option<(String, &str)>) {
+    let mut rejected = REJECTED.lock().expect("rejected");
+    let rejected = rejected.get_or_insert_with(Default::default);
+    // 服务名中最后一个'+'之后的部分，即group:namespace
+    let name = service.rsplit('+').next().expect("service");
+    let status = match r {
+        None if rejected.remove(service).is_some() => metrics::Status::OK,
+        None => return,
+        Some((error, cfg)) => {
+            let at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let cfg = cfg.to_string();
+            rejected.insert(service.to_string(), Rejected { error, cfg, at });
+            metrics::Status::NOTIFY
+        }
+    };
+    let mut metric = metrics::Path::new(vec!["any", name]).status("config_rejected");
+    metric += status;
+}
+
+// 服务最后一次被拒绝的配置，之后有配置校验通过时清除
+pub fn rejected(service: &str) -> Option<Rejected> {
+    let rejected = REJECTED.lock().expect("rejected");
+    rejected.as_ref()?.get(service).cloned()
+}
+
 pub trait Inited {
     fn inited(&self) -> bool;
 }
@@ -61,9 +111,17 @@ impl<T: Inited, O> Inited for (T, O) {
     }
 }
 
+// 读写两端共享的状态。放在同一个Arc中，不增加每个连接持有的TopologyReadGuard的大小
+#[derive(Default)]
+struct State {
+    updates: AtomicUsize,
+    // 服务下线后，读写两端都不再使用
+    removed: AtomicBool,
+}
+
 #[derive(Clone)]
 pub struct TopologyReadGuard<T> {
-    updates: Arc<AtomicUsize>,
+    state: Arc<State>,
     inner: CowReadHandle<T>,
 }
 pub struct TopologyWriteGuard<T>
@@ -73,7 +131,7 @@ where
     updating: Option<T>,
     inner: CowWriteHandle<T>,
     service: String,
-    updates: Arc<AtomicUsize>,
+    state: Arc<State>,
 }
 
 impl<T> Deref for TopologyReadGuard<T> {
@@ -87,7 +145,16 @@ impl<T> Deref for TopologyReadGuard<T> {
 impl<T> TopologyReadGuard<T> {
     #[inline]
     pub fn version(&self) -> usize {
-        self.updates.load(Ordering::Acquire)
+        self.state.updates.load(Ordering::Acquire)
+    }
+    // 服务下线：watch_discovery不再更新，已有的连接在refresh时关闭
+    #[inline]
+    pub fn remove(&self) {
+        self.state.removed.store(true, Ordering::Release);
+    }
+    #[inline]
+    pub fn removed(&self) -> bool {
+        self.state.removed.load(Ordering::Acquire)
     }
 }
 
@@ -98,7 +165,7 @@ where
     #[inline]
     pub fn inited(&self) -> bool {
         //这一层是否还有必要，只判断后面条件不行吗？
-        self.updates.load(Ordering::Relaxed) > 0 && self.inner.get().inited()
+        self.state.updates.load(Ordering::Relaxed) > 0 && self.inner.get().inited()
     }
 }
 
@@ -106,14 +173,14 @@ impl<T> TopologyWriteGuard<T>
 where
     T: Clone,
 {
-    fn update_inner(&mut self, f: impl Fn(&mut T) -> bool) -> bool {
+    fn update_inner(&mut self, mut f: impl FnMut(&mut T) -> bool) -> bool {
         let mut t = self.updating.take().unwrap_or_else(|| self.inner.copy());
         if !f(&mut t) {
             let _ = self.updating.insert(t);
             return false;
         }
         self.inner.update(t);
-        self.updates.fetch_add(1, Ordering::AcqRel);
+        self.state.updates.fetch_add(1, Ordering::AcqRel);
         return true;
     }
 }
@@ -122,11 +189,21 @@ impl<T> TopologyWrite for TopologyWriteGuard<T>
 where
     T: TopologyWrite + Clone,
 {
-    fn update(&mut self, name: &str, cfg: &str) {
+    fn update(&mut self, name: &str, cfg: &str) -> Result<(), UpdateError> {
+        let mut r = Ok(());
+        // 被拒绝的配置不生效，topology保持不变
         self.update_inner(|t| {
-            t.update(name, cfg);
-            !t.need_load() || t.load()
+            r = t.update(name, cfg);
+            r.is_ok() && (!t.need_load() || t.load())
         });
+        match &r {
+            Ok(()) => reject(&self.service, None),
+            Err(e) => {
+                log::warn!("{} config rejected: {} => {}", self.service, e, cfg);
+                reject(&self.service, Some((e.to_string(), cfg)));
+            }
+        }
+        r
     }
     #[inline]
     fn disgroup<'a>(&self, path: &'a str, cfg: &'a str) -> Vec<(&'a str, &'a str)> {
@@ -144,6 +221,10 @@ where
     fn load(&mut self) -> bool {
         self.update_inner(|t| t.load())
     }
+    #[inline]
+    fn removed(&self) -> bool {
+        self.state.removed.load(Ordering::Acquire)
+    }
 }
 
 impl<T> crate::ServiceId for TopologyWriteGuard<T>
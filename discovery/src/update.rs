@@ -1,13 +1,58 @@
 use metrics::Metric;
 // 定期更新discovery.
-use super::{Discover, TopologyWrite};
+use super::{Discover, Snapshot, TopologyWrite};
 use ds::chan::Receiver;
 use ds::time::{interval, Duration};
 
 use std::collections::HashMap;
+use std::sync::Mutex;
+
+// 每个group当前配置的签名，key为group的local_path，用于admin接口
+static SIGNATURES: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+
+// 服务当前配置的签名，服务名的格式与register相同
+pub fn signature(service: &str) -> Option<String> {
+    let (full_group, _, _) = split_service(service);
+    let sigs = SIGNATURES.lock().expect("signatures");
+    sigs.as_ref()?.get(full_group).cloned()
+}
+
+// admin发起的回滚请求，key为group的local_path：
+// Some(version): 回滚到指定的历史版本，并且不再从discovery更新，直到unpin
+// None: unpin，恢复从discovery更新
+static PINS: Mutex<Option<HashMap<String, Option<u64>>>> = Mutex::new(None);
+
+// 把服务所在的group回滚到历史版本version，并pin住。在下一个tick中生效，进程重启后失效
+pub fn rollback(service: &str, version: u64) {
+    pin(service, Some(version));
+}
+pub fn unpin(service: &str) {
+    pin(service, None);
+}
+fn pin(service: &str, version: Option<u64>) {
+    let (full_group, _, _) = split_service(service);
+    let mut pins = PINS.lock().expect("pins");
+    let pins = pins.get_or_insert_with(Default::default);
+    pins.insert(full_group.to_string(), version);
+}
+
+// name的格式。 dir0+dir1+dir2+...+group:namespace
+// 规范：最后一个+号之后的是group:namespace
+// namespace是可选。
+// namespace之前的是group的路径，如：分隔符为'+'。
+// 返回 (group的完整路径, group, namespace)
+pub(crate) fn split_service(name: &str) -> (&str, &str, &str) {
+    let group = name.split('+').last().expect("name");
+    let mut group_namespace = group.split(':');
+    let group = group_namespace.next().expect("group");
+    match group_namespace.next() {
+        Some(ns) => (&name[..name.len() - ns.len() - 1], group, ns),
+        None => (name, group, group),
+    }
+}
 
 pub async fn watch_discovery<D, T>(
-    snapshot: String,
+    snapshot: Snapshot,
     discovery: D,
     rx: Receiver<(String, T)>,
     tick: Duration,
@@ -29,7 +74,7 @@ pub async fn watch_discovery<D, T>(
 
 struct Refresher<D, T> {
     discovery: D,
-    snapshot: String,
+    snapshot: Snapshot,
     tick: Duration,
     rx: Receiver<(String, T)>,
     cb: super::fixed::Fixed,
@@ -54,6 +99,8 @@ where
             while let Ok((name, t)) = self.rx.try_recv() {
                 services.register(name, t, &self.discovery).await;
             }
+            services.clean();
+            services.pin();
             let cycle_i = tick_i % cycle;
             for (idx, group) in services.groups.iter_mut().enumerate() {
                 if idx % cycle == cycle_i {
@@ -74,14 +121,14 @@ where
 }
 
 struct Services<T> {
-    snapshot: String,
+    snapshot: Snapshot,
     indices: HashMap<String, usize>,
     groups: Vec<ServiceGroup<T>>,
 }
 impl<T: TopologyWrite> Services<T> {
-    fn new(snapshot: &str) -> Self {
+    fn new(snapshot: &Snapshot) -> Self {
         Self {
-            snapshot: snapshot.to_string(),
+            snapshot: snapshot.clone(),
             groups: Vec::with_capacity(64),
             indices: HashMap::with_capacity(64),
         }
@@ -91,19 +138,10 @@ impl<T: TopologyWrite> Services<T> {
         assert!(idx < self.groups.len());
         Some(&mut self.groups[idx])
     }
-    // name的格式。 dir0+dir1+dir2+...+group:namespace
-    // 规范：最后一个+号之后的是group:namespace
-    // namespace是可选。
-    // namespace之前的是group的路径，如：分隔符为'+'。
+    // name的格式见split_service
     async fn register<D: Discover>(&mut self, name: String, top: T, d: &D) {
         log::info!("receive new service: {}", name);
-        let group = name.split('+').last().expect("name");
-        let mut group_namespace = group.split(':');
-        let group = group_namespace.next().expect("group");
-        let (full_group, service) = match group_namespace.next() {
-            Some(ns) => (&name[..name.len() - ns.len() - 1], ns),
-            None => (name.as_str(), group),
-        };
+        let (full_group, group, service) = split_service(&name);
 
         let g = match self.get_group(full_group) {
             Some(g) => g,
@@ -124,6 +162,46 @@ impl<T: TopologyWrite> Services<T> {
         log::info!("register service: {} => {}", name, group);
         g.register(service.to_string(), top);
     }
+    // 处理admin的回滚请求
+    fn pin(&mut self) {
+        let Some(pins) = PINS.lock().expect("pins").take() else {
+            return;
+        };
+        for (group, version) in pins {
+            match self.get_group(&group) {
+                Some(g) => g.pin = Some(version),
+                None => log::warn!("pin {:?} failed, group not found: {}", version, group),
+            }
+        }
+    }
+    // 清理已下线的服务，group中没有服务后，group也一并清理
+    fn clean(&mut self) {
+        let mut empty = false;
+        for g in self.groups.iter_mut() {
+            let group = &g.local_path;
+            g.namespaces.retain(|s| {
+                if s.top.removed() {
+                    log::info!("unregister service: {} => {}", s.name, group);
+                }
+                !s.top.removed()
+            });
+            empty |= g.namespaces.is_empty();
+        }
+        if empty {
+            if let Some(sigs) = SIGNATURES.lock().expect("signatures").as_mut() {
+                for g in self.groups.iter().filter(|g| g.namespaces.is_empty()) {
+                    sigs.remove(&g.local_path);
+                }
+            }
+            self.groups.retain(|g| !g.namespaces.is_empty());
+            self.indices = self
+                .groups
+                .iter()
+                .enumerate()
+                .map(|(idx, g)| (g.local_path.clone(), idx))
+                .collect();
+        }
+    }
 }
 
 // 一个group会有一个或者多个namespace,共享一个group配置。
@@ -136,13 +214,15 @@ struct ServiceGroup<T> {
     cfg: String,
     cache: HashMap<String, String>,
     namespaces: Vec<Service<T>>,
+    // 待处理的回滚请求
+    pin: Option<Option<u64>>,
+    // 已回滚到的历史版本，不再从discovery更新
+    pinned: Option<u64>,
 }
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
 impl<T: TopologyWrite> ServiceGroup<T> {
     // 先从snapshot获取配置。然后再从discover刷新。
-    async fn init<D: Discover>(&mut self, snapshot: &str, d: &D) {
-        self.load_from_snapshot(snapshot).await;
+    async fn init<D: Discover>(&mut self, snapshot: &Snapshot, d: &D) {
+        self.load_from_snapshot(snapshot, None).await;
         self.load_from_discover(snapshot, d).await;
     }
     // load所有的namespace。
@@ -155,55 +235,57 @@ impl<T: TopologyWrite> ServiceGroup<T> {
             }
         }
     }
-    // 第一行是sig
-    // 第二行是group name
-    // 后面是cfg
-    async fn load_from_snapshot(&mut self, snapshot: &str) -> Option<()> {
-        let path = format!("{}/{}", snapshot, self.local_path);
-        // 返回第一行与剩余的内容
-        fn take_line(mut s: String) -> Option<(String, String)> {
-            let idx = s.find("\n")?;
-            let left = s.split_off(idx + 1);
-            s.pop();
-            if s.len() > 0 && s.as_bytes()[s.len() - 1] == b'\r' {
-                s.pop();
-            }
-            Some((s, left))
-        }
-        let content = tokio::fs::read_to_string(&path).await.ok()?;
-        let (sig, group_cfg) = take_line(content)?;
-        let (group, cfg) = take_line(group_cfg)?;
-
-        if group != self.name {
-            log::warn!("group name changed: {path} '{}' -> '{}'", self.name, group);
+    // version为None时加载最新的快照，否则加载历史版本
+    async fn load_from_snapshot(&mut self, snapshot: &Snapshot, version: Option<u64>) -> Option<()> {
+        let c = snapshot.load(&self.local_path, version).await?;
+        if c.group != self.name {
+            log::warn!(
+                "group name changed: {} '{}' -> '{}'",
+                self.local_path,
+                self.name,
+                c.group
+            );
             return None;
         }
-        log::info!("load from snapshot: {} {} => {}", path, self.sig, sig);
-        self.sig = sig;
-        self.cfg = cfg;
+        let path = &self.local_path;
+        log::info!("load from snapshot: {path} {version:?} {} => {}", self.sig, c.sig);
+        self.set_sig(c.sig);
+        self.cfg = c.cfg;
+        self.cache.clear();
         self.changed = true;
         Some(())
     }
-    // 第一行是sig
-    // 第二行是group name
-    // 后面是cfg
-    async fn _dump_to_snapshot(&self, snapshot: &str) -> std::io::Result<()> {
-        let path = format!("{}/{}", snapshot, self.local_path);
-        log::info!("dump to snapshot: {}", path);
-        let mut file = File::create(path).await?;
-        file.write_all(&self.sig.as_bytes()).await?;
-        file.write_all(b"\n").await?;
-        file.write_all(&self.name.as_bytes()).await?;
-        file.write_all(b"\n").await?;
-        file.write_all(&self.cfg.as_bytes()).await?;
-        Ok(())
-    }
-    async fn dump_to_snapshot(&self, snapshot: &str) {
-        if let Err(e) = self._dump_to_snapshot(snapshot).await {
+    async fn dump_to_snapshot(&self, snapshot: &Snapshot) {
+        let c = super::snapshot::Content {
+            sig: self.sig.clone(),
+            group: self.name.clone(),
+            cfg: self.cfg.clone(),
+        };
+        if let Err(e) = snapshot.dump(&self.local_path, &c).await {
             log::warn!("failed to dump to snapshot: {}, {}", self.name, e);
         }
     }
-    async fn load_from_discover<D: Discover>(&mut self, snapshot: &str, d: &D) {
+    // 回滚到历史版本并pin住；unpin之后，下次从discovery获取时因sig不同而重新加载最新的配置
+    async fn apply_pin(&mut self, snapshot: &Snapshot) {
+        let Some(pin) = self.pin.take() else {
+            return;
+        };
+        match pin {
+            Some(version) => match self.load_from_snapshot(snapshot, Some(version)).await {
+                Some(()) => self.pinned = Some(version),
+                None => log::warn!("rollback failed: {} version {}", self.local_path, version),
+            },
+            None => {
+                log::info!("unpinned: {} version {:?}", self.local_path, self.pinned);
+                self.pinned = None;
+            }
+        }
+    }
+    async fn load_from_discover<D: Discover>(&mut self, snapshot: &Snapshot, d: &D) {
+        if let Some(version) = self.pinned {
+            log::debug!("pinned, ignore discover: {} version {}", self.path, version);
+            return;
+        }
         log::debug!("load from discover: {}", self.path);
         use crate::Config;
         match d.get_service::<String>(&self.path, &self.sig).await {
@@ -216,7 +298,7 @@ impl<T: TopologyWrite> ServiceGroup<T> {
             Ok(Config::NotChanged) => log::debug!("service not changed: {}", self.path),
             Ok(Config::Config(sig, cfg)) => {
                 log::info!("service changed: {} sig {} => {}", self.path, self.sig, sig);
-                self.sig = sig;
+                self.set_sig(sig);
                 self.cfg = cfg;
                 self.dump_to_snapshot(snapshot).await;
                 self.cache.clear();
@@ -225,13 +307,15 @@ impl<T: TopologyWrite> ServiceGroup<T> {
             Err(e) => log::error!("failed to get service: {}, {}", self.path, e),
         }
     }
-    async fn refresh_new<D: Discover>(&mut self, snapshot: &str, d: &D) {
+    async fn refresh_new<D: Discover>(&mut self, snapshot: &Snapshot, d: &D) {
+        self.apply_pin(snapshot).await;
         if self.cfg.len() == 0 {
             self.load_from_discover(snapshot, d).await;
         }
         self.update_all(true);
     }
-    async fn refresh<D: Discover>(&mut self, snapshot: &str, d: &D) {
+    async fn refresh<D: Discover>(&mut self, snapshot: &Snapshot, d: &D) {
+        self.apply_pin(snapshot).await;
         self.load_from_discover(snapshot, d).await;
         self.update_all(false);
     }
@@ -265,8 +349,15 @@ impl<T: TopologyWrite> ServiceGroup<T> {
             self.changed = false;
         }
     }
+    fn set_sig(&mut self, sig: String) {
+        let mut sigs = SIGNATURES.lock().expect("signatures");
+        let sigs = sigs.get_or_insert_with(Default::default);
+        sigs.insert(self.local_path.clone(), sig.clone());
+        self.sig = sig;
+    }
     fn register(&mut self, ns: String, top: T) {
-        assert!(self.namespaces.iter().find(|s| s.name == ns).is_none());
+        // 下线后又重新注册的服务，旧的可能还没有被清理
+        self.namespaces.retain(|s| s.name != ns);
         let service = Service::new(ns, top);
         self.namespaces.push(service);
         self.changed = true;
@@ -281,6 +372,8 @@ impl<T: TopologyWrite> ServiceGroup<T> {
             namespaces: Vec::new(),
             cache: HashMap::new(),
             changed: false,
+            pin: None,
+            pinned: None,
         }
     }
 }
@@ -304,9 +397,10 @@ impl<T: TopologyWrite> Service<T> {
     fn update(&mut self, cfg: &str) {
         if cfg != self.cfg {
             self.cfg = cfg.to_string();
-            self.top.update(&self.name, cfg);
-            self.metric += 1;
-            log::info!("Updated {}", self.name);
+            if self.top.update(&self.name, cfg).is_ok() {
+                self.metric += 1;
+                log::info!("Updated {}", self.name);
+            }
         }
     }
 }
@@ -1,6 +1,8 @@
 use rand::{seq::SliceRandom, thread_rng};
 use serde::{Deserialize, Serialize};
 use sharding::hash;
+
+use crate::validate::{self, ConfigError};
 //use ds::time::Duration;
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default, Hash)]
@@ -39,6 +41,15 @@ pub struct Namespace {
     pub local_affinity: bool,
     #[serde(default)]
     pub flag: u64, // 通过bit位，设置不同的策略/属性，详见下面Flag定义
+    // 每个后端建立的连接数，未配置时为1
+    #[serde(default)]
+    pub conns_per_backend: usize,
+    // 读请求超过该分位数的耗时仍未完成时，访问下一层；0表示不开启
+    #[serde(default)]
+    pub hedge_percentile: u8,
+    // layer按local_affinity选择，不支持ewma，只用于校验
+    #[serde(default)]
+    pub selector: String,
 }
 
 // 通过bit位，设置不同的策略/属性；从低位开始依次排列
@@ -60,50 +71,46 @@ impl Namespace {
     //        _ => false,
     //    }
     //}
-    pub(crate) fn try_from(cfg: &str, _namespace: &str) -> Option<Self> {
+    pub(crate) fn validate(cfg: &str, _namespace: &str) -> Result<Self, ConfigError> {
         log::debug!("namespace:{} cfg:{} updating", _namespace, cfg);
-        match serde_yaml::from_str::<Namespace>(cfg) {
-            Err(_e) => {
-                log::warn!("parse namespace error. {} msg:{:?}", _namespace, _e);
-                None
-            }
-            Ok(mut ns) => {
-                if ns.master.len() == 0 {
-                    log::info!("cache service master empty. namespace:{}", _namespace);
-                    None
-                } else {
-                    // 对于mc，crc32实际是crc32-short，这里需要做一次转换
-                    if ns.hash.eq("crc32") {
-                        ns.hash = format!(
-                            "crc32{}{}",
-                            hash::HASHER_NAME_DELIMITER,
-                            hash::CRC32_EXT_SHORT
-                        );
-                        log::debug!("change mc crc32 to {}", ns.hash);
-                    }
-
-                    // TODO 暂时保留，线上稳定后清理
-                    // refresh flag
-                    // if ns.force_write_all {
-                    //     ns.flag.set(Flag::ForceWriteAll as u8);
-                    // }
-                    use protocol::Bit;
-                    if ns.update_slave_l1 {
-                        ns.flag.set(Flag::UpdateSlavel1 as u8);
-                    }
-                    if ns.local_affinity {
-                        ns.flag.set(Flag::LocalAffinity as u8);
-                    }
-
-                    // 如果update_slave_l1为false，去掉slave_l1
-                    if !ns.flag.get(Flag::UpdateSlavel1 as u8) {
-                        ns.slave_l1 = Vec::with_capacity(0);
-                        log::info!("{} update slave l1: false", _namespace);
-                    }
-                    Some(ns)
-                }
-            }
+        let mut ns = serde_yaml::from_str::<Namespace>(cfg)?;
+        if ns.master.len() == 0 {
+            return Err(ConfigError::Empty("master"));
+        }
+        // 对于mc，crc32实际是crc32-short，这里需要做一次转换
+        if ns.hash.eq("crc32") {
+            ns.hash = format!(
+                "crc32{}{}",
+                hash::HASHER_NAME_DELIMITER,
+                hash::CRC32_EXT_SHORT
+            );
+            log::debug!("change mc crc32 to {}", ns.hash);
+        }
+        validate::check_hash(&ns.hash)?;
+        validate::check_distribution(&ns.distribution, &ns.master)?;
+        validate::check_conns(ns.conns_per_backend)?;
+        validate::check_hedge(ns.hedge_percentile)?;
+        validate::check_no_ewma(&ns.selector)?;
+
+        // TODO 暂时保留，线上稳定后清理
+        // refresh flag
+        // if ns.force_write_all {
+        //     ns.flag.set(Flag::ForceWriteAll as u8);
+        // }
+        use protocol::Bit;
+        if ns.update_slave_l1 {
+            ns.flag.set(Flag::UpdateSlavel1 as u8);
+        }
+        if ns.local_affinity {
+            ns.flag.set(Flag::LocalAffinity as u8);
+        }
+
+        // 如果update_slave_l1为false，去掉slave_l1
+        if !ns.flag.get(Flag::UpdateSlavel1 as u8) {
+            ns.slave_l1 = Vec::with_capacity(0);
+            log::info!("{} update slave l1: false", _namespace);
         }
+        Ok(ns)
     }
     fn default_update_slave_l1() -> bool {
         return true;
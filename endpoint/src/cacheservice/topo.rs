@@ -1,8 +1,8 @@
+use crate::hedge::Hedge;
 use crate::select::Distance;
 use crate::{Endpoint, Endpoints, Topology};
 use discovery::TopologyWrite;
-use protocol::memcache::Binary;
-use protocol::{Protocol, Request, Resource::Memcache};
+use protocol::{Protocol, Request, ResOption, Resource::Memcache};
 use sharding::hash::{Hash, HashKey, Hasher};
 
 use super::config::Flag;
@@ -31,6 +31,7 @@ pub struct CacheService<E, P> {
     backend_no_storage: bool, // true：mc后面没有存储
     update_master_l1: bool,   // false：不更新master_L1，issue#834
     writer_idx: Vec<usize>,   // 写操作对象在streams里的索引
+    hedge: Option<Hedge>,
 }
 
 impl<E, P> From<P> for CacheService<E, P> {
@@ -45,6 +46,7 @@ impl<E, P> From<P> for CacheService<E, P> {
             backend_no_storage: false,
             update_master_l1: true,
             writer_idx: Default::default(),
+            hedge: None,
         }
     }
 }
@@ -84,6 +86,16 @@ where
     fn exp_sec(&self) -> u32 {
         self.exp_sec
     }
+    #[inline]
+    fn hedge(&self) -> Option<&Hedge> {
+        self.hedge.as_ref()
+    }
+    // 每一层的分片及地址，layers的顺序同inspect
+    fn route(&self, key: &[u8]) -> serde_json::Value {
+        let hash = self.hasher.hash(&key);
+        let layers: Vec<_> = self.streams.iter().map(|s| s.route(hash)).collect();
+        serde_json::json!({ "hash": hash, "layers": layers })
+    }
 }
 
 impl<E, Req, P> Endpoint for CacheService<E, P>
@@ -100,9 +112,13 @@ where
         // let mut idx: usize = 0; // master
         let mut ctx = super::Context::from(*req.mut_context());
         // gets及store类指令，都需要先请求master，然后再考虑masterL1
-        let (idx, try_next, write_back) = if req.operation().is_store() {
+        let mut hedge = false;
+        let store = req.operation().is_store();
+        let (idx, try_next, write_back) = if store {
             self.context_store(&mut ctx)
         } else {
+            // 第一次读，并且还有下一层可以访问时，允许对冲
+            hedge = !ctx.inited() && self.hedge.is_some();
             if !ctx.inited() {
                 // ctx未初始化, 是第一次读请求；仅第一次请求记录时间，原因如下：
                 // 第一次读一般访问L1，miss之后再读master；
@@ -116,8 +132,15 @@ where
         req.try_next(try_next);
         req.write_back(write_back);
         // TODO 有点怪异，先实现，晚点调整，这个属性直接从request获取更佳？ fishermen
-        req.retry_on_rsp_notok(req.can_retry_on_rsp_notok());
+        req.retry_on_rsp_notok(self.parser.can_retry_on_rsp_notok(&req));
         *req.mut_context() = ctx.ctx;
+        if hedge && try_next {
+            req.hedge();
+        }
+        // 读请求完成后更新所访问layer的异常检测统计
+        if !store {
+            req.outlier(self.streams.outlier(idx).clone());
+        }
         log::debug!("+++ request sent prepared:{} - {} {}", idx, req, self);
         assert!(idx < self.streams.len(), "{} {} => {:?}", idx, self, req);
 
@@ -134,6 +157,7 @@ where
         ctx.check_and_inited(true);
         if ctx.is_write() {
             // 写指令，总是从master开始
+            // incr/decr、append/prepend、touch等同样按writer_idx依次回写L1、slave
             let seq = ctx.take_write_idx() as usize; // 第几次写
             write_back = seq + 1 < self.writer_idx.len();
 
@@ -199,46 +223,50 @@ where
     E: Endpoint,
 {
     #[inline]
-    fn update(&mut self, namespace: &str, cfg: &str) {
-        if let Some(ns) = super::config::Namespace::try_from(cfg, namespace) {
-            self.hasher = Hasher::from(&ns.hash);
+    fn update(&mut self, namespace: &str, cfg: &str) -> Result<(), discovery::UpdateError> {
+        let ns = super::config::Namespace::validate(cfg, namespace)?;
+        self.hasher = Hasher::from(&ns.hash);
 
-            self.exp_sec = (ns.exptime / 1000) as u32; // 转换成秒
+        self.exp_sec = (ns.exptime / 1000) as u32; // 转换成秒
+        crate::hedge::refresh(&mut self.hedge, ns.hedge_percentile);
 
-            // self.force_write_all = ns.flag.get(Flag::ForceWriteAll as u8);
-            self.backend_no_storage = ns.flag.get(Flag::BackendNoStorage as u8);
-            use crate::cacheservice::UpdateMasterL1;
-            self.update_master_l1 = namespace.update_master_l1();
-            let dist = &ns.distribution.clone();
+        // self.force_write_all = ns.flag.get(Flag::ForceWriteAll as u8);
+        self.backend_no_storage = ns.flag.get(Flag::BackendNoStorage as u8);
+        use crate::cacheservice::UpdateMasterL1;
+        self.update_master_l1 = namespace.update_master_l1();
+        let dist = &ns.distribution.clone();
 
-            // 把所有的endpoints cache下来
-            let mut endpoints: Endpoints<'_, P, E> =
-                Endpoints::new(namespace, &self.parser, Memcache);
-            self.streams.take().into_iter().for_each(|shard| {
-                endpoints.cache(shard.into());
-            });
+        // 把所有的endpoints cache下来
+        let mut endpoints: Endpoints<'_, P, E> = Endpoints::new(namespace, &self.parser, Memcache);
+        self.streams.take().into_iter().for_each(|shard| {
+            endpoints.cache(shard.into());
+        });
 
-            let mto = crate::TO_MC_M.to(ns.timeout_ms_master);
-            let rto = crate::TO_MC_S.to(ns.timeout_ms_slave);
+        let mto = crate::TO_MC_M.to(ns.timeout_ms_master);
+        let rto = crate::TO_MC_S.to(ns.timeout_ms_slave);
+        let res = ResOption {
+            conns: ns.conns_per_backend,
+            ..Default::default()
+        };
 
-            //use discovery::distance::{Balance, ByDistance};
-            //let master = ns.master.clone();
-            let is_performance = ns.flag.get(Flag::LocalAffinity as u8).tuning_mode();
-            let (local_len, backends, writer_idx) = ns.take_backends(self.update_master_l1);
-            self.writer_idx = writer_idx;
+        //use discovery::distance::{Balance, ByDistance};
+        //let master = ns.master.clone();
+        let is_performance = ns.flag.get(Flag::LocalAffinity as u8).tuning_mode();
+        let (local_len, backends, writer_idx) = ns.take_backends(self.update_master_l1);
+        self.writer_idx = writer_idx;
 
-            let mut new = Vec::with_capacity(backends.len());
-            for (i, group) in backends.into_iter().enumerate() {
-                // 第一组是master
-                let to = if i == 0 { mto } else { rto };
-                let backends = endpoints.take_or_build(&group, to);
-                let shard = Shards::from_dist(dist, backends);
+        let mut new = Vec::with_capacity(backends.len());
+        for (i, group) in backends.into_iter().enumerate() {
+            // 第一组是master
+            let to = if i == 0 { mto } else { rto };
+            let backends = endpoints.take_or_build_with_res(&group, to, res.clone());
+            let shard = Shards::from_dist(dist, backends);
 
-                new.push(shard);
-            }
-            self.streams.update(new, local_len, is_performance);
+            new.push(shard);
         }
+        self.streams.update(new, local_len, is_performance);
         // old 会被dopped
+        Ok(())
     }
     // 不同的业务共用一个配置。把不同的业务配置给拆分开
     #[inline]
@@ -265,3 +293,23 @@ impl<E, P> Display for CacheService<E, P> {
         )
     }
 }
+
+impl<E, P> crate::Inspect for CacheService<E, P>
+where
+    E: Endpoint + discovery::Inited,
+{
+    // layers的顺序：master, master l1, slave, slave l1
+    fn inspect(&self) -> serde_json::Value {
+        let layers: Vec<_> = self.streams.iter().map(|s| s.inspect()).collect();
+        serde_json::json!({
+            "layers": layers,
+            "writers": self.writer_idx,
+        })
+    }
+}
+
+impl<E, P> crate::Validate for CacheService<E, P> {
+    fn validate(&self, namespace: &str, cfg: &str) -> Result<(), crate::validate::ConfigError> {
+        super::config::Namespace::validate(cfg, namespace).map(|_| ())
+    }
+}
@@ -0,0 +1,155 @@
+// 对冲读（hedging）：从副本读的请求，超过按耗时分位数计算的延迟仍未完成时，
+// 向下一个副本再发送一次相同的请求，使用先返回的响应，降低单个副本抖动带来的长尾耗时。
+// 1. 按namespace统计读请求的耗时分布，每秒按配置的分位数（hedge_percentile）更新一次对冲延迟；
+// 2. 样本数不足时不对冲；
+// 3. 对冲请求受全局预算限制：每个读请求增加budget%个令牌，每次对冲消耗一个，避免后端负载被放大。
+use std::sync::atomic::{AtomicI64, AtomicU32, AtomicU64, Ordering::*};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+// 每个2的幂次区间再等分为8个桶，超过MAX_US的耗时计入最后一个桶
+const SUB_BITS: u32 = 3;
+const MAX_US: u64 = (1 << 25) - 1;
+const BUCKETS: usize = bucket(MAX_US) + 1;
+// 样本数少于该值时，不更新对冲延迟
+const MIN_SAMPLES: u32 = 100;
+const MIN_DELAY_US: u64 = 500;
+// 对冲预算：令牌按百分之一计数，最多累计MAX_TOKENS / 100次对冲
+const DEFAULT_BUDGET: i64 = 10;
+const MAX_TOKENS: i64 = 100 * 100;
+
+static BUDGET: AtomicI64 = AtomicI64::new(DEFAULT_BUDGET);
+static TOKENS: AtomicI64 = AtomicI64::new(0);
+
+// 对冲请求数不超过读请求数的percent%，所有namespace共享
+pub fn set_budget(percent: u8) {
+    BUDGET.store(percent.min(100) as i64, Relaxed);
+}
+
+// 进程启动后的秒数
+fn now_sec() -> u64 {
+    static START: OnceLock<std::time::Instant> = OnceLock::new();
+    START
+        .get_or_init(std::time::Instant::now)
+        .elapsed()
+        .as_secs()
+}
+
+// 对数线性分桶：小于16us时每1us一个桶
+const fn bucket(us: u64) -> usize {
+    let us = if us > MAX_US { MAX_US } else { us };
+    if us < 1 << (SUB_BITS + 1) {
+        return us as usize;
+    }
+    let p = 63 - us.leading_zeros();
+    (((p - SUB_BITS + 1) << SUB_BITS) as u64 + ((us >> (p - SUB_BITS)) & ((1 << SUB_BITS) - 1)))
+        as usize
+}
+// 桶的上界
+fn upper(idx: usize) -> u64 {
+    if idx < 1 << (SUB_BITS + 1) {
+        return idx as u64 + 1;
+    }
+    let p = (idx >> SUB_BITS) as u32 + SUB_BITS - 1;
+    let m = (idx & ((1 << SUB_BITS) - 1)) as u64;
+    ((1 << SUB_BITS) + m + 1) << (p - SUB_BITS)
+}
+
+struct Latency {
+    counts: [AtomicU32; BUCKETS],
+    total: AtomicU32,
+    sec: AtomicU64,
+    // 0表示样本不足，不对冲
+    delay_us: AtomicU64,
+}
+
+#[derive(Clone)]
+pub struct Hedge {
+    percentile: u8,
+    latency: Arc<Latency>,
+}
+
+impl Hedge {
+    // percentile为0时不开启对冲
+    pub fn new(percentile: u8) -> Option<Self> {
+        (percentile > 0).then(|| Self {
+            percentile,
+            latency: Arc::new(Latency {
+                counts: [(); BUCKETS].map(|_| AtomicU32::new(0)),
+                total: 0.into(),
+                sec: now_sec().into(),
+                delay_us: 0.into(),
+            }),
+        })
+    }
+    // 对冲延迟：请求超过该时间未完成时发送对冲请求。样本不足时返回None
+    #[inline]
+    pub fn delay(&self) -> Option<Duration> {
+        let us = self.latency.delay_us.load(Relaxed);
+        (us > 0).then(|| Duration::from_micros(us))
+    }
+    // 记录一个读请求的耗时（微秒），对冲过的请求按原请求完成的时间记录，避免分位数被低估
+    #[inline]
+    pub fn record(&self, us: u64) {
+        let l = &*self.latency;
+        l.counts[bucket(us)].fetch_add(1, Relaxed);
+        l.total.fetch_add(1, Relaxed);
+        if TOKENS.load(Relaxed) < MAX_TOKENS {
+            TOKENS.fetch_add(BUDGET.load(Relaxed), Relaxed);
+        }
+
+        let sec = now_sec();
+        let old = l.sec.load(Acquire);
+        if old != sec && l.sec.compare_exchange(old, sec, AcqRel, Relaxed).is_ok() {
+            self.update();
+        }
+    }
+    // 按分位数更新对冲延迟，并开始新一轮的统计。并发更新时的少量误差可以忽略
+    fn update(&self) {
+        let l = &*self.latency;
+        let total = l.total.load(Relaxed);
+        if total < MIN_SAMPLES {
+            return;
+        }
+        let target = (total as u64 * self.percentile as u64).div_ceil(100);
+        let mut sum = 0u64;
+        let mut us = MAX_US;
+        for (idx, c) in l.counts.iter().enumerate() {
+            sum += c.load(Relaxed) as u64;
+            if sum >= target {
+                us = upper(idx);
+                break;
+            }
+        }
+        l.delay_us.store(us.max(MIN_DELAY_US), Relaxed);
+        l.counts.iter().for_each(|c| c.store(0, Relaxed));
+        l.total.store(0, Relaxed);
+    }
+    // 获取一次对冲的预算
+    #[inline]
+    pub fn acquire(&self) -> bool {
+        if TOKENS.fetch_sub(100, AcqRel) >= 100 {
+            return true;
+        }
+        TOKENS.fetch_add(100, AcqRel);
+        false
+    }
+    // 获取预算后没有发送对冲请求，归还预算
+    #[inline]
+    pub fn refund(&self) {
+        TOKENS.fetch_add(100, AcqRel);
+    }
+}
+
+// 配置的分位数变化时才重新统计
+pub(crate) fn refresh(hedge: &mut Option<Hedge>, percentile: u8) {
+    if hedge.as_ref().map_or(0, |h| h.percentile) != percentile {
+        *hedge = Hedge::new(percentile);
+    }
+}
+
+impl std::fmt::Debug for Hedge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "p{} delay:{:?}", self.percentile, self.delay())
+    }
+}
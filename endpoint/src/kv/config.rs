@@ -3,6 +3,9 @@ use serde::{de::Error, Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
 use std::fs;
 
+use net::tls::TlsOption;
+
+use crate::validate::{self, ConfigError};
 use crate::{Timeout, TO_MYSQL_M, TO_MYSQL_S};
 
 //时间间隔，闭区间, 可以是2010, 或者2010-2015
@@ -76,47 +79,74 @@ pub struct Basic {
     pub(crate) max_slave_conns: u16,
     #[serde(default)]
     pub(crate) region_enabled: bool,
+    // 每个后端建立的连接数，未配置时为1
+    #[serde(default)]
+    pub(crate) conns_per_backend: usize,
+    // 读从的请求超过该分位数的耗时仍未完成时，向下一个从发送对冲请求；0表示不开启
+    #[serde(default)]
+    pub(crate) hedge_percentile: u8,
+    // 后端连接的TLS，在mysql协议握手中通过SSLRequest协商
+    #[serde(default)]
+    pub(crate) tls: Option<TlsOption>,
 }
 pub const ARCHIVE_DEFAULT_KEY: &str = "__default__";
 
+// 1. 配置的年需要连续，不重叠
+// 2. 策略默认所有年都有同样的分片数，且db_count是分片数的整数倍
+pub(crate) fn check_years(
+    backends: &HashMap<Years, Vec<String>>,
+    db_count: u32,
+) -> Result<(), ConfigError> {
+    let mut years: Vec<_> = backends.iter().collect();
+    if years.is_empty() {
+        return Err(ConfigError::Empty("backends"));
+    }
+    years.sort();
+    let shards = years[0].1.len();
+    let mut last_year = years[0].0.0.wrapping_sub(1);
+    for (year, backends) in years {
+        if year.0 > year.1 || year.0 != last_year.wrapping_add(1) {
+            return Err(ConfigError::Years(format!("{}-{}", year.0, year.1)));
+        }
+        last_year = year.1;
+        if backends.len() != shards {
+            return Err(ConfigError::ShardMismatch {
+                what: "shards of years",
+                expected: shards,
+                actual: backends.len(),
+            });
+        }
+    }
+    if shards == 0 || db_count == 0 || !(db_count as usize).is_multiple_of(shards) {
+        return Err(ConfigError::ShardMismatch {
+            what: "db_count should be multiple of shards",
+            expected: shards,
+            actual: db_count as usize,
+        });
+    }
+    Ok(())
+}
+
 impl KvNamespace {
     #[inline]
-    pub(crate) fn try_from(cfg: &str) -> Option<Self> {
-        match serde_yaml::from_str::<KvNamespace>(cfg) {
-            Ok(mut ns) => {
-                //移除default分片，兼容老defalut
-                ns.backends.remove(&Years(0, 0));
-                //配置的年需要连续，不重叠
-                let mut years: Vec<_> = ns.backends.keys().collect();
-                if years.len() == 0 {
-                    return None;
-                }
-                years.sort();
-                let mut last_year = years[0].0 - 1;
-                for year in years {
-                    if year.0 > year.1 || year.0 != last_year + 1 {
-                        return None;
-                    }
-                    last_year = year.1;
-                }
-                match ns.decrypt_password() {
-                    Ok(password) => ns.basic.password = password,
-                    Err(e) => {
-                        log::warn!("failed to decrypt password, e:{}", e);
-                        return None;
-                    }
-                }
-                ns.backends_flaten = ns.backends.iter().fold(Vec::new(), |mut init, b| {
-                    init.extend_from_slice(b.1);
-                    init
-                });
-                Some(ns)
-            }
-            Err(e) => {
-                log::info!("failed to parse mysql  e:{} config:{}", e, cfg);
-                None
-            }
+    pub(crate) fn validate(cfg: &str) -> Result<Self, ConfigError> {
+        let mut ns = serde_yaml::from_str::<KvNamespace>(cfg)?;
+        //移除default分片，兼容老defalut
+        ns.backends.remove(&Years(0, 0));
+        check_years(&ns.backends, ns.basic.db_count)?;
+        validate::check_conns(ns.basic.conns_per_backend)?;
+        validate::check_hedge(ns.basic.hedge_percentile)?;
+        validate::check_tls(ns.basic.tls.as_ref())?;
+        if !ns.basic.password.is_empty() {
+            ns.basic.password = ns
+                .decrypt_password()
+                .map_err(|e| ConfigError::Decrypt(e.to_string()))?;
         }
+        ns.backends_flaten = ns.backends.iter().fold(Vec::new(), |mut init, b| {
+            init.extend_from_slice(b.1);
+            init
+        });
+        Ok(ns)
     }
 
     #[inline]
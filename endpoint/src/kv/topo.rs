@@ -4,7 +4,8 @@ use discovery::distance::ByDistance;
 use discovery::dns;
 use discovery::dns::IPPort;
 use discovery::TopologyWrite;
-use ds::MemGuard;
+use ds::{MemGuard, RingSlice};
+use net::tls::TlsOption;
 use protocol::kv::Binary;
 use protocol::kv::ContextStatus;
 use protocol::kv::MysqlBuilder;
@@ -17,6 +18,7 @@ use rand::seq::SliceRandom;
 use sharding::hash::{Hash, HashKey};
 
 use crate::dns::DnsConfig;
+use crate::hedge::Hedge;
 use crate::Timeout;
 use crate::{shards::Shard, Endpoint, Topology};
 
@@ -30,7 +32,9 @@ pub struct KvService<E, P> {
     // selector: Selector,
     strategist: Strategist,
     parser: P,
+    tls: Option<Box<TlsOption>>,
     cfg: Box<DnsConfig<KvNamespace>>,
+    hedge: Option<Hedge>,
 }
 
 impl<E, P> From<P> for KvService<E, P> {
@@ -40,7 +44,9 @@ impl<E, P> From<P> for KvService<E, P> {
             parser,
             shards: Default::default(),
             strategist: Default::default(),
+            tls: None,
             cfg: Default::default(),
+            hedge: None,
             // selector: Selector::Random,
         }
     }
@@ -63,6 +69,26 @@ where
     Req: Request,
     P: Protocol,
 {
+    #[inline]
+    fn hedge(&self) -> Option<&Hedge> {
+        self.hedge.as_ref()
+    }
+    // 与send一致：按key定位年库，按hash定位分片
+    fn route(&self, key: &[u8]) -> serde_json::Value {
+        let hash = self.strategist.hasher().hash(&key);
+        let key = RingSlice::from_slice(key);
+        let year = self.strategist.get_key(&key);
+        let idx = self.shard_idx(hash);
+        let mut table = String::new();
+        self.strategist.write_database_table(&mut table, &key);
+        serde_json::json!({
+            "hash": hash,
+            "year": year,
+            "shard": idx,
+            "table": table,
+            "backend": self.shards.get(year).get(idx).map(Shard::route),
+        })
+    }
 }
 
 impl<E, Req, P> Endpoint for KvService<E, P>
@@ -124,6 +150,8 @@ where
                 }
             }
             let ctx = req.ctx_mut();
+            // 访问的是从时，请求完成后更新从的异常检测统计
+            let slave = ctx.runs == 0 || (ctx.runs as usize) < shard.slaves.len();
             let (idx, endpoint) = if ctx.runs == 0 {
                 shard.select()
             } else {
@@ -138,7 +166,15 @@ where
             ctx.runs += 1;
             // 只重试一次，重试次数过多，可能会导致雪崩。如果不重试，现在的配额策略在当前副本也只会连续发送四次请求，问题也不大
             let try_next = ctx.runs == 1;
+            // 第一次访问从，并且还有其他的从时，允许对冲
+            let hedge = try_next && self.hedge.is_some() && shard.slaves.len() > 1;
             req.try_next(try_next);
+            if slave {
+                req.outlier(shard.outlier(idx).clone());
+            }
+            if hedge {
+                req.hedge();
+            }
             endpoint.send(req)
         } else {
             shard.master().send(req);
@@ -161,11 +197,12 @@ where
     fn load(&mut self) -> bool {
         self.cfg.load_guard().check_load(|| self.load_inner())
     }
-    fn update(&mut self, namespace: &str, cfg: &str) {
-        if let Some(ns) = KvNamespace::try_from(cfg) {
-            self.strategist = Strategist::try_from(&ns);
-            self.cfg.update(namespace, ns);
-        }
+    fn update(&mut self, namespace: &str, cfg: &str) -> Result<(), discovery::UpdateError> {
+        let ns = KvNamespace::validate(cfg)?;
+        self.strategist = Strategist::try_from(&ns);
+        crate::hedge::refresh(&mut self.hedge, ns.basic.hedge_percentile);
+        self.cfg.update(namespace, ns);
+        Ok(())
     }
 }
 impl<E, P> KvService<E, P>
@@ -181,8 +218,10 @@ where
         timeout: Timeout,
         res: ResOption,
     ) -> E {
+        // 连接数变化时，不复用原有的endpoint
+        let conns = crate::validate::conns(res.conns);
         match old.get_mut(addr).map(|endpoints| endpoints.pop()) {
-            Some(Some(end)) => end,
+            Some(Some(end)) if end.conns() == conns => end,
             _ => E::build_o(
                 &addr,
                 self.parser.clone(),
@@ -250,6 +289,11 @@ where
                 old.entry(addr).or_insert(Vec::new()).push(endpoint);
             }
         }
+        // tls配置变化时，不复用原有的endpoint
+        if self.tls.as_deref() != self.cfg.basic.tls.as_ref() {
+            old.clear();
+            self.tls = self.cfg.basic.tls.clone().map(Box::new);
+        }
 
         let mut rng = rand::thread_rng();
         for (interval, addrs_per_interval) in addrs {
@@ -262,6 +306,9 @@ where
                 let res_option = ResOption {
                     token: self.cfg.basic.password.clone(),
                     username: self.cfg.basic.user.clone(),
+                    conns: self.cfg.basic.conns_per_backend,
+                    tls: self.cfg.basic.tls.clone(),
+                    ..Default::default()
                 };
                 let master = self.take_or_build(
                     &mut old,
@@ -301,9 +348,8 @@ where
                     replicas.push(slave);
                 }
 
-                use crate::PerformanceTuning;
                 let shard = Shard::selector(
-                    self.cfg.basic.selector.tuning_mode(),
+                    &self.cfg.basic.selector,
                     master,
                     replicas,
                     self.cfg.basic.region_enabled,
@@ -466,3 +512,41 @@ impl<E> Shards<E> {
         &self.shards[index]
     }
 }
+
+impl<E: Endpoint + discovery::Inited> Shards<E> {
+    // 按年份区间输出分片，相邻年份使用同一组分片时合并为一个区间
+    pub(crate) fn inspect(&self) -> serde_json::Value {
+        let mut years = Vec::new();
+        let mut start = 0;
+        for i in 1..=YEAR_LEN {
+            if i < YEAR_LEN && self.index[i] == self.index[start] {
+                continue;
+            }
+            if self.index[start] != usize::MAX {
+                let shards = &self.shards[self.index[start]];
+                years.push(serde_json::json!({
+                    "start": YEAR_START + start as u16,
+                    "end": YEAR_START + i as u16 - 1,
+                    "shards": shards.iter().map(|s| s.inspect()).collect::<Vec<_>>(),
+                }));
+            }
+            start = i;
+        }
+        serde_json::Value::Array(years)
+    }
+}
+
+impl<E, P> crate::Inspect for KvService<E, P>
+where
+    E: Endpoint + discovery::Inited,
+{
+    fn inspect(&self) -> serde_json::Value {
+        serde_json::json!({ "years": self.shards.inspect() })
+    }
+}
+
+impl<E, P> crate::Validate for KvService<E, P> {
+    fn validate(&self, _namespace: &str, cfg: &str) -> Result<(), crate::validate::ConfigError> {
+        KvNamespace::validate(cfg).map(|_| ())
+    }
+}
@@ -6,12 +6,15 @@ pub mod cacheservice;
 pub mod kv;
 pub mod msgque;
 pub mod phantomservice;
+pub mod rediscluster;
 pub mod redisservice;
 pub mod select;
 pub mod uuid;
 pub mod vector;
 
 pub mod dns;
+pub mod hedge;
+pub mod validate;
 
 // 不同资源默认的超时时间
 const TO_PHANTOM_M: Timeout = Timeout::from_millis(200);
@@ -2,6 +2,8 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
+use crate::validate::ConfigError;
+
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct Namespace {
     #[serde(default)]
@@ -30,37 +32,28 @@ pub struct Basic {
 }
 
 impl Namespace {
-    pub(crate) fn try_from(cfg: &str, _namespace: &str) -> Option<Self> {
+    pub(crate) fn validate(cfg: &str, _namespace: &str) -> Result<Self, ConfigError> {
         log::debug!("mq/{} parsing - cfg: {}", _namespace, cfg);
-        match serde_yaml::from_str::<Namespace>(cfg) {
-            Ok(mut ns) => {
-                let bkends = ns.parse_and_sort_backends();
-                if bkends.is_none() {
-                    log::warn!("+++ mq malformed cfg:{}/{}", _namespace, cfg);
-                    return None;
-                }
-                let bkends = bkends.expect("mq");
-                bkends.into_iter().for_each(|(qsize, domain)| {
-                    ns.backends_qsize.push(qsize);
-                    ns.backends_flatten.push(domain);
-                });
-                return Some(ns);
-            }
-            Err(_e) => {
-                log::warn!("parse ns/{} failed: {:?}, cfg: {}", _namespace, _e, cfg);
-                return None;
-            }
+        let mut ns = serde_yaml::from_str::<Namespace>(cfg)?;
+        if ns.backends.is_empty() {
+            return Err(ConfigError::Empty("backends"));
         }
+        let bkends = ns.parse_and_sort_backends();
+        bkends.into_iter().for_each(|(qsize, domain)| {
+            ns.backends_qsize.push(qsize);
+            ns.backends_flatten.push(domain);
+        });
+        Ok(ns)
     }
 
     /// 解析backends，对新的backends进行按qsize递增排序；
     #[inline]
-    fn parse_and_sort_backends(&mut self) -> Option<Vec<(usize, String)>> {
+    fn parse_and_sort_backends(&mut self) -> Vec<(usize, String)> {
         let mut bkends: Vec<(usize, String)> = Vec::with_capacity(self.backends.len());
         self.backends
             .iter()
             .for_each(|(qsize, domains)| bkends.push((qsize.clone(), domains.to_string())));
         bkends.sort_by(|a, b| a.0.cmp(&b.0));
-        Some(bkends)
+        bkends
     }
 }
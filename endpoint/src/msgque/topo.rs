@@ -292,15 +292,15 @@ where
     E: Endpoint,
 {
     #[inline]
-    fn update(&mut self, name: &str, cfg: &str) {
-        if let Some(ns) = super::config::Namespace::try_from(cfg, name) {
-            log::debug!("+++ updating msgque for {}", name);
-
-            // 设置topo元数据
-            self.service = name.to_string();
-            self.timeout.adjust(ns.basic.timeout);
-            self.cfg.update(name, ns);
-        }
+    fn update(&mut self, name: &str, cfg: &str) -> Result<(), discovery::UpdateError> {
+        let ns = super::config::Namespace::validate(cfg, name)?;
+        log::debug!("+++ updating msgque for {}", name);
+
+        // 设置topo元数据
+        self.service = name.to_string();
+        self.timeout.adjust(ns.basic.timeout);
+        self.cfg.update(name, ns);
+        Ok(())
     }
 
     // backends、writers长度不一致的时候，且大于2分钟，都需要load
@@ -348,3 +348,11 @@ where
         )
     }
 }
+
+impl<E, P> crate::Inspect for MsgQue<E, P> {}
+
+impl<E, P> crate::Validate for MsgQue<E, P> {
+    fn validate(&self, namespace: &str, cfg: &str) -> Result<(), crate::validate::ConfigError> {
+        super::config::Namespace::validate(cfg, namespace).map(|_| ())
+    }
+}
@@ -2,6 +2,8 @@
 
 use serde::Deserialize;
 
+use crate::validate::{self, ConfigError};
+
 #[derive(Debug, Clone, Deserialize, Default)]
 pub struct PhantomNamespace {
     pub(crate) basic: Basic,
@@ -20,21 +22,25 @@ pub struct Basic {
     //resource_type: String,
     #[serde(default)]
     pub(crate) timeout_ms: u32,
+    // 每个后端建立的连接数，未配置时为1
+    #[serde(default)]
+    pub(crate) conns_per_backend: usize,
+    // 按顺序访问所有实例，不支持ewma，只用于校验
+    #[serde(default)]
+    pub(crate) selector: String,
 }
 
 impl PhantomNamespace {
     #[inline]
-    pub fn try_from(cfg: &str) -> Option<PhantomNamespace> {
-        let ns = serde_yaml::from_str::<PhantomNamespace>(cfg)
-            .map_err(|_err| {
-                log::warn!("parse phantome cfg failed:{:?}, cfg:{}", _err, cfg);
-            })
-            .ok()?;
+    pub fn validate(cfg: &str) -> Result<PhantomNamespace, ConfigError> {
+        let ns = serde_yaml::from_str::<PhantomNamespace>(cfg)?;
         if ns.backends.len() < 1 {
-            log::warn!("found malfromed phantome cfg:{}", cfg);
-            return None;
+            return Err(ConfigError::Empty("backends"));
         }
-        Some(ns)
+        validate::check_distribution(&ns.basic.distribution, &ns.backends)?;
+        validate::check_conns(ns.basic.conns_per_backend)?;
+        validate::check_no_ewma(&ns.basic.selector)?;
+        Ok(ns)
     }
 
     pub(super) fn timeout(&self) -> crate::Timeout {
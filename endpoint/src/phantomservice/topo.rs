@@ -4,7 +4,7 @@ use crate::{
     Endpoint, Endpoints, Topology,
 };
 use discovery::{Inited, TopologyWrite};
-use protocol::{Protocol, Request, Resource::Phantom};
+use protocol::{Protocol, Request, ResOption, Resource::Phantom};
 use sharding::{
     distribution::Range,
     hash::{Crc32, Hash, HashKey},
@@ -90,20 +90,19 @@ where
     E: Endpoint,
 {
     #[inline]
-    fn update(&mut self, namespace: &str, cfg: &str) {
-        if let Some(ns) = PhantomNamespace::try_from(cfg) {
-            log::info!("topo updating {:?} => {:?}", self, ns);
-            // phantome 只会使用crc32
-            //self.hasher = Hasher::from(&ns.basic.hash);
-            let dist = &ns.basic.distribution;
-            let num = dist
-                .find('-')
-                .map(|idx| dist[idx + 1..].parse::<u64>().ok())
-                .flatten();
-            self.distribution = Range::from(num, ns.backends.len());
+    fn update(&mut self, namespace: &str, cfg: &str) -> Result<(), discovery::UpdateError> {
+        let ns = PhantomNamespace::validate(cfg)?;
+        log::info!("topo updating {:?} => {:?}", self, ns);
+        // phantome 只会使用crc32
+        //self.hasher = Hasher::from(&ns.basic.hash);
+        let dist = &ns.basic.distribution;
+        let num = dist
+            .find('-')
+            .and_then(|idx| dist[idx + 1..].parse::<u64>().ok());
+        self.distribution = Range::from(num, ns.backends.len());
 
-            self.cfg.update(namespace, ns);
-        }
+        self.cfg.update(namespace, ns);
+        Ok(())
     }
 
     // 更新条件：
@@ -137,9 +136,13 @@ where
         self.streams.split_off(0).into_iter().for_each(|shard| {
             endpoints.cache(shard.into_inner());
         });
+        let res = ResOption {
+            conns: self.cfg.basic.conns_per_backend,
+            ..Default::default()
+        };
         addrs.iter().for_each(|shard| {
             assert!(!shard.is_empty());
-            let backends = endpoints.take_or_build(&*shard, self.cfg.timeout());
+            let backends = endpoints.take_or_build_with_res(shard, self.cfg.timeout(), res.clone());
             self.streams.push(Distance::from(backends));
         });
         // endpoints中如果还有stream，会被drop掉
@@ -166,3 +169,11 @@ impl<E, P> std::fmt::Debug for PhantomService<E, P> {
         write!(f, "{:?}", self.cfg)
     }
 }
+
+impl<E, P> crate::Inspect for PhantomService<E, P> {}
+
+impl<E, P> crate::Validate for PhantomService<E, P> {
+    fn validate(&self, _namespace: &str, cfg: &str) -> Result<(), crate::validate::ConfigError> {
+        PhantomNamespace::validate(cfg).map(|_| ())
+    }
+}
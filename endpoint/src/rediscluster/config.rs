@@ -0,0 +1,31 @@
+use serde::Deserialize;
+
+use crate::redisservice::config::Basic;
+use crate::validate::ConfigError;
+use crate::{TO_REDIS_M, Timeout};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RedisClusterNamespace {
+    // 只使用其中的超时、密码、认证用户等配置，hash固定为crc16，分布由CLUSTER SLOTS决定
+    pub(crate) basic: Basic,
+    // 种子节点，用于获取slot的分布，格式：域名:端口,域名:端口
+    pub(crate) backends: Vec<String>,
+}
+
+impl RedisClusterNamespace {
+    pub(super) fn validate(cfg: &str) -> Result<Self, ConfigError> {
+        let mut ns = serde_yaml::from_str::<RedisClusterNamespace>(cfg)?;
+        if ns.backends.is_empty() {
+            return Err(ConfigError::Empty("backends"));
+        }
+        crate::validate::check_tls(ns.basic.tls.as_ref())?;
+        crate::validate::check_conns(ns.basic.conns_per_backend)?;
+        ns.basic.decrypt()?;
+        Ok(ns)
+    }
+
+    #[inline]
+    pub(super) fn timeout_master(&self) -> Timeout {
+        TO_REDIS_M.to(self.basic.timeout_ms_master)
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, AtomicUsize, Ordering::*},
+};
+use std::time::Duration;
+
+use net::tls::TlsOption;
+use protocol::{
+    Error, Result,
+    redis::cluster::{self, SlotRange},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    time::timeout,
+};
+
+// 获取一次slot分布的超时时间
+const FETCH_TIMEOUT: Duration = Duration::from_secs(3);
+// 两次获取slot分布之间的最小间隔，避免迁移过程中大量MOVED导致频繁刷新
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+// 在后台通过CLUSTER SLOTS获取slot的分布，获取成功后通知topo重新load。
+// topo的所有副本共享同一个Fetcher，任何一个副本都可以触发刷新。
+#[derive(Default)]
+pub(super) struct Fetcher {
+    // 最近一次获取到的slot分布，及其版本，每获取成功一次版本加1
+    ranges: Mutex<Vec<SlotRange>>,
+    version: AtomicUsize,
+    refreshing: AtomicBool,
+}
+
+impl Fetcher {
+    #[inline]
+    pub(super) fn get(&self) -> (usize, Vec<SlotRange>) {
+        let ranges = self.ranges.lock().expect("slot ranges");
+        (self.version.load(Acquire), ranges.clone())
+    }
+    #[inline]
+    pub(super) fn refreshing(&self) -> bool {
+        self.refreshing.load(Acquire)
+    }
+    // 依次从nodes中获取slot分布，直到成功。同一时刻只有一个刷新任务
+    pub(super) fn refresh(
+        self: &Arc<Self>,
+        nodes: Vec<String>,
+        password: String,
+        tls: Option<TlsOption>,
+        updated: Arc<AtomicBool>,
+    ) {
+        if self.refreshing.swap(true, AcqRel) {
+            return;
+        }
+        let fetcher = self.clone();
+        rt::spawn(async move {
+            for node in &nodes {
+                match timeout(FETCH_TIMEOUT, fetch(node, &password, tls.as_ref())).await {
+                    Ok(Ok(ranges)) => {
+                        log::info!("cluster slots fetched from {}: {:?}", node, ranges);
+                        *fetcher.ranges.lock().expect("slot ranges") = ranges;
+                        fetcher.version.fetch_add(1, AcqRel);
+                        updated.store(true, Release);
+                        break;
+                    }
+                    Ok(Err(e)) => {
+                        log::warn!("failed to fetch cluster slots from {}: {:?}", node, e)
+                    }
+                    Err(_) => log::warn!("fetch cluster slots from {} timeout", node),
+                }
+            }
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+            fetcher.refreshing.store(false, Release);
+        });
+    }
+}
+
+async fn fetch(addr: &str, password: &str, tls: Option<&TlsOption>) -> Result<Vec<SlotRange>> {
+    let mut stream = net::tls::connect(addr, tls).await?;
+    let mut req = Vec::with_capacity(64);
+    if !password.is_empty() {
+        req.extend_from_slice(b"*2\r\n$4\r\nAUTH\r\n$");
+        req.extend_from_slice(password.len().to_string().as_bytes());
+        req.extend_from_slice(b"\r\n");
+        req.extend_from_slice(password.as_bytes());
+        req.extend_from_slice(b"\r\n");
+    }
+    req.extend_from_slice(cluster::CLUSTER_SLOTS);
+    stream.write_all(&req).await?;
+
+    let mut buf = Vec::with_capacity(4096);
+    // 跳过AUTH的响应
+    let mut oft = 0;
+    loop {
+        if stream.read_buf(&mut buf).await? == 0 {
+            return Err(Error::Eof);
+        }
+        if !password.is_empty() && oft == 0 {
+            let Some(end) = buf.windows(2).position(|w| w == b"\r\n") else {
+                continue;
+            };
+            if buf[0] != b'+' {
+                return Err(Error::AuthFailed);
+            }
+            oft = end + 2;
+        }
+        match cluster::parse_slots(&buf[oft..]) {
+            Err(Error::ProtocolIncomplete(_)) => continue,
+            ranges => return ranges,
+        }
+    }
+}
@@ -0,0 +1,20 @@
+pub(super) mod config;
+mod fetcher;
+pub mod topo;
+
+struct Context {
+    runs: u16,      // 运行的次数
+    shard_idx: u16, // sendto_all时，下一个要发送的分片
+    _ignore: u32,
+}
+
+// Context由请求的u64上下文转换而来，大小、对齐都不能超过u64
+const _: () = assert!(
+    std::mem::size_of::<Context>() == std::mem::size_of::<u64>()
+        && std::mem::align_of::<Context>() <= std::mem::align_of::<u64>()
+);
+
+#[inline]
+fn transmute(ctx: &mut u64) -> &mut Context {
+    unsafe { std::mem::transmute(ctx) }
+}
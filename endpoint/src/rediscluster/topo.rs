@@ -0,0 +1,367 @@
+use std::sync::Arc;
+
+use crate::{
+    Endpoint, Endpoints, Timeout, Topology,
+    dns::{DnsConfig, DnsLookup},
+};
+use discovery::TopologyWrite;
+use ds::MemGuard;
+use protocol::{
+    Acl, Error, Protocol, RedisFlager, Request, ResOption,
+    Resource::Redis,
+    redis::cluster::{ASKING, Redirect},
+};
+use sharding::hash::{Crc16, Hash, HashKey, crc16::SLOTS};
+
+use super::{config::RedisClusterNamespace, fetcher::Fetcher};
+
+// slot未分配到任何master
+const NO_SHARD: u16 = u16::MAX;
+
+#[derive(Clone)]
+pub struct RedisCluster<E, P> {
+    // 每个master一个分片，slave不参与读写
+    shards: Vec<E>,
+    // 每个slot所在的分片
+    slots: Vec<u16>,
+    parser: P,
+    cfg: Box<DnsConfig<RedisClusterNamespace>>,
+    password: String,
+    resp3: bool,
+    // 后台获取的slot分布，及当前已经load的版本
+    fetcher: Arc<Fetcher>,
+    version: usize,
+}
+impl<E, P> From<P> for RedisCluster<E, P> {
+    #[inline]
+    fn from(parser: P) -> Self {
+        Self {
+            parser,
+            shards: Default::default(),
+            slots: vec![NO_SHARD; SLOTS],
+            cfg: Default::default(),
+            password: Default::default(),
+            resp3: false,
+            fetcher: Default::default(),
+            version: 0,
+        }
+    }
+}
+
+impl<E, P> Hash for RedisCluster<E, P>
+where
+    E: Endpoint,
+    P: Protocol,
+{
+    // hash即key所在的slot
+    #[inline]
+    fn hash<K: HashKey>(&self, k: &K) -> i64 {
+        Crc16.hash(k)
+    }
+}
+
+impl<E, Req, P> Topology for RedisCluster<E, P>
+where
+    E: Endpoint<Item = Req>,
+    Req: Request,
+    P: Protocol,
+{
+    #[inline]
+    fn dedicated_backend(&self, hash: i64) -> Option<(String, ResOption, Timeout)> {
+        let shard = self.shards.get(self.shard_idx(hash))?;
+        let option = ResOption {
+            token: self.password.clone(),
+            username: String::new(),
+            resp3: false,
+            tls: self.cfg.basic.tls.clone(),
+            conns: 1,
+        };
+        Some((shard.addr().to_string(), option, self.cfg.timeout_master()))
+    }
+    #[inline]
+    fn shards(&self) -> usize {
+        self.shards.len()
+    }
+    #[inline]
+    fn max_set_members(&self) -> usize {
+        self.cfg.basic.max_set_members
+    }
+    #[inline]
+    fn require_auth(&self) -> bool {
+        !self.cfg.basic.users.is_empty()
+    }
+    #[inline]
+    fn resp3(&self) -> bool {
+        self.cfg.basic.resp3
+    }
+    #[inline]
+    fn auth(&self, user: &[u8], pass: &[u8]) -> Option<Acl> {
+        if !self.require_auth() {
+            return Some(Acl::ALL);
+        }
+        self.cfg
+            .basic
+            .users
+            .iter()
+            .find(|u| u.matches(user, pass))
+            .and_then(|u| u.acl())
+    }
+    // hash即slot
+    fn route(&self, key: &[u8]) -> serde_json::Value {
+        let slot = self.hash(&key);
+        let idx = self.shard_idx(slot);
+        serde_json::json!({
+            "hash": slot,
+            "shard": idx,
+            "backend": self.shards.get(idx).map(|e| e.addr()),
+        })
+    }
+}
+
+impl<E, Req, P> Endpoint for RedisCluster<E, P>
+where
+    E: Endpoint<Item = Req>,
+    Req: Request,
+    P: Protocol,
+{
+    type Item = Req;
+    #[inline]
+    fn send(&self, mut req: Self::Item) {
+        debug_assert_ne!(self.shards.len(), 0);
+
+        if req.sendto_all() {
+            //全节点分发请求
+            let ctx = super::transmute(req.context_mut());
+            let idx = ctx.shard_idx as usize;
+            ctx.shard_idx += 1;
+            req.write_back(idx < self.shards.len() - 1);
+            return self.shards[idx].send(req);
+        }
+
+        let ctx = super::transmute(req.context_mut());
+        ctx.runs += 1;
+        if ctx.runs > 1 {
+            return self.redirect(req);
+        }
+
+        // scan、keys、dbsize等遍历所有分片的请求，在解析时已经指定了分片
+        let shard_idx = req.shard().unwrap_or_else(|| self.shard_idx(req.hash()));
+        assert!(
+            shard_idx < self.shards.len(),
+            "{} {:?} {}",
+            shard_idx,
+            req,
+            self
+        );
+        log::debug!("{} send {}=>{:?}", self, shard_idx, req);
+
+        // 收到MOVED、ASK时，到指定的节点重试
+        req.try_next(true);
+        req.retry_on_rsp_notok(true);
+        req.redirect(true);
+        self.shards[shard_idx].send(req)
+    }
+
+    #[inline]
+    fn shard_idx(&self, hash: i64) -> usize {
+        // slot未分配时发给第一个分片，由其返回MOVED或CLUSTERDOWN
+        match self.slots[hash as usize & (SLOTS - 1)] {
+            NO_SHARD => 0,
+            idx => idx as usize,
+        }
+    }
+}
+
+impl<E, Req, P> RedisCluster<E, P>
+where
+    E: Endpoint<Item = Req>,
+    Req: Request,
+    P: Protocol,
+{
+    // 按MOVED、ASK重定向。MOVED说明slot已经迁移完成，需要刷新slot分布；
+    // ASK说明slot正在迁移，需要在目标节点先发送ASKING，不刷新slot分布。
+    #[inline]
+    fn redirect(&self, mut req: Req) {
+        let Some(redirect) = req.last_response().and_then(|rsp| Redirect::parse(rsp)) else {
+            // 没有收到响应时，写请求可能已经执行，不再重试；其他异常响应直接返回给client
+            req.try_next(false);
+            return req.on_err(Error::Waiting);
+        };
+        log::debug!("{} redirect {:?} => {:?}", self, redirect, req);
+        if !redirect.ask {
+            self.refresh();
+        }
+        let Some(shard) = self.shards.iter().find(|s| s.addr() == redirect.addr) else {
+            // 新加入的节点，刷新slot分布之后才能访问，本次不再重试，把重定向响应直接返回给client
+            self.refresh();
+            req.try_next(false);
+            return req.on_err(Error::TopChanged);
+        };
+        if redirect.ask && req.prefix_responses() == 0 {
+            let mut cmd = Vec::with_capacity(ASKING.len() + req.len());
+            cmd.extend_from_slice(ASKING);
+            req.copy_to_vec(&mut cmd);
+            req.reshape(MemGuard::from_vec(cmd));
+            req.prefix(1);
+        }
+        shard.send(req)
+    }
+}
+
+impl<E, P> RedisCluster<E, P>
+where
+    E: Endpoint,
+{
+    // 从当前的master及种子节点获取slot分布
+    fn refresh(&self) {
+        if self.fetcher.refreshing() {
+            return;
+        }
+        let mut nodes: Vec<String> = self.shards.iter().map(|s| s.addr().to_string()).collect();
+        nodes.extend(self.cfg.shards_url.flatten_lookup().unwrap_or_default());
+        let updated = self.cfg.updated.clone();
+        let tls = self.cfg.basic.tls.clone();
+        self.fetcher
+            .refresh(nodes, self.password.clone(), tls, updated);
+    }
+}
+
+impl<E, P> TopologyWrite for RedisCluster<E, P>
+where
+    P: Protocol,
+    E: Endpoint,
+{
+    #[inline]
+    fn update(&mut self, namespace: &str, cfg: &str) -> Result<(), discovery::UpdateError> {
+        let ns = RedisClusterNamespace::validate(cfg)?;
+        self.cfg.update(namespace, ns);
+        Ok(())
+    }
+    // 配置、dns变化，或者获取到新的slot分布时，由fetcher设置为需要load
+    #[inline]
+    fn need_load(&self) -> bool {
+        self.cfg.need_load()
+    }
+    #[inline]
+    fn load(&mut self) -> bool {
+        self.cfg
+            .load_guard()
+            .check_load(|| self.load_inner().is_some())
+    }
+}
+
+impl<E, P> RedisCluster<E, P>
+where
+    P: Protocol,
+    E: Endpoint,
+{
+    #[inline]
+    fn load_inner(&mut self) -> Option<()> {
+        // 种子节点需要都能解析出ip
+        self.cfg.shards_url.flatten_lookup()?;
+
+        // 如果密码、协议版本不一致，则清空所有现有的shard
+        if self.password != self.cfg.basic.password || self.resp3 != self.cfg.basic.resp3 {
+            self.shards.clear();
+            self.password = self.cfg.basic.password.clone();
+            self.resp3 = self.cfg.basic.resp3;
+        }
+
+        // 不是由新的slot分布触发的load，说明配置或dns有变化，重新获取slot分布
+        let (version, ranges) = self.fetcher.get();
+        if version == self.version {
+            self.refresh();
+        }
+        if ranges.is_empty() {
+            return None;
+        }
+
+        let mut masters: Vec<String> = Vec::new();
+        let mut slots = vec![NO_SHARD; SLOTS];
+        for range in &ranges {
+            let master = &range.nodes[0];
+            let idx = match masters.iter().position(|m| m == master) {
+                Some(idx) => idx,
+                None => {
+                    masters.push(master.clone());
+                    masters.len() - 1
+                }
+            };
+            slots[range.start..=range.end].fill(idx as u16);
+        }
+
+        let res_option = ResOption {
+            token: self.cfg.basic.password.clone(),
+            username: String::new(),
+            // 开启后共享连接协商RESP3，响应按client的协议版本转换
+            resp3: self.cfg.basic.resp3,
+            tls: self.cfg.basic.tls.clone(),
+            conns: self.cfg.basic.conns_per_backend,
+        };
+        let mut endpoints: Endpoints<'_, P, E> =
+            Endpoints::new(&self.cfg.service, &self.parser, Redis);
+        endpoints.cache(self.shards.split_off(0));
+        self.shards =
+            endpoints.take_or_build_with_res(&masters, self.cfg.timeout_master(), res_option);
+        self.slots = slots;
+        self.version = version;
+        log::info!("{} loaded slots version:{}", self, version);
+        Some(())
+    }
+}
+
+impl<E, P> discovery::Inited for RedisCluster<E, P>
+where
+    E: discovery::Inited,
+{
+    // 每一个master都初始化完成
+    #[inline]
+    fn inited(&self) -> bool {
+        !self.shards.is_empty() && self.shards.iter().all(|e| e.inited())
+    }
+}
+
+impl<E, P> std::fmt::Display for RedisCluster<E, P>
+where
+    E: Endpoint,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisCluster")
+            .field("service", &self.cfg.service)
+            .field("shards", &self.shards.len())
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+impl<E, P> crate::Inspect for RedisCluster<E, P>
+where
+    E: Endpoint + discovery::Inited,
+{
+    // slots按连续的区间输出：[start, end, shard]
+    fn inspect(&self) -> serde_json::Value {
+        let mut slots = Vec::new();
+        let mut start = 0;
+        for i in 1..=SLOTS {
+            if i < SLOTS && self.slots[i] == self.slots[start] {
+                continue;
+            }
+            if self.slots[start] != NO_SHARD {
+                slots.push((start, i - 1, self.slots[start]));
+            }
+            start = i;
+        }
+        let shards: Vec<_> = self.shards.iter().map(crate::inspect_backend).collect();
+        serde_json::json!({
+            "shards": shards,
+            "slots": slots,
+            "version": self.version,
+        })
+    }
+}
+
+impl<E, P> crate::Validate for RedisCluster<E, P> {
+    fn validate(&self, _namespace: &str, cfg: &str) -> Result<(), crate::validate::ConfigError> {
+        RedisClusterNamespace::validate(cfg).map(|_| ())
+    }
+}
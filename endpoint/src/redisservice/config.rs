@@ -2,7 +2,10 @@ use base64::{Engine as _, engine::general_purpose};
 use serde::{Deserialize, Serialize};
 use std::{collections::HashSet, fmt::Debug, fs};
 
+use crate::validate::{self, ConfigError};
 use crate::{TO_REDIS_M, TO_REDIS_S, Timeout};
+use net::tls::TlsOption;
+use protocol::Acl;
 
 // range/modrange 对应的distribution配置项如果有此后缀，不进行后端数量的校验
 const NO_CHECK_SUFFIX: &str = "-nocheck";
@@ -41,16 +44,92 @@ pub struct Basic {
     pub(crate) master_read: bool,
     #[serde(default)]
     pub(crate) password: String,
+    // 未通过hashkey指定分片的sinter、sunion、sdiff，按key从各分片获取成员后在mesh内计算，
+    // 各分片返回的成员总数不能超过该值；0表示不支持，只能通过hashkey指定分片
+    #[serde(default)]
+    pub(crate) max_set_members: usize,
+    // client访问需要认证的用户，为空时不需要认证。密码与password一样是加密的
+    #[serde(default)]
+    pub(crate) users: Vec<User>,
+    // 配置后，与后端的连接使用TLS
+    #[serde(default)]
+    pub(crate) tls: Option<TlsOption>,
+    // 每个后端建立的连接数，未配置时为1
+    #[serde(default)]
+    pub(crate) conns_per_backend: usize,
+    // 共享连接是否与后端协商RESP3，开启后client才能通过hello 3切换到RESP3。后端需要是redis 6.0及以上版本
+    #[serde(default)]
+    pub(crate) resp3: bool,
+    // 读从的请求超过该分位数的耗时仍未完成时，向下一个从发送对冲请求；0表示不开启
+    #[serde(default)]
+    pub(crate) hedge_percentile: u8,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct User {
+    // auth只带密码时，使用default用户
+    #[serde(default = "User::default_name")]
+    pub(crate) name: String,
+    pub(crate) password: String,
+    // 允许执行的指令类型：read、write、meta，为空时允许所有指令
+    #[serde(default)]
+    pub(crate) acl: Vec<String>,
+}
+
+impl User {
+    fn default_name() -> String {
+        "default".to_string()
+    }
+    // 密码按固定耗时比较，避免通过响应时间猜测密码
+    #[inline]
+    pub(crate) fn matches(&self, name: &[u8], pass: &[u8]) -> bool {
+        self.name.as_bytes() == name && eq_constant_time(self.password.as_bytes(), pass)
+    }
+    pub(crate) fn acl(&self) -> Option<Acl> {
+        match self.acl.is_empty() {
+            true => Some(Acl::ALL),
+            false => self.acl.iter().try_fold(Acl::from_bits(0), |acl, name| {
+                Some(acl.union(Acl::from_name(name)?))
+            }),
+        }
+    }
+}
+
+// 耗时只与两者中较长的长度有关，与内容及第一个不同字节的位置无关
+fn eq_constant_time(a: &[u8], b: &[u8]) -> bool {
+    let mut diff = a.len() ^ b.len();
+    for i in 0..a.len().max(b.len()) {
+        let x = a.get(i).copied().unwrap_or(0);
+        let y = b.get(i).copied().unwrap_or(0);
+        diff |= (x ^ y) as usize;
+    }
+    std::hint::black_box(diff) == 0
+}
+
+impl Basic {
+    // 解密password及所有用户的密码，同时校验用户的acl
+    pub(crate) fn decrypt(&mut self) -> Result<(), ConfigError> {
+        if !self.password.is_empty() {
+            self.password = RedisNamespace::decrypt_password(&self.password)
+                .map_err(|e| ConfigError::Decrypt(e.to_string()))?;
+        }
+        for user in &mut self.users {
+            if user.acl().is_none() {
+                let acl = format!("acl of user {}: {:?}", user.name, user.acl);
+                return Err(ConfigError::Invalid(acl));
+            }
+            user.password = RedisNamespace::decrypt_password(&user.password)
+                .map_err(|e| ConfigError::Decrypt(format!("user {}: {}", user.name, e)))?;
+        }
+        Ok(())
+    }
 }
 
 impl RedisNamespace {
-    pub(super) fn try_from(cfg: &str) -> Option<Self> {
-        let mut ns = serde_yaml::from_str::<RedisNamespace>(cfg)
-            .map_err(|e| log::info!("failed to parse redis config:{} => {e:?}", cfg))
-            .ok()?;
+    pub(super) fn validate(cfg: &str) -> Result<Self, ConfigError> {
+        let mut ns = serde_yaml::from_str::<RedisNamespace>(cfg)?;
         if ns.backends.len() == 0 {
-            log::warn!("cfg invalid:{:?}", ns);
-            return None;
+            return Err(ConfigError::Empty("backends"));
         }
 
         // check backends，分离出names
@@ -72,24 +151,16 @@ impl RedisNamespace {
             log::info!("+++ found redis backends with name: {}", cfg);
         }
 
-        if !ns.validate_and_correct() {
-            log::error!("malformed names or shards {}: {}", ns.backends.len(), cfg);
-            return None;
-        }
-
-        // 解密密码
-        if !ns.basic.password.is_empty() {
-            match ns.decrypt_password() {
-                Ok(password) => ns.basic.password = password,
-                Err(e) => {
-                    log::warn!("failed to decrypt password, e:{}", e);
-                    return None;
-                }
-            }
-        }
+        ns.validate_and_correct()?;
+        validate::check_tls(ns.basic.tls.as_ref())?;
+        validate::check_conns(ns.basic.conns_per_backend)?;
+        validate::check_hedge(ns.basic.hedge_percentile)?;
+        validate::check_hash(&ns.basic.hash)?;
+        validate::check_distribution(&ns.basic.distribution, &ns.backends)?;
+        ns.basic.decrypt()?;
 
         log::debug!("parsed redis config:{}/{}", ns.basic.distribution, cfg);
-        return Some(ns);
+        Ok(ns)
     }
 
     fn default_selector() -> String {
@@ -116,7 +187,7 @@ impl RedisNamespace {
 
     /// 对配置进行合法性校验，当前只检验部分dist的后端数量
     #[inline(always)]
-    fn validate_and_correct(&mut self) -> bool {
+    fn validate_and_correct(&mut self) -> Result<(), ConfigError> {
         let dist = &self.basic.distribution;
 
         // 需要检测dist时（默认场景），对于range/modrange类型的dist需要限制后端数量为2^n
@@ -126,34 +197,42 @@ impl RedisNamespace {
             // 对于range、morange，如果后有-nocheck后缀，不进行后端数量检测，并将该后缀清理掉
             if dist.ends_with(NO_CHECK_SUFFIX) {
                 self.basic.distribution = dist.trim_end_matches(NO_CHECK_SUFFIX).to_string();
-                return true;
+                return Ok(());
             }
             let len = self.backends.len();
             let power_two = len > 0 && ((len & len - 1) == 0);
             if !power_two {
-                return false;
+                return Err(ConfigError::ShardMismatch {
+                    what: "range shards should be power of two",
+                    expected: len.next_power_of_two(),
+                    actual: len,
+                });
             }
         }
 
         // 如果backend有name，则所有的后端都必须有name，且name不能重复
         if self.backend_names.len() > 0 {
             if self.backend_names.len() != self.backends.len() {
-                return false;
+                return Err(ConfigError::ShardMismatch {
+                    what: "backend names",
+                    expected: self.backends.len(),
+                    actual: self.backend_names.len(),
+                });
             }
             let mut names_unique = HashSet::with_capacity(self.backend_names.len());
             names_unique.extend(self.backend_names.clone());
             if names_unique.len() != self.backend_names.len() {
-                return false;
+                return Err(ConfigError::Invalid("duplicated backend names".to_string()));
             }
         }
 
-        true
+        Ok(())
     }
 
     #[inline]
-    fn decrypt_password(&self) -> Result<String, Box<dyn std::error::Error>> {
+    fn decrypt_password(password: &str) -> Result<String, Box<dyn std::error::Error>> {
         let key_pem = fs::read_to_string(&context::get().redis_key_path)?;
-        let encrypted_data = general_purpose::STANDARD.decode(self.basic.password.as_bytes())?;
+        let encrypted_data = general_purpose::STANDARD.decode(password.as_bytes())?;
         let decrypted_data = ds::decrypt::decrypt_password(&key_pem, &encrypted_data)?;
         let decrypted_string = String::from_utf8(decrypted_data)?;
         Ok(decrypted_string)
@@ -1,10 +1,12 @@
 use crate::{
-    Endpoint, Endpoints, PerformanceTuning, Topology,
+    Endpoint, Endpoints, Timeout, Topology,
     dns::{DnsConfig, DnsLookup},
+    hedge::Hedge,
     shards::Shard,
 };
 use discovery::TopologyWrite;
-use protocol::{Protocol, RedisFlager, Request, ResOption, Resource::Redis};
+use net::tls::TlsOption;
+use protocol::{Acl, Protocol, RedisFlager, Request, ResOption, Resource::Redis};
 use sharding::distribution::Distribute;
 use sharding::hash::{Hash, HashKey, Hasher};
 
@@ -19,6 +21,9 @@ pub struct RedisService<E, P> {
     parser: P,
     cfg: Box<DnsConfig<RedisNamespace>>,
     password: String,
+    resp3: bool,
+    tls: Option<Box<TlsOption>>,
+    hedge: Option<Hedge>,
 }
 impl<E, P> From<P> for RedisService<E, P> {
     #[inline]
@@ -30,6 +35,9 @@ impl<E, P> From<P> for RedisService<E, P> {
             distribute: Default::default(),
             cfg: Default::default(),
             password: Default::default(),
+            resp3: false,
+            tls: None,
+            hedge: None,
         }
     }
 }
@@ -51,6 +59,66 @@ where
     Req: Request,
     P: Protocol,
 {
+    // 订阅、事务需要独占连接，不复用分片的后端连接，这里只返回master的地址，由调用方自行建连
+    #[inline]
+    fn dedicated_backend(&self, hash: i64) -> Option<(String, ResOption, Timeout)> {
+        let shard = self.shards.get(self.distribute.index(hash))?;
+        // 独占连接的响应直接透传给client，不能协商RESP3
+        let option = ResOption {
+            token: self.password.clone(),
+            username: String::new(),
+            resp3: false,
+            tls: self.cfg.basic.tls.clone(),
+            conns: 1,
+        };
+        Some((
+            shard.master().addr().to_string(),
+            option,
+            self.cfg.timeout_master(),
+        ))
+    }
+    #[inline]
+    fn shards(&self) -> usize {
+        self.shards.len()
+    }
+    #[inline]
+    fn max_set_members(&self) -> usize {
+        self.cfg.basic.max_set_members
+    }
+    #[inline]
+    fn hedge(&self) -> Option<&Hedge> {
+        self.hedge.as_ref()
+    }
+    #[inline]
+    fn require_auth(&self) -> bool {
+        !self.cfg.basic.users.is_empty()
+    }
+    #[inline]
+    fn resp3(&self) -> bool {
+        self.cfg.basic.resp3
+    }
+    // 未配置用户时不需要认证，auth总是成功
+    #[inline]
+    fn auth(&self, user: &[u8], pass: &[u8]) -> Option<Acl> {
+        if !self.require_auth() {
+            return Some(Acl::ALL);
+        }
+        self.cfg
+            .basic
+            .users
+            .iter()
+            .find(|u| u.matches(user, pass))
+            .and_then(|u| u.acl())
+    }
+    fn route(&self, key: &[u8]) -> serde_json::Value {
+        let hash = self.hasher.hash(&key);
+        let idx = self.distribute.index(hash);
+        serde_json::json!({
+            "hash": hash,
+            "shard": idx,
+            "backend": self.shards.get(idx).map(Shard::route),
+        })
+    }
 }
 
 impl<E, Req, P> Endpoint for RedisService<E, P>
@@ -71,6 +139,9 @@ where
             ctx.shard_idx += 1;
             req.write_back(idx < self.shards.len() - 1);
             idx
+        } else if let Some(idx) = req.shard() {
+            // scan、keys、dbsize等遍历所有分片的请求，在解析时已经指定了分片
+            idx
         } else {
             self.distribute.index(req.hash())
         };
@@ -88,6 +159,8 @@ where
                 }
             }
             let ctx = super::transmute(req.context_mut());
+            // 访问的是从时，请求完成后更新从的异常检测统计
+            let slave = ctx.runs == 0 || (ctx.runs as usize) < shard.slaves.len();
             let (idx, endpoint) = if ctx.runs == 0 {
                 shard.select()
             } else {
@@ -106,7 +179,15 @@ where
             //let try_next = ctx.runs == 1 || (ctx.runs as usize) < shard.slaves.len();
             // 只重试一次，重试次数过多，可能会导致雪崩。
             let try_next = ctx.runs == 1;
+            // 第一次访问从，并且还有其他的从时，允许对冲
+            let hedge = try_next && self.hedge.is_some() && shard.slaves.len() > 1;
             req.try_next(try_next);
+            if slave {
+                req.outlier(shard.outlier(idx).clone());
+            }
+            if hedge {
+                req.hedge();
+            }
 
             endpoint.send(req)
         } else {
@@ -125,17 +206,18 @@ where
     E: Endpoint,
 {
     #[inline]
-    fn update(&mut self, namespace: &str, cfg: &str) {
-        if let Some(ns) = RedisNamespace::try_from(cfg) {
-            self.hasher = Hasher::from(&ns.basic.hash);
-            let backends = match ns.backend_names.len() {
-                0 => &ns.backends,
-                _ => &ns.backend_names,
-            };
-            log::debug!("+++ dist with backends:{:?}", backends);
-            self.distribute = Distribute::from(ns.basic.distribution.as_str(), backends);
-            self.cfg.update(namespace, ns);
-        }
+    fn update(&mut self, namespace: &str, cfg: &str) -> Result<(), discovery::UpdateError> {
+        let ns = RedisNamespace::validate(cfg)?;
+        self.hasher = Hasher::from(&ns.basic.hash);
+        let backends = match ns.backend_names.len() {
+            0 => &ns.backends,
+            _ => &ns.backend_names,
+        };
+        log::debug!("+++ dist with backends:{:?}", backends);
+        self.distribute = Distribute::from(ns.basic.distribution.as_str(), backends);
+        crate::hedge::refresh(&mut self.hedge, ns.basic.hedge_percentile);
+        self.cfg.update(namespace, ns);
+        Ok(())
     }
     // 满足以下两个条件之一，则需要更新：
     // 1. 存在某dns未成功解析，并且dns数据准备就绪
@@ -190,16 +272,25 @@ where
         assert_eq!(addrs.len(), self.cfg.shards_url.len());
         // 到这之后，所有的shard都能解析出ip
 
-        // 如果密码不一致，则清空所有现有的shard
-        if self.password != self.cfg.basic.password {
+        // 如果密码、协议版本、tls不一致，则清空所有现有的shard
+        if self.password != self.cfg.basic.password
+            || self.resp3 != self.cfg.basic.resp3
+            || self.tls.as_deref() != self.cfg.basic.tls.as_ref()
+        {
             self.shards.clear();
             self.password = self.cfg.basic.password.clone();
+            self.resp3 = self.cfg.basic.resp3;
+            self.tls = self.cfg.basic.tls.clone().map(Box::new);
         }
 
         // Redis认证只需要密码，无需用户名
         let res_option = ResOption {
             token: self.cfg.basic.password.clone(),
             username: String::new(), // Redis不需要用户名
+            // 开启后共享连接协商RESP3，响应按client的协议版本转换
+            resp3: self.cfg.basic.resp3,
+            tls: self.cfg.basic.tls.clone(),
+            conns: self.cfg.basic.conns_per_backend,
         };
 
         // 把所有的endpoints cache下来
@@ -226,7 +317,7 @@ where
                 res_option.clone(),
             );
             let shard = Shard::selector(
-                self.cfg.basic.selector.tuning_mode(),
+                &self.cfg.basic.selector,
                 master,
                 slaves,
                 self.cfg.basic.region_enabled,
@@ -252,3 +343,19 @@ where
             .finish()
     }
 }
+
+impl<E, P> crate::Inspect for RedisService<E, P>
+where
+    E: Endpoint + discovery::Inited,
+{
+    fn inspect(&self) -> serde_json::Value {
+        let shards: Vec<_> = self.shards.iter().map(|s| s.inspect()).collect();
+        serde_json::json!({ "shards": shards })
+    }
+}
+
+impl<E, P> crate::Validate for RedisService<E, P> {
+    fn validate(&self, _namespace: &str, cfg: &str) -> Result<(), crate::validate::ConfigError> {
+        RedisNamespace::validate(cfg).map(|_| ())
+    }
+}
@@ -1,5 +1,6 @@
 use discovery::distance::{Addr, ByDistance};
 use protocol::BackendQuota;
+use protocol::outlier::Outlier;
 use rand::Rng;
 use std::sync::atomic::{AtomicUsize, Ordering::*};
 
@@ -10,9 +11,13 @@ pub struct Distance<T> {
     len_local: u16,  // 实际使用的local实例数量
     len_region: u16, // 通过排序计算出的可用区内的实例数量，len_region <= len_local
     backend_quota: bool,
+    // ewma selector：按副本的访问代价选择，不使用quota
+    ewma: bool,
     region_enabled: bool,
     idx: AtomicUsize,
     replicas: Vec<(T, BackendQuota)>,
+    // 与replicas一一对应，被摘除的副本在选择时跳过
+    outliers: Vec<Outlier>,
 }
 
 impl<T: Clone> Clone for Distance<T> {
@@ -21,11 +26,13 @@ impl<T: Clone> Clone for Distance<T> {
             len_local: self.len_local.clone(),
             len_region: self.len_region.clone(),
             backend_quota: self.backend_quota.clone(),
+            ewma: self.ewma,
             region_enabled: self.region_enabled.clone(),
             //不同Distance之间没必要共享idx，也许应该设置为0，但当前对外暴露的更新接口更新replicas时都会更新idx，没有问题，否则可能产生越界
             //警告：更新replicas需要同时更新idx
             idx: self.idx.load(Relaxed).into(),
             replicas: self.replicas.clone(),
+            outliers: self.outliers.clone(),
         }
     }
 }
@@ -35,9 +42,11 @@ impl<T> Distance<T> {
             len_local: 0,
             len_region: 0,
             backend_quota: false,
+            ewma: false,
             region_enabled: false,
             idx: Default::default(),
             replicas: Vec::new(),
+            outliers: Vec::new(),
         }
     }
     #[inline]
@@ -84,7 +93,7 @@ impl<T> Distance<T> {
             replicas.len()
         };
 
-        me.refresh(unsafe { std::mem::transmute(replicas) });
+        me.refresh(unsafe { std::mem::transmute(replicas) }, |r| r.addr());
 
         // 性能模式当前实现为按时间quota访问后端资源
         me.backend_quota = performance;
@@ -93,6 +102,16 @@ impl<T> Distance<T> {
 
         me
     }
+    // 按namespace配置的selector创建
+    pub fn with_selector(replicas: Vec<T>, selector: &str, region_first: bool) -> Self
+    where
+        T: Endpoint,
+    {
+        let ewma = Selector::from(selector) == Selector::Ewma;
+        let mut me = Self::with_mode(replicas, !ewma && selector.tuning_mode(), region_first);
+        me.ewma = ewma;
+        me
+    }
     // None说明没有启动
     pub fn len_region(&self) -> Option<u16> {
         self.region_enabled.then(|| self.len_region)
@@ -104,17 +123,22 @@ impl<T> Distance<T> {
     {
         Self::with_mode(replicas, true, false)
     }
-    // 同时更新配额
-    fn refresh(&mut self, replicas: Vec<T>) {
+    // 同时更新配额及异常检测的统计，地址相同的副本保留摘除状态
+    fn refresh(&mut self, replicas: Vec<T>, addr: impl Fn(&T) -> &str) {
+        self.outliers = Outlier::regroup(&self.outliers, replicas.iter().map(addr));
         self.replicas = replicas
             .into_iter()
             .map(|r| (r, BackendQuota::default()))
             .collect();
     }
     //和新建不等价，谨慎使用
-    pub fn update(&mut self, replicas: Vec<T>, topn: usize, is_performance: bool) {
+    pub fn update(&mut self, replicas: Vec<T>, topn: usize, is_performance: bool)
+    where
+        T: Addr,
+    {
         self.backend_quota = is_performance; // 性能模式当前实现为按时间quota访问后端资源
-        self.refresh(replicas);
+        self.ewma = false;
+        self.refresh(replicas, |r| r.addr());
         self.topn(topn);
     }
     // 只取前n个进行批量随机访问
@@ -130,6 +154,7 @@ impl<T> Distance<T> {
     //     let local = self.replicas.sort(Vec::new());
     //     self.topn(local);
     // }
+    // 保留outliers，之后update时按地址继承摘除状态
     #[inline]
     pub fn take(&mut self) -> Vec<T> {
         self.replicas
@@ -152,7 +177,8 @@ impl<T> Distance<T> {
     #[inline]
     fn check_quota_get_idx(&self) -> usize {
         if !self.backend_quota {
-            return (self.idx.fetch_add(1, Relaxed) >> 10) % self.local_len();
+            let idx = (self.idx.fetch_add(1, Relaxed) >> 10) % self.local_len();
+            return self.skip_ejected(idx);
         }
 
         let mut idx = self.idx();
@@ -167,7 +193,44 @@ impl<T> Distance<T> {
             }
             idx = new;
         }
-        idx
+        // 当前节点被摘除，则idx+1
+        let new = self.skip_ejected(idx);
+        if new != idx && self.idx.compare_exchange(idx, new, AcqRel, Relaxed).is_ok() {
+            unsafe { self.replicas.get_unchecked(new).1.reset() };
+        }
+        new
+    }
+    // 从idx开始，在local中选择第一个未被摘除的节点；local都被摘除时返回idx
+    #[inline]
+    fn skip_ejected(&self, idx: usize) -> usize {
+        let n = self.local_len();
+        (0..n)
+            .map(|i| (idx + i) % n)
+            .find(|&i| self.outliers[i].available())
+            .unwrap_or(idx)
+    }
+    // power of two choices：在local中随机选择两个副本，取访问代价小的一个
+    #[inline]
+    fn p2c_idx(&self) -> usize
+    where
+        T: Endpoint,
+    {
+        let n = self.local_len();
+        let mut rng = rand::thread_rng();
+        let a = rng.gen_range(0..n);
+        let idx = if n == 1 {
+            a
+        } else {
+            let b = (a + rng.gen_range(1..n)) % n;
+            let cost = |i: usize| unsafe { self.replicas.get_unchecked(i).0.cost() };
+            if cost(b) < cost(a) { b } else { a }
+        };
+        self.skip_ejected(idx)
+    }
+    // 副本的异常检测统计，请求完成时更新
+    #[inline]
+    pub fn outlier(&self, idx: usize) -> &Outlier {
+        &self.outliers[idx]
     }
     #[inline]
     pub unsafe fn get_unchecked(&self, idx: usize) -> &T {
@@ -176,10 +239,15 @@ impl<T> Distance<T> {
     }
     // 从local选择一个实例
     #[inline]
-    pub fn select_idx(&self) -> usize {
+    pub fn select_idx(&self) -> usize
+    where
+        T: Endpoint,
+    {
         assert_ne!(self.len(), 0);
         let idx = if self.len() == 1 {
             0
+        } else if self.ewma {
+            self.p2c_idx()
         } else {
             self.check_quota_get_idx()
         };
@@ -188,7 +256,10 @@ impl<T> Distance<T> {
     }
     // 只从local获取
     #[inline]
-    pub fn unsafe_select(&self) -> (usize, &T) {
+    pub fn unsafe_select(&self) -> (usize, &T)
+    where
+        T: Endpoint,
+    {
         let idx = self.select_idx();
         (idx, unsafe { &self.replicas.get_unchecked(idx).0 })
     }
@@ -225,7 +296,7 @@ impl<T> Distance<T> {
         assert!(runs < self.len(), "{} {} {:?}", current_idx, runs, self);
         for run in runs..self.len() {
             current_idx = self.select_next_idx_inner(current_idx, run);
-            if self.replicas[current_idx].0.available() {
+            if self.replicas[current_idx].0.available() && self.outliers[current_idx].available() {
                 return current_idx;
             }
         }
@@ -239,6 +310,17 @@ impl<T> Distance<T> {
         let idx = self.select_next_idx(idx, runs);
         (idx, unsafe { &self.replicas.get_unchecked(idx).0 })
     }
+    // 被异常检测摘除的副本
+    pub fn ejected(&self) -> impl std::iter::Iterator<Item = &str>
+    where
+        T: Endpoint,
+    {
+        self.replicas
+            .iter()
+            .zip(&self.outliers)
+            .filter(|(_, o)| o.ejected())
+            .map(|((r, _), _)| r.addr())
+    }
     pub fn into_inner(self) -> Vec<T> {
         self.replicas.into_iter().map(|(r, _)| r).collect()
     }
@@ -248,7 +330,9 @@ impl<T> Distance<T> {
     }
 }
 
+use super::Selector;
 use crate::Endpoint;
+use crate::PerformanceTuning;
 
 impl<T: Endpoint> std::fmt::Debug for Distance<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
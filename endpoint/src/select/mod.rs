@@ -8,10 +8,12 @@ pub use by_distance::*;
 
 //use discovery::distance::*;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Selector {
     Random,
     ByDistance,
+    // 按耗时的EWMA及未完成的请求数，在local中随机选两个副本，访问代价小的一个
+    Ewma,
 }
 impl Selector {
     // pub fn is_local(&self) -> bool {
@@ -26,6 +28,7 @@ impl From<&str> for Selector {
     fn from(selector: &str) -> Self {
         match selector {
             "random" => Self::Random,
+            "ewma" => Self::Ewma,
             _ => Self::ByDistance,
         }
     }
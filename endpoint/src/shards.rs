@@ -63,6 +63,21 @@ impl<E: Endpoint> Shards<E> {
     }
 }
 
+impl<E, Req> Shards<E>
+where
+    E: Endpoint<Item = Req>,
+    Req: protocol::Request,
+{
+    // hash所在的分片及其地址
+    pub(crate) fn route(&self, hash: i64) -> serde_json::Value {
+        if self.backends.is_empty() {
+            return serde_json::Value::Null;
+        }
+        let idx = self.shard_idx(hash);
+        serde_json::json!({ "shard": idx, "addr": self.backends[idx].addr() })
+    }
+}
+
 use discovery::distance::Addr;
 impl<E: Endpoint> Addr for Shards<E> {
     #[inline]
@@ -73,6 +88,11 @@ impl<E: Endpoint> Addr for Shards<E> {
         self.backends.iter().for_each(|b| f(b.addr()))
     }
 }
+impl<E: Endpoint + Inited> Shards<E> {
+    pub(crate) fn inspect(&self) -> serde_json::Value {
+        self.backends.iter().map(crate::inspect_backend).collect()
+    }
+}
 
 use crate::select::Distance;
 #[derive(Clone)]
@@ -82,12 +102,19 @@ pub struct Shard<E> {
 }
 impl<E: Endpoint> Shard<E> {
     #[inline]
-    pub fn selector(performance: bool, master: E, replicas: Vec<E>, region_enabled: bool) -> Self {
+    pub fn selector(selector: &str, master: E, replicas: Vec<E>, region_enabled: bool) -> Self {
         Self {
             master,
-            slaves: Distance::with_mode(replicas, performance, region_enabled),
+            slaves: Distance::with_selector(replicas, selector, region_enabled),
         }
     }
+    // master及slaves的地址
+    pub(crate) fn route(&self) -> serde_json::Value {
+        serde_json::json!({
+            "master": self.master.addr(),
+            "slaves": self.slaves.iter().map(|e| e.addr()).collect::<Vec<_>>(),
+        })
+    }
 }
 impl<E> Shard<E> {
     #[inline]
@@ -99,10 +126,17 @@ impl<E> Shard<E> {
         &self.master
     }
     #[inline]
-    pub(crate) fn select(&self) -> (usize, &E) {
+    pub(crate) fn select(&self) -> (usize, &E)
+    where
+        E: Endpoint,
+    {
         self.slaves.unsafe_select()
     }
     #[inline]
+    pub(crate) fn outlier(&self, idx: usize) -> &protocol::outlier::Outlier {
+        self.slaves.outlier(idx)
+    }
+    #[inline]
     pub(crate) fn next(&self, idx: usize, runs: usize) -> (usize, &E)
     where
         E: Endpoint,
@@ -148,6 +182,15 @@ impl<E: discovery::Inited> Shard<E> {
                 .fold(true, |inited, e| inited && e.inited())
     }
 }
+impl<E: Endpoint + discovery::Inited> Shard<E> {
+    pub(crate) fn inspect(&self) -> serde_json::Value {
+        serde_json::json!({
+            "master": crate::inspect_backend(&self.master),
+            "slaves": self.slaves.iter().map(crate::inspect_backend).collect::<Vec<_>>(),
+            "ejected": self.slaves.ejected().collect::<Vec<_>>(),
+        })
+    }
+}
 // 为Shard实现Debug
 impl<E: Endpoint> std::fmt::Debug for Shard<E> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -1,5 +1,5 @@
 use discovery::{Inited, TopologyWrite};
-use protocol::{Protocol, Request, ResOption, Resource};
+use protocol::{Acl, Protocol, Request, ResOption, Resource};
 use sharding::hash::{Hash, HashKey};
 
 use crate::Timeout;
@@ -7,6 +7,7 @@ use crate::Timeout;
 pub type TopologyProtocol<E, P> = Topologies<E, P>;
 
 // 1. 生成一个try_from(parser, endpoint)的方法，endpoint是名字的第一个单词或者是所有单词的首字母。RedisService的名字为"rs"或者"redis"
+//    与之前的variant重名的名字会被忽略，RedisCluster的名字为"rc"
 // 2. trait => where表示，为Topologies实现trait，满足where的条件.
 //    第一个参数必须是self，否则无法dispatcher
 // 3. 如果trait是pub的，则同时会创建这个trait。非pub的trait，只会为Topologies实现
@@ -20,6 +21,7 @@ procs::topology_dispatcher! {
         KvService(crate::kv::topo::KvService<E, P>),
         UuidService(crate::uuid::topo::UuidService<E, P>),
         VectorService(crate::vector::topo::VectorService<E, P>),
+        RedisCluster(crate::rediscluster::topo::RedisCluster<E, P>),
     }
 
     pub trait Endpoint: Sized + Send + Sync {
@@ -29,6 +31,10 @@ procs::topology_dispatcher! {
         fn shard_idx(&self, hash: i64) -> usize {todo!("shard_idx not implemented");}
         fn available(&self) -> bool {todo!("available not implemented");}
         fn addr(&self) -> &str {"addr not implemented"}
+        // 与后端建立的连接数
+        fn conns(&self) -> usize {1}
+        // 访问代价，ewma selector优先选择代价小的副本
+        fn cost(&self) -> u64 {0}
         #[allow(unused_variables)]
         fn build_o<P:Protocol>(addr: &str, p: P, r: Resource, service: &str, to: Timeout, o: ResOption) -> Self {todo!("build not implemented")}
         fn build<P:Protocol>(addr: &str, p: P, r: Resource, service: &str, to: Timeout) -> Self {Self::build_o(addr, p, r, service, to, Default::default())}
@@ -36,14 +42,42 @@ procs::topology_dispatcher! {
 
     pub trait Topology : Endpoint + Hash{
         fn exp_sec(&self) -> u32 {86400}
+        // hash所在分片master的地址、认证信息及超时时间，用于订阅、事务等需要独占连接的场景，不支持的资源返回None
+        #[allow(unused_variables)]
+        fn dedicated_backend(&self, hash: i64) -> Option<(String, ResOption, Timeout)> {None}
+        // 分片数量，用于scan、keys、dbsize等需要遍历所有分片的请求
+        fn shards(&self) -> usize {1}
+        // 跨分片的集合运算在mesh内合并时，允许的最大成员数量，0表示不支持
+        fn max_set_members(&self) -> usize {0}
+        // client是否需要认证，以及认证client，返回用户允许执行的指令类型
+        fn require_auth(&self) -> bool {false}
+        // 共享连接是否与后端协商了RESP3，未协商时client不能切换到RESP3
+        fn resp3(&self) -> bool {false}
+        #[allow(unused_variables)]
+        fn auth(&self, user: &[u8], pass: &[u8]) -> Option<Acl> {Some(Acl::ALL)}
+        // key的路由信息：hash、分片及后端地址等，用于admin接口及keyroute指令，不支持的资源返回Null
+        #[allow(unused_variables)]
+        fn route(&self, key: &[u8]) -> serde_json::Value {serde_json::Value::Null}
+        // 开启对冲读时，按耗时分位数计算的对冲延迟及预算
+        fn hedge(&self) -> Option<&crate::hedge::Hedge> {None}
     } => where P:Protocol, E:Endpoint<Item = R>, R:Request, Topologies<E, P>: Endpoint
 
     trait Inited {
         fn inited(&self) -> bool;
     } => where E:Inited
 
+    // 当前的分片、副本布局及各后端的状态，用于admin接口
+    pub trait Inspect {
+        fn inspect(&self) -> serde_json::Value {serde_json::Value::Null}
+    } => where P:Protocol, E:Endpoint + Inited
+
+    // 只校验namespace的配置，不更新topology，用于离线校验配置
+    pub trait Validate {
+        fn validate(&self, namespace: &str, cfg: &str) -> Result<(), crate::validate::ConfigError>;
+    } => where P:Protocol, E:Endpoint
+
     trait TopologyWrite {
-        fn update(&mut self, name: &str, cfg: &str);
+        fn update(&mut self, name: &str, cfg: &str) -> Result<(), discovery::UpdateError>;
         fn disgroup<'a>(&self, _path: &'a str, cfg: &'a str) -> Vec<(&'a str, &'a str)>;
         fn need_load(&self) -> bool;
         fn load(&mut self) -> bool;
@@ -55,6 +89,15 @@ procs::topology_dispatcher! {
 
 }
 
+// inited: 至少连接成功过一次；available: 当前连接可用
+pub(crate) fn inspect_backend<E: Endpoint + Inited>(e: &E) -> serde_json::Value {
+    serde_json::json!({
+        "addr": e.addr(),
+        "inited": e.inited(),
+        "available": e.available(),
+    })
+}
+
 // 从环境变量获取是否开启后端资源访问的性能模式
 #[inline]
 fn is_performance_tuning_from_env() -> bool {
@@ -65,13 +108,10 @@ pub(crate) trait PerformanceTuning {
     fn tuning_mode(&self) -> bool;
 }
 
-impl PerformanceTuning for String {
+impl PerformanceTuning for str {
     fn tuning_mode(&self) -> bool {
-        is_performance_tuning_from_env()
-            || match self.as_str() {
-                "distance" | "timeslice" => true,
-                _ => false,
-            }
+        // 先判断配置，配置了性能模式时不需要读取启动参数
+        matches!(self, "distance" | "timeslice") || is_performance_tuning_from_env()
     }
 }
 
@@ -129,19 +169,7 @@ impl<'a, P: Protocol, E: Endpoint> Endpoints<'a, P, E> {
     }
 
     pub fn take_or_build(&mut self, addrs: &[String], to: Timeout) -> Vec<E> {
-        addrs
-            .iter()
-            .map(|addr| {
-                self.cache
-                    .get_mut(addr)
-                    .map(|endpoints| endpoints.pop())
-                    .flatten()
-                    .unwrap_or_else(|| {
-                        let p = self.parser.clone();
-                        E::build(&addr, p, self.resource, self.service, to)
-                    })
-            })
-            .collect()
+        self.take_or_build_with_res(addrs, to, Default::default())
     }
 
     pub fn take_or_build_with_res(
@@ -150,13 +178,16 @@ impl<'a, P: Protocol, E: Endpoint> Endpoints<'a, P, E> {
         to: Timeout,
         res: ResOption,
     ) -> Vec<E> {
+        let conns = crate::validate::conns(res.conns);
         addrs
             .iter()
             .map(|addr| {
+                // 连接数变化时，不复用原有的endpoint
                 self.cache
                     .get_mut(addr)
                     .map(|endpoints| endpoints.pop())
                     .flatten()
+                    .filter(|e| e.conns() == conns)
                     .unwrap_or_else(|| {
                         let p = self.parser.clone();
                         E::build_o(&addr, p, self.resource, self.service, to, res.clone())
@@ -1,3 +1,4 @@
+use crate::validate::ConfigError;
 use crate::{Timeout, TO_UUID};
 use serde::{Deserialize, Serialize};
 
@@ -18,11 +19,12 @@ pub struct Basic {
 }
 
 impl UuidNamespace {
-    pub(super) fn try_from(cfg: &str) -> Option<Self> {
-        let ns = serde_yaml::from_str::<UuidNamespace>(cfg)
-            .map_err(|e| log::info!("parse uuid:{cfg} => {e:?}"))
-            .ok()?;
-        (ns.backends.len() > 0).then_some(ns)
+    pub(super) fn validate(cfg: &str) -> Result<Self, ConfigError> {
+        let ns = serde_yaml::from_str::<UuidNamespace>(cfg)?;
+        if ns.backends.len() == 0 {
+            return Err(ConfigError::Empty("backends"));
+        }
+        Ok(ns)
     }
     pub(super) fn timeout(&self) -> Timeout {
         let mut to = TO_UUID;
@@ -1,7 +1,7 @@
 use crate::{
     dns::{DnsConfig, DnsLookup},
     select::Distance,
-    Endpoint, Endpoints, PerformanceTuning, Topology,
+    Endpoint, Endpoints, Topology,
 };
 use discovery::TopologyWrite;
 use protocol::{Protocol, Request, Resource::Uuid};
@@ -75,6 +75,7 @@ where
 
         let try_next = ctx.runs == 1;
         req.try_next(try_next);
+        req.outlier(self.shard.outlier(idx).clone());
         endpoint.send(req);
     }
 
@@ -89,10 +90,10 @@ where
     E: Endpoint,
 {
     #[inline]
-    fn update(&mut self, namespace: &str, cfg: &str) {
-        if let Some(ns) = UuidNamespace::try_from(cfg) {
-            self.cfg.update(namespace, ns);
-        }
+    fn update(&mut self, namespace: &str, cfg: &str) -> Result<(), discovery::UpdateError> {
+        let ns = UuidNamespace::validate(cfg)?;
+        self.cfg.update(namespace, ns);
+        Ok(())
     }
     #[inline]
     fn need_load(&self) -> bool {
@@ -132,9 +133,9 @@ where
         let mut endpoints: Endpoints<'_, P, E> =
             Endpoints::new(&self.cfg.service, &self.parser, Uuid).with_cache(self.shard.take());
         let backends = endpoints.take_or_build(&addrs, self.cfg.timeout());
-        self.shard = Distance::with_mode(
+        self.shard = Distance::with_selector(
             backends,
-            self.cfg.basic.selector.tuning_mode(),
+            &self.cfg.basic.selector,
             self.cfg.basic.region_enabled,
         );
 
@@ -151,3 +152,11 @@ impl<E, P> std::fmt::Display for UuidService<E, P> {
             .finish()
     }
 }
+
+impl<E, P> crate::Inspect for UuidService<E, P> {}
+
+impl<E, P> crate::Validate for UuidService<E, P> {
+    fn validate(&self, _namespace: &str, cfg: &str) -> Result<(), crate::validate::ConfigError> {
+        UuidNamespace::validate(cfg).map(|_| ())
+    }
+}
@@ -0,0 +1,130 @@
+// namespace配置的统一校验：
+// 1. 各资源的配置解析统一返回ConfigError，而不是打印日志后返回None；
+// 2. 校验失败时topology的update返回ConfigError，保留原有的topology，
+//    由discovery记录每个服务最后一次被拒绝的配置及原因，供admin接口查询。
+use std::fmt::{self, Display, Formatter};
+use std::ops::Deref;
+
+use net::tls::TlsOption;
+use sharding::distribution::Distribute;
+use sharding::hash::Hasher;
+
+use crate::select::Selector;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    // yaml格式错误
+    Parse(String),
+    // 必需的配置项为空，如backends
+    Empty(&'static str),
+    UnknownHash(String),
+    UnknownDistribution(String),
+    // 分片数量不匹配
+    ShardMismatch {
+        what: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+    // 年份区间不连续或者重叠
+    Years(String),
+    // 密码解密失败
+    Decrypt(String),
+    Invalid(String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(e) => write!(f, "parse failed: {}", e),
+            Self::Empty(item) => write!(f, "{} is empty", item),
+            Self::UnknownHash(h) => write!(f, "unknown hash: {}", h),
+            Self::UnknownDistribution(d) => write!(f, "unknown distribution: {}", d),
+            Self::ShardMismatch {
+                what,
+                expected,
+                actual,
+            } => write!(f, "{}: expected {}, actual {}", what, expected, actual),
+            Self::Years(e) => write!(f, "invalid years: {}", e),
+            Self::Decrypt(e) => write!(f, "decrypt failed: {}", e),
+            Self::Invalid(e) => write!(f, "invalid config: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<serde_yaml::Error> for ConfigError {
+    fn from(e: serde_yaml::Error) -> Self {
+        Self::Parse(e.to_string())
+    }
+}
+
+// 为空时使用各资源默认的hash
+pub(crate) fn check_hash(hash: &str) -> Result<(), ConfigError> {
+    if hash.is_empty() || Hasher::try_from(hash).is_some() {
+        Ok(())
+    } else {
+        Err(ConfigError::UnknownHash(hash.to_string()))
+    }
+}
+
+// 为空时使用各资源默认的distribution
+pub(crate) fn check_distribution<T: Deref<Target = str>>(
+    dist: &str,
+    names: &[T],
+) -> Result<(), ConfigError> {
+    if dist.is_empty() || Distribute::try_from(dist, names).is_some() {
+        Ok(())
+    } else {
+        Err(ConfigError::UnknownDistribution(dist.to_string()))
+    }
+}
+
+// 每个后端最多允许的连接数
+const MAX_CONNS_PER_BACKEND: usize = 16;
+
+// 未配置时为1
+pub(crate) fn check_conns(conns: usize) -> Result<(), ConfigError> {
+    if conns <= MAX_CONNS_PER_BACKEND {
+        Ok(())
+    } else {
+        Err(ConfigError::Invalid(format!(
+            "conns_per_backend {} > {}",
+            conns, MAX_CONNS_PER_BACKEND
+        )))
+    }
+}
+
+// 实际建立的连接数
+pub fn conns(conns: usize) -> usize {
+    conns.clamp(1, MAX_CONNS_PER_BACKEND)
+}
+
+// 0表示不开启对冲读
+pub(crate) fn check_hedge(percentile: u8) -> Result<(), ConfigError> {
+    if percentile == 0 || (50..100).contains(&percentile) {
+        Ok(())
+    } else {
+        Err(ConfigError::Invalid(format!(
+            "hedge_percentile {} not in [50, 99]",
+            percentile
+        )))
+    }
+}
+
+// 配置了tls时，必须指定校验后端证书的ca
+pub(crate) fn check_tls(tls: Option<&TlsOption>) -> Result<(), ConfigError> {
+    match tls {
+        Some(tls) if tls.ca.is_empty() => Err(ConfigError::Empty("tls.ca")),
+        _ => Ok(()),
+    }
+}
+
+// 不按副本的访问代价选择的资源，配置了ewma时拒绝，避免配置被静默忽略
+pub(crate) fn check_no_ewma(selector: &str) -> Result<(), ConfigError> {
+    if Selector::from(selector) == Selector::Ewma {
+        Err(ConfigError::Invalid("selector ewma is not supported".to_string()))
+    } else {
+        Ok(())
+    }
+}
@@ -3,7 +3,11 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 
+use net::tls::TlsOption;
+
+use crate::kv::config::check_years;
 pub use crate::kv::config::Years;
+use crate::validate::{self, ConfigError};
 use crate::{Timeout, TO_VECTOR_M, TO_VECTOR_S};
 
 #[derive(Debug, Clone, Default, Deserialize)]
@@ -44,46 +48,33 @@ pub struct Basic {
     pub(crate) user: String,
     #[serde(default)]
     pub(crate) region_enabled: bool,
+    // 每个后端建立的连接数，未配置时为1
+    #[serde(default)]
+    pub(crate) conns_per_backend: usize,
+    // 后端连接的TLS，在mysql协议握手中通过SSLRequest协商
+    #[serde(default)]
+    pub(crate) tls: Option<TlsOption>,
 }
 
 impl VectorNamespace {
     #[inline]
-    pub(crate) fn try_from(cfg: &str) -> Option<Self> {
-        match serde_yaml::from_str::<VectorNamespace>(cfg) {
-            Ok(mut ns) => {
-                //移除default分片，兼容老defalut
-                ns.backends.remove(&Years(0, 0));
-                //配置的年需要连续，不重叠
-                let mut years: Vec<_> = ns.backends.keys().collect();
-                if years.len() == 0 {
-                    return None;
-                }
-                years.sort();
-                let mut last_year = years[0].0 - 1;
-                for year in years {
-                    if year.0 > year.1 || year.0 != last_year + 1 {
-                        return None;
-                    }
-                    last_year = year.1;
-                }
-                match ns.decrypt_password() {
-                    Ok(password) => ns.basic.password = password,
-                    Err(e) => {
-                        log::warn!("failed to decrypt password, e:{}", e);
-                        return None;
-                    }
-                }
-                ns.backends_flaten = ns.backends.iter().fold(Vec::new(), |mut init, b| {
-                    init.extend_from_slice(b.1);
-                    init
-                });
-                Some(ns)
-            }
-            Err(e) => {
-                log::info!("failed to parse mysql  e:{} config:{}", e, cfg);
-                None
-            }
+    pub(crate) fn validate(cfg: &str) -> Result<Self, ConfigError> {
+        let mut ns = serde_yaml::from_str::<VectorNamespace>(cfg)?;
+        //移除default分片，兼容老defalut
+        ns.backends.remove(&Years(0, 0));
+        check_years(&ns.backends, ns.basic.db_count)?;
+        validate::check_conns(ns.basic.conns_per_backend)?;
+        validate::check_tls(ns.basic.tls.as_ref())?;
+        if !ns.basic.password.is_empty() {
+            ns.basic.password = ns
+                .decrypt_password()
+                .map_err(|e| ConfigError::Decrypt(e.to_string()))?;
         }
+        ns.backends_flaten = ns.backends.iter().fold(Vec::new(), |mut init, b| {
+            init.extend_from_slice(b.1);
+            init
+        });
+        Ok(ns)
     }
 
     #[inline]
@@ -129,6 +129,8 @@ mod tests {
                 password: Default::default(),
                 user: Default::default(),
                 region_enabled: Default::default(),
+                conns_per_backend: Default::default(),
+                tls: Default::default(),
             },
             backends_flaten: Default::default(),
             backends: HashMap::from([(
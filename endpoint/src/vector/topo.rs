@@ -4,7 +4,8 @@ use chrono::Datelike;
 use discovery::dns;
 use discovery::dns::IPPort;
 use discovery::TopologyWrite;
-use ds::MemGuard;
+use ds::{MemGuard, RingSlice};
+use net::tls::TlsOption;
 use protocol::kv::{ContextStatus, MysqlBuilder};
 use protocol::Protocol;
 use protocol::Request;
@@ -27,6 +28,7 @@ pub struct VectorService<E, P> {
     shards: Shards<E>,
     strategist: Strategist,
     parser: P,
+    tls: Option<Box<TlsOption>>,
     cfg: Box<DnsConfig<VectorNamespace>>,
 }
 
@@ -37,6 +39,7 @@ impl<E, P> From<P> for VectorService<E, P> {
             parser,
             shards: Default::default(),
             strategist: Default::default(),
+            tls: None,
             cfg: Default::default(),
         }
     }
@@ -59,6 +62,26 @@ where
     Req: Request,
     P: Protocol,
 {
+    // key为','分隔的多个字段，与send一致：按字段中的日期定位年库，按hash定位分片
+    fn route(&self, key: &[u8]) -> serde_json::Value {
+        use protocol::vector::Strategy;
+        let hash = self.hash(&key);
+        let idx = self.shard_idx(hash);
+        let keys: Vec<_> = key.split(|c| *c == b',').map(RingSlice::from_slice).collect();
+        let Ok(date) = self.strategist.get_date(&keys) else {
+            return serde_json::json!({ "hash": hash, "shard": idx });
+        };
+        let year = date.year() as u16;
+        let mut table = String::new();
+        self.strategist.write_database_table(&mut table, &date, hash);
+        serde_json::json!({
+            "hash": hash,
+            "year": year,
+            "shard": idx,
+            "table": table,
+            "backend": self.shards.get(year).get(idx).map(Shard::route),
+        })
+    }
 }
 
 impl<E, Req, P> Endpoint for VectorService<E, P>
@@ -130,6 +153,8 @@ where
                 }
             }
             let ctx = req.ctx_mut();
+            // 访问的是从时，请求完成后更新从的异常检测统计
+            let slave = ctx.runs == 0 || (ctx.runs as usize) < shard.slaves.len();
             let (idx, endpoint) = if ctx.runs == 0 {
                 shard.select()
             } else {
@@ -145,6 +170,9 @@ where
             // 只重试一次，重试次数过多，可能会导致雪崩。如果不重试，现在的配额策略在当前副本也只会连续发送四次请求，问题也不大
             let try_next = ctx.runs == 1;
             req.try_next(try_next);
+            if slave {
+                req.outlier(shard.outlier(idx).clone());
+            }
             endpoint.send(req)
         } else {
             shard.master().send(req);
@@ -167,11 +195,11 @@ where
     fn load(&mut self) -> bool {
         self.cfg.load_guard().check_load(|| self.load_inner())
     }
-    fn update(&mut self, namespace: &str, cfg: &str) {
-        if let Some(ns) = VectorNamespace::try_from(cfg) {
-            self.strategist = Strategist::try_from(&ns);
-            self.cfg.update(namespace, ns);
-        }
+    fn update(&mut self, namespace: &str, cfg: &str) -> Result<(), discovery::UpdateError> {
+        let ns = VectorNamespace::validate(cfg)?;
+        self.strategist = Strategist::try_from(&ns);
+        self.cfg.update(namespace, ns);
+        Ok(())
     }
 }
 impl<E, P> VectorService<E, P>
@@ -187,8 +215,10 @@ where
         timeout: Timeout,
         res: ResOption,
     ) -> E {
+        // 连接数变化时，不复用原有的endpoint
+        let conns = crate::validate::conns(res.conns);
         match old.get_mut(addr).map(|endpoints| endpoints.pop()) {
-            Some(Some(end)) => end,
+            Some(Some(end)) if end.conns() == conns => end,
             _ => E::build_o(
                 &addr,
                 self.parser.clone(),
@@ -256,6 +286,11 @@ where
                 old.entry(addr).or_insert(Vec::new()).push(endpoint);
             }
         }
+        // tls配置变化时，不复用原有的endpoint
+        if self.tls.as_deref() != self.cfg.basic.tls.as_ref() {
+            old.clear();
+            self.tls = self.cfg.basic.tls.clone().map(Box::new);
+        }
 
         for (interval, addrs_per_interval) in addrs {
             let mut shards_per_interval = Vec::with_capacity(addrs_per_interval.len());
@@ -267,6 +302,9 @@ where
                 let res_option = ResOption {
                     token: self.cfg.basic.password.clone(),
                     username: self.cfg.basic.user.clone(),
+                    conns: self.cfg.basic.conns_per_backend,
+                    tls: self.cfg.basic.tls.clone(),
+                    ..Default::default()
                 };
                 let master = self.take_or_build(
                     &mut old,
@@ -286,9 +324,8 @@ where
                     replicas.push(slave);
                 }
 
-                use crate::PerformanceTuning;
                 let shard = Shard::selector(
-                    self.cfg.basic.selector.tuning_mode(),
+                    &self.cfg.basic.selector,
                     master,
                     replicas,
                     false,
@@ -318,3 +355,18 @@ where
             && self.shards.inited()
     }
 }
+
+impl<E, P> crate::Inspect for VectorService<E, P>
+where
+    E: Endpoint + discovery::Inited,
+{
+    fn inspect(&self) -> serde_json::Value {
+        serde_json::json!({ "years": self.shards.inspect() })
+    }
+}
+
+impl<E, P> crate::Validate for VectorService<E, P> {
+    fn validate(&self, _namespace: &str, cfg: &str) -> Result<(), crate::validate::ConfigError> {
+        VectorNamespace::validate(cfg).map(|_| ())
+    }
+}
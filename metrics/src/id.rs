@@ -49,6 +49,10 @@ impl Path {
         }
         self.path.push_str(name.as_ref());
     }
+    #[inline]
+    pub(crate) fn as_str(&self) -> &str {
+        &self.path
+    }
     pub fn num(&self, key: &'static str) -> Metric {
         self.with_type(key, MetricType::Count(Count))
     }
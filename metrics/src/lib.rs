@@ -3,8 +3,10 @@ extern crate lazy_static;
 
 mod id;
 mod ip;
+pub mod otlp;
 pub mod prometheus;
 mod register;
+pub mod snapshot;
 mod types;
 
 pub use crate::pub_status::Status;
@@ -110,8 +112,11 @@ unsafe impl Send for Metric {}
 
 pub(crate) trait WriteTo {
     fn write_to<W: ItemWriter>(&self, w: &mut W);
+    // snapshot时保存的数值
+    fn value(&self) -> Value;
 }
 
+use crate::snapshot::Value;
 use ds::NumStr;
 impl WriteTo for i64 {
     #[inline]
@@ -124,6 +129,10 @@ impl WriteTo for i64 {
         };
         v.with_str(|s| w.put_slice(s));
     }
+    #[inline]
+    fn value(&self) -> Value {
+        Value::Int(*self)
+    }
 }
 impl WriteTo for f64 {
     #[inline]
@@ -143,6 +152,10 @@ impl WriteTo for f64 {
             });
         }
     }
+    #[inline]
+    fn value(&self) -> Value {
+        Value::Float(*self)
+    }
 }
 //impl WriteTo for f64 {
 //    #[inline]
@@ -163,6 +176,15 @@ pub(crate) trait ItemWriter {
         val: V,
         opts: Vec<(&str, &str)>,
     );
+    // 耗时的累计分布：{key}_us_bucket、{key}_us_sum、{key}_us_count
+    fn write_histogram(
+        &mut self,
+        name: &str,
+        key: &str,
+        les: &[(u64, u64)],
+        count: u64,
+        sum_us: u64,
+    );
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -0,0 +1,335 @@
+// OTLP（HTTP/protobuf）导出，由agent定期编码后推送到collector：
+// 1. 指标：与/metrics使用同一次snapshot的结果，按gauge输出，rtt的耗时分布按cumulative histogram输出；
+// 2. 链路：每个线程按比例采样请求，每个请求一个trace，包含解析、发送到topology、后端、写响应四个阶段的span。
+// 资源属性（pool、idc、region、version等）在init时编码一次。
+// protobuf按opentelemetry-proto的字段编号手工编码，只包含用到的字段。
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering::*};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ds::time::Instant;
+
+use crate::snapshot::Records;
+use crate::{ItemWriter, Path, WriteTo};
+
+// 导出间隔内最多缓存的span数量，超过后丢弃
+const MAX_SPANS: usize = 4096;
+const SCOPE: &str = "breeze";
+// Span.kind
+const INTERNAL: u64 = 1;
+const SERVER: u64 = 2;
+const CLIENT: u64 = 3;
+// Status.code
+const STATUS_OK: u64 = 1;
+const STATUS_ERROR: u64 = 2;
+// AggregationTemporality
+const CUMULATIVE: u64 = 2;
+
+struct Config {
+    // 编码后的Resource
+    resource: Vec<u8>,
+    start: u64,
+}
+static CONFIG: OnceLock<Config> = OnceLock::new();
+// 每隔多少个请求采样一个，0表示不采样
+static SAMPLE_EVERY: AtomicU64 = AtomicU64::new(0);
+thread_local! {
+    // 当前线程还要跳过多少个请求才采样
+    static SKIP: Cell<u64> = const { Cell::new(0) };
+}
+
+struct Spans {
+    n: usize,
+    buf: Pb,
+}
+static SPANS: Mutex<Spans> = Mutex::new(Spans {
+    n: 0,
+    buf: Pb(Vec::new()),
+});
+
+// 开启otlp导出。ratio为采样的请求比例，0表示只导出指标
+pub fn init(resource: &[(&str, &str)], ratio: f64) {
+    let mut r = Pb::default();
+    for (k, v) in resource {
+        r.attr(1, k, v);
+    }
+    let config = Config {
+        resource: r.0,
+        start: unix_nanos(),
+    };
+    if CONFIG.set(config).is_err() {
+        return;
+    }
+    if ratio > 0.0 {
+        let every = (1.0 / ratio.min(1.0)).round() as u64;
+        SAMPLE_EVERY.store(every, Relaxed);
+    }
+}
+
+#[inline]
+pub fn enabled() -> bool {
+    SAMPLE_EVERY.load(Relaxed) > 0
+}
+
+// 当前线程的下一个请求是否会被采样，不影响采样的计数
+#[inline]
+pub fn sample_next() -> bool {
+    SAMPLE_EVERY.load(Relaxed) > 0 && SKIP.with(|s| s.get() == 0)
+}
+
+// 当前请求是否需要记录span，每个请求调用一次
+#[inline]
+pub fn sampled() -> bool {
+    let every = SAMPLE_EVERY.load(Relaxed);
+    every > 0
+        && SKIP.with(|s| match s.get() {
+            0 => {
+                s.set(every - 1);
+                true
+            }
+            n => {
+                s.set(n - 1);
+                false
+            }
+        })
+}
+
+// 采样请求各阶段的时间：开始解析、解析完成、发送到后端、收到响应、写完响应
+pub fn record_span(path: &Path, op: &str, ok: bool, stages: [Instant; 5]) {
+    let now = unix_nanos();
+    let ts = stages.map(|t| now.saturating_sub(t.elapsed().as_micros() as u64 * 1000));
+    let trace_id: [u8; 16] = rand::random();
+    let root: [u8; 8] = rand::random();
+    let mut buf = Pb::default();
+    buf.msg(2, |s| {
+        s.bytes(1, &trace_id);
+        s.bytes(2, &root);
+        s.str(5, "request");
+        s.uint(6, SERVER);
+        s.fixed64(7, ts[0]);
+        s.fixed64(8, ts[4]);
+        for (k, v) in crate::prometheus::labels(path.as_str()) {
+            if !v.is_empty() {
+                s.attr(9, k, v);
+            }
+        }
+        s.attr(9, "op", op);
+        s.msg(15, |status| {
+            status.uint(3, if ok { STATUS_OK } else { STATUS_ERROR })
+        });
+    });
+    let stages = ["parse", "topology.send", "backend", "write_response"];
+    for (i, name) in stages.iter().enumerate() {
+        let span_id: [u8; 8] = rand::random();
+        buf.msg(2, |s| {
+            s.bytes(1, &trace_id);
+            s.bytes(2, &span_id);
+            s.bytes(4, &root);
+            s.str(5, name);
+            s.uint(6, if *name == "backend" { CLIENT } else { INTERNAL });
+            s.fixed64(7, ts[i]);
+            s.fixed64(8, ts[i + 1]);
+        });
+    }
+    let mut spans = SPANS.lock().expect("spans");
+    if spans.n < MAX_SPANS {
+        spans.n += stages.len() + 1;
+        spans.buf.0.extend_from_slice(&buf.0);
+    }
+}
+
+// 编码ExportTraceServiceRequest，并清空已缓存的span。没有span时返回None
+pub fn export_spans() -> Option<Vec<u8>> {
+    let config = CONFIG.get()?;
+    let spans = {
+        let mut spans = SPANS.lock().expect("spans");
+        spans.n = 0;
+        std::mem::take(&mut spans.buf.0)
+    };
+    if spans.is_empty() {
+        return None;
+    }
+    let mut req = Pb::default();
+    req.msg(1, |rs| {
+        rs.bytes(1, &config.resource);
+        rs.msg(2, |ss| {
+            ss.msg(1, |scope| scope.str(1, SCOPE));
+            ss.0.extend_from_slice(&spans);
+        });
+    });
+    Some(req.0)
+}
+
+// 编码ExportMetricsServiceRequest，records为本周期的snapshot，与/metrics共用
+pub fn export_metrics(records: &Records) -> Option<Vec<u8>> {
+    let config = CONFIG.get()?;
+    let mut w = OtlpItemWriter {
+        start: config.start,
+        now: unix_nanos(),
+        metrics: Pb::default(),
+    };
+    for record in &records.items {
+        record.write_to(&mut w);
+    }
+    let mut req = Pb::default();
+    req.msg(1, |rm| {
+        rm.bytes(1, &config.resource);
+        rm.msg(2, |sm| {
+            sm.msg(1, |scope| scope.str(1, SCOPE));
+            sm.0.extend_from_slice(&w.metrics.0);
+        });
+    });
+    Some(req.0)
+}
+
+fn unix_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+fn metric_name(key: &str, sub_key: &str) -> String {
+    if sub_key.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}_{}", key, sub_key)
+    }
+}
+
+// 每个数据点编码为一个Metric
+struct OtlpItemWriter {
+    start: u64,
+    now: u64,
+    // ScopeMetrics.metrics
+    metrics: Pb,
+}
+
+impl ItemWriter for OtlpItemWriter {
+    // 只输出数值，不需要文本
+    fn put_slice<S: AsRef<[u8]>>(&mut self, _data: S) {}
+    #[inline]
+    fn write<V: WriteTo>(&mut self, name: &str, key: &str, sub_key: &str, val: V) {
+        self.write_opts(name, key, sub_key, val, Vec::new());
+    }
+    fn write_opts<V: WriteTo>(
+        &mut self,
+        name: &str,
+        key: &str,
+        sub_key: &str,
+        val: V,
+        opts: Vec<(&str, &str)>,
+    ) {
+        let now = self.now;
+        self.metrics.msg(2, |m| {
+            m.str(1, &metric_name(key, sub_key));
+            // Gauge.data_points
+            m.msg(5, |gauge| {
+                gauge.msg(1, |p| {
+                    p.attrs(7, name, &opts);
+                    p.fixed64(3, now);
+                    p.double(4, val.value().as_f64());
+                });
+            });
+        });
+    }
+    fn write_histogram(
+        &mut self,
+        name: &str,
+        key: &str,
+        les: &[(u64, u64)],
+        count: u64,
+        sum_us: u64,
+    ) {
+        let (start, now) = (self.start, self.now);
+        // otlp的bucket_counts是每个桶的数量，比边界多一个
+        let mut counts = Vec::with_capacity(les.len() + 1);
+        let mut prev = 0;
+        for &(_, c) in les {
+            counts.push(c - prev);
+            prev = c;
+        }
+        counts.push(count.saturating_sub(prev));
+        let bounds: Vec<u64> = les.iter().map(|(le, _)| (*le as f64).to_bits()).collect();
+        self.metrics.msg(2, |m| {
+            m.str(1, &metric_name(key, "us"));
+            m.str(3, "us");
+            // Histogram.data_points
+            m.msg(9, |h| {
+                h.msg(1, |p| {
+                    p.attrs(9, name, &[]);
+                    p.fixed64(2, start);
+                    p.fixed64(3, now);
+                    p.fixed64(4, count);
+                    p.double(5, sum_us as f64);
+                    p.packed_fixed64(6, &counts);
+                    p.packed_fixed64(7, &bounds);
+                });
+                h.uint(2, CUMULATIVE);
+            });
+        });
+    }
+}
+
+// protobuf编码
+#[derive(Default)]
+struct Pb(Vec<u8>);
+
+impl Pb {
+    fn varint(&mut self, mut v: u64) {
+        while v >= 0x80 {
+            self.0.push(v as u8 | 0x80);
+            v >>= 7;
+        }
+        self.0.push(v as u8);
+    }
+    fn tag(&mut self, field: u32, wire: u32) {
+        self.varint((field << 3 | wire) as u64);
+    }
+    fn uint(&mut self, field: u32, v: u64) {
+        self.tag(field, 0);
+        self.varint(v);
+    }
+    fn fixed64(&mut self, field: u32, v: u64) {
+        self.tag(field, 1);
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    fn double(&mut self, field: u32, v: f64) {
+        self.fixed64(field, v.to_bits());
+    }
+    fn bytes(&mut self, field: u32, v: &[u8]) {
+        self.tag(field, 2);
+        self.varint(v.len() as u64);
+        self.0.extend_from_slice(v);
+    }
+    fn str(&mut self, field: u32, v: &str) {
+        self.bytes(field, v.as_bytes());
+    }
+    fn packed_fixed64(&mut self, field: u32, vs: &[u64]) {
+        self.tag(field, 2);
+        self.varint(vs.len() as u64 * 8);
+        vs.iter()
+            .for_each(|v| self.0.extend_from_slice(&v.to_le_bytes()));
+    }
+    fn msg(&mut self, field: u32, f: impl FnOnce(&mut Pb)) {
+        let mut m = Pb::default();
+        f(&mut m);
+        self.bytes(field, &m.0);
+    }
+    // KeyValue，value为AnyValue.string_value
+    fn attr(&mut self, field: u32, k: &str, v: &str) {
+        self.msg(field, |kv| {
+            kv.str(1, k);
+            kv.msg(2, |any| any.str(1, v));
+        });
+    }
+    // 从metric的path中解析出的属性，与prometheus的label一致
+    fn attrs(&mut self, field: u32, name: &str, opts: &[(&str, &str)]) {
+        let labels = crate::prometheus::labels(name);
+        for (k, v) in labels.iter().chain(opts.iter()) {
+            if !v.is_empty() {
+                self.attr(field, k, v);
+            }
+        }
+    }
+}
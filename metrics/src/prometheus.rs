@@ -1,19 +1,21 @@
+use crate::snapshot::Records;
 use crate::{ItemWriter, WriteTo};
+use ds::NumStr;
+use std::sync::Arc;
+// 输出一次snapshot的结果
 pub struct Prometheus {
-    secs: f64,
+    records: Arc<Records>,
     idx: usize,
-    host: bool,       // host相关的metrics是否已经发送
     ext_buf: Vec<u8>, // 扩展的buffer，如果ReadBuf没有足够的空间，则使用这个buffer
     ext_oft: usize,
 }
 
 impl Prometheus {
-    pub fn new(secs: f64) -> Self {
+    pub fn new(records: Arc<Records>) -> Self {
         Self {
+            records,
             idx: 0,
-            secs,
             ext_buf: Vec::with_capacity(1024),
-            host: false,
             ext_oft: 0,
         }
     }
@@ -45,22 +47,16 @@ impl AsyncRead for Prometheus {
     ) -> Poll<std::io::Result<()>> {
         // 1. copy buffer
         self.copy_buf(buf);
-        let metrics = crate::get_metrics();
-        let len = metrics.len();
-        let secs = self.secs;
         let Self {
-            host, idx, ext_buf, ..
+            records,
+            idx,
+            ext_buf,
+            ..
         } = &mut *self;
-        // 2. write host metrics
+        // 2. write records by idx
         let mut w = PrometheusItemWriter::new(buf, ext_buf);
-        if !*host {
-            *host = true;
-            HOST.try_lock().expect("host lock").snapshot(&mut w, secs);
-        };
-        // 3. write metrics by idx
-        while w.remaining() > 0 && *idx < len {
-            let (id, item) = metrics.get_item_id(*idx);
-            item.snapshot(id, &mut w, secs);
+        while w.remaining() > 0 && *idx < records.items.len() {
+            records.items[*idx].write_to(&mut w);
             *idx += 1;
         }
         Poll::Ready(Ok(()))
@@ -134,17 +130,6 @@ impl<'a, 'r, 'b> ItemWriter for PrometheusItemWriter<'a, 'r, 'b> {
         <4>   msgque_backend/msgque                             timeout     qps             timeout_qps{source="msgque_backend",pool="default_pool",namespace="ns",topic="top",bip="127.0.0.1:8080"} 0
          */
 
-        //从 name 中截取 source、namespace和topic、instance
-        let mut all_iter = name.split(crate::TARGET_SPLIT as char);
-        let source = all_iter.next().unwrap_or("").as_bytes();
-        let nameandtopic = all_iter.next().unwrap_or("");
-        let bip = all_iter.next().unwrap_or("").as_bytes();
-        //let charname = name.split(crate::TARGET_SPLIT as char).nth(1).unwrap_or("");
-        //针对mcq,namespace中可能包含topic,先根据 ‘#’分割;
-        let mut name_iter = nameandtopic.split("#");
-        let namespace = name_iter.next().unwrap_or("").as_bytes();
-        let topic = name_iter.next().unwrap_or("").as_bytes();
-
         let metric_name = MetricName(key, sub_key);
 
         //promethues # TYPE
@@ -155,13 +140,7 @@ impl<'a, 'r, 'b> ItemWriter for PrometheusItemWriter<'a, 'r, 'b> {
         //promethues metrics
         metric_name.write_to(self);
         self.put_slice("{");
-        self.first = true;
-        //确保第一个put的label一定不为空; 后续优化
-        self.put_label("src", source);
-        //self.put_label("pool", context::get().service_pool.as_bytes());
-        self.put_label("ns", namespace);
-        self.put_label("topic", topic);
-        self.put_label("bip", bip);
+        self.put_labels(name);
 
         for (k, v) in opts {
             self.put_label(k, v.as_bytes());
@@ -173,17 +152,73 @@ impl<'a, 'r, 'b> ItemWriter for PrometheusItemWriter<'a, 'r, 'b> {
         val.write_to(self);
         self.put_slice("\n");
     }
+    fn write_histogram(
+        &mut self,
+        name: &str,
+        key: &str,
+        les: &[(u64, u64)],
+        count: u64,
+        sum_us: u64,
+    ) {
+        let metric_name = MetricName(key, "us");
+        self.put_slice("# TYPE ");
+        metric_name.write_to(self);
+        self.put_slice(" histogram\n");
+
+        let count = count as i64;
+        for &(le, c) in les {
+            (le as usize).with_str(|le| self.write_bucket(name, key, le, c as i64));
+        }
+        self.write_bucket(name, key, b"+Inf", count);
+        let sum = sum_us as i64;
+        for (sub_key, v) in [("us_sum", sum), ("us_count", count)] {
+            MetricName(key, sub_key).write_to(self);
+            self.put_slice("{");
+            self.put_labels(name);
+            self.put_slice("} ");
+            v.write_to(self);
+            self.put_slice("\n");
+        }
+    }
 }
-use crate::Host;
-use ds::lock::Lock;
-use lazy_static::lazy_static;
-lazy_static! {
-    static ref HOST: Lock<Host> = Host::new().into();
+
+impl<'a, 'r, 'b> PrometheusItemWriter<'a, 'r, 'b> {
+    fn write_bucket(&mut self, name: &str, key: &str, le: &[u8], count: i64) {
+        MetricName(key, "us_bucket").write_to(self);
+        self.put_slice("{");
+        self.put_labels(name);
+        self.put_label("le", le);
+        self.put_slice("} ");
+        count.write_to(self);
+        self.put_slice("\n");
+    }
+    // 从name中解析出的label，不包含{}
+    fn put_labels(&mut self, name: &str) {
+        self.first = true;
+        //确保第一个put的label一定不为空; 后续优化
+        //self.put_label("pool", context::get().service_pool.as_bytes());
+        for (k, v) in labels(name) {
+            self.put_label(k, v.as_bytes());
+        }
+    }
 }
 
+// 从 name 中截取 source、namespace和topic、instance
+pub(crate) fn labels(name: &str) -> [(&'static str, &str); 4] {
+    let mut all_iter = name.split(crate::TARGET_SPLIT);
+    let source = all_iter.next().unwrap_or("");
+    let nameandtopic = all_iter.next().unwrap_or("");
+    let bip = all_iter.next().unwrap_or("");
+    //let charname = name.split(crate::TARGET_SPLIT as char).nth(1).unwrap_or("");
+    //针对mcq,namespace中可能包含topic,先根据 ‘#’分割;
+    let mut name_iter = nameandtopic.split("#");
+    let namespace = name_iter.next().unwrap_or("");
+    let topic = name_iter.next().unwrap_or("");
+    [("src", source), ("ns", namespace), ("topic", topic), ("bip", bip)]
+}
 struct MetricName<'a>(&'a str, &'a str);
 
-impl<'a> WriteTo for MetricName<'a> {
+impl<'a> MetricName<'a> {
     #[inline]
     fn write_to<W: ItemWriter>(&self, w: &mut W) {
         w.put_slice(self.0);
@@ -1,6 +1,7 @@
 use ds::time::{interval, Duration};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering::*};
 
 use crate::{Id, Item, ItemData, Metric};
 
@@ -8,6 +9,7 @@ const CHUNK_SIZE: usize = 4096;
 
 use tokio::{
     sync::mpsc::{unbounded_channel, UnboundedReceiver as Receiver, UnboundedSender as Sender},
+    sync::oneshot,
     time::Interval,
 };
 
@@ -18,11 +20,25 @@ pub struct Metrics {
     len: usize,
     id_idx: HashMap<Arc<Id>, usize>,
     idx_id: Vec<Arc<Id>>,
+    // 已下线服务的metric，不再输出。由于idx被引用，不释放
+    retired: HashSet<usize>,
+    // 每个idx最后一次注册时的EPOCH。下线只针对在unregister调用之前注册的metric
+    epochs: Vec<usize>,
+    // 已处理的Unregister数量，与EPOCH相等时说明所有的下线都已反映在当前的metrics中
+    epoch: usize,
 }
 
+// unregister调用的次数
+static EPOCH: AtomicUsize = AtomicUsize::new(0);
+
 enum Op {
-    Register(Arc<Id>),
+    // 注册时的EPOCH
+    Register(Arc<Id>, usize),
     Flush(Arc<Id>, ItemData),
+    // 下线path及其子path下，在EPOCH之前注册的所有metric
+    Unregister(String, usize),
+    // 之前的Op都已处理完成后通知
+    Sync(oneshot::Sender<()>),
 }
 use crate::ItemPtr;
 
@@ -34,12 +50,21 @@ impl Metrics {
             len: 0,
             id_idx: Default::default(),
             idx_id: Default::default(),
+            retired: Default::default(),
+            epochs: Default::default(),
+            epoch: 0,
         };
         me.reserve_chunk_num(1);
         me
     }
     fn register(&self, id: Id) -> Metric {
+        let epoch = EPOCH.load(Acquire);
         if let Some(&idx) = self.id_idx.get(&id) {
+            // 已下线的metric重新注册后恢复输出。
+            // 有尚未处理完成的下线时也要注册，避免被之前的unregister下线
+            if self.retired.contains(&idx) || self.epoch != epoch {
+                let _r = get_register().send(Op::Register(Arc::new(id), epoch));
+            }
             let item = self.get_item(idx);
             let item = ItemPtr::global(item);
             return Metric::from(item);
@@ -49,7 +74,7 @@ impl Metrics {
         let item = ItemPtr::local(id.clone());
         let metric = Metric::from(item);
         log::debug!("register sent {id:?}");
-        let _r = get_register().send(Op::Register(id));
+        let _r = get_register().send(Op::Register(id, epoch));
         assert!(_r.is_ok());
         return metric;
     }
@@ -76,16 +101,34 @@ impl Metrics {
         unsafe { &*self.chunks.get_unchecked(slot).offset(offset as isize) }
     }
     #[inline]
-    fn reserve_idx(&mut self, id: &Arc<Id>) -> bool {
+    fn reserve_idx(&mut self, id: &Arc<Id>, epoch: usize) -> bool {
         self.reserve_chunk_num(1);
         let idx = *self.id_idx.entry(id.clone()).or_insert(self.len);
         if idx == self.len() {
             self.len += 1;
             self.idx_id.push(id.clone());
+            self.epochs.push(epoch);
             assert_eq!(self.len, self.idx_id.len());
             return true;
         }
-        false
+        self.epochs[idx] = self.epochs[idx].max(epoch);
+        self.retired.remove(&idx)
+    }
+    // 只下线在第epoch次unregister调用之前注册的metric，之后重新注册的（服务重新上线）保留
+    fn retire(&mut self, path: &str, epoch: usize) {
+        self.epoch += 1;
+        for (idx, id) in self.idx_id.iter().enumerate() {
+            let sub = id.path.strip_prefix(path);
+            if sub.is_some_and(|sub| sub.is_empty() || sub.starts_with(crate::TARGET_SPLIT))
+                && self.epochs[idx] < epoch
+            {
+                self.retired.insert(idx);
+            }
+        }
+    }
+    #[inline]
+    pub(crate) fn retired(&self, idx: usize) -> bool {
+        self.retired.contains(&idx)
     }
     #[inline]
     fn reserve_chunk_num(&mut self, n: usize) {
@@ -149,6 +192,20 @@ pub(crate) fn get_item(id: &Id) -> Option<*const Item> {
         .map(|&idx| metrics.get_item(idx) as *const _)
 }
 
+// 服务下线后，其metric不再输出
+pub fn unregister(path: &crate::Path) {
+    let epoch = EPOCH.fetch_add(1, AcqRel) + 1;
+    let _r = get_register().send(Op::Unregister(path.as_str().to_string(), epoch));
+}
+
+// 等待已经提交的注册、flush都合并到全局的metrics中。退出进程前调用
+pub async fn flush() {
+    let (tx, rx) = oneshot::channel();
+    if get_register().send(Op::Sync(tx)).is_ok() {
+        let _ = rx.await;
+    }
+}
+
 use once_cell::sync::OnceCell;
 static METRICS: OnceCell<CowReadHandle<Metrics>> = OnceCell::new();
 static SENDER: OnceCell<Sender<Op>> = OnceCell::new();
@@ -158,12 +215,47 @@ fn get_register() -> &'static Sender<Op> {
 pub mod tests {
     use super::*;
     static mut TEST_RECEIVER: Option<Receiver<Op>> = None;
+    static mut TEST_WRITER: Option<CowWriteHandle<Metrics>> = None;
+    // 多个测试用例可能都需要初始化，只初始化一次
     pub fn init_metrics_onlyfor_test() {
-        let (register_tx, chan_rx) = unbounded_channel();
-        let (_tx, rx) = ds::cow(Metrics::new());
-        let _ = SENDER.set(register_tx).map_err(|_e| panic!("init"));
-        let _ = METRICS.set(rx).map_err(|_e| panic!("init"));
-        unsafe { TEST_RECEIVER = Some(chan_rx) };
+        static INIT: std::sync::Once = std::sync::Once::new();
+        INIT.call_once(|| {
+            let (register_tx, chan_rx) = unbounded_channel();
+            let (tx, rx) = ds::cow(Metrics::new());
+            let _ = SENDER.set(register_tx).map_err(|_e| panic!("init"));
+            let _ = METRICS.set(rx).map_err(|_e| panic!("init"));
+            unsafe { TEST_RECEIVER = Some(chan_rx) };
+            unsafe { TEST_WRITER = Some(tx) };
+        });
+    }
+    // path下仍在输出（未下线）的metric数量
+    pub fn live_onlyfor_test(path: &crate::Path) -> usize {
+        let metrics = get_metrics();
+        let ids = metrics.idx_id.iter().enumerate();
+        ids.filter(|(idx, id)| id.path == path.as_str() && !metrics.retired(*idx))
+            .count()
+    }
+    // 需要metric注册完成的测试（如端到端的pipeline）在独立的线程中运行注册任务，只启动一次
+    pub fn start_register_onlyfor_test() {
+        init_metrics_onlyfor_test();
+        static START: std::sync::Once = std::sync::Once::new();
+        START.call_once(|| {
+            let rx = unsafe { (*std::ptr::addr_of_mut!(TEST_RECEIVER)).take() };
+            let metrics = unsafe { (*std::ptr::addr_of_mut!(TEST_WRITER)).take() };
+            let (rx, metrics) = (rx.expect("receiver"), metrics.expect("writer"));
+            std::thread::spawn(move || {
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_time()
+                    .build()
+                    .expect("runtime");
+                rt.block_on(async move {
+                    let cache = metrics.copy();
+                    let tick = interval(Duration::from_secs(1));
+                    let has_new = false;
+                    MetricRegister { rx, metrics, tick, cache, has_new }.await
+                });
+            });
+        });
     }
 }
 
@@ -223,8 +315,8 @@ impl Future for MetricRegister {
             let ret = me.rx.poll_recv(cx);
             if let Poll::Ready(Some(op)) = ret {
                 match op {
-                    Op::Register(id) => {
-                        if me.cache.reserve_idx(&id) {
+                    Op::Register(id, epoch) => {
+                        if me.cache.reserve_idx(&id, epoch) {
                             me.has_new = true;
                         }
                     }
@@ -235,6 +327,18 @@ impl Future for MetricRegister {
                         use crate::Snapshot;
                         id.t.merge(global.data(), &local);
                     }
+                    Op::Unregister(path, epoch) => {
+                        // epoch变化也需要更新，否则register会一直发送Register
+                        me.cache.retire(&path, epoch);
+                        me.has_new = true;
+                    }
+                    Op::Sync(done) => {
+                        if me.has_new {
+                            me.metrics.update(me.cache.clone());
+                            me.has_new = false;
+                        }
+                        let _ = done.send(());
+                    }
                 }
                 continue;
             }
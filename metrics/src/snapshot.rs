@@ -0,0 +1,167 @@
+// 每个周期对所有的metrics做一次snapshot，/metrics与otlp都使用这一次的结果：
+// 1. qps、分位数等在snapshot时重置，只在这里调用，多次拉取之间不会相互影响；
+// 2. 结果只保存数值，输出时再按各自的格式编码。
+use std::sync::{Arc, Mutex};
+
+use crate::{Host, ItemWriter, WriteTo};
+use ds::lock::Lock;
+
+lazy_static! {
+    static ref HOST: Lock<Host> = Host::new().into();
+}
+static LATEST: Mutex<Option<Arc<Records>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+}
+
+impl Value {
+    #[inline]
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            Value::Int(v) => v as f64,
+            Value::Float(v) => v,
+        }
+    }
+}
+
+impl WriteTo for Value {
+    #[inline]
+    fn write_to<W: ItemWriter>(&self, w: &mut W) {
+        match self {
+            Value::Int(v) => v.write_to(w),
+            Value::Float(v) => v.write_to(w),
+        }
+    }
+    #[inline]
+    fn value(&self) -> Value {
+        *self
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum Record {
+    Gauge {
+        name: String,
+        key: String,
+        sub_key: String,
+        val: Value,
+        opts: Vec<(String, String)>,
+    },
+    Histogram {
+        name: String,
+        key: String,
+        les: Vec<(u64, u64)>,
+        count: u64,
+        sum_us: u64,
+    },
+}
+
+impl Record {
+    // 按snapshot时的调用重新写入w
+    pub(crate) fn write_to<W: ItemWriter>(&self, w: &mut W) {
+        match self {
+            Record::Gauge {
+                name,
+                key,
+                sub_key,
+                val,
+                opts,
+            } => {
+                let opts = opts.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+                w.write_opts(name, key, sub_key, *val, opts);
+            }
+            Record::Histogram {
+                name,
+                key,
+                les,
+                count,
+                sum_us,
+            } => w.write_histogram(name, key, les, *count, *sum_us),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Records {
+    pub(crate) items: Vec<Record>,
+}
+
+impl Records {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl ItemWriter for Records {
+    fn put_slice<S: AsRef<[u8]>>(&mut self, _data: S) {}
+    #[inline]
+    fn write<V: WriteTo>(&mut self, name: &str, key: &str, sub_key: &str, val: V) {
+        self.write_opts(name, key, sub_key, val, Vec::new());
+    }
+    fn write_opts<V: WriteTo>(
+        &mut self,
+        name: &str,
+        key: &str,
+        sub_key: &str,
+        val: V,
+        opts: Vec<(&str, &str)>,
+    ) {
+        self.items.push(Record::Gauge {
+            name: name.to_string(),
+            key: key.to_string(),
+            sub_key: sub_key.to_string(),
+            val: val.value(),
+            opts: opts
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        });
+    }
+    fn write_histogram(
+        &mut self,
+        name: &str,
+        key: &str,
+        les: &[(u64, u64)],
+        count: u64,
+        sum_us: u64,
+    ) {
+        self.items.push(Record::Histogram {
+            name: name.to_string(),
+            key: key.to_string(),
+            les: les.to_vec(),
+            count,
+            sum_us,
+        });
+    }
+}
+
+// 对所有的metrics做一次snapshot。secs为距上一次snapshot的时间，用于计算qps
+pub fn take(secs: f64) -> Arc<Records> {
+    let mut records = Records::default();
+    HOST.try_lock()
+        .expect("host lock")
+        .snapshot(&mut records, secs);
+    let metrics = crate::get_metrics();
+    for idx in 0..metrics.len() {
+        if !metrics.retired(idx) {
+            let (id, item) = metrics.get_item_id(idx);
+            item.snapshot(id, &mut records, secs);
+        }
+    }
+    let records = Arc::new(records);
+    *LATEST.lock().expect("latest") = Some(records.clone());
+    records
+}
+
+// 最近一次snapshot的结果，还没有snapshot时返回None
+pub fn latest() -> Option<Arc<Records>> {
+    LATEST.lock().expect("latest").clone()
+}
@@ -0,0 +1,163 @@
+// 耗时的直方图：对数线性分桶，每个2的幂次区间再等分为8个桶，相对误差不超过12.5%。
+// 1. 每个线程只更新自己的shard，不与其他线程竞争同一个计数；
+// 2. 周期性的snapshot把各shard的增量合并到累计计数，用这部分增量计算p50/p99/p999，
+//    prometheus按histogram输出累计值（_bucket/_sum/_count）；
+// 3. 只有rtt类型的item在第一次记录耗时时分配。
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering::*};
+
+const SUB_BITS: u32 = 3;
+// 超过MAX_US（约33秒）的耗时计入最后一个桶
+const MAX_US: u64 = (1 << 25) - 1;
+const BUCKETS: usize = bucket(MAX_US) + 1;
+// 输出到prometheus的桶边界：128us到16s之间2的幂次，恰好是分桶的边界
+const LE_MIN_BITS: u32 = 7;
+const LE_MAX_BITS: u32 = 24;
+const LES: usize = (LE_MAX_BITS - LE_MIN_BITS + 1) as usize;
+// 分位数，以千分之一为单位
+const PERMILLES: [u64; 3] = [500, 990, 999];
+// 超过SHARDS个线程时，多个线程共用一个shard
+const SHARDS: usize = 4;
+
+static NEXT_SLOT: AtomicUsize = AtomicUsize::new(0);
+thread_local! {
+    static SLOT: usize = NEXT_SLOT.fetch_add(1, Relaxed) % SHARDS;
+}
+
+// 小于16us时每1us一个桶
+const fn bucket(us: u64) -> usize {
+    let us = if us > MAX_US { MAX_US } else { us };
+    if us < 1 << (SUB_BITS + 1) {
+        return us as usize;
+    }
+    let p = 63 - us.leading_zeros();
+    (((p - SUB_BITS + 1) << SUB_BITS) as u64 + ((us >> (p - SUB_BITS)) & ((1 << SUB_BITS) - 1)))
+        as usize
+}
+// 桶的上界（不包含）
+fn upper(idx: usize) -> u64 {
+    if idx < 1 << (SUB_BITS + 1) {
+        return idx as u64 + 1;
+    }
+    let p = (idx >> SUB_BITS) as u32 + SUB_BITS - 1;
+    let m = (idx & ((1 << SUB_BITS) - 1)) as u64;
+    ((1 << SUB_BITS) + m + 1) << (p - SUB_BITS)
+}
+
+// 一个snapshot周期内的增量，u32足够
+#[derive(Debug)]
+#[repr(align(64))]
+struct Shard {
+    counts: [AtomicU32; BUCKETS],
+    sum_us: AtomicU64,
+}
+
+impl Default for Shard {
+    fn default() -> Self {
+        Self {
+            counts: [(); BUCKETS].map(|_| AtomicU32::new(0)),
+            sum_us: 0.into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Histogram {
+    shards: [Shard; SHARDS],
+    // 已合并的累计计数，只在snapshot时更新
+    counts: [AtomicU64; BUCKETS],
+    sum_us: AtomicU64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            shards: Default::default(),
+            counts: [(); BUCKETS].map(|_| AtomicU64::new(0)),
+            sum_us: 0.into(),
+        }
+    }
+}
+
+impl Histogram {
+    #[inline]
+    pub fn record(&self, us: u64) {
+        let shard = &self.shards[SLOT.with(|s| *s)];
+        shard.counts[bucket(us)].fetch_add(1, Relaxed);
+        shard.sum_us.fetch_add(us, Relaxed);
+    }
+    // 截止到上一次snapshot的累计数量
+    #[inline]
+    pub fn count(&self) -> u64 {
+        self.counts.iter().map(|c| c.load(Relaxed)).sum()
+    }
+    #[inline]
+    pub fn sum_us(&self) -> u64 {
+        self.sum_us.load(Relaxed)
+    }
+    // 耗时小于le（微秒）的累计请求数。耗时按微秒截断，因此实际耗时也一定小于le
+    pub fn cumulative(&self) -> [(u64, u64); LES] {
+        let mut les = [(0, 0); LES];
+        let mut sum = 0;
+        let mut bits = LE_MIN_BITS;
+        for (idx, c) in self.counts.iter().enumerate() {
+            if bits > LE_MAX_BITS {
+                break;
+            }
+            sum += c.load(Relaxed);
+            if upper(idx) == 1 << bits {
+                les[(bits - LE_MIN_BITS) as usize] = (1 << bits, sum);
+                bits += 1;
+            }
+        }
+        les
+    }
+    // 把各shard的增量合并到累计计数，返回这部分增量的p50、p99、p999，取所在桶的上界。
+    // 没有新的请求时返回None。只由每个周期的snapshot调用
+    pub fn take_percentiles(&self) -> Option<[u64; 3]> {
+        let mut delta = [0u64; BUCKETS];
+        let mut total = 0;
+        for shard in &self.shards {
+            for (d, c) in delta.iter_mut().zip(shard.counts.iter()) {
+                *d += c.swap(0, Relaxed) as u64;
+            }
+            self.sum_us
+                .fetch_add(shard.sum_us.swap(0, Relaxed), Relaxed);
+        }
+        for (c, d) in self.counts.iter().zip(delta.iter()) {
+            if *d > 0 {
+                c.fetch_add(*d, Relaxed);
+                total += *d;
+            }
+        }
+        if total == 0 {
+            return None;
+        }
+        let mut ps = [MAX_US + 1; 3];
+        let mut n = 0;
+        let mut sum = 0;
+        for (idx, d) in delta.iter().enumerate() {
+            sum += d;
+            while n < ps.len() && sum * 1000 >= total * PERMILLES[n] {
+                ps[n] = upper(idx);
+                n += 1;
+            }
+            if n == ps.len() {
+                break;
+            }
+        }
+        Some(ps)
+    }
+    // 把other中未合并的增量合并到当前线程的shard，并清空other
+    pub(crate) fn merge(&self, other: &Self) {
+        let shard = &self.shards[SLOT.with(|s| *s)];
+        for o in &other.shards {
+            for (c, oc) in shard.counts.iter().zip(o.counts.iter()) {
+                let v = oc.swap(0, Relaxed);
+                if v > 0 {
+                    c.fetch_add(v, Relaxed);
+                }
+            }
+            shard.sum_us.fetch_add(o.sum_us.swap(0, Relaxed), Relaxed);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+mod histogram;
 mod host;
 mod number;
 mod qps;
@@ -7,6 +8,7 @@ mod status;
 
 use crate::MetricType;
 
+pub use histogram::Histogram;
 pub(crate) use host::*;
 pub use host::{decr_task, incr_task, set_sockfile_failed};
 pub(crate) use number::*;
@@ -75,7 +77,7 @@ pub mod base {
 
 use crate::ItemWriter as Writer;
 use enum_dispatch::enum_dispatch;
-use std::sync::atomic::AtomicI64;
+use std::sync::atomic::{AtomicI64, AtomicPtr, Ordering::*};
 #[enum_dispatch]
 pub(crate) trait Snapshot {
     fn snapshot<W: Writer>(&self, path: &str, key: &str, data: &ItemData, w: &mut W, secs: f64);
@@ -90,12 +92,45 @@ pub(crate) trait Snapshot {
     }
 }
 // 用4个i64来存储数据。
+// rtt类型额外使用hist记录耗时分布，第一次使用时分配
 #[derive(Default, Debug)]
 pub(crate) struct ItemData {
     d0: AtomicI64,
     d1: AtomicI64,
     d2: AtomicI64,
     d3: AtomicI64,
+    hist: AtomicPtr<Histogram>,
+}
+
+impl ItemData {
+    #[inline]
+    pub(crate) fn hist(&self) -> Option<&Histogram> {
+        unsafe { self.hist.load(Acquire).as_ref() }
+    }
+    #[inline]
+    pub(crate) fn hist_or_init(&self) -> &Histogram {
+        if let Some(h) = self.hist() {
+            return h;
+        }
+        let new = Box::into_raw(Box::default());
+        match self.hist.compare_exchange(std::ptr::null_mut(), new, AcqRel, Acquire) {
+            Ok(_) => unsafe { &*new },
+            Err(cur) => {
+                // 并发初始化，使用先安装的
+                let _ = unsafe { Box::from_raw(new) };
+                unsafe { &*cur }
+            }
+        }
+    }
+}
+
+impl Drop for ItemData {
+    fn drop(&mut self) {
+        let h = *self.hist.get_mut();
+        if !h.is_null() {
+            let _ = unsafe { Box::from_raw(h) };
+        }
+    }
 }
 
 pub(crate) trait IncrTo {
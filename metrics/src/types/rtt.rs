@@ -19,9 +19,13 @@ pub struct Rtt;
 // d1: 总的耗时
 // d2: 慢的数量
 // d3: 最大的耗时
+// hist: 耗时分布
 impl super::Snapshot for Rtt {
     #[inline]
     fn snapshot<W: Writer>(&self, path: &str, key: &str, data: &ItemData, w: &mut W, secs: f64) {
+        // 每个周期只在这里合并一次直方图，分位数是本周期的增量
+        let hist = data.hist();
+        let percentiles = hist.and_then(|h| h.take_percentiles());
         // qps
         let count = data.d0.take();
         if count > 0 {
@@ -41,6 +45,25 @@ impl super::Snapshot for Rtt {
             if max > 0 {
                 w.write(path, key, "max_us", max);
             }
+            if let Some([p50, p99, p999]) = percentiles {
+                w.write(path, key, "p50_us", p50 as i64);
+                w.write(path, key, "p99_us", p99 as i64);
+                w.write(path, key, "p999_us", p999 as i64);
+            }
+        }
+        // 直方图是累计值，没有新的请求时也输出
+        if let Some(h) = hist {
+            w.write_histogram(path, key, &h.cumulative(), h.count(), h.sum_us());
+        }
+    }
+    // 未注册到global之前的耗时，合并到global中
+    fn merge(&self, global: &ItemData, cache: &ItemData) {
+        global.d0.incr_by(cache.d0.take());
+        global.d1.incr_by(cache.d1.take());
+        global.d2.incr_by(cache.d2.take());
+        global.d3.max(cache.d3.take());
+        if let Some(hist) = cache.hist() {
+            global.hist_or_init().merge(hist);
         }
     }
 }
@@ -64,5 +87,6 @@ impl IncrTo for Duration {
         if us >= MAX_US {
             data.d3.max(us);
         }
+        data.hist_or_init().record(us as u64);
     }
 }
@@ -1,6 +1,9 @@
 mod stream;
 pub use stream::*;
 
+pub mod tls;
+pub mod upgrade;
+
 pub trait StreamInit {
     #[inline]
     fn init(&mut self) {}
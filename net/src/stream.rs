@@ -6,6 +6,8 @@ pub enum Stream {
     $(
         $var($t),
     )+
+    // tcp连接上的TLS，包括侦听端accept的连接与后端连接
+    Tls(Box<crate::tls::TlsStream>),
 }
 impl tokio::io::AsyncRead for Stream {
     #[inline]
@@ -14,6 +16,7 @@ impl tokio::io::AsyncRead for Stream {
             $(
             &mut Self::$var(ref mut stream) => tokio::io::AsyncRead::poll_read(Pin::new(stream), cx, buf),
             )+
+            &mut Self::Tls(ref mut stream) => tokio::io::AsyncRead::poll_read(Pin::new(stream), cx, buf),
         }
     }
 }
@@ -25,6 +28,7 @@ impl tokio::io::AsyncWrite for Stream {
             $(
             &mut Stream::$var(ref mut stream) => tokio::io::AsyncWrite::poll_write(Pin::new(stream), cx, buf),
             )+
+            &mut Stream::Tls(ref mut stream) => tokio::io::AsyncWrite::poll_write(Pin::new(stream), cx, buf),
         }
     }
     #[inline]
@@ -33,6 +37,7 @@ impl tokio::io::AsyncWrite for Stream {
             $(
             &mut Stream::$var(ref mut stream) => tokio::io::AsyncWrite::poll_flush(Pin::new(stream), cx),
             )+
+            &mut Stream::Tls(ref mut stream) => tokio::io::AsyncWrite::poll_flush(Pin::new(stream), cx),
         }
     }
     #[inline]
@@ -41,6 +46,7 @@ impl tokio::io::AsyncWrite for Stream {
             $(
             &mut Stream::$var(ref mut stream) => tokio::io::AsyncWrite::poll_shutdown(Pin::new(stream), cx),
             )+
+            &mut Stream::Tls(ref mut stream) => tokio::io::AsyncWrite::poll_shutdown(Pin::new(stream), cx),
         }
     }
 }
@@ -63,7 +69,17 @@ impl Listener {
     pub async fn bind(protocol: &str, addr: &str) -> std::io::Result<Self> {
         match protocol.to_lowercase().as_str() {
             $(
-            $name  => Ok(Self::$var(<$listener>::binding(addr).await?)),
+            $name  => {
+                // 热升级时优先使用从旧进程继承的fd
+                let name = crate::upgrade::name($name, addr);
+                let upgrade = crate::upgrade::global();
+                let l = match upgrade.take(&name) {
+                    Some(fd) => <$listener>::from_fd(fd)?,
+                    None => <$listener>::binding(addr).await?,
+                };
+                upgrade.register(name, l.as_raw_fd());
+                Ok(Self::$var(l))
+            }
             )+
             _ => Err(Error::new(ErrorKind::InvalidInput, addr)),
         }
@@ -82,27 +98,48 @@ impl Listener {
         }
     }
 }
+impl Drop for Listener {
+    fn drop(&mut self) {
+        match self {
+            $(
+            Self::$var(l) => crate::upgrade::global().unregister(l.as_raw_fd()),
+            )+
+        }
+    }
+}
 
 
     };
 } // end of macro define_stream
 
 use std::io::{Error, ErrorKind, Result};
+use std::os::fd::{AsRawFd, OwnedFd};
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
 trait Bind: Sized {
     async fn binding(addr: &str) -> Result<Self>;
+    fn from_fd(fd: OwnedFd) -> Result<Self>;
 }
 impl Bind for tokio::net::TcpListener {
     async fn binding(addr: &str) -> Result<Self> {
         Self::bind(addr).await
     }
+    fn from_fd(fd: OwnedFd) -> Result<Self> {
+        let l = std::net::TcpListener::from(fd);
+        l.set_nonblocking(true)?;
+        Self::from_std(l)
+    }
 }
 impl Bind for tokio::net::UnixListener {
     async fn binding(addr: &str) -> Result<Self> {
         Self::bind(addr)
     }
+    fn from_fd(fd: OwnedFd) -> Result<Self> {
+        let l = std::os::unix::net::UnixListener::from(fd);
+        l.set_nonblocking(true)?;
+        Self::from_std(l)
+    }
 }
 
 define_stream!(
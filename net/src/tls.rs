@@ -0,0 +1,349 @@
+// TLS支持：
+// 1. 后端连接：按namespace配置的ca、sni、客户端证书，在建连后进行TLS握手；
+//    mysql在协议握手中协商TLS（SSLRequest），见connect_mysql；
+// 2. 侦听：quadruple中声明了tls的tcp侦听，accept后使用启动参数指定的证书终结TLS。
+// 证书文件按修改时间定期在blocking线程中检查，变更后新建立的连接使用新的证书，不需要重启。
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind, Result};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::{ClientConfig, RootCertStore, ServerConfig};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::TcpStream;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+use crate::Stream;
+
+// 证书文件变更的检查间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+// 后端连接的TLS配置，文件均为pem格式
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct TlsOption {
+    // 校验后端证书的CA
+    pub ca: String,
+    // 为空时使用后端地址中的host
+    #[serde(default)]
+    pub sni: String,
+    // 客户端证书及私钥，后端要求双向认证时配置
+    #[serde(default)]
+    pub cert: String,
+    #[serde(default)]
+    pub key: String,
+}
+
+// 建立到addr的连接，tls不为None时进行TLS握手
+pub async fn connect(addr: &str, tls: Option<&TlsOption>) -> Result<Stream> {
+    let s = TcpStream::connect(addr).await?;
+    let _ = s.set_nodelay(true);
+    match tls {
+        None => Ok(Stream::Tcp(s)),
+        Some(o) => handshake(addr, o, s, Vec::new()).await,
+    }
+}
+
+// mysql的TLS在协议握手中协商：
+// 1. 读取server的初始握手包（greeting），确认server支持CLIENT_SSL；
+// 2. 发送SSLRequest（seq为1），之后进行TLS握手；
+// 3. greeting的seq改为1后交给上层的协议握手处理，使HandshakeResponse的seq为2，在TLS上发送。
+pub async fn connect_mysql(addr: &str, tls: Option<&TlsOption>) -> Result<Stream> {
+    let Some(o) = tls else {
+        return connect(addr, None).await;
+    };
+    let mut s = TcpStream::connect(addr).await?;
+    let _ = s.set_nodelay(true);
+    let mut greeting = vec![0u8; 4];
+    s.read_exact(&mut greeting).await?;
+    let len = u32::from_le_bytes([greeting[0], greeting[1], greeting[2], 0]) as usize;
+    greeting.resize(4 + len, 0);
+    s.read_exact(&mut greeting[4..]).await?;
+    let caps = mysql_caps(&greeting[4..]).ok_or_else(|| invalid_data("mysql greeting"))?;
+    if caps & CLIENT_SSL == 0 {
+        return Err(invalid_data("mysql server does not support ssl"));
+    }
+    // 完整的能力在之后的HandshakeResponse中发送
+    let caps = caps & (CLIENT_PROTOCOL_41 | CLIENT_SECURE_CONNECTION) | CLIENT_SSL;
+    let mut req = Vec::with_capacity(4 + 32);
+    req.extend_from_slice(&[32, 0, 0, 1]);
+    req.extend_from_slice(&caps.to_le_bytes());
+    // max packet 16M，utf8mb4_general_ci，与HandshakeResponse一致
+    req.extend_from_slice(&[0, 0, 0, 1, 45]);
+    req.extend_from_slice(&[0; 23]);
+    s.write_all(&req).await?;
+    greeting[3] = 1;
+    handshake(addr, o, s, greeting).await
+}
+
+const CLIENT_PROTOCOL_41: u32 = 0x0000_0200;
+const CLIENT_SSL: u32 = 0x0000_0800;
+const CLIENT_SECURE_CONNECTION: u32 = 0x0000_8000;
+
+// greeting: protocol version(1) server version(以0结尾) connection id(4) auth data(8) filler(1) capabilities低16位(2)
+// charset(1) status(2) capabilities高16位(2)
+fn mysql_caps(payload: &[u8]) -> Option<u32> {
+    let version_end = payload.get(1..)?.iter().position(|&b| b == 0)? + 1;
+    let caps = payload.get(version_end + 1 + 4 + 8 + 1..)?;
+    let low = u16::from_le_bytes([*caps.first()?, *caps.get(1)?]) as u32;
+    let high = match caps.get(5..7) {
+        Some(high) => u16::from_le_bytes([high[0], high[1]]) as u32,
+        None => 0,
+    };
+    Some(high << 16 | low)
+}
+
+async fn handshake(addr: &str, o: &TlsOption, s: TcpStream, pending: Vec<u8>) -> Result<Stream> {
+    let sni = if o.sni.is_empty() {
+        addr.rsplit_once(':').map_or(addr, |(host, _)| host)
+    } else {
+        &o.sni
+    };
+    let name = ServerName::try_from(sni.to_string()).map_err(invalid)?;
+    let s = connector(o).await?.connect(name, s).await?;
+    Ok(Stream::Tls(Box::new(TlsStream::new(s.into(), pending))))
+}
+
+// tcp连接上的TLS。pending为TLS握手之前收到、需要先交给上层的明文数据，如mysql的greeting
+#[derive(Debug)]
+pub struct TlsStream {
+    s: tokio_rustls::TlsStream<TcpStream>,
+    pending: Vec<u8>,
+    oft: usize,
+}
+
+impl TlsStream {
+    fn new(s: tokio_rustls::TlsStream<TcpStream>, pending: Vec<u8>) -> Self {
+        Self { s, pending, oft: 0 }
+    }
+}
+
+impl AsyncRead for TlsStream {
+    #[inline]
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<Result<()>> {
+        let me = self.get_mut();
+        if me.oft < me.pending.len() {
+            let n = (me.pending.len() - me.oft).min(buf.remaining());
+            buf.put_slice(&me.pending[me.oft..me.oft + n]);
+            me.oft += n;
+            if me.oft == me.pending.len() {
+                me.pending = Vec::new();
+                me.oft = 0;
+            }
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut me.s).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TlsStream {
+    #[inline]
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
+        Pin::new(&mut self.get_mut().s).poll_write(cx, buf)
+    }
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.get_mut().s).poll_flush(cx)
+    }
+    #[inline]
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.get_mut().s).poll_shutdown(cx)
+    }
+}
+
+// 侦听端终结TLS。每次accept时获取当前的证书，证书文件变更后自动重新加载
+#[derive(Clone, Debug)]
+pub struct Acceptor {
+    cert: String,
+    key: String,
+}
+
+impl Acceptor {
+    // 启动时加载一次，证书不可用时不侦听
+    pub fn new(cert: &str, key: &str) -> Result<Self> {
+        let me = Self {
+            cert: cert.to_string(),
+            key: key.to_string(),
+        };
+        load(&ACCEPTORS, me.key())?;
+        Ok(me)
+    }
+    pub async fn accept(&self, s: Stream) -> Result<Stream> {
+        match s {
+            Stream::Tcp(s) => {
+                let s = cached(&ACCEPTORS, self.key()).await?.accept(s).await?;
+                Ok(Stream::Tls(Box::new(TlsStream::new(s.into(), Vec::new()))))
+            }
+            s => Ok(s),
+        }
+    }
+    fn key(&self) -> (String, String) {
+        (self.cert.clone(), self.key.clone())
+    }
+}
+
+async fn connector(o: &TlsOption) -> Result<TlsConnector> {
+    cached(&CONNECTORS, o.clone()).await
+}
+
+// 由证书文件build出的TLS配置
+trait Build: Sized {
+    type Key: std::hash::Hash + Eq + Clone + Send + 'static;
+    fn files(key: &Self::Key) -> Vec<&str>;
+    fn build(key: &Self::Key) -> Result<Self>;
+}
+
+impl Build for TlsAcceptor {
+    type Key = (String, String);
+    fn files((cert, key): &Self::Key) -> Vec<&str> {
+        vec![cert, key]
+    }
+    fn build((cert, key): &Self::Key) -> Result<Self> {
+        let config = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(invalid)?
+            .with_no_client_auth()
+            .with_single_cert(certs(cert)?, private_key(key)?)
+            .map_err(invalid)?;
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+impl Build for TlsConnector {
+    type Key = TlsOption;
+    fn files(o: &Self::Key) -> Vec<&str> {
+        vec![&o.ca, &o.cert, &o.key]
+    }
+    fn build(o: &Self::Key) -> Result<Self> {
+        let mut roots = RootCertStore::empty();
+        for cert in certs(&o.ca)? {
+            roots.add(cert).map_err(invalid)?;
+        }
+        let builder = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(invalid)?
+            .with_root_certificates(roots);
+        let config = if o.cert.is_empty() {
+            builder.with_no_client_auth()
+        } else {
+            builder
+                .with_client_auth_cert(certs(&o.cert)?, private_key(&o.key)?)
+                .map_err(invalid)?
+        };
+        Ok(TlsConnector::from(Arc::new(config)))
+    }
+}
+
+struct Loaded<T> {
+    // 加载时各文件的修改时间
+    modified: Vec<Option<SystemTime>>,
+    checked: Instant,
+    v: T,
+}
+type Cache<T> = Mutex<Option<HashMap<<T as Build>::Key, Loaded<T>>>>;
+static CONNECTORS: Cache<TlsConnector> = Mutex::new(None);
+static ACCEPTORS: Cache<TlsAcceptor> = Mutex::new(None);
+
+// 建连、accept时只读取缓存，锁内不进行文件操作。
+// 第一次使用及超过CHECK_INTERVAL后，在blocking线程中检查文件的修改时间，有变更时重新build；
+// 重新build失败时继续使用之前加载的配置
+async fn cached<T: Build + Clone + Send + 'static>(
+    cache: &'static Cache<T>,
+    key: T::Key,
+) -> Result<T> {
+    {
+        let mut guard = cache.lock().expect("tls cache");
+        if let Some(loaded) = guard.get_or_insert_with(Default::default).get_mut(&key) {
+            if loaded.checked.elapsed() >= CHECK_INTERVAL {
+                loaded.checked = Instant::now();
+                let key = key.clone();
+                tokio::task::spawn_blocking(move || reload(cache, key));
+            }
+            return Ok(loaded.v.clone());
+        }
+    }
+    tokio::task::spawn_blocking(move || load(cache, key))
+        .await
+        .map_err(Error::other)?
+}
+
+// 同步加载，已加载时直接返回
+fn load<T: Build + Clone>(cache: &Cache<T>, key: T::Key) -> Result<T> {
+    let modified = modified(&T::files(&key));
+    let v = T::build(&key)?;
+    let mut guard = cache.lock().expect("tls cache");
+    let cache = guard.get_or_insert_with(Default::default);
+    let loaded = cache.entry(key).or_insert_with(|| Loaded {
+        modified,
+        checked: Instant::now(),
+        v,
+    });
+    Ok(loaded.v.clone())
+}
+
+fn reload<T: Build>(cache: &Cache<T>, key: T::Key) {
+    let files = T::files(&key);
+    let modified = modified(&files);
+    {
+        let guard = cache.lock().expect("tls cache");
+        let loaded = guard.as_ref().and_then(|c| c.get(&key));
+        if loaded.is_none_or(|l| l.modified == modified) {
+            return;
+        }
+    }
+    let r = T::build(&key);
+    let mut guard = cache.lock().expect("tls cache");
+    if let Some(loaded) = guard.as_mut().and_then(|c| c.get_mut(&key)) {
+        match r {
+            Ok(v) => {
+                log::info!("tls reloaded: {:?}", files);
+                loaded.v = v;
+            }
+            Err(e) => log::warn!("tls reload {:?} failed: {}", files, e),
+        }
+        loaded.modified = modified;
+    }
+}
+
+fn modified(files: &[&str]) -> Vec<Option<SystemTime>> {
+    let modified = |f: &&str| std::fs::metadata(f).and_then(|m| m.modified()).ok();
+    files.iter().map(modified).collect()
+}
+
+fn provider() -> Arc<rustls::crypto::CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let mut r = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut r).collect::<Result<Vec<_>>>()?;
+    if certs.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("no cert in {}", path),
+        ));
+    }
+    Ok(certs)
+}
+
+fn private_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let mut r = BufReader::new(File::open(path)?);
+    rustls_pemfile::private_key(&mut r)?
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("no key in {}", path)))
+}
+
+fn invalid_data(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn invalid<E: std::error::Error + Send + Sync + 'static>(e: E) -> Error {
+    Error::new(ErrorKind::InvalidInput, e)
+}
@@ -0,0 +1,219 @@
+// 热升级：新进程以upgrade模式启动后，连接旧进程的控制socket，
+// 旧进程通过SCM_RIGHTS把所有侦听的fd传递给新进程，新进程bind时直接使用，不再重新bind；
+// 新进程确认后，旧进程停止accept，处理完已有的连接后退出。整个过程中侦听的socket不会关闭，unix socket的文件也不会删除。
+//
+// 传递的格式：新进程先发送"upgrade\n"请求，未发送请求的连接（如探测控制socket是否在使用）不会触发交接；
+// 每个侦听一条消息，内容为"protocol@addr\n"，同时附带一个fd；最后以一个空行结束。
+// 新进程接收完成后回复"ok\n"。
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::mem::size_of;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// 进程内的侦听都注册在GLOBAL中
+static GLOBAL: Upgrade = Upgrade::new();
+
+const REQ: &[u8] = b"upgrade\n";
+const ACK: &[u8] = b"ok\n";
+// 单条消息的最大长度
+const MAX_MSG: usize = 4096;
+// 等待对端的超时时间
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[inline]
+pub(crate) fn name(protocol: &str, addr: &str) -> String {
+    format!("{}@{}", protocol, addr)
+}
+#[inline]
+pub(crate) fn global() -> &'static Upgrade {
+    &GLOBAL
+}
+// 是否从旧进程继承了侦听的fd
+pub fn inherited() -> bool {
+    GLOBAL.inherited()
+}
+pub fn handover(stream: UnixStream) -> Result<usize> {
+    GLOBAL.handover(stream)
+}
+pub fn inherit(stream: UnixStream) -> Result<usize> {
+    GLOBAL.inherit(stream)
+}
+
+// 侦听的fd及从旧进程继承的fd。进程内使用GLOBAL，测试可以使用独立的实例
+pub struct Upgrade {
+    // 当前正在侦听的fd
+    listening: Mutex<Vec<(String, RawFd)>>,
+    // 从旧进程继承的fd，bind时取走
+    inherited: Mutex<Option<HashMap<String, OwnedFd>>>,
+}
+
+impl Default for Upgrade {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Upgrade {
+    pub const fn new() -> Self {
+        Self {
+            listening: Mutex::new(Vec::new()),
+            inherited: Mutex::new(None),
+        }
+    }
+    pub fn register(&self, name: String, fd: RawFd) {
+        self.listening.lock().expect("listening").push((name, fd));
+    }
+    pub fn unregister(&self, fd: RawFd) {
+        self.listening
+            .lock()
+            .expect("listening")
+            .retain(|(_, f)| *f != fd);
+    }
+    pub fn inherited(&self) -> bool {
+        self.inherited.lock().expect("inherited").is_some()
+    }
+    pub fn take(&self, name: &str) -> Option<OwnedFd> {
+        self.inherited
+            .lock()
+            .expect("inherited")
+            .as_mut()?
+            .remove(name)
+    }
+
+    // 旧进程：收到升级请求后，把当前所有侦听的fd发送给新进程，新进程确认后返回发送的数量。
+    // 发送过程中持有锁，避免listener被drop后fd失效。
+    pub fn handover(&self, mut stream: UnixStream) -> Result<usize> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        let mut req = [0u8; REQ.len()];
+        stream.read_exact(&mut req)?;
+        if req != REQ {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "invalid upgrade request",
+            ));
+        }
+        let listening = self.listening.lock().expect("listening");
+        for (name, fd) in listening.iter() {
+            send_fd(stream.as_raw_fd(), format!("{}\n", name).as_bytes(), *fd)?;
+        }
+        stream.write_all(b"\n")?;
+        let mut ack = [0u8; ACK.len()];
+        stream.read_exact(&mut ack)?;
+        if ack != ACK {
+            return Err(Error::new(ErrorKind::InvalidData, "invalid upgrade ack"));
+        }
+        Ok(listening.len())
+    }
+
+    // 新进程：发送升级请求，从旧进程接收所有侦听的fd，返回接收的数量。
+    pub fn inherit(&self, mut stream: UnixStream) -> Result<usize> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        stream.write_all(REQ)?;
+        let mut names = Vec::new();
+        let mut fds = Vec::new();
+        let mut data = Vec::new();
+        let mut buf = vec![0u8; MAX_MSG];
+        'recv: loop {
+            let n = recv_fds(stream.as_raw_fd(), &mut buf, &mut fds)?;
+            if n == 0 {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "upgrade closed"));
+            }
+            data.extend_from_slice(&buf[..n]);
+            while let Some(pos) = data.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = data.drain(..=pos).collect();
+                if line.len() == 1 {
+                    break 'recv;
+                }
+                names.push(String::from_utf8_lossy(&line[..line.len() - 1]).to_string());
+            }
+        }
+        if names.len() != fds.len() {
+            let msg = format!("{} listeners with {} fds", names.len(), fds.len());
+            return Err(Error::new(ErrorKind::InvalidData, msg));
+        }
+        let n = names.len();
+        self.inherited
+            .lock()
+            .expect("inherited")
+            .get_or_insert_with(Default::default)
+            .extend(names.into_iter().zip(fds));
+        stream.write_all(ACK)?;
+        Ok(n)
+    }
+}
+
+// 与cmsghdr对齐的控制消息buffer
+fn cmsg_buffer(fds: usize) -> Vec<u64> {
+    let space = unsafe { libc::CMSG_SPACE((fds * size_of::<RawFd>()) as u32) } as usize;
+    vec![0u64; space.div_ceil(size_of::<u64>())]
+}
+
+fn send_fd(sock: RawFd, data: &[u8], fd: RawFd) -> Result<()> {
+    let mut control = cmsg_buffer(1);
+    let mut iov = libc::iovec {
+        iov_base: data.as_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+    let n = unsafe {
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = (control.len() * size_of::<u64>()) as _;
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(size_of::<RawFd>() as u32) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut RawFd, fd);
+        libc::sendmsg(sock, &msg, 0)
+    };
+    match n {
+        n if n < 0 => Err(Error::last_os_error()),
+        n if n as usize != data.len() => {
+            Err(Error::new(ErrorKind::WriteZero, "partial upgrade msg"))
+        }
+        _ => Ok(()),
+    }
+}
+
+// 接收数据及附带的fd，fd按接收的顺序追加到fds中
+fn recv_fds(sock: RawFd, buf: &mut [u8], fds: &mut Vec<OwnedFd>) -> Result<usize> {
+    // 一次最多接收的fd数量
+    const MAX_FDS: usize = 64;
+    let mut control = cmsg_buffer(MAX_FDS);
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    unsafe {
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = (control.len() * size_of::<u64>()) as _;
+        let n = libc::recvmsg(sock, &mut msg, libc::MSG_CMSG_CLOEXEC);
+        if n < 0 {
+            return Err(Error::last_os_error());
+        }
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let len = (*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+                let data = libc::CMSG_DATA(cmsg) as *const RawFd;
+                for i in 0..len / size_of::<RawFd>() {
+                    fds.push(OwnedFd::from_raw_fd(std::ptr::read_unaligned(data.add(i))));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+        if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "upgrade fds truncated"));
+        }
+        Ok(n as usize)
+    }
+}
@@ -77,6 +77,7 @@ pub fn topology_dispatcher(input: TokenStream) -> TokenStream {
     }
     });
 
+    let mut used = std::collections::HashSet::new();
     let try_from_arms = enum_def.variants.iter().map(|variant| {
         let variant_ident = &variant.ident;
         // 使用第一个单词，或者每个单词的首字母作为endpoint
@@ -92,6 +93,8 @@ pub fn topology_dispatcher(input: TokenStream) -> TokenStream {
         if s.eq("PhantomService") {
             endpoints.push("pt".to_string());
         }
+        // 4. 与之前的variant重名时，以先定义的为准，如RedisCluster只能使用"rc"
+        endpoints.retain(|e| used.insert(e.clone()));
 
         quote! {
             #(#endpoints) | * => Ok(Self::#variant_ident(p.into())),
@@ -4,11 +4,12 @@ use std::{
     ptr::{self, NonNull},
     sync::{
         Arc,
-        atomic::{AtomicBool, AtomicU8, Ordering::*},
+        atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering::*},
     },
 };
 
 use crate::BackendQuota;
+use crate::outlier::Outlier;
 use ds::{AtomicWaker, time::Instant};
 
 use crate::{Command, Error, HashedCommand, request::Request};
@@ -38,6 +39,8 @@ pub struct CallbackContext {
     pub(crate) try_next: bool,           // 请求失败后，topo层面是否允许重试
     pub(crate) retry_on_rsp_notok: bool, // 有响应且响应不ok时，协议层面是否允许重试
     pub(crate) write_back: bool,         // 请求结束后，是否需要回写。
+    pub(crate) redirect: bool,           // 是否按MOVED、ASK响应重定向，如redis cluster
+    pub(crate) prefix: u8,               // 请求之前附加的指令（如ASKING）数量，其响应直接丢弃
     pub(crate) max_tries: OnceCell<u8>,  // 最大重试次数
     first: bool,                         // 当前请求是否是所有子请求的第一个
     last: bool,                          // 当前请求是否是所有子请求的最后一个
@@ -48,8 +51,35 @@ pub struct CallbackContext {
     waker: *const Arc<AtomicWaker>,
     callback: CallbackPtr,
     quota: Option<BackendQuota>,
+    // 本次访问的副本及发送时间
+    outlier: Option<(Outlier, Instant)>,
+    // 允许对冲时，为第一次发送后的flag，对冲请求按重试的路径发送到下一个副本。
+    // 0: 不允许对冲；HEDGED: 已经发送过对冲请求
+    hedge: AtomicU64,
+    // 采样的请求记录各阶段的时间，导出到otlp
+    trace: Option<Box<Trace>>,
 }
 
+// 请求各阶段的时间。重试时，发送时间为第一次发送，响应时间为最后一次
+pub struct Trace {
+    parse: Instant,
+    parsed: Instant,
+    sent: Option<Instant>,
+    received: Option<Instant>,
+}
+
+impl Trace {
+    // 响应写入client之后调用
+    pub fn record(&self, path: &metrics::Path, op: &str, ok: bool) {
+        let sent = self.sent.unwrap_or(self.parsed);
+        let received = self.received.unwrap_or(sent);
+        let stages = [self.parse, self.parsed, sent, received, Instant::now()];
+        metrics::otlp::record_span(path, op, ok, stages);
+    }
+}
+
+const HEDGED: u64 = u64::MAX;
+
 impl CallbackContext {
     #[inline]
     pub fn new(
@@ -73,6 +103,8 @@ impl CallbackContext {
             try_next: false,
             retry_on_rsp_notok,
             write_back: false,
+            redirect: false,
+            prefix: 0,
             max_tries: OnceCell::from(max_tries),
             request: req,
             response: MaybeUninit::uninit(),
@@ -81,9 +113,17 @@ impl CallbackContext {
             tries: 0.into(),
             waker,
             quota: None,
+            outlier: None,
+            hedge: 0.into(),
+            trace: None,
         }
     }
 
+    // 同一个client的请求（包括对冲、子请求）共享pipeline的waker
+    #[inline]
+    pub fn client(&self) -> usize {
+        self.waker as usize
+    }
     #[inline]
     pub fn flag(&self) -> crate::Context {
         self.flag
@@ -106,6 +146,9 @@ impl CallbackContext {
     #[inline]
     pub(crate) fn on_sent(&mut self) -> bool {
         log::debug!("request sent: {} ", self);
+        if let Some(t) = self.trace.as_mut() {
+            t.sent.get_or_insert_with(Instant::now);
+        }
         if self.request().sentonly() {
             self.on_done();
             false
@@ -142,8 +185,9 @@ impl CallbackContext {
             // 当前重试条件为 rsp == None || ("mc" && !rsp.ok())
             if self.inited() {
                 // 优先筛出正常的请求，便于理解
-                // rsp.ok 不需要重试
-                if unsafe { self.unchecked_response().ok() } {
+                // rsp.ok 不需要重试；重定向的响应由topo按响应重试
+                let rsp = unsafe { self.unchecked_response() };
+                if rsp.ok() && !(self.redirect && crate::redis::cluster::is_redirect(rsp)) {
                     return false;
                 }
                 //有响应并且!ok，配置了!retry_on_rsp_notok，不需要重试，比如mysql
@@ -163,9 +207,15 @@ impl CallbackContext {
     #[inline]
     fn on_done(&mut self) {
         log::debug!("on-done:{}", self);
+        if let Some(t) = self.trace.as_mut() {
+            t.received = Some(Instant::now());
+        }
         if !self.async_mode {
             // 更新backend使用的时间
             self.quota.take().map(|q| q.incr(self.start_at().elapsed()));
+            if let Some((o, start)) = self.outlier.take() {
+                o.record(start, self.inited());
+            }
         }
 
         if self.need_gone() {
@@ -204,6 +254,9 @@ impl CallbackContext {
         self.quota
             .take()
             .map(|q| q.err_incr(self.start_at().elapsed()));
+        if let Some((o, start)) = self.outlier.take() {
+            o.record(start, false);
+        }
         self.on_done();
     }
     #[inline]
@@ -220,6 +273,10 @@ impl CallbackContext {
         unsafe { self.response.assume_init_ref() }
     }
     #[inline]
+    pub fn last_response(&self) -> Option<&Command> {
+        self.inited().then(|| unsafe { self.unchecked_response() })
+    }
+    #[inline]
     pub fn complete(&self) -> bool {
         debug_assert!(!self.async_mode, "{:?}", self);
         self.done.load(Acquire)
@@ -248,9 +305,48 @@ impl CallbackContext {
 
     #[inline]
     fn goon(&mut self) {
+        // 重试的请求不再对冲
+        self.hedge.store(0, Release);
         self.send();
     }
     #[inline]
+    pub(crate) fn set_hedge(&mut self) {
+        self.hedge.store(self.flag, Release);
+    }
+    // 允许对冲，或者已经发送过对冲请求
+    #[inline]
+    pub fn hedgeable(&self) -> bool {
+        self.hedge.load(Acquire) != 0
+    }
+    // 已经发送过对冲请求
+    #[inline]
+    pub fn hedged(&self) -> bool {
+        self.hedge.load(Acquire) == HEDGED
+    }
+    // 构建对冲请求，每个请求最多对冲一次。
+    // 请求已经发送到后端，只能读取request，不能修改ctx的其他状态
+    #[inline]
+    pub fn hedge(&self) -> Option<Self> {
+        let flag = self.hedge.load(Acquire);
+        if flag == 0 || flag == HEDGED {
+            return None;
+        }
+        self.hedge
+            .compare_exchange(flag, HEDGED, AcqRel, Relaxed)
+            .ok()?;
+        let mut ctx = Self::new(
+            self.request.copy(),
+            self.waker,
+            self.callback.clone(),
+            self.first,
+            self.last,
+            self.retry_on_rsp_notok,
+            *self.max_tries.get().expect("max tries"),
+        );
+        ctx.flag = flag;
+        Some(ctx)
+    }
+    #[inline]
     pub fn async_mode(&mut self) {
         // 在异步处理之前，必须要先处理完response
         debug_assert!(
@@ -290,6 +386,24 @@ impl CallbackContext {
     pub fn quota(&mut self, quota: BackendQuota) {
         self.quota = Some(quota);
     }
+    #[inline]
+    pub fn outlier(&mut self, outlier: Outlier) {
+        self.outlier = Some((outlier, Instant::now()));
+    }
+    // parse: 开始解析该请求的时间
+    #[inline]
+    pub fn trace(&mut self, parse: Instant) {
+        self.trace = Some(Box::new(Trace {
+            parse,
+            parsed: self.start,
+            sent: None,
+            received: None,
+        }));
+    }
+    #[inline]
+    pub fn take_trace(&mut self) -> Option<Box<Trace>> {
+        self.trace.take()
+    }
 }
 
 impl Drop for CallbackContext {
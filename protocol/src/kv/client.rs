@@ -12,6 +12,8 @@ pub struct Client {
     pub status_flags: StatusFlags,
     pub character_set: u8,
     pub server_version: Option<(u16, u16, u16)>,
+    // 连接建立时已通过SSLRequest开启TLS，HandshakeResponse中需要声明CLIENT_SSL
+    pub ssl: bool,
 }
 
 impl Client {
@@ -23,7 +25,7 @@ impl Client {
     }
 
     pub fn get_flags(&self) -> CapabilityFlags {
-        let client_flags = CapabilityFlags::CLIENT_PROTOCOL_41
+        let mut client_flags = CapabilityFlags::CLIENT_PROTOCOL_41
             | CapabilityFlags::CLIENT_SECURE_CONNECTION
             | CapabilityFlags::CLIENT_LONG_PASSWORD
             | CapabilityFlags::CLIENT_TRANSACTIONS
@@ -47,10 +49,9 @@ impl Client {
         //     }
         // }
 
-        // 暂时不支持ssl fishermen
-        // if self.is_insecure() && self.0.opts.get_ssl_opts().is_some() {
-        //     client_flags.insert(CapabilityFlags::CLIENT_SSL);
-        // }
+        if self.ssl {
+            client_flags.insert(CapabilityFlags::CLIENT_SSL);
+        }
 
         client_flags | self.opts.get_additional_capabilities()
     }
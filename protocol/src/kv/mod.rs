@@ -85,6 +85,7 @@ impl Protocol for Kv {
     fn config(&self) -> crate::Config {
         crate::Config {
             need_auth: true,
+            tls_in_handshake: true,
             ..Default::default()
         }
     }
@@ -268,7 +269,8 @@ impl Kv {
         option: &mut crate::ResOption,
     ) -> crate::Result<HandShake> {
         log::debug!("+++ recv mysql handshake packet:{:?}", stream.slice());
-        let client = Client::from_user_pwd(option.username.clone(), option.token.clone());
+        let mut client = Client::from_user_pwd(option.username.clone(), option.token.clone());
+        client.ssl = option.tls.is_some();
         let mut packet = ResponsePacket::new(stream, Some(client));
 
         match packet.ctx().status {
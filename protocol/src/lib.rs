@@ -35,6 +35,7 @@ pub use metrics::HostMetric;
 pub use operation::*;
 
 pub mod callback;
+pub mod outlier;
 pub mod request;
 
 #[derive(Copy, Clone)]
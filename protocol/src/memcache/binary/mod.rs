@@ -14,6 +14,7 @@ use crate::{
     RequestProcessor, Result, Stream, Writer,
 };
 
+use ds::ByteOrder;
 use sharding::hash::Hash;
 
 impl Protocol for MemcacheBinary {
@@ -76,9 +77,12 @@ impl Protocol for MemcacheBinary {
                 } else {
                     // quite rsp只有一种情况：出错了;但这种错误往往并不需要断连接：如deleteq的not-found,setq的not-stored。
                     // quite请求是异步处理，可以考虑直接忽略即可，先忽略deleteq，后续setq。
+                    // incrq/appendq等回写其他layer时，key不存在的错误同样忽略。
                     let _ = data.take(pl);
                     match r.op() {
-                        OP_DELQ => Ok(None),
+                        OP_DELQ | OP_REPLACEQ | OP_INCRQ | OP_DECRQ | OP_APPENDQ | OP_PREPENDQ => {
+                            Ok(None)
+                        }
                         _ => Err(Error::ResponseQuiet),
                     }
                 };
@@ -156,11 +160,13 @@ impl Protocol for MemcacheBinary {
             OP_VERSION => w.write(&VERSION_RESPONSE),
             OP_STAT => w.write(&STAT_RESPONSE),
             // TODO 参考packet::is_quiet_get，需要同步变，性能考虑继续放这里 fishermen
-            OP_GETQ | OP_GETKQ | OP_GETSQ => Ok(()),
-            OP_SET | OP_DEL | OP_ADD => {
+            OP_GETQ | OP_GETKQ | OP_GETSQ | OP_GATQ | OP_GATKQ => Ok(()),
+            // 写请求没有响应时不能返回NotFound，否则client会误以为key不存在
+            OP_SET | OP_DEL | OP_ADD | OP_REPLACE | OP_APPEND | OP_PREPEND | OP_INCR | OP_DECR
+            | OP_TOUCH => {
                 w.write(&self.build_empty_response(NotStored, old_op_code, ctx.request()))
             }
-            OP_GET | OP_GETS => {
+            OP_GET | OP_GETS | OP_GAT | OP_GATK => {
                 w.write(&self.build_empty_response(NotFound, old_op_code, ctx.request()))
             }
             OP_QUIT | OP_QUITQ => Err(Error::Quit),
@@ -170,6 +176,7 @@ impl Protocol for MemcacheBinary {
 
     // 如果是写请求，把cas请求转换为set请求。
     // 如果是读请求，则通过response重新构建一个新的写请求。
+    // 如果是gat请求，则通过response及请求中的过期时间构建一个新的写请求。
     #[inline]
     fn build_writeback_request<C, M, I>(
         &self,
@@ -185,12 +192,22 @@ impl Protocol for MemcacheBinary {
         if ctx.request_mut().operation().is_retrival() {
             let req = &*ctx.request();
             self.build_write_back_get(req, response, exp_sec)
+        } else if is_gat(ctx.request().op_code() as u8) {
+            let req = ctx.request();
+            // gat的extra只有4字节的expiration
+            let exp_sec = req.extra_or_flag().u32_be(0);
+            self.build_write_back_get(req, response, exp_sec)
         } else {
             self.build_write_back_inplace(ctx.request_mut());
             None
         }
     }
 
+    #[inline]
+    fn can_retry_on_rsp_notok(&self, req: &HashedCommand) -> bool {
+        Binary::can_retry_on_rsp_notok(&***req)
+    }
+
     // mc目前不需要统计error，因为mc的error基本都是get miss，del not-found这种，这种错误不需要统计
     #[inline]
     fn metric_err(&self, _req_op: Operation) -> bool {
@@ -222,7 +239,10 @@ impl MemcacheBinary {
         assert!(req.len() >= HEADER_LEN, "req: {:?}", req);
         // 把cas请求转换成非cas请求: cas值设置为0
         req.clear_cas();
-        let op = req.map_op_noreply();
+        // touch没有对应的quiet指令，回写时仍需等待响应，不设置sentonly
+        let Some(op) = req.map_op_noreply() else {
+            return;
+        };
         let cmd = req.operation();
         assert!(req.is_quiet(), "rqe:{:?}", req);
         req.reset_flag(op as u16, cmd);
@@ -55,24 +55,28 @@ pub(crate) const STAT_RESPONSE: [u8; 24] = [
 // https://github.com/memcached/memcached/wiki/BinaryProtocolRevamped#command-opcodes
 // MC包含Get, MGet, Gets, Store, Meta四类命令，索引分别是0-4
 // 0x48 是Gets请求
+// incr/decr、append/prepend、replace、touch、gat/gatk会变更数据或过期时间，均按Store处理：先写master，再回写其他layer
 pub(crate) const COMMAND_IDX: [u8; 128] = [
-    0, 3, 3, 3, 3, 3, 3, 4, 4, 1, 1, 4, 0, 1, 0, 0, 4, 3, 3, 3, 3, 3, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 3, 3, 3, 3, 3, 3, 4, 4, 1, 1, 4, 0, 1, 3, 3, 4, 3, 3, 3, 3, 3, 3, 0, 0, 3, 3, 0, 3, 3, 3, 0,
+    0, 0, 0, 3, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     0, 0, 0, 0, 0, 0, 0, 0, 2, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
 ];
 // OP_CODE对应的noreply code。
 // 注意：根据业务逻辑，add会转换成setq
 // cas 变更为setq
+// touch没有对应的quiet指令，映射为NO_QUIET(0xff)；gat => gatq，gatk => gatkq
 pub(crate) const NOREPLY_MAPPING: [u8; 128] = [
     0x09, 0x11, 0x11, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x09, 0x00, 0x00, 0x0d, 0x0d, 0x19, 0x1a,
-    0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f,
-    0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x2b, 0x2c, 0x2d, 0x2e, 0x2f,
+    0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0xff, 0x1e, 0x1e, 0x1f,
+    0x20, 0x21, 0x22, 0x24, 0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x2b, 0x2c, 0x2d, 0x2e, 0x2f,
     0x30, 0x32, 0x32, 0x34, 0x34, 0x36, 0x36, 0x38, 0x38, 0x3a, 0x3a, 0x3c, 0x3c, 0x3d, 0x3e, 0x3f,
     0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x49, 0x49, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     0, 0, 0, 0, 0, 0, 0, 0, 0,
 ];
+// 没有对应的quiet指令
+pub(crate) const NO_QUIET: u8 = 0xff;
 // 哪些请求是不需要转发的. 比如noop请求，version, status, quit等请求。这些请求可以直接计算出response。
 //pub(crate) const NO_FORWARD_OPS: [u8; 128] = [
 //    0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0,
@@ -82,10 +86,10 @@ pub(crate) const NOREPLY_MAPPING: [u8; 128] = [
 //];
 
 // 请求完毕后，不考虑layer及其他配置，如果cmd失败,是否继续retry:
-// (1) 0: not retry (对cas/casq/add/replace生效);  (2) 1: retry ; (3) 2: unknown，需要进一步check.
+// (1) 0: not retry (对cas/casq/add/replace/append/prepend/touch/gat生效);  (2) 1: retry ; (3) 2: unknown，需要进一步check.
 // TODO 本次修改影响：注意check set/cas、add/addq、setq/casq的影响
 const RETRY_TABLE: [u8; 128] = [
-    1, 2, 0, 0, 1, 1, 1, 1, 0, 1, 0, 1, 1, 1, 0, 0, 1, 2, 0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 1, 1, 1, 0,
+    1, 2, 0, 0, 1, 1, 1, 1, 0, 1, 0, 1, 1, 1, 0, 0, 1, 2, 0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0,
     1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1,
     0, 0, 1, 0, 0, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
@@ -114,6 +118,16 @@ pub enum Opcode {
     GETS = OP_GETS,
     GETSQ = OP_GETSQ,
     SETQ = OP_SETQ,
+    REPLACE = OP_REPLACE,
+    INCR = OP_INCR,
+    DECR = OP_DECR,
+    APPEND = OP_APPEND,
+    PREPEND = OP_PREPEND,
+    TOUCH = OP_TOUCH,
+    GAT = OP_GAT,
+    GATQ = OP_GATQ,
+    GATK = OP_GATK,
+    GATKQ = OP_GATKQ,
 }
 
 pub(crate) const REQUEST_MAGIC: u8 = 0x80;
@@ -134,6 +148,21 @@ pub const OP_ADD: u8 = 0x02;
 pub(crate) const OP_ADDQ: u8 = 0x12;
 pub const OP_GETK: u8 = 0x0c;
 pub(crate) const OP_SETQ: u8 = 0x11;
+pub const OP_REPLACE: u8 = 0x03;
+pub(crate) const OP_REPLACEQ: u8 = 0x13;
+pub const OP_INCR: u8 = 0x05;
+pub(crate) const OP_INCRQ: u8 = 0x15;
+pub const OP_DECR: u8 = 0x06;
+pub(crate) const OP_DECRQ: u8 = 0x16;
+pub const OP_APPEND: u8 = 0x0e;
+pub(crate) const OP_APPENDQ: u8 = 0x19;
+pub const OP_PREPEND: u8 = 0x0f;
+pub(crate) const OP_PREPENDQ: u8 = 0x1a;
+pub const OP_TOUCH: u8 = 0x1c;
+pub const OP_GAT: u8 = 0x1d;
+pub(crate) const OP_GATQ: u8 = 0x1e;
+pub const OP_GATK: u8 = 0x23;
+pub(crate) const OP_GATKQ: u8 = 0x24;
 // 这个专门为gets扩展
 pub const OP_GETS: u8 = 0x48;
 // 这个没有业务使用，先注销掉
@@ -142,6 +171,8 @@ pub const OP_GETSQ: u8 = 0x49;
 // 0x09: getq
 // 0x0d: getkq
 // 0x49: getsq
+// 0x1e: gatq
+// 0x24: gatkq
 //pub(crate) const QUITE_GET_TABLE: [u8; 128] = [
 //    0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
 //    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
@@ -151,16 +182,23 @@ pub const OP_GETSQ: u8 = 0x49;
 #[inline(always)]
 pub(crate) fn is_quiet_get(op_code: u8) -> bool {
     match op_code {
-        OP_GETQ | OP_GETKQ | OP_GETSQ => true,
+        OP_GETQ | OP_GETKQ | OP_GETSQ | OP_GATQ | OP_GATKQ => true,
         _ => false,
     }
     //QUITE_GET_TABLE[op_code as usize] == 1
 }
 
+// gat系列指令：取值的同时更新过期时间
+#[inline(always)]
+pub(crate) fn is_gat(op_code: u8) -> bool {
+    matches!(op_code, OP_GAT | OP_GATQ | OP_GATK | OP_GATKQ)
+}
+
 // 在请求时，部分场景下把op_code进行一次映射。
 // 1. quite get请求映射成 non-quite get请求。
 //      getq(0x09) => get(0x00); getkq(0x0d) => getk(0x0c); 以实现multiget的pipeline
 // 2. 把gets(0x48), getsq(0x49) => get(0x00)请求。 // 支持gets请求只发送给master
+// 3. gatq(0x1e) => gat(0x1d); gatkq(0x24) => gatk(0x23)
 //pub(crate) const OPS_MAPPING_TABLE: [u8; 128] = [
 //    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x00, 0x0a, 0x0b, 0x0c, 0x0c, 0x0e, 0x0f,
 //    0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f,
@@ -195,7 +233,8 @@ pub trait Binary {
     fn is_quiet(&self) -> bool;
     fn clear_cas(&mut self);
     fn map_op(&mut self) -> u8;
-    fn map_op_noreply(&mut self) -> u8;
+    // 映射为对应的quiet指令，返回原op_code。没有quiet指令时不变，返回None
+    fn map_op_noreply(&mut self) -> Option<u8>;
     fn restore_op(&mut self, op: u8);
     fn hash<H: sharding::hash::Hash>(&self, alg: &H) -> i64;
     fn check_request(&self) -> Result<()>;
@@ -261,6 +300,7 @@ impl Binary for RingSlice {
     ///   2 cas/casq只要不是Key Exists异常，则认为是成功，然后set其他layers；
     ///   3 add/addq只要不是Key Exists 则认为请求成功，然后set其他layers；
     ///   4 del/delq不管什么状态都认为成功，然后del其他layers；
+    ///   5 incr/decr、append/prepend、replace、touch、gat以响应状态为准，成功后再更新其他layers；
     /// 不管mesh认为是否成功，写指令的master响应都会原封不动的返回给client。
     #[inline(always)]
     fn status_ok(&self) -> bool {
@@ -331,12 +371,14 @@ impl Binary for RingSlice {
     #[inline(always)]
     fn is_quiet(&self) -> bool {
         let op = self.op();
-        NOREPLY_MAPPING[op as usize] == op
+        let quiet = NOREPLY_MAPPING[op as usize];
+        quiet != NO_QUIET && quiet == op
     }
     // 在请求时，部分场景下把op_code进行一次映射。
     // 1. quite get请求映射成 non-quite get请求。
     //      getq(0x09) => get(0x00); getkq(0x0d) => getk(0x0c); 以实现multiget的pipeline
     // 2. 把gets(0x48), getsq(0x49) => get(0x00)请求。 // 支持gets请求只发送给master
+    // 3. gatq(0x1e) => gat(0x1d); gatkq(0x24) => gatk(0x23)
     #[inline(always)]
     fn map_op(&mut self) -> u8 {
         let old = self.op();
@@ -344,6 +386,8 @@ impl Binary for RingSlice {
         let new = match old {
             OP_GETQ | OP_GETS => OP_GET,
             OP_GETKQ | OP_GETSQ => OP_GETK,
+            OP_GATQ => OP_GAT,
+            OP_GATKQ => OP_GATK,
             o => o,
         };
         if new != old {
@@ -352,10 +396,14 @@ impl Binary for RingSlice {
         old
     }
     #[inline(always)]
-    fn map_op_noreply(&mut self) -> u8 {
+    fn map_op_noreply(&mut self) -> Option<u8> {
         let op = self.op();
-        self.update(PacketPos::Opcode as usize, NOREPLY_MAPPING[op as usize]);
-        op
+        let quiet = NOREPLY_MAPPING[op as usize];
+        if quiet == NO_QUIET {
+            return None;
+        }
+        self.update(PacketPos::Opcode as usize, quiet);
+        Some(op)
     }
     #[inline(always)]
     fn restore_op(&mut self, op: u8) {
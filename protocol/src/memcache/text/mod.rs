@@ -0,0 +1,396 @@
+mod packet;
+
+use packet::*;
+
+use crate::{
+    Bit, Command, Commander, Error, Flag, HashedCommand, Metric, MetricItem, Operation,
+    Operation::*, Protocol, RequestProcessor, Result, Stream, Writer,
+};
+use ds::{ByteOrder, MemGuard, RingSlice};
+use sharding::hash::Hash;
+
+pub const OP_GET: u16 = 0;
+pub const OP_GETS: u16 = 1;
+pub const OP_SET: u16 = 2;
+pub const OP_ADD: u16 = 3;
+pub const OP_CAS: u16 = 4;
+pub const OP_DELETE: u16 = 5;
+pub const OP_INCR: u16 = 6;
+pub const OP_DECR: u16 = 7;
+pub const OP_TOUCH: u16 = 8;
+pub const OP_VERSION: u16 = 9;
+pub const OP_QUIT: u16 = 10;
+// 无法识别的指令，不转发，返回ERROR
+pub const OP_ERROR: u16 = 11;
+
+// 指令名、op_code、operation、最少的token数
+const COMMANDS: [(&[u8], u16, Operation, usize); 11] = [
+    (b"get", OP_GET, Get, 2),
+    (b"gets", OP_GETS, Gets, 2),
+    // <command name> <key> <flags> <exptime> <bytes> [noreply]\r\n<data>\r\n
+    (b"set", OP_SET, Store, 5),
+    (b"add", OP_ADD, Store, 5),
+    // cas <key> <flags> <exptime> <bytes> <cas unique> [noreply]\r\n<data>\r\n
+    (b"cas", OP_CAS, Store, 6),
+    // delete <key> [noreply]\r\n
+    (b"delete", OP_DELETE, Store, 2),
+    // incr/decr <key> <value> [noreply]\r\n
+    (b"incr", OP_INCR, Store, 3),
+    (b"decr", OP_DECR, Store, 3),
+    // touch <key> <exptime> [noreply]\r\n
+    (b"touch", OP_TOUCH, Store, 3),
+    (b"version", OP_VERSION, Meta, 1),
+    (b"quit", OP_QUIT, Meta, 1),
+];
+
+const END: u32 = u32::from_le_bytes(*b"END\r");
+const VALUE: u32 = u32::from_le_bytes(*b"VALU");
+const STORED: u32 = u32::from_le_bytes(*b"STOR");
+const NOT_: u32 = u32::from_le_bytes(*b"NOT_");
+const EXISTS: u32 = u32::from_le_bytes(*b"EXIS");
+const DELETED: u32 = u32::from_le_bytes(*b"DELE");
+const TOUCHED: u32 = u32::from_le_bytes(*b"TOUC");
+const VERSION: u32 = u32::from_le_bytes(*b"VERS");
+const ERROR: u32 = u32::from_le_bytes(*b"ERRO");
+const CLIENT_ERROR: u32 = u32::from_le_bytes(*b"CLIE");
+const SERVER_ERROR: u32 = u32::from_le_bytes(*b"SERV");
+
+// flag ext的第0位：是否是get拆分后的最后一个key，写响应时据此补上END
+const LAST_KEY_SHIFT: u8 = 0;
+// flag ext的第1位：key超长，不转发，直接返回CLIENT_ERROR
+const BAD_KEY_SHIFT: u8 = 1;
+// 与memcached一致，key最长250字节
+const MAX_KEY_LEN: usize = 250;
+
+#[derive(Clone, Default)]
+pub struct MemcacheText;
+
+impl Protocol for MemcacheText {
+    #[inline]
+    fn config(&self) -> crate::Config {
+        crate::Config {
+            retry_on_rsp_notok: true,
+            ..Default::default()
+        }
+    }
+    // 解析请求。与binary一致，把get k1 k2 ...拆分成n个单key的get请求。
+    #[inline]
+    fn parse_request<S: Stream, H: Hash, P: RequestProcessor>(
+        &self,
+        stream: &mut S,
+        alg: &H,
+        process: &mut P,
+    ) -> Result<()> {
+        let data = stream.slice();
+        log::debug!("+++ recv mc text:{:?}", data);
+
+        let mut oft = 0;
+        while let Some(lfcr) = data.find_lf_cr(oft) {
+            // 按完整的指令名匹配，无法识别的指令整行返回ERROR
+            let (start, end) = Split::new(data, oft, lfcr).next().unwrap_or((oft, oft));
+            let name = data.sub_slice(start, end - start);
+            let Some(&(_, op_code, op, min)) = COMMANDS.iter().find(|(n, ..)| name.equal(n)) else {
+                let line = stream.take(lfcr + 2 - oft);
+                oft = lfcr + 2;
+                let mut flag = Flag::from_op(OP_ERROR, Meta);
+                flag.set_noforward(true);
+                process.process(HashedCommand::new(line, 0, flag), true);
+                continue;
+            };
+
+            if op.is_retrival() {
+                // get/gets 可能携带多个key，逐个拆分
+                let keys = Split::new(data, oft, lfcr).skip(1);
+                let key_count = keys.clone().count();
+                if key_count == 0 {
+                    return Err(Error::ProtocolNotSupported);
+                }
+                let line = stream.take(lfcr + 2 - oft);
+                oft = lfcr + 2;
+                if keys.clone().any(|(start, end)| end - start > MAX_KEY_LEN) {
+                    // 任一key超长，整行返回CLIENT_ERROR
+                    process.process(Self::bad_key(line, op_code, op), true);
+                    continue;
+                }
+                if key_count == 1 {
+                    // 单key请求直接透传
+                    let (start, end) = keys.clone().next().expect("key");
+                    let hash = alg.hash(&data.sub_slice(start, end - start));
+                    let mut flag = Flag::from_op(op_code, op);
+                    flag.set(LAST_KEY_SHIFT);
+                    process.process(HashedCommand::new(line, hash, flag), true);
+                    continue;
+                }
+                // 多个key时，每个key重新构建为一个单key的请求，构建完毕前须持有原始请求
+                let sub_op = match op_code {
+                    OP_GET => MGet,
+                    _ => Gets,
+                };
+                for (i, (start, end)) in keys.enumerate() {
+                    let last = i + 1 == key_count;
+                    let key = data.sub_slice(start, end - start);
+                    let mut flag = Flag::from_op(op_code, sub_op);
+                    if last {
+                        flag.set(LAST_KEY_SHIFT);
+                    }
+                    let cmd = Self::build_get(op_code, &key);
+                    let hash = alg.hash(&key);
+                    process.process(HashedCommand::new(cmd, hash, flag), last);
+                }
+                drop(line);
+                continue;
+            }
+
+            let tokens = Tokens::parse(&data, oft, lfcr)?;
+            tokens.check(min)?;
+            let mut packet_end = lfcr + 2;
+            if op_code == OP_SET || op_code == OP_ADD || op_code == OP_CAS {
+                // 大value一次申请
+                let val_len = tokens.num(&data, 4)?;
+                let val_end = packet_end + val_len;
+                if data.len() < val_end + 2 {
+                    stream.reserve(val_end + 2 - data.len());
+                    return Ok(());
+                }
+                if !data.start_with(val_end, b"\r\n") {
+                    return Err(Error::ProtocolNotSupported);
+                }
+                packet_end = val_end + 2;
+            }
+            if op != Meta && tokens.slice(&data, 1).len() > MAX_KEY_LEN {
+                let cmd = stream.take(packet_end - oft);
+                oft = packet_end;
+                process.process(Self::bad_key(cmd, op_code, op), true);
+                continue;
+            }
+
+            let mut flag = Flag::from_op(op_code, op);
+            flag.set_noforward(op == Meta);
+            // noreply的请求，发送完毕即结束
+            flag.set_sentonly(tokens.noreply(&data, min));
+            let hash = match op {
+                Meta => 0,
+                _ => alg.hash(&tokens.slice(&data, 1)),
+            };
+            let cmd = stream.take(packet_end - oft);
+            oft = packet_end;
+            process.process(HashedCommand::new(cmd, hash, flag), true);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn parse_response<S: Stream>(&self, stream: &mut S) -> Result<Option<Command>> {
+        let data = stream.slice();
+        log::debug!("+++ mc text will parse rsp:{:?}", data);
+        let Some(lfcr) = data.find_lf_cr(0) else {
+            return Ok(None);
+        };
+        // incr/decr的响应是数字
+        if data[0].is_ascii_digit() {
+            return Ok(Some(Command::from_ok(stream.take(lfcr + 2))));
+        }
+        // 除数字外，最短响应是END\r\n
+        if lfcr < 3 {
+            return Err(Error::UnexpectedData);
+        }
+        let mut packet_end = lfcr + 2;
+        let ok = match data.u32_le(0) {
+            // get miss，需要继续访问其他layer
+            END => false,
+            VALUE => {
+                // VALUE <key> <flags> <bytes> [<cas unique>]\r\n<data>\r\nEND\r\n
+                let tokens = Tokens::parse(&data, 0, lfcr).map_err(|_| Error::UnexpectedData)?;
+                tokens.check(4).map_err(|_| Error::UnexpectedData)?;
+                let val_len = tokens.num(&data, 3).map_err(|_| Error::UnexpectedData)?;
+                let val_end = packet_end + val_len;
+                if data.len() < val_end + 2 + END_LEN {
+                    stream.reserve(val_end + 2 + END_LEN - data.len());
+                    return Ok(None);
+                }
+                if !data.start_with(val_end, b"\r\nEND\r\n") {
+                    return Err(Error::UnexpectedData);
+                }
+                packet_end = val_end + 2 + END_LEN;
+                true
+            }
+            // 与binary保持一致：delete/incr/touch的not found认为成功，不再重试；
+            // add的not stored、cas的exists，说明key已存在，认为失败
+            NOT_ => data.start_with(0, b"NOT_FOUND"),
+            STORED | DELETED | TOUCHED | VERSION => true,
+            EXISTS => false,
+            ERROR | CLIENT_ERROR | SERVER_ERROR => {
+                log::warn!("+++ err mc text rsp: {:?}", data);
+                false
+            }
+            _ => {
+                log::warn!("+++ unknown mc text rsp: {:?}", data);
+                return Err(Error::UnexpectedData);
+            }
+        };
+        Ok(Some(Command::from(ok, stream.take(packet_end))))
+    }
+
+    #[inline]
+    fn write_response<C, W, M, I>(
+        &self,
+        ctx: &mut C,
+        response: Option<&mut Command>,
+        w: &mut W,
+    ) -> Result<()>
+    where
+        W: Writer,
+        C: Commander<M, I>,
+        M: Metric<I>,
+        I: MetricItem,
+    {
+        let request = ctx.request();
+        // noreply 直接返回
+        if request.sentonly() {
+            assert!(response.is_none(), "req:{:?}", request);
+            return Ok(());
+        }
+        if request.flag().get(BAD_KEY_SHIFT) {
+            return w.write(b"CLIENT_ERROR bad command line format\r\n");
+        }
+        let op = request.operation();
+        if op.is_query() {
+            ctx.metric()
+                .cache(response.as_ref().map(|r| r.ok()).unwrap_or_default());
+        }
+        let request = ctx.request();
+
+        if op.is_retrival() {
+            // 每个key只回写VALUE部分，在最后一个key之后补上END
+            if let Some(rsp) = response.filter(|r| r.ok()) {
+                w.write_ringslice(&rsp.sub_slice(0, rsp.len() - END_LEN), 0)?;
+            }
+            if request.flag().get(LAST_KEY_SHIFT) {
+                w.write(b"END\r\n")?;
+            }
+            return Ok(());
+        }
+
+        if let Some(rsp) = response {
+            log::debug!("+++ will write mc text rsp:{:?}", rsp.data());
+            return w.write_slice(rsp, 0);
+        }
+
+        match request.op_code() {
+            OP_VERSION => w.write(b"VERSION 0.0.1\r\n"),
+            OP_QUIT => Err(Error::Quit),
+            OP_ERROR => w.write(b"ERROR\r\n"),
+            // 后端没有响应，不能返回NOT_STORED/NOT_FOUND，否则client会误以为是业务结果
+            OP_SET | OP_ADD | OP_CAS | OP_DELETE | OP_INCR | OP_DECR | OP_TOUCH => {
+                w.write(b"SERVER_ERROR no response\r\n")
+            }
+            op_code => Err(Error::OpCodeNotSupported(op_code)),
+        }
+    }
+
+    // 如果是读请求，则通过response构建一个set noreply请求。
+    // 如果是写请求，则把原请求转换成noreply请求，cas/add均转换为set。
+    #[inline]
+    fn build_writeback_request<C, M, I>(
+        &self,
+        ctx: &mut C,
+        response: &Command,
+        exp_sec: u32,
+    ) -> Option<HashedCommand>
+    where
+        C: Commander<M, I>,
+        M: Metric<I>,
+        I: MetricItem,
+    {
+        let req = ctx.request();
+        let (op_code, cmd) = if req.operation().is_retrival() {
+            (OP_SET, self.build_write_back_get(response, exp_sec)?)
+        } else {
+            self.build_write_back_store(req)?
+        };
+        let mut flag = Flag::from_op(op_code, Store);
+        flag.set_sentonly(true);
+        Some(HashedCommand::new(cmd, req.hash(), flag))
+    }
+
+    #[inline]
+    fn can_retry_on_rsp_notok(&self, req: &HashedCommand) -> bool {
+        // 与binary一致，add/cas失败后不可以retry
+        !matches!(req.op_code(), OP_ADD | OP_CAS)
+    }
+
+    // 与binary一致，get miss等不需要统计error
+    #[inline]
+    fn metric_err(&self, _req_op: Operation) -> bool {
+        false
+    }
+}
+
+impl MemcacheText {
+    // key超长的请求不转发，写响应时返回CLIENT_ERROR
+    #[inline]
+    fn bad_key(cmd: MemGuard, op_code: u16, op: Operation) -> HashedCommand {
+        let mut flag = Flag::from_op(op_code, op);
+        flag.set(BAD_KEY_SHIFT);
+        flag.set_noforward(true);
+        HashedCommand::new(cmd, 0, flag)
+    }
+    #[inline]
+    fn build_get(op_code: u16, key: &RingSlice) -> MemGuard {
+        let name: &[u8] = match op_code {
+            OP_GET => b"get ",
+            _ => b"gets ",
+        };
+        let mut cmd = Vec::with_capacity(name.len() + key.len() + 2);
+        cmd.extend_from_slice(name);
+        key.copy_to_vec(&mut cmd);
+        cmd.extend_from_slice(b"\r\n");
+        MemGuard::from_vec(cmd)
+    }
+    // VALUE <key> <flags> <bytes> [<cas unique>]\r\n<data>\r\nEND\r\n
+    // => set <key> <flags> <exptime> <bytes> noreply\r\n<data>\r\n
+    #[inline]
+    fn build_write_back_get(&self, resp: &Command, exp_sec: u32) -> Option<MemGuard> {
+        assert!(resp.ok(), "resp: {:?}", resp.data());
+        let data: RingSlice = ***resp;
+        let lfcr = data.find_lf_cr(0)?;
+        let tokens = Tokens::parse(&data, 0, lfcr).ok()?;
+        let mut cmd = Vec::with_capacity(data.len() + 16);
+        cmd.extend_from_slice(b"set");
+        tokens.append(&data, 1, &mut cmd);
+        tokens.append(&data, 2, &mut cmd);
+        cmd.push(b' ');
+        use ds::NumStr;
+        (exp_sec as usize).with_str(|s| cmd.extend_from_slice(s));
+        tokens.append(&data, 3, &mut cmd);
+        cmd.extend_from_slice(b" noreply");
+        // 从VALUE行的\r\n开始，到数据之后的\r\n为止
+        data.copy_to_vec_r(&mut cmd, lfcr..data.len() - END_LEN);
+        Some(MemGuard::from_vec(cmd))
+    }
+    #[inline]
+    fn build_write_back_store(&self, req: &HashedCommand) -> Option<(u16, MemGuard)> {
+        let data: RingSlice = ***req;
+        let lfcr = data.find_lf_cr(0)?;
+        let tokens = Tokens::parse(&data, 0, lfcr).ok()?;
+        let mut cmd = Vec::with_capacity(data.len() + 16);
+        let op_code = match req.op_code() {
+            OP_SET | OP_ADD | OP_CAS => {
+                // 去掉cas unique，统一转换为set
+                cmd.extend_from_slice(b"set");
+                for i in 1..5 {
+                    tokens.append(&data, i, &mut cmd);
+                }
+                cmd.extend_from_slice(b" noreply");
+                data.copy_to_vec_r(&mut cmd, lfcr..data.len());
+                OP_SET
+            }
+            op_code => {
+                data.copy_to_vec_r(&mut cmd, 0..lfcr);
+                cmd.extend_from_slice(b" noreply\r\n");
+                op_code
+            }
+        };
+        Some((op_code, MemGuard::from_vec(cmd)))
+    }
+}
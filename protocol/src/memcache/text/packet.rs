@@ -0,0 +1,104 @@
+// mc 文本协议的行解析，所有文本指令/响应的token切分均放在这里
+use ds::RingSlice;
+
+use crate::{Error, Result};
+
+// 单行最多支持的token数量：cas <key> <flags> <exptime> <bytes> <cas unique> [noreply]
+pub(super) const MAX_TOKENS: usize = 8;
+pub(super) const NOREPLY: &[u8] = b"noreply";
+// 响应结尾的 END\r\n
+pub(super) const END_LEN: usize = 5;
+
+// 按空格切分[oft, end)之间的token，返回每个token的[start, end)
+#[derive(Clone)]
+pub(super) struct Split {
+    data: RingSlice,
+    oft: usize,
+    end: usize,
+}
+
+impl Split {
+    #[inline]
+    pub(super) fn new(data: RingSlice, oft: usize, end: usize) -> Self {
+        debug_assert!(oft <= end && end <= data.len());
+        Self { data, oft, end }
+    }
+}
+
+impl Iterator for Split {
+    type Item = (usize, usize);
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.oft < self.end && self.data[self.oft] == b' ' {
+            self.oft += 1;
+        }
+        if self.oft >= self.end {
+            return None;
+        }
+        let start = self.oft;
+        while self.oft < self.end && self.data[self.oft] != b' ' {
+            self.oft += 1;
+        }
+        Some((start, self.oft))
+    }
+}
+
+// 一行文本内所有token的位置，第0个是指令名
+pub(super) struct Tokens {
+    pos: [(usize, usize); MAX_TOKENS],
+    len: usize,
+}
+
+impl Tokens {
+    #[inline]
+    pub(super) fn parse(data: &RingSlice, oft: usize, end: usize) -> Result<Self> {
+        let mut tokens = Self {
+            pos: [(0, 0); MAX_TOKENS],
+            len: 0,
+        };
+        for p in Split::new(*data, oft, end) {
+            if tokens.len >= MAX_TOKENS {
+                return Err(Error::ProtocolNotSupported);
+            }
+            tokens.pos[tokens.len] = p;
+            tokens.len += 1;
+        }
+        Ok(tokens)
+    }
+    #[inline]
+    pub(super) fn get(&self, idx: usize) -> (usize, usize) {
+        debug_assert!(idx < self.len);
+        self.pos[idx]
+    }
+    // 检查token数量至少为min，不足则为非法请求
+    #[inline]
+    pub(super) fn check(&self, min: usize) -> Result<()> {
+        match self.len >= min {
+            true => Ok(()),
+            false => Err(Error::ProtocolNotSupported),
+        }
+    }
+    #[inline]
+    pub(super) fn num(&self, data: &RingSlice, idx: usize) -> Result<usize> {
+        let (start, end) = self.get(idx);
+        data.try_str_num(start..end)
+            .ok_or(Error::ProtocolNotSupported)
+    }
+    #[inline]
+    pub(super) fn slice(&self, data: &RingSlice, idx: usize) -> RingSlice {
+        let (start, end) = self.get(idx);
+        data.sub_slice(start, end - start)
+    }
+    // 必选参数之后的最后一个token为noreply
+    #[inline]
+    pub(super) fn noreply(&self, data: &RingSlice, min: usize) -> bool {
+        self.len > min && self.slice(data, self.len - 1).equal(NOREPLY)
+    }
+    // 把第idx个token追加到v中，并在前面补一个空格
+    #[inline]
+    pub(super) fn append(&self, data: &RingSlice, idx: usize, v: &mut Vec<u8>) {
+        let (start, end) = self.get(idx);
+        v.push(b' ');
+        data.copy_to_vec_r(v, start..end);
+    }
+}
@@ -82,3 +82,45 @@ impl Hash for Operation {
         (*self as u8).hash(state)
     }
 }
+
+// client认证通过后允许执行的指令类型：读、写、meta，Other类指令按写处理
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Acl(u8);
+impl Acl {
+    pub const READ: Acl = Acl(1);
+    pub const WRITE: Acl = Acl(1 << 1);
+    pub const META: Acl = Acl(1 << 2);
+    pub const ALL: Acl = Acl(Self::READ.0 | Self::WRITE.0 | Self::META.0);
+
+    #[inline]
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits & Self::ALL.0)
+    }
+    #[inline]
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+    // 配置中的名字：read、write、meta
+    #[inline]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "read" => Some(Self::READ),
+            "write" => Some(Self::WRITE),
+            "meta" => Some(Self::META),
+            _ => None,
+        }
+    }
+    #[inline]
+    pub fn union(&self, other: Acl) -> Self {
+        Self(self.0 | other.0)
+    }
+    #[inline]
+    pub fn allow(&self, op: Operation) -> bool {
+        let need = match op {
+            Get | MGet | Gets => Self::READ,
+            Meta => Self::META,
+            Store | Other => Self::WRITE,
+        };
+        self.0 & need.0 != 0
+    }
+}
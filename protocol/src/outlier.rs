@@ -0,0 +1,327 @@
+// 副本的异常检测：
+// 1. 按滑动窗口（10个1秒的桶）统计每个副本的请求数、失败数以及成功请求的耗时；
+// 2. 窗口内请求数足够时，失败率超过50%，或者平均耗时超过同组其他副本的3倍，则摘除该副本；
+// 3. 摘除时间从10秒开始，按连续摘除的次数指数增长，最长5分钟；
+// 4. 摘除到期后进入半开状态，只放行一个探测请求：成功则恢复，失败则继续摘除；
+// 5. 同一组副本中同时被摘除的数量不超过一半，避免流量集中到少数副本上；
+// 6. 副本列表变化时，按地址继承原有副本的摘除状态；被丢弃的副本重置摘除的指标。
+// 统计在请求完成时由回调更新，endpoint在选择副本时通过available跳过被摘除的副本。
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering::*};
+use std::sync::{Arc, OnceLock};
+
+use ds::time::Instant;
+
+const BUCKETS: u64 = 10;
+// 窗口内请求数少于该值时，不进行判断
+const MIN_REQUESTS: u32 = 20;
+const MAX_ERR_PERCENT: u32 = 50;
+// 平均耗时超过同组其他副本的倍数，且超过MIN_SLOW_US时，认为是慢副本
+const SLOW_FACTOR: u64 = 3;
+const MIN_SLOW_US: u64 = 10_000;
+const BASE_EJECT_MS: u64 = 10_000;
+const MAX_EJECT_MS: u64 = 300_000;
+// 探测请求超过该时间没有结果，允许再次探测
+const PROBE_TIMEOUT_MS: u64 = 5_000;
+const MAX_EJECT_PERCENT: usize = 50;
+
+// 进程启动后的毫秒数，从1开始，0表示未设置
+fn now_ms() -> u64 {
+    static START: OnceLock<std::time::Instant> = OnceLock::new();
+    START
+        .get_or_init(std::time::Instant::now)
+        .elapsed()
+        .as_millis() as u64
+        + 1
+}
+
+#[derive(Default)]
+struct Bucket {
+    sec: AtomicU64,
+    total: AtomicU32,
+    errs: AtomicU32,
+    // 成功请求的累计耗时
+    us: AtomicU64,
+}
+
+#[derive(Default)]
+struct Window {
+    buckets: [Bucket; BUCKETS as usize],
+}
+
+impl Window {
+    // 返回是否切换到了新的桶。并发切换时的少量误差可以忽略
+    #[inline]
+    fn record(&self, sec: u64, ok: bool, us: u64) -> bool {
+        let b = &self.buckets[(sec % BUCKETS) as usize];
+        let old = b.sec.load(Acquire);
+        let rotated = old != sec && b.sec.compare_exchange(old, sec, AcqRel, Relaxed).is_ok();
+        if rotated {
+            b.total.store(0, Relaxed);
+            b.errs.store(0, Relaxed);
+            b.us.store(0, Relaxed);
+        }
+        b.total.fetch_add(1, Relaxed);
+        if ok {
+            b.us.fetch_add(us, Relaxed);
+        } else {
+            b.errs.fetch_add(1, Relaxed);
+        }
+        rotated
+    }
+    // 窗口内的请求数、失败数、成功请求的累计耗时
+    fn sum(&self, sec: u64) -> (u32, u32, u64) {
+        self.buckets
+            .iter()
+            .filter(|b| sec - b.sec.load(Acquire).min(sec) < BUCKETS)
+            .fold((0, 0, 0), |(total, errs, us), b| {
+                (
+                    total + b.total.load(Relaxed),
+                    errs + b.errs.load(Relaxed),
+                    us + b.us.load(Relaxed),
+                )
+            })
+    }
+    fn clear(&self) {
+        for b in &self.buckets {
+            b.total.store(0, Relaxed);
+            b.errs.store(0, Relaxed);
+            b.us.store(0, Relaxed);
+        }
+    }
+}
+
+// 一组副本共享，限制同时被摘除的数量，同时统计整组的耗时
+#[derive(Default)]
+struct Group {
+    len: usize,
+    ejected: AtomicUsize,
+    window: Window,
+}
+
+struct Replica {
+    group: Arc<Group>,
+    addr: String,
+    window: Window,
+    // 摘除的截止时间，0表示未摘除
+    ejected_until: AtomicU64,
+    // 连续摘除的次数，决定下一次的摘除时间
+    ejections: AtomicU32,
+    // 半开状态下，探测请求的发出时间，0表示未探测
+    probe_at: AtomicU64,
+    restored_at: AtomicU64,
+    // 摘除状态已由新的副本继承，drop时不重置指标
+    moved: AtomicBool,
+}
+
+impl Drop for Replica {
+    fn drop(&mut self) {
+        if !*self.moved.get_mut() && *self.ejected_until.get_mut() != 0 {
+            metric(&self.addr, false);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Outlier {
+    inner: Arc<Replica>,
+}
+
+impl Outlier {
+    // 为一组副本创建统计，顺序与addrs一致
+    pub fn group<'a>(addrs: impl ExactSizeIterator<Item = &'a str>) -> Vec<Self> {
+        Self::regroup(&[], addrs)
+    }
+    // 副本列表变化时重新分组，地址相同的副本继承prev中的摘除状态及连续摘除的次数
+    pub fn regroup<'a>(prev: &[Self], addrs: impl ExactSizeIterator<Item = &'a str>) -> Vec<Self> {
+        let group = Arc::new(Group {
+            len: addrs.len(),
+            ..Default::default()
+        });
+        addrs
+            .map(|addr| {
+                let r = Replica {
+                    group: group.clone(),
+                    addr: addr.to_string(),
+                    window: Default::default(),
+                    ejected_until: 0.into(),
+                    ejections: 0.into(),
+                    probe_at: 0.into(),
+                    restored_at: 0.into(),
+                    moved: false.into(),
+                };
+                if let Some(old) = prev.iter().map(|o| &*o.inner).find(|o| o.addr == addr) {
+                    r.ejections.store(old.ejections.load(Relaxed), Relaxed);
+                    r.restored_at.store(old.restored_at.load(Acquire), Relaxed);
+                    let until = old.ejected_until.load(Acquire);
+                    if until != 0 && !old.moved.swap(true, AcqRel) {
+                        r.ejected_until.store(until, Relaxed);
+                        r.probe_at.store(old.probe_at.load(Acquire), Relaxed);
+                        group.ejected.fetch_add(1, Relaxed);
+                    }
+                }
+                Self { inner: Arc::new(r) }
+            })
+            .collect()
+    }
+    #[inline]
+    pub fn ejected(&self) -> bool {
+        self.inner.ejected_until.load(Acquire) != 0
+    }
+    // 剩余的摘除时间，摘除到期（等待探测）或者未摘除时为0
+    pub fn ejected_ms(&self) -> u64 {
+        self.inner
+            .ejected_until
+            .load(Acquire)
+            .saturating_sub(now_ms())
+    }
+    // 摘除立即到期，用于测试探测及恢复
+    pub fn expire_onlyfor_test(&self) {
+        let r = &*self.inner;
+        let until = r.ejected_until.load(Acquire);
+        if until != 0 {
+            let _ = r
+                .ejected_until
+                .compare_exchange(until, now_ms(), AcqRel, Relaxed);
+        }
+    }
+    // 未被摘除；或者摘除已到期，并且获取到了探测的机会
+    #[inline]
+    pub fn available(&self) -> bool {
+        let until = self.inner.ejected_until.load(Acquire);
+        if until == 0 {
+            return true;
+        }
+        let now = now_ms();
+        if now < until {
+            return false;
+        }
+        let probe_at = self.inner.probe_at.load(Acquire);
+        if probe_at != 0 && now - probe_at < PROBE_TIMEOUT_MS {
+            return false;
+        }
+        self.inner
+            .probe_at
+            .compare_exchange(probe_at, now, AcqRel, Relaxed)
+            .is_ok()
+    }
+    // 请求完成：ok为false表示请求失败（超时、连接异常等），start为发送到该副本的时间
+    #[inline]
+    pub fn record(&self, start: Instant, ok: bool) {
+        let r = &*self.inner;
+        let now = now_ms();
+        let sec = now / 1000;
+        let us = start.elapsed().as_micros() as u64;
+        r.group.window.record(sec, ok, us);
+        let rotated = r.window.record(sec, ok, us);
+
+        let until = r.ejected_until.load(Acquire);
+        if until != 0 {
+            // 摘除期间完成的请求是摘除前发出的，只有探测请求的结果会改变状态。
+            // 探测请求在获取到探测机会之后发出，之前发出的请求以及超时后被替代的探测请求都忽略
+            let probe_at = r.probe_at.load(Acquire);
+            let sent = now.saturating_sub(us / 1000);
+            if now >= until && probe_at != 0 && sent + 1 >= probe_at {
+                if ok {
+                    self.restore(now);
+                } else {
+                    self.eject(now, true);
+                }
+            }
+            return;
+        }
+        if rotated || !ok {
+            self.check(sec, now);
+        }
+    }
+    fn check(&self, sec: u64, now: u64) {
+        let r = &*self.inner;
+        let (total, errs, us) = r.window.sum(sec);
+        if total < MIN_REQUESTS {
+            return;
+        }
+        if errs * 100 >= total * MAX_ERR_PERCENT {
+            log::warn!("{} outlier: {}/{} failed", r.addr, errs, total);
+            self.eject(now, false);
+            return;
+        }
+        // 与同组其他副本的平均耗时比较
+        let (g_total, g_errs, g_us) = r.group.window.sum(sec);
+        let oks = total.saturating_sub(errs) as u64;
+        let others = (g_total.saturating_sub(g_errs) as u64).saturating_sub(oks);
+        if oks == 0 || others < MIN_REQUESTS as u64 {
+            return;
+        }
+        let avg = us / oks;
+        let others_avg = g_us.saturating_sub(us) / others;
+        if avg > MIN_SLOW_US && avg > others_avg * SLOW_FACTOR {
+            log::warn!("{} outlier: avg {}us, others {}us", r.addr, avg, others_avg);
+            self.eject(now, false);
+        }
+    }
+    // again: 探测失败，继续摘除
+    fn eject(&self, now: u64, again: bool) {
+        let r = &*self.inner;
+        // 恢复后稳定运行超过最长摘除时间，重新开始计算摘除时间
+        if !again && now - r.restored_at.load(Acquire).min(now) > MAX_EJECT_MS {
+            r.ejections.store(0, Relaxed);
+        }
+        let n = r.ejections.load(Relaxed).min(16);
+        let period = (BASE_EJECT_MS << n).min(MAX_EJECT_MS);
+        r.probe_at.store(0, Release);
+        if again {
+            r.ejected_until.store(now + period, Release);
+        } else {
+            // 并发检查时只摘除一次
+            if r.ejected_until
+                .compare_exchange(0, now + period, AcqRel, Relaxed)
+                .is_err()
+            {
+                return;
+            }
+            let max = r.group.len * MAX_EJECT_PERCENT / 100;
+            if r.group.ejected.fetch_add(1, AcqRel) >= max {
+                r.group.ejected.fetch_sub(1, AcqRel);
+                r.ejected_until.store(0, Release);
+                return;
+            }
+            metric(&r.addr, true);
+        }
+        r.ejections.fetch_add(1, Relaxed);
+        log::info!("{} ejected {}ms, ejections:{}", r.addr, period, n + 1);
+    }
+    fn restore(&self, now: u64) {
+        let r = &*self.inner;
+        if r.ejected_until.swap(0, AcqRel) == 0 {
+            return;
+        }
+        r.group.ejected.fetch_sub(1, AcqRel);
+        r.probe_at.store(0, Release);
+        r.restored_at.store(now, Release);
+        // 摘除前的统计不再参与判断
+        r.window.clear();
+        metric(&r.addr, false);
+        log::info!("{} restored", r.addr);
+    }
+}
+
+// 摘除期间上报base/outlier/{addr}/ejected，同时统计摘除次数
+fn metric(addr: &str, ejected: bool) {
+    let mut path = metrics::Path::base();
+    path.push("outlier");
+    path.push(addr);
+    let mut status = path.status("ejected");
+    if ejected {
+        status += metrics::Status::NOTIFY;
+        let mut ejections = path.qps("ejection");
+        ejections += 1;
+    } else {
+        status += metrics::Status::OK;
+    }
+}
+
+impl std::fmt::Debug for Outlier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let r = &*self.inner;
+        write!(f, "{} ejected:{}", r.addr, self.ejected())
+    }
+}
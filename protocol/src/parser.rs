@@ -3,18 +3,19 @@ use enum_dispatch::enum_dispatch;
 use sharding::hash::Hash;
 
 use crate::kv::Kv;
-use crate::memcache::MemcacheBinary;
+use crate::memcache::{MemcacheBinary, MemcacheText};
 use crate::metrics::HostMetric;
 use crate::msgque::MsgQue;
 use crate::redis::Redis;
 use crate::uuid::Uuid;
 use crate::vector::Vector;
-use crate::{Error, Flag, OpCode, Operation, Result, Stream, Writer};
+use crate::{Acl, Error, Flag, OpCode, Operation, Result, Stream, Writer};
 
 #[derive(Clone)]
 #[enum_dispatch(Proto)]
 pub enum Parser {
     McBin(MemcacheBinary),
+    McText(MemcacheText),
     Redis(Redis),
     MsgQue(MsgQue),
     // TODO 暂时保留，待client修改上线完毕后，清理
@@ -27,6 +28,7 @@ impl Parser {
     pub fn try_from(name: &str) -> Result<Self> {
         match name {
             "mc" => Ok(Self::McBin(Default::default())),
+            "mctext" => Ok(Self::McText(Default::default())),
             "redis" | "phantom" => Ok(Self::Redis(Default::default())),
             "msgque" => Ok(Self::MsgQue(Default::default())),
             "kv" => Ok(Self::Kv(Default::default())),
@@ -56,6 +58,12 @@ pub struct ResOption {
     // pub method: AuthMethod,
     pub token: String,
     pub username: String,
+    // 与后端协商RESP3，目前只有redis支持
+    pub resp3: bool,
+    // 后端连接的TLS配置，为None时使用明文连接
+    pub tls: Option<net::tls::TlsOption>,
+    // 每个后端建立的连接数，0与1相同
+    pub conns: usize,
 }
 
 #[derive(Default, Clone)]
@@ -63,6 +71,35 @@ pub struct Config {
     pub need_auth: bool,
     pub pipeline: bool,
     pub retry_on_rsp_notok: bool,
+    // 在协议握手中协商TLS（mysql的SSLRequest），而不是建连后直接进行TLS握手
+    pub tls_in_handshake: bool,
+}
+
+// 事务相关的指令类型，目前只有redis支持事务
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxCmd {
+    Watch,
+    Unwatch,
+    Multi,
+    Exec,
+    Discard,
+    // 普通指令，在multi之后需要排队
+    Other,
+    // 不带key的指令，如ping，事务外本地响应，multi之后排队但不决定事务所在分片
+    Keyless,
+    // 不能在事务中执行的指令，如拆分成多个子请求的multi-key指令
+    Unsupported,
+}
+
+// 订阅相关的指令类型，目前只有redis支持订阅
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubCmd {
+    Subscribe,
+    PSubscribe,
+    Unsubscribe,
+    PUnsubscribe,
+    // 其他指令，订阅模式下不能执行
+    Other,
 }
 
 pub enum HandShake {
@@ -120,6 +157,54 @@ pub trait Proto: Unpin + Clone + Send + Sync + 'static {
     fn config(&self) -> Config {
         Config::default()
     }
+    // 有响应且响应不ok时，当前请求在协议层面是否允许重试，如cas、add失败后不可重试
+    #[inline]
+    fn can_retry_on_rsp_notok(&self, _req: &HashedCommand) -> bool {
+        true
+    }
+    // 拆分到多个分片的请求，是否需要在所有子请求完成后合并成一个响应，如redis的keys、dbsize
+    #[inline]
+    fn need_merge(&self, _req: &HashedCommand) -> bool {
+        false
+    }
+    // 部分子请求完成时，是否已经可以确定合并的结果，不再等待其他子请求。req是第一个子请求，
+    // responses为已完成的子请求的响应，如redis集合运算的成员数量已超过上限
+    #[inline]
+    fn merge_early(&self, _req: &HashedCommand, _responses: &[&Command]) -> bool {
+        false
+    }
+    // 合并所有子请求的响应并写入client。ctx是第一个子请求，responses与子请求一一对应，
+    // 提前合并时未完成的子请求响应为None
+    #[allow(unused_variables)]
+    fn merge_response<C, W, M, I>(
+        &self,
+        ctx: &mut C,
+        responses: &mut [Option<Command>],
+        w: &mut W,
+    ) -> Result<()>
+    where
+        W: Writer,
+        C: Commander<M, I>,
+        M: Metric<I>,
+        I: MetricItem,
+    {
+        Err(Error::ProtocolNotSupported)
+    }
+    // 请求在订阅模式中的类型
+    #[inline]
+    fn sub_cmd(&self, _req: &HashedCommand) -> SubCmd {
+        SubCmd::Other
+    }
+    // 请求是否会让client连接进入订阅模式，如redis的subscribe、psubscribe
+    #[inline]
+    fn subscribe(&self, req: &HashedCommand) -> bool {
+        matches!(self.sub_cmd(req), SubCmd::Subscribe | SubCmd::PSubscribe)
+    }
+    // 请求在事务中的类型
+    #[inline]
+    fn tx_cmd(&self, _req: &HashedCommand) -> TxCmd {
+        TxCmd::Other
+    }
     // 统计每个mesh实例在后端的请求统计，这些统计是按cmd类型维度的，目前只有mq需要
     fn on_sent(&self, _req_op: Operation, _metrics: &mut HostMetric) {}
 
@@ -141,6 +226,30 @@ pub trait RequestProcessor {
     // 2. 请求被拆分成了多个子请求；
     // 3. 当前子请求为最后一个；
     fn process(&mut self, req: HashedCommand, last: bool);
+    // 后端的分片数量，用于把scan、keys、dbsize等请求拆分到所有分片
+    fn shards(&self) -> usize {
+        1
+    }
+    // 跨分片的集合运算（如redis的sinter）在mesh内合并时，允许的最大成员数量，0表示不支持
+    fn max_set_members(&self) -> usize {
+        0
+    }
+    // client是否需要先认证，才能执行其他指令
+    fn require_auth(&self) -> bool {
+        false
+    }
+    // 后端是否支持RESP3，不支持时client不能通过hello切换到RESP3
+    fn resp3(&self) -> bool {
+        false
+    }
+    // 认证client，返回用户允许执行的指令类型，认证失败返回None
+    fn auth(&self, _user: &[u8], _pass: &[u8]) -> Option<Acl> {
+        Some(Acl::ALL)
+    }
+    // key的路由信息（hash、分片、后端地址等），用于keyroute指令，不支持时返回None
+    fn route(&self, _key: &[u8]) -> Option<String> {
+        None
+    }
 }
 
 pub struct Command {
@@ -252,6 +361,20 @@ impl HashedCommand {
             panic!("origin is null, req:{:?}", self.cmd.data())
         }
     }
+    // 复制一份请求，用于对冲等需要同时发送到多个后端的场景
+    pub fn copy(&self) -> Self {
+        let copy = |m: &MemGuard| {
+            let mut data = Vec::with_capacity(m.len());
+            m.copy_to_vec(&mut data);
+            MemGuard::from_vec(data)
+        };
+        Self {
+            hash: self.hash,
+            flag: self.flag.clone(),
+            cmd: copy(&self.cmd),
+            origin_cmd: self.origin_cmd.as_ref().map(copy),
+        }
+    }
     #[inline]
     pub fn reshape(&mut self, mut dest_cmd: MemGuard) {
         assert!(
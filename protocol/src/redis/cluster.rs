@@ -0,0 +1,145 @@
+// redis cluster 相关的协议处理：
+//   1. 通过 CLUSTER SLOTS 获取slot与节点的对应关系；
+//   2. slot迁移完成后，节点返回 -MOVED slot ip:port，需要到新节点重试，并刷新slot；
+//   3. slot迁移过程中，节点返回 -ASK slot ip:port，需要在新节点上先发送 ASKING 再重试，不刷新slot。
+use crate::{Error, Result};
+use ds::RingSlice;
+
+pub const CLUSTER_SLOTS: &[u8] = b"*2\r\n$7\r\nCLUSTER\r\n$5\r\nSLOTS\r\n";
+// 按ASK重定向时，附加在请求之前，会额外产生一个+OK的响应
+pub const ASKING: &[u8] = b"*1\r\n$6\r\nASKING\r\n";
+
+const MOVED: &[u8] = b"-MOVED ";
+const ASK: &[u8] = b"-ASK ";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub ask: bool,
+    pub slot: usize,
+    pub addr: String,
+}
+
+impl Redirect {
+    // 格式：-MOVED 3999 127.0.0.1:6381\r\n 或 -ASK 3999 127.0.0.1:6381\r\n
+    pub fn parse(rsp: &RingSlice) -> Option<Self> {
+        let (ask, oft) = match is_redirect(rsp) {
+            true if rsp.start_with(0, ASK) => (true, ASK.len()),
+            true => (false, MOVED.len()),
+            false => return None,
+        };
+        let space = rsp.find(oft, b' ')?;
+        let slot = rsp.try_str_num(oft..space)?;
+        let end = rsp.find(space, b'\r')?;
+        let addr = rsp.sub_slice(space + 1, end - space - 1).as_string_lossy();
+        (!addr.is_empty()).then_some(Self { ask, slot, addr })
+    }
+}
+
+// 是否为MOVED、ASK重定向响应
+#[inline]
+pub(crate) fn is_redirect(rsp: &RingSlice) -> bool {
+    rsp.start_with(0, MOVED) || rsp.start_with(0, ASK)
+}
+
+// CLUSTER SLOTS 中的一段slot，nodes[0]是master，其他是slave
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotRange {
+    pub start: usize,
+    pub end: usize,
+    pub nodes: Vec<String>,
+}
+
+// 解析 CLUSTER SLOTS 的响应，数据不完整时返回ProtocolIncomplete：
+// *n
+//   *m  :start :end *k($ip :port $id ...) *k(...)
+pub fn parse_slots(data: &[u8]) -> Result<Vec<SlotRange>> {
+    let mut oft = 0;
+    let ranges = match value(data, &mut oft)? {
+        Value::Array(ranges) => ranges,
+        _ => return Err(Error::ResponseProtocolInvalid),
+    };
+    let mut slots = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        let Value::Array(range) = range else {
+            return Err(Error::ResponseProtocolInvalid);
+        };
+        let (Some(Value::Int(start)), Some(Value::Int(end))) = (range.first(), range.get(1)) else {
+            return Err(Error::ResponseProtocolInvalid);
+        };
+        let mut nodes = Vec::with_capacity(range.len().saturating_sub(2));
+        for node in &range[2..] {
+            // ip为空或"?"时，表示节点的地址未知
+            if let Value::Array(node) = node
+                && let (Some(Value::Bulk(ip)), Some(Value::Int(port))) = (node.first(), node.get(1))
+                && !ip.is_empty()
+                && *ip != b"?"
+            {
+                nodes.push(format!("{}:{}", String::from_utf8_lossy(ip), port));
+            }
+        }
+        if nodes.is_empty() || *start > *end || *end as usize >= sharding::hash::crc16::SLOTS {
+            return Err(Error::ResponseProtocolInvalid);
+        }
+        slots.push(SlotRange {
+            start: *start as usize,
+            end: *end as usize,
+            nodes,
+        });
+    }
+    Ok(slots)
+}
+
+enum Value<'a> {
+    Int(i64),
+    Bulk(&'a [u8]),
+    Array(Vec<Value<'a>>),
+    // 简单字符串等其他类型，CLUSTER SLOTS中不关注其内容
+    Other,
+}
+
+// 读取一个完整的值，oft指向下一个值的开始
+fn value<'a>(data: &'a [u8], oft: &mut usize) -> Result<Value<'a>> {
+    let start = *oft;
+    let end = start
+        + data[start..]
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or(Error::ProtocolIncomplete(0))?;
+    *oft = end + 2;
+    let line = &data[start + 1..end];
+    let num = || -> Result<i64> {
+        std::str::from_utf8(line)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or(Error::ResponseProtocolInvalid)
+    };
+    match data[start] {
+        b':' => Ok(Value::Int(num()?)),
+        b'+' => Ok(Value::Other),
+        b'$' => match num()? {
+            n if n < 0 => Ok(Value::Other),
+            n => {
+                let bulk_end = *oft + n as usize;
+                if bulk_end + 2 > data.len() {
+                    return Err(Error::ProtocolIncomplete(bulk_end + 2 - data.len()));
+                }
+                *oft = bulk_end + 2;
+                Ok(Value::Bulk(&data[end + 2..bulk_end]))
+            }
+        },
+        b'*' | b'%' => {
+            let n = num()?.max(0) as usize;
+            let n = if data[start] == b'%' { 2 * n } else { n };
+            let mut values = Vec::with_capacity(n);
+            for _ in 0..n {
+                if *oft >= data.len() {
+                    return Err(Error::ProtocolIncomplete(0));
+                }
+                values.push(value(data, oft)?);
+            }
+            Ok(Value::Array(values))
+        }
+        // -ERR This instance has cluster support disabled
+        _ => Err(Error::ResponseProtocolInvalid),
+    }
+}
@@ -20,11 +20,50 @@ pub(crate) enum CommandType {
     SpecLocalCmdHashkey,
     // 计算批量key的分片索引
     SpecLocalCmdKeyshard,
+    // 计算key的路由信息：hash、分片、后端地址等
+    KeyRoute,
+    // 协商client连接的协议版本，RESP2或RESP3
+    Hello,
+    // client认证
+    Auth,
+    // 本地响应，在事务中需要排队
+    Ping,
+    // zrange等带withscores时，RESP3的响应是[member, score]组成的数组，RESP2是平铺的数组
+    WithScores,
+    //============== 订阅类指令 ==============//
+    // subscribe、psubscribe：client连接进入订阅模式，独占一个后端连接
+    Subscribe,
+    PSubscribe,
+    // unsubscribe、punsubscribe：所有channel、pattern都退订后，退出订阅模式
+    Unsubscribe,
+    PUnsubscribe,
+    //============== 事务类指令 ==============//
+    Watch,
+    Unwatch,
+    Multi,
+    Exec,
+    Discard,
+    //============== 遍历所有分片的指令 ==============//
+    // scan：cursor中编码分片索引，逐个分片遍历
+    Scan,
+    // keys、dbsize：拆分到所有分片，合并各分片的响应
+    Keys,
+    DbSize,
+    // randomkey：随机选择一个分片
+    RandomKey,
+    // del、exists、unlink、touch：按key拆分，合并时累加各key的整数响应
+    MkeyCount,
+    //============== 集合运算指令 ==============//
+    // 未通过hashkey指定分片时，按key拆分成smembers，在mesh内计算交集、并集、差集
+    SInter,
+    SUnion,
+    SDiff,
 }
 
+// RANGE：指令映射的区间，由各协议的指令表决定
 #[derive(Default)]
-pub(crate) struct CommandHasher(i32);
-impl CommandHasher {
+pub(crate) struct CommandHasher<const RANGE: usize>(i32);
+impl<const RANGE: usize> CommandHasher<RANGE> {
     #[inline(always)]
     pub(crate) fn hash(&mut self, mut b: u8) {
         if b.is_ascii_lowercase() {
@@ -37,10 +76,10 @@ impl CommandHasher {
     #[inline(always)]
     pub(crate) fn finish(self) -> u16 {
         // +1 避免0
-        1 + (self.0.abs() as usize & (Commands::MAPPING_RANGE - 1)) as u16
+        1 + (self.0.unsigned_abs() as usize & (RANGE - 1)) as u16
     }
     pub(crate) fn hash_bytes(data: &[u8]) -> u16 {
-        let mut h = CommandHasher::default();
+        let mut h = Self::default();
         for b in data {
             h.hash(*b);
         }
@@ -49,7 +88,7 @@ impl CommandHasher {
     // oft: 指向'\r'的位置
     #[inline(always)]
     pub(crate) fn hash_slice(slice: &RingSlice, oft: usize) -> Result<(u16, usize)> {
-        let mut h = CommandHasher::default();
+        let mut h = Self::default();
         for i in oft..slice.len() - 1 {
             if slice[i] == b'\r' {
                 return Ok((h.finish(), i));
@@ -101,7 +140,7 @@ pub(crate) struct CommandProperties {
 
 // 默认响应
 // 第0个表示quit
-const PADDING_RSP_TABLE: [&str; 9] = [
+const PADDING_RSP_TABLE: [&str; 11] = [
     "",
     "+OK\r\n",
     "+PONG\r\n",
@@ -111,6 +150,8 @@ const PADDING_RSP_TABLE: [&str; 9] = [
     "$-1\r\n",                           // mget 等指令对应的nil
     ":-10\r\n",                          //phantom -1返回已被服务端占用
     "*2\r\n$5\r\nproto\r\n:2\r\n",       //hello 指令返回的响应
+    "-ERR EXEC without MULTI\r\n",
+    "-ERR DISCARD without MULTI\r\n",
 ];
 
 // 调用式确保idx < PADDING_RSP_TABLE.len()
@@ -143,6 +184,15 @@ impl CommandProperties {
         Err(RedisError::ReqInvalidBulkNum.into())
     }
 
+    // sinter、sunion、sdiff 可以在mesh内合并
+    #[inline]
+    pub(crate) fn set_op(&self) -> bool {
+        matches!(
+            self.cmd_type,
+            CommandType::SInter | CommandType::SUnion | CommandType::SDiff
+        )
+    }
+
     pub(crate) fn flag(&self) -> crate::Flag {
         let mut flag = crate::Flag::from_op(self.op_code, self.op);
         flag.set_noforward(self.noforward);
@@ -197,15 +247,19 @@ impl CommandProperties {
 // https://redis.io/commands 一共145大类命令。使用 crate::sharding::Hash::Crc32
 // 算法能够完整的将其映射到0~4095这个区间。因为使用这个避免大量的match消耗。
 pub(super) struct Commands {
-    supported: [CommandProperties; Self::MAPPING_RANGE],
+    // 数组过大，放在堆上，避免初始化时栈溢出
+    supported: Vec<CommandProperties>,
     // hash: Crc32,
     //hash: Bkdr,
 }
 impl Commands {
-    const MAPPING_RANGE: usize = 2048;
+    // 2048、4096时discard与lsmexists冲突
+    pub(super) const MAPPING_RANGE: usize = 8192;
     fn new() -> Self {
         Self {
-            supported: array_init::array_init(|_| Default::default()),
+            supported: (0..Self::MAPPING_RANGE)
+                .map(|_| Default::default())
+                .collect(),
             // hash: Crc32::default(),
             //hash: Bkdr::default(),
         }
@@ -224,7 +278,7 @@ impl Commands {
 
     #[inline]
     fn add_support(&mut self, mut c: CommandProperties) {
-        let idx = CommandHasher::hash_bytes(c.name.as_bytes()) as usize;
+        let idx = CommandHasher::<{ Self::MAPPING_RANGE }>::hash_bytes(c.name.as_bytes()) as usize;
         assert!(idx > 0 && idx < self.supported.len(), "idx:{}", idx);
         // 之前没有添加过。
         assert!(!self.supported[idx].supported);
@@ -260,9 +314,10 @@ pub(super) static SUPPORTED: Commands = {
         //// 不支持select 0以外的请求。所有的select请求直接返回，默认使用db0
         //// hello 参数应该是-1，可以不带或者带多个
         Cmd::new("command").arity(-1).op(Meta).padding(pt[1]).nofwd(),
-        Cmd::new("ping").arity(-1).op(Meta).padding(pt[2]).nofwd(),
+        Cmd::new("ping").arity(-1).op(Meta).padding(pt[2]).nofwd().cmd_type(CommandType::Ping),
         Cmd::new("select").arity(2).op(Meta).padding(pt[1]).nofwd(),
-        Cmd::new("hello").arity(-1).op(Meta).padding(pt[8]).nofwd(),
+        Cmd::new("hello").arity(-1).op(Meta).padding(pt[8]).nofwd().cmd_type(CommandType::Hello),
+        Cmd::new("auth").arity(-2).op(Meta).padding(pt[1]).nofwd().cmd_type(CommandType::Auth),
         // quit、master的指令token数/arity应该都是1,quit 的padding设为1 
         Cmd::new("quit").arity(1).op(Meta).padding(pt[1]).nofwd().quit(),
 
@@ -283,14 +338,13 @@ pub(super) static SUPPORTED: Commands = {
         // multi请求：异常响应需要改为$-1
         Cmd::new("mincr").arity(-2).op(Store).first(1).last(-1).step(1).padding(pt[6]).multi().key().bulk(),
 
-        //// mset、del 是mlti指令，但只返回一个result，即need_bulk_num为false，那就只返回第一个key的响应
-        //// del 删除多个key时，返回删除的key数量，先不聚合这个数字，反正client也会忽略？
-        //// exists 虽然原生支持多key，但业务client支持单key，故此处只支持单key
+        //// mset 是mlti指令，但只返回一个result，即need_bulk_num为false，那就只返回第一个key的响应
         Cmd::new("mset").m("set").arity(-3).op(Store).first(1).last(-1).step(2).padding(pt[3]).multi().key().val(),
-        Cmd::new("del").arity(-2).op(Store).first(1).last(-1).step(1).padding(pt[3]).multi().key(),
-
-        // 即便应对多语言，exists 也只支持一个key，否则需要计算多个后端数据，作为一个数字返回 fishermen
-        Cmd::new("exists").arity(2).op(Get).first(1).last(1).step(1).padding(pt[3]).key(),
+        //// del、exists、unlink、touch 按key拆分到各自的分片，所有key的响应返回后，累加成一个整数返回
+        Cmd::new("del").arity(-2).op(Store).first(1).last(-1).step(1).padding(pt[3]).multi().key().cmd_type(CommandType::MkeyCount),
+        Cmd::new("unlink").arity(-2).op(Store).first(1).last(-1).step(1).padding(pt[3]).multi().key().cmd_type(CommandType::MkeyCount),
+        Cmd::new("exists").arity(-2).op(Get).first(1).last(-1).step(1).padding(pt[3]).multi().key().cmd_type(CommandType::MkeyCount),
+        Cmd::new("touch").arity(-2).op(Get).first(1).last(-1).step(1).padding(pt[3]).multi().key().cmd_type(CommandType::MkeyCount),
         Cmd::new("expire").arity(3).op(Store).first(1).last(1).step(1).padding(pt[3]).key(),
         Cmd::new("expireat").arity(3).op(Store).first(1).last(1).step(1).padding(pt[3]).key(),
         Cmd::new("pexpire").arity(3).op(Store).first(1).last(1).step(1).padding(pt[3]).key(),
@@ -304,14 +358,14 @@ pub(super) static SUPPORTED: Commands = {
         Cmd::new("zremrangebyrank").arity(4).op(Store).first(1).last(1).step(1).padding(pt[3]).key(),
         Cmd::new("zremrangebyscore").arity(4).op(Store).first(1).last(1).step(1).padding(pt[3]).key(),
         Cmd::new("zremrangebylex").arity(4).op(Store).first(1).last(1).step(1).padding(pt[3]).key(),
-        Cmd::new("zrevrange").arity(-4).op(Get).first(1).last(1).step(1).padding(pt[3]).key(),
+        Cmd::new("zrevrange").arity(-4).op(Get).first(1).last(1).step(1).padding(pt[3]).key().cmd_type(CommandType::WithScores),
         Cmd::new("zcard").arity(2).op(Get).first(1).last(1).step(1).padding(pt[3]).key(),
-        Cmd::new("zrange").arity(-4).op(Get).first(1).last(1).step(1).padding(pt[3]).key(),
+        Cmd::new("zrange").arity(-4).op(Get).first(1).last(1).step(1).padding(pt[3]).key().cmd_type(CommandType::WithScores),
         Cmd::new("zrank").arity(3).op(Get).first(1).last(1).step(1).padding(pt[3]).key(),
-        Cmd::new("zrangebyscore").arity(-4).op(Get).first(1).last(1).step(1).padding(pt[3]).key(),
+        Cmd::new("zrangebyscore").arity(-4).op(Get).first(1).last(1).step(1).padding(pt[3]).key().cmd_type(CommandType::WithScores),
 
         Cmd::new("zrevrank").arity(3).op(Get).first(1).last(1).step(1).padding(pt[3]).key(),
-        Cmd::new("zrevrangebyscore").arity(-4).op(Get).first(1).last(1).step(1).padding(pt[3]).key(),
+        Cmd::new("zrevrangebyscore").arity(-4).op(Get).first(1).last(1).step(1).padding(pt[3]).key().cmd_type(CommandType::WithScores),
         Cmd::new("zrangebylex").arity(-4).op(Get).first(1).last(1).step(1).padding(pt[3]).key(),
         Cmd::new("zrevrangebylex").arity(-4).op(Get).first(1).last(1).step(1).padding(pt[3]).key(),
         Cmd::new("zcount").arity(4).op(Get).first(1).last(1).step(1).padding(pt[3]).key(),
@@ -392,9 +446,10 @@ pub(super) static SUPPORTED: Commands = {
         Cmd::new("smembers").arity(2).op(Get).first(1).last(1).step(1).padding(pt[3]).key(),
         Cmd::new("sscan").arity(-3).op(Get).first(1).last(1).step(1).padding(pt[3]).key(),
         // set 多个key相关的指令
-        Cmd::new("sinter").arity(-2).op(Get).first(1).last(-1).step(1).padding(pt[4]).need_resv_hash().key(),
-        Cmd::new("sunion").arity(-2).op(Get).first(1).last(-1).step(1).padding(pt[4]).need_resv_hash().key(),
-        Cmd::new("sdiff").arity(-2).op(Get).first(1).last(-1).step(1).padding(pt[4]).need_resv_hash().key(),
+        // 未通过hashkey指定分片时，如果开启了mesh内合并，按key拆分成smembers发送到各自的分片
+        Cmd::new("sinter").m("smembers").arity(-2).op(Get).first(1).last(-1).step(1).padding(pt[4]).need_resv_hash().key().cmd_type(CommandType::SInter),
+        Cmd::new("sunion").m("smembers").arity(-2).op(Get).first(1).last(-1).step(1).padding(pt[4]).need_resv_hash().key().cmd_type(CommandType::SUnion),
+        Cmd::new("sdiff").m("smembers").arity(-2).op(Get).first(1).last(-1).step(1).padding(pt[4]).need_resv_hash().key().cmd_type(CommandType::SDiff),
         Cmd::new("sunionstore").arity(-3).op(Store).first(1).last(-1).step(1).padding(pt[4]).need_resv_hash().key(),
         Cmd::new("sinterstore").arity(-3).op(Store).first(1).last(-1).step(1).padding(pt[4]).need_resv_hash().key(),
         Cmd::new("sdiffstore").arity(-3).op(Store).first(1).last(-1).step(1).padding(pt[4]).need_resv_hash().key(),
@@ -425,6 +480,8 @@ pub(super) static SUPPORTED: Commands = {
         cmd_type(CommandType::SpecLocalCmdHashkey).effect_on_next_req(),
         Cmd::new("keyshard").arity(-2).op(Meta).first(1).last(-1).step(1).padding(pt[5]).multi().
         nofwd().key().bulk().cmd_type(CommandType::SpecLocalCmdKeyshard),
+        // key由KeyRoute自行解析，不参与hash
+        Cmd::new("keyroute").arity(2).op(Meta).padding(pt[6]).nofwd().cmd_type(CommandType::KeyRoute),
 
         // lua script 相关指令，不解析相关key，由hashkey提前指定，业务一般在操作check+变更的事务时使用 fishermen
         Cmd::new("script").arity(-2).op(Store).padding(pt[3]).need_resv_hash(),
@@ -437,13 +494,36 @@ pub(super) static SUPPORTED: Commands = {
         Cmd::new("bfmget").m("bfget").arity(-2).op(MGet).first(1).last(-1).step(1).padding(pt[7]).multi().key().bulk(),
         Cmd::new("bfmset").m("bfset").arity(-2).op(Store).first(1).last(1).step(1).padding(pt[7]).multi().key().bulk(),
 
+        // 订阅类指令：subscribe、psubscribe按channel拆分，client连接进入订阅模式后，订阅类指令透传到channel所在分片的独占连接
+        // pattern可能匹配任意分片的channel，psubscribe需要通过hashkey指定路由的key
+        // publish 按channel计算hash，确保与订阅者路由到同一个分片；pubsub 需要通过hashkey指定channel
+        Cmd::new("subscribe").arity(-2).op(Get).first(1).last(-1).step(1).padding(pt[3]).multi().key().cmd_type(CommandType::Subscribe),
+        Cmd::new("psubscribe").arity(-2).op(Get).first(1).last(-1).step(1).padding(pt[3]).need_resv_hash().multi().key().cmd_type(CommandType::PSubscribe),
+        Cmd::new("unsubscribe").arity(-1).op(Get).padding(pt[3]).cmd_type(CommandType::Unsubscribe),
+        Cmd::new("punsubscribe").arity(-1).op(Get).padding(pt[3]).cmd_type(CommandType::PUnsubscribe),
+        Cmd::new("publish").arity(3).op(Store).first(1).last(1).step(1).padding(pt[3]).key().val(),
+        Cmd::new("pubsub").arity(-2).op(Get).padding(pt[4]).need_resv_hash(),
+
+        // 事务类指令：事务内所有key必须在同一个分片，multi之后的指令在mesh内排队，exec时一起发送到该分片master的独占连接
+        // watch 按key拆分，用于校验每个key的分片；multi、exec、discard、unwatch 在事务外直接本地响应，unwatch 在事务内排队
+        Cmd::new("watch").arity(-2).op(Get).first(1).last(-1).step(1).padding(pt[3]).multi().key().cmd_type(CommandType::Watch),
+        Cmd::new("unwatch").arity(1).op(Meta).padding(pt[1]).nofwd().cmd_type(CommandType::Unwatch),
+        Cmd::new("multi").arity(1).op(Meta).padding(pt[1]).nofwd().cmd_type(CommandType::Multi),
+        Cmd::new("exec").arity(1).op(Store).padding(pt[9]).nofwd().cmd_type(CommandType::Exec),
+        Cmd::new("discard").arity(1).op(Meta).padding(pt[10]).nofwd().cmd_type(CommandType::Discard),
+
+        // 遍历类指令：scan的cursor中编码了分片索引，按分片依次遍历；keys、dbsize发送到所有分片，由mesh合并响应
+        Cmd::new("scan").arity(-2).op(Get).padding(pt[3]).cmd_type(CommandType::Scan),
+        Cmd::new("keys").arity(2).op(Get).padding(pt[3]).cmd_type(CommandType::Keys),
+        Cmd::new("dbsize").arity(1).op(Get).padding(pt[3]).cmd_type(CommandType::DbSize),
+        Cmd::new("randomkey").arity(1).op(Get).padding(pt[6]).cmd_type(CommandType::RandomKey),
+
         // 有些redis客户端要求支持client指令; 为了方便测试，在debug模式下极简化支持client指令
         #[cfg(debug_assertions)]
         Cmd::new("client").arity(-1).op(Meta).padding(pt[1]).nofwd(),
         
         // 待支持
         // {"lsmalloc",lsmallocCommand,3,REDIS_CMD_DENYOOM|REDIS_CMD_WRITE,NULL,1,1,1},
-        // {"unlink",unlinkCommand,-2,REDIS_CMD_WRITE,NULL,1,-1,1},
 
         // {"riskauth",riskAuthCommand,2,0,NULL,0,0,0},
 
@@ -474,17 +554,7 @@ pub(super) static SUPPORTED: Commands = {
         // {"version",versionCommand,1,0,NULL,0,0,0},
         // {"debug",debugCommand,-2,0,NULL,0,0,0},
 
-        // 订阅类指令，暂不支持
-        // {"unsubscribe",unsubscribeCommand,-1,0,NULL,0,0,0},
-        // {"psubscribe",psubscribeCommand,-2,0,NULL,0,0,0},
-        // {"punsubscribe",punsubscribeCommand,-1,0,NULL,0,0,0},
-        // {"publish",publishCommand,3,REDIS_CMD_FORCE_REPLICATION|REDIS_CMD_WRITE,NULL,0,0,0},
-        // "subscribe" => (-2, Operation::Get, 0, 0, 0),
-        // {"pubsub", pubsubCommand, -2, REDIS_CMD_READONLY|REDIS_CMD_PUBSUB, NULL, 0, 0, 0},
-
         // 特殊指令，暂不支持
-        // {"watch",watchCommand,-2,0,NULL,1,-1,1},
-        // {"unwatch",unwatchCommand,1,0,NULL,0,0,0},
         // {"object",objectCommand,-2,0,NULL,2,2,1},
 
         // 涉及多个key，先不支持了
@@ -511,14 +581,9 @@ pub(super) static SUPPORTED: Commands = {
         // "hstrlen" => (3, Operation::Get, 1, 1, 1),
 
         // "msetnx" => (-3, Operation::Store, 1, -1, 2),
-        // "randomkey" => (1, Operation::Get, 0, 0, 0),
         // "move" => (3, Operation::Store, 1, 1, 1),
         // "rename" => (3, Operation::Store, 1, 2, 1),
         // "renamenx" => (3, Operation::Store, 1, 2, 1),
-        // "keys" => (2, Operation::Get, 0, 0, 0),
-        // "scan" => (-2, Operation::Get, 0, 0, 0),
-        // "dbsize" => (1, Operation::Get, 0, 0, 0),
-        // "auth" => (2, Operation::Meta, 0, 0, 0),
         // "echo" => (2, Operation::Meta, 0, 0, 0),
         // info 先不在client支持
         // "info" => (-1, Operation::Meta, 0, 0, 0),
@@ -528,10 +593,7 @@ pub(super) static SUPPORTED: Commands = {
         // "time" => (1, Operation::Get, 0, 0, 0),
 
         // ********** 二期实现
-        // 事务类、脚本类cmd，暂时先不支持，二期再处理 fishermen
-        // "multi" => (1, Operation::Store, 0, 0, 0),
-        // "exec" => (1, Operation::Store, 0, 0, 0),
-        // "discard" => (1, Operation::Get, 0, 0, 0),
+        // 脚本类cmd，暂时先不支持，二期再处理 fishermen
         // "sort" => (-2, Operation::Store, 1, 1, 1),
         // "client" => (-2, Operation::Meta, 0, 0, 0),
 
@@ -9,6 +9,8 @@ pub enum RedisError {
     ReqInvalidNoReturn,
     ReqInvalidBulkNum,
     ReqNotSupported,
+    // 分片数量超过flag、scan cursor能编码的上限，无法遍历所有分片
+    ReqTooManyShards,
     RespInvalid,
     // ReqInvalidNumZero,
     // ReqInvalidDigit,
@@ -20,6 +22,7 @@ const REQ_INVALID_NUM: &'static [u8] = b"-ERR invalid num\r\n";
 const REQ_INVALID_NO_RETURN: &'static [u8] = b"-ERR invalid no return char\r\n";
 const REQ_INVALID_BULK_NUM: &'static [u8] = b"-ERR invalid bulk num\r\n";
 const REQ_NOT_SUPPORTED: &'static [u8] = b"-ERR unsupport cmd\r\n";
+const REQ_TOO_MANY_SHARDS: &[u8] = b"-ERR too many shards to traverse\r\n";
 const RESP_INVALID: &'static [u8] = b"-ERR  mesh bug for parsing resp\r\n";
 
 /// 将Redis error转为通用可flush的Error，保留Error细节
@@ -33,6 +36,7 @@ impl Into<Error> for RedisError {
             Self::ReqInvalidNoReturn => Error::FlushOnClose(REQ_INVALID_NO_RETURN.into()),
             Self::ReqInvalidBulkNum => Error::FlushOnClose(REQ_INVALID_BULK_NUM.into()),
             Self::ReqNotSupported => Error::FlushOnClose(REQ_NOT_SUPPORTED.into()),
+            Self::ReqTooManyShards => Error::FlushOnClose(REQ_TOO_MANY_SHARDS.into()),
             Self::RespInvalid => Error::FlushOnClose(RESP_INVALID.into()),
         }
     }
@@ -11,7 +11,24 @@ const MASTER_ONLY_SHIFT: u8 = MKEY_FIRST_SHIFT + MKEY_FIRST_BIT;
 const MASTER_ONLY_BIT: u8 = 1;
 // [18]: sendto_all
 const SENDTO_ALL_SHIFT: u8 = MASTER_ONLY_SHIFT + MASTER_ONLY_BIT;
-const _SENDTO_ALL_BIT: u8 = 1;
+const SENDTO_ALL_BIT: u8 = 1;
+// [19..31]: 12bit 指定请求发送的分片，值为分片索引+1，0表示按hash路由。
+// 用于scan、keys、dbsize等需要遍历所有分片的指令
+const SHARD_SHIFT: u8 = SENDTO_ALL_SHIFT + SENDTO_ALL_BIT;
+pub(crate) const SHARD_BITS: u8 = 12;
+const SHARD_MASK: u64 = (1 << SHARD_BITS) - 1;
+// 可以指定的最大分片数量
+pub(crate) const MAX_SHARDS: usize = SHARD_MASK as usize;
+// [31..61]: 30bit sinter、sunion、sdiff 在mesh内合并时，允许的最大成员数量
+const MAX_MEMBERS_SHIFT: u8 = SHARD_SHIFT + SHARD_BITS;
+const MAX_MEMBERS_BITS: u8 = 30;
+const MAX_MEMBERS_MASK: u64 = (1 << MAX_MEMBERS_BITS) - 1;
+// [61..63]: 2bit client认证、鉴权失败的原因，请求不转发，直接返回对应的异常
+const REJECTED_SHIFT: u8 = MAX_MEMBERS_SHIFT + MAX_MEMBERS_BITS;
+const REJECTED_BITS: u8 = 2;
+const REJECTED_MASK: u64 = (1 << REJECTED_BITS) - 1;
+// [63]: client通过hello协商了RESP3
+const RESP3_SHIFT: u8 = REJECTED_SHIFT + REJECTED_BITS;
 
 pub trait RedisFlager {
     fn set_key_count(&mut self, cnt: u16);
@@ -26,6 +43,14 @@ pub trait RedisFlager {
     fn master_only(&self) -> bool;
     fn set_sendto_all(&mut self);
     fn sendto_all(&self) -> bool;
+    fn set_shard(&mut self, idx: usize);
+    fn shard(&self) -> Option<usize>;
+    fn set_max_members(&mut self, max: usize);
+    fn max_members(&self) -> usize;
+    fn set_rejected(&mut self, reason: u8);
+    fn rejected(&self) -> u8;
+    fn set_resp3(&mut self);
+    fn resp3(&self) -> bool;
 
     // fn set_ignore_rsp(&mut self, ignore_rsp: bool);
     // fn ignore_rs(&self) -> bool;
@@ -84,4 +109,41 @@ impl<T: Ext> RedisFlager for T {
     fn sendto_all(&self) -> bool {
         self.get(SENDTO_ALL_SHIFT)
     }
+    #[inline]
+    fn set_shard(&mut self, idx: usize) {
+        debug_assert!(idx < MAX_SHARDS, "shard:{}", idx);
+        self.mask_set(SHARD_SHIFT, SHARD_MASK, idx as u64 + 1)
+    }
+    #[inline]
+    fn shard(&self) -> Option<usize> {
+        match self.mask_get(SHARD_SHIFT, SHARD_MASK) {
+            0 => None,
+            idx => Some(idx as usize - 1),
+        }
+    }
+    #[inline]
+    fn set_max_members(&mut self, max: usize) {
+        let max = max.min(MAX_MEMBERS_MASK as usize);
+        self.mask_set(MAX_MEMBERS_SHIFT, MAX_MEMBERS_MASK, max as u64)
+    }
+    #[inline]
+    fn max_members(&self) -> usize {
+        self.mask_get(MAX_MEMBERS_SHIFT, MAX_MEMBERS_MASK) as usize
+    }
+    #[inline]
+    fn set_rejected(&mut self, reason: u8) {
+        self.mask_set(REJECTED_SHIFT, REJECTED_MASK, reason as u64)
+    }
+    #[inline]
+    fn rejected(&self) -> u8 {
+        self.mask_get(REJECTED_SHIFT, REJECTED_MASK) as u8
+    }
+    #[inline]
+    fn set_resp3(&mut self) {
+        self.set(RESP3_SHIFT)
+    }
+    #[inline]
+    fn resp3(&self) -> bool {
+        self.get(RESP3_SHIFT)
+    }
 }
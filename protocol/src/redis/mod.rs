@@ -1,15 +1,19 @@
+pub mod cluster;
 pub(crate) mod command;
 pub(crate) mod error;
 pub(crate) mod flag;
 pub use flag::RedisFlager;
 pub(crate) mod packet;
+mod resp3;
 
 use crate::{
-    Command, Commander, Error, HandShake, HashedCommand, Metric, MetricItem, MetricName, Protocol,
-    RequestProcessor, ResOption, Result, Stream, Writer,
+    Acl, Command, Commander, Error, Flag, HandShake, HashedCommand, Metric, MetricItem, MetricName,
+    Protocol, RequestProcessor, ResOption, Result, Stream, SubCmd, TxCmd, Writer,
     redis::command::CommandType,
-    redis::{error::RedisError, packet::RequestPacket},
+    redis::flag::{MAX_SHARDS, SHARD_BITS},
+    redis::{error::RedisError, packet::CRLF_LEN, packet::RequestPacket},
 };
+use ds::{MemGuard, RingSlice};
 pub use packet::{Packet, ResponseContext, transmute};
 use sharding::hash::Hash;
 
@@ -22,6 +26,8 @@ pub enum HandShakeStatus {
     Init = 0,
     Sent = 1,
     Success = 2,
+    // 已发送hello 3，协商RESP3
+    Hello = 3,
 }
 
 impl Default for HandShakeStatus {
@@ -31,6 +37,12 @@ impl Default for HandShakeStatus {
 }
 
 impl Redis {
+    // 与后端协商RESP3，后端返回RESP3的响应，由write_response按client的协议版本转换
+    fn hello(&self, stream: &mut impl Stream) -> Result<HandShake> {
+        stream.write_all(b"*2\r\n$5\r\nHELLO\r\n$1\r\n3\r\n")?;
+        transmute(stream.context()).status = HandShakeStatus::Hello;
+        Ok(HandShake::Continue)
+    }
     #[inline]
     fn parse_request_inner<S: Stream, H: Hash, P: RequestProcessor>(
         &self,
@@ -45,7 +57,26 @@ impl Redis {
         while packet.available() {
             packet.parse_bulk_num()?;
             let cfg = packet.parse_cmd()?;
-            if cfg.multi {
+            // 未认证、无权限执行的指令不转发，直接返回异常
+            let reason = rejected(cfg, packet.acl(), process.require_auth());
+            if reason != 0 {
+                packet.ignore_all_bulks()?;
+                let cmd = packet.take();
+                let mut flag = cfg.flag();
+                if !flag.noforward() {
+                    flag.set_noforward(true);
+                }
+                flag.set_rejected(reason);
+                process.process(HashedCommand::new(cmd, 0, flag), true);
+                packet.clear_status(cfg);
+                continue;
+            }
+            // 未指定分片的集合运算，按key拆分成smembers，所有响应返回后在mesh内合并
+            let merge_set = cfg.set_op() && !packet.hash_reserved();
+            if merge_set && process.max_set_members() == 0 {
+                return Err(RedisError::ReqInvalid.into());
+            }
+            if cfg.multi || merge_set {
                 packet.multi_ready();
                 while packet.has_bulk() {
                     // take会将first变为false, 需要在take之前调用。
@@ -53,7 +84,10 @@ impl Redis {
                     let first = packet.first();
                     debug_assert!(cfg.has_key, "cfg:{}", cfg.name);
 
-                    let flag = packet.flag(cfg);
+                    let mut flag = packet.flag(cfg);
+                    if merge_set {
+                        flag.set_max_members(process.max_set_members());
+                    }
                     let hash = packet.hash(cfg, alg)?;
 
                     if cfg.has_val {
@@ -70,11 +104,106 @@ impl Redis {
                     (packet.flag(cfg), packet.hash(cfg, alg)?)
                 };
 
-                packet.ignore_all_bulks()?;
-                let cmd = packet.take();
-                if !cfg.swallowed {
-                    let req = HashedCommand::new(cmd, hash, flag);
-                    process.process(req, true);
+                match cfg.cmd_type {
+                    // keys、dbsize 按分片拆分成多个子请求，所有子请求完成后合并响应
+                    CommandType::Keys | CommandType::DbSize => {
+                        packet.ignore_all_bulks()?;
+                        let cmd = packet.take();
+                        let shards = traverse_shards(process)?;
+                        for shard in 0..shards {
+                            let mut flag = packet.flag(cfg);
+                            flag.set_shard(shard);
+                            if shard == 0 {
+                                flag.set_mkey_first();
+                                flag.set_key_count(shards as u16);
+                            }
+                            let mut data = Vec::with_capacity(cmd.len());
+                            cmd.copy_to_vec(&mut data);
+                            let req = HashedCommand::new(MemGuard::from_vec(data), hash, flag);
+                            process.process(req, shard + 1 == shards);
+                        }
+                    }
+                    // hello [protover [AUTH username password] [SETNAME clientname]]
+                    CommandType::Hello => {
+                        let mut ver = None;
+                        if packet.has_bulk() {
+                            ver = packet.parse_key()?.try_str_num(..);
+                        }
+                        // 认证失败时不切换协议版本
+                        let mut reason = 0;
+                        if packet.bulk() >= 3 && packet.parse_key()?.equal_ignore_case(b"auth") {
+                            let user = packet.parse_key()?;
+                            let pass = packet.parse_key()?;
+                            match auth(process, Some(&user), &pass) {
+                                Some(acl) => packet.set_acl(acl),
+                                None => reason = WRONGPASS,
+                            }
+                        } else if process.require_auth() && packet.acl().is_none() {
+                            reason = NOAUTH;
+                        }
+                        match (reason, ver) {
+                            (0, Some(2)) => packet.set_resp(2),
+                            (0, Some(3)) if process.resp3() => packet.set_resp(3),
+                            _ => {}
+                        }
+                        packet.ignore_all_bulks()?;
+                        let cmd = packet.take();
+                        // 按协商后的协议版本返回响应
+                        let mut flag = packet.flag(cfg);
+                        flag.set_rejected(reason);
+                        process.process(HashedCommand::new(cmd, hash, flag), true);
+                    }
+                    // auth [username] password，认证失败时保留之前的认证状态
+                    CommandType::Auth => {
+                        let user = match packet.bulk() {
+                            1 => None,
+                            _ => Some(packet.parse_key()?),
+                        };
+                        let pass = packet.parse_key()?;
+                        packet.ignore_all_bulks()?;
+                        let mut flag = flag;
+                        match auth(process, user.as_ref(), &pass) {
+                            Some(acl) => packet.set_acl(acl),
+                            None => flag.set_rejected(WRONGPASS),
+                        }
+                        let cmd = packet.take();
+                        process.process(HashedCommand::new(cmd, hash, flag), true);
+                    }
+                    // keyroute key：请求不转发，路由信息作为请求内容，在write_response中原样返回
+                    CommandType::KeyRoute => {
+                        let mut key = Vec::new();
+                        packet.parse_key()?.copy_to_vec(&mut key);
+                        packet.ignore_all_bulks()?;
+                        let _cmd = packet.take();
+                        let rsp = match process.route(&key) {
+                            Some(route) => format!("${}\r\n{}\r\n", route.len(), route),
+                            None => String::from_utf8_lossy(cfg.get_padding_rsp()).into(),
+                        };
+                        let req = HashedCommand::new(MemGuard::from_vec(rsp.into()), hash, flag);
+                        process.process(req, true);
+                    }
+                    CommandType::Scan => {
+                        let cursor = packet.parse_key()?;
+                        let cursor = cursor_num(&cursor, ..);
+                        packet.ignore_all_bulks()?;
+                        let cmd = packet.take();
+                        let shards = traverse_shards(process)?;
+                        let req = scan_request(cmd, cursor, shards, flag, hash);
+                        process.process(req, true);
+                    }
+                    _ => {
+                        packet.ignore_all_bulks()?;
+                        let cmd = packet.take();
+                        if !cfg.swallowed {
+                            // randomkey 随机选择一个分片
+                            let hash = match cfg.cmd_type {
+                                CommandType::RandomKey => rand::random::<u32>() as i64,
+                                _ => hash,
+                            };
+                            let req = HashedCommand::new(cmd, hash, flag);
+                            process.process(req, true);
+                        }
+                    }
                 }
             }
             //一个请求结束才会走到这
@@ -90,9 +219,9 @@ impl Redis {
         log::debug!("+++ will parse redis rsp:{:?}", data);
 
         match data.at(0) {
-            b'-' | b':' | b'+' => data.line(&mut ctx.oft)?,
-            b'$' => data.skip_string_check(&mut ctx.oft)?,
-            b'*' => data.skip_multibulks_with_ctx(ctx)?,
+            b'-' | b':' | b'+' | b'_' | b',' | b'#' | b'(' => data.line(&mut ctx.oft)?,
+            b'$' | b'=' | b'!' => data.skip_string_check(&mut ctx.oft)?,
+            b'*' | b'%' | b'~' | b'>' | b'|' => data.skip_multibulks_with_ctx(ctx)?,
             _ => return Err(RedisError::RespInvalid.into()),
         }
 
@@ -109,6 +238,7 @@ impl Protocol for Redis {
         let status = transmute(stream.context()).status;
 
         match status {
+            HandShakeStatus::Init if option.token.is_empty() => self.hello(stream),
             HandShakeStatus::Init => {
                 // a two-bulk "AUTH" command.
                 let pass = &option.token;
@@ -130,6 +260,9 @@ impl Protocol for Redis {
                     // response should be +OK\r\n
                     if data.start_with(0, b"+OK\r\n") {
                         stream.ignore(idx + 2);
+                        if option.resp3 {
+                            return self.hello(stream);
+                        }
                         transmute(stream.context()).status = HandShakeStatus::Success;
                         return Ok(HandShake::Success);
                     }
@@ -140,6 +273,17 @@ impl Protocol for Redis {
                     Ok(HandShake::Continue)
                 }
             }
+            // 不支持RESP3的后端返回异常，继续使用RESP2
+            HandShakeStatus::Hello => match self.parse_response(stream)? {
+                Some(rsp) => {
+                    if rsp[0] == b'-' {
+                        log::info!("redis backend not support resp3:{:?}", rsp);
+                    }
+                    transmute(stream.context()).status = HandShakeStatus::Success;
+                    Ok(HandShake::Success)
+                }
+                None => Ok(HandShake::Continue),
+            },
             HandShakeStatus::Success => Ok(HandShake::Success),
         }
     }
@@ -209,11 +353,31 @@ impl Protocol for Redis {
     {
         let request = ctx.request();
         let cfg = command::get_cfg(request.op_code())?;
+        if request.rejected() != 0 {
+            return w.write(REJECTED[request.rejected() as usize]);
+        }
+        // 每个channel都有各自的确认消息，不能只返回第一个key的响应
+        if matches!(cfg.cmd_type, CommandType::Subscribe | CommandType::PSubscribe) {
+            return match response {
+                Some(rsp) => w.write_slice(rsp, 0),
+                None if request.mkey_first() => w.write(cfg.get_padding_rsp()),
+                None => Ok(()),
+            };
+        }
 
         if !cfg.multi {
             // 非multi请求,有响应直接返回client，否则构建
             if let Some(rsp) = response {
-                w.write_slice(rsp, 0)?;
+                match cfg.cmd_type {
+                    CommandType::Scan => write_scan_response(request, rsp, w)?,
+                    _ => write_rsp(cfg, request, rsp, w)?,
+                }
+            } else if cfg.cmd_type == CommandType::Scan && request.noforward() {
+                w.write(INVALID_CURSOR)?;
+            } else if cfg.cmd_type == CommandType::Hello {
+                write_hello(cfg, request, w)?;
+            } else if cfg.cmd_type == CommandType::KeyRoute {
+                w.write_slice(request, 0)?;
             } else {
                 // 无响应，则根据cmd name构建对应响应
                 w.write(cfg.get_padding_rsp())?;
@@ -237,7 +401,7 @@ impl Protocol for Redis {
                 // 如果rsp是ok，或者不需要bulk num，直接发送；否则构建rsp or padding rsp
                 if let Some(rsp) = response {
                     if rsp.ok() || !cfg.need_bulk_num {
-                        w.write_slice(rsp, 0)?;
+                        write_rsp(cfg, request, rsp, w)?;
                         return Ok(());
                     }
                 }
@@ -269,6 +433,143 @@ impl Protocol for Redis {
         Ok(())
     }
 
+    #[inline]
+    fn need_merge(&self, req: &HashedCommand) -> bool {
+        command::get_cfg(req.op_code())
+            .map(|cfg| {
+                matches!(
+                    cfg.cmd_type,
+                    CommandType::Keys | CommandType::DbSize | CommandType::MkeyCount
+                ) || cfg.set_op()
+            })
+            .unwrap_or(false)
+    }
+
+    // 任何一个分片失败，都返回该分片的异常；否则dbsize、del等累加各分片的数量，keys拼接各分片的key，
+    // 集合运算在成员总数不超过上限时计算结果
+    fn merge_response<C, W, M, I>(
+        &self,
+        ctx: &mut C,
+        responses: &mut [Option<Command>],
+        w: &mut W,
+    ) -> Result<()>
+    where
+        W: Writer,
+        C: Commander<M, I>,
+        M: Metric<I>,
+        I: MetricItem,
+    {
+        let cfg = command::get_cfg(ctx.request().op_code())?;
+        // 集合运算的成员总数超过上限时，不需要等待所有分片的响应
+        if cfg.set_op() && members_exceeded(ctx.request(), responses.iter().flatten()) {
+            return w.write(TOO_MANY_MEMBERS);
+        }
+        // 响应的数值，以及数值之后的数据起始位置，如 :10\r\n、*2\r\n$1\r\na\r\n$1\r\nb\r\n
+        let mut nums = Vec::with_capacity(responses.len());
+        for rsp in responses.iter() {
+            let Some(rsp) = rsp else {
+                return w.write(cfg.get_padding_rsp());
+            };
+            let num = rsp
+                .find(0, b'\r')
+                .and_then(|idx| Some((rsp.try_str_num(1..idx)?, idx + CRLF_LEN)));
+            // 后端协商了RESP3时，smembers返回的是set
+            match num {
+                Some(num) if matches!(rsp[0], b':' | b'*' | b'~') => nums.push(num),
+                _ => return write_rsp(cfg, ctx.request(), rsp, w),
+            }
+        }
+        let total: usize = nums.iter().map(|(n, _)| n).sum();
+        match cfg.cmd_type {
+            _ if cfg.set_op() => {
+                let mut sets = Vec::with_capacity(nums.len());
+                for (rsp, (num, oft)) in responses.iter().flatten().zip(nums) {
+                    match members(rsp, num, oft) {
+                        Some(set) => sets.push(set),
+                        None => return write_rsp(cfg, ctx.request(), rsp, w),
+                    }
+                }
+                let members = set_op(cfg.cmd_type, sets);
+                w.write_u8(if ctx.request().resp3() { b'~' } else { b'*' })?;
+                w.write_str_num(members.len())?;
+                w.write(b"\r\n")?;
+                for m in members {
+                    w.write_u8(b'$')?;
+                    w.write_str_num(m.len())?;
+                    w.write(b"\r\n")?;
+                    w.write(&m)?;
+                    w.write(b"\r\n")?;
+                }
+            }
+            CommandType::DbSize | CommandType::MkeyCount => {
+                w.write_u8(b':')?;
+                w.write_str_num(total)?;
+                w.write(b"\r\n")?;
+            }
+            _ => {
+                w.write_u8(b'*')?;
+                w.write_str_num(total)?;
+                w.write(b"\r\n")?;
+                for (rsp, (_, oft)) in responses.iter().flatten().zip(nums) {
+                    w.write_slice(rsp, oft)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn merge_early(&self, req: &HashedCommand, responses: &[&Command]) -> bool {
+        command::get_cfg(req.op_code()).is_ok_and(|cfg| cfg.set_op())
+            && members_exceeded(req, responses.iter().copied())
+    }
+
+    #[inline]
+    fn sub_cmd(&self, req: &HashedCommand) -> SubCmd {
+        let Ok(cfg) = command::get_cfg(req.op_code()) else {
+            return SubCmd::Other;
+        };
+        // 未认证、无权限的指令直接返回异常
+        if req.rejected() != 0 {
+            return SubCmd::Other;
+        }
+        match cfg.cmd_type {
+            CommandType::Subscribe => SubCmd::Subscribe,
+            CommandType::PSubscribe => SubCmd::PSubscribe,
+            CommandType::Unsubscribe => SubCmd::Unsubscribe,
+            CommandType::PUnsubscribe => SubCmd::PUnsubscribe,
+            _ => SubCmd::Other,
+        }
+    }
+
+    // 拆分后的multi-key指令，只有单个key、且不需要bulk num的才能在事务中排队，如 del k、mset k v
+    #[inline]
+    fn tx_cmd(&self, req: &HashedCommand) -> TxCmd {
+        let Ok(cfg) = command::get_cfg(req.op_code()) else {
+            return TxCmd::Other;
+        };
+        // 未认证、无权限的指令直接返回异常
+        if req.rejected() != 0 {
+            return TxCmd::Other;
+        }
+        match cfg.cmd_type {
+            CommandType::Watch => TxCmd::Watch,
+            CommandType::Unwatch => TxCmd::Unwatch,
+            CommandType::Multi => TxCmd::Multi,
+            CommandType::Exec => TxCmd::Exec,
+            CommandType::Discard => TxCmd::Discard,
+            CommandType::Ping => TxCmd::Keyless,
+            // 遍历所有分片的指令无法在单分片事务中执行
+            CommandType::Scan | CommandType::Keys | CommandType::DbSize => TxCmd::Unsupported,
+            // 拆分成smembers的集合运算
+            _ if cfg.set_op() && req.max_members() > 0 => TxCmd::Unsupported,
+            _ if cfg.multi && (cfg.need_bulk_num || !req.mkey_first() || req.key_count() != 1) => {
+                TxCmd::Unsupported
+            }
+            _ => TxCmd::Other,
+        }
+    }
+
     #[inline(always)]
     fn check(&self, _req: &HashedCommand, _resp: &Command) {
         if _resp[0] == b'-' {
@@ -277,5 +578,238 @@ impl Protocol for Redis {
     }
 }
 
+// client认证、鉴权失败的原因，对应返回的异常
+const NOAUTH: u8 = 1;
+const NOPERM: u8 = 2;
+const WRONGPASS: u8 = 3;
+const REJECTED: [&[u8]; 4] = [
+    b"",
+    b"-NOAUTH Authentication required.\r\n",
+    b"-NOPERM this user has no permissions to run this command\r\n",
+    b"-WRONGPASS invalid username-password pair or user is disabled.\r\n",
+];
+
+// auth、hello、quit总是可以执行；吞噬的指令没有响应，由下一个指令鉴权
+#[inline]
+fn rejected(cfg: &command::CommandProperties, acl: Option<Acl>, require_auth: bool) -> u8 {
+    if cfg.swallowed || cfg.quit || matches!(cfg.cmd_type, CommandType::Auth | CommandType::Hello) {
+        return 0;
+    }
+    match acl {
+        None if require_auth => NOAUTH,
+        Some(acl) if !acl.allow(cfg.op) => NOPERM,
+        _ => 0,
+    }
+}
+
+// 未指定用户名时使用default用户
+fn auth<P: RequestProcessor>(
+    process: &P,
+    user: Option<&RingSlice>,
+    pass: &RingSlice,
+) -> Option<Acl> {
+    let mut name = Vec::with_capacity(16);
+    match user {
+        Some(user) => user.copy_to_vec(&mut name),
+        None => name.extend_from_slice(b"default"),
+    }
+    let mut password = Vec::with_capacity(pass.len());
+    pass.copy_to_vec(&mut password);
+    process.auth(&name, &password)
+}
+
+// 未通过hello协商RESP3的client，需要把后端返回的RESP3响应转换为RESP2
+#[inline]
+fn write_rsp<W: Writer>(
+    cfg: &command::CommandProperties,
+    req: &HashedCommand,
+    rsp: &Command,
+    w: &mut W,
+) -> Result<()> {
+    if !req.resp3() && resp3::is_resp3(rsp) {
+        let pairs = cfg.cmd_type == CommandType::WithScores;
+        resp3::write_resp2(rsp, pairs, w)
+    } else {
+        w.write_slice(rsp, 0)
+    }
+}
+
+// hello协商RESP3后的响应
+const HELLO_RESP3: &[u8] = b"%1\r\n$5\r\nproto\r\n:3\r\n";
+const NOPROTO: &[u8] = b"-NOPROTO sorry, this protocol version is not supported\r\n";
+
+// hello的响应：未指定版本时返回当前的协议版本，版本不支持时返回NOPROTO
+fn write_hello<W: Writer>(
+    cfg: &command::CommandProperties,
+    req: &HashedCommand,
+    w: &mut W,
+) -> Result<()> {
+    // *N\r\n$5\r\nhello\r\n$len\r\nprotover\r\n...，protover在第5行
+    let ver = req.skip_lf_cr(0, 4).map(|start| {
+        req.find(start, b'\r')
+            .and_then(|end| req.try_str_num(start..end))
+    });
+    match ver {
+        // 后端未开启RESP3
+        Some(Some(3)) if !req.resp3() => w.write(NOPROTO),
+        Some(Some(2 | 3)) | None if req.resp3() => w.write(HELLO_RESP3),
+        Some(Some(2 | 3)) | None => w.write(cfg.get_padding_rsp()),
+        Some(_) => w.write(NOPROTO),
+    }
+}
+
+// scan 的cursor不合法，或者其中的分片已不存在
+const INVALID_CURSOR: &[u8] = b"-ERR invalid cursor\r\n";
+const CURSOR_OVERFLOW: &[u8] = b"-ERR backend cursor too large to encode shard\r\n";
+
+// 解析cursor，不是数字或者超出usize时返回None
+#[inline]
+fn cursor_num(data: &RingSlice, r: impl ds::Range) -> Option<usize> {
+    let (start, end) = r.range(data);
+    if start >= end {
+        return None;
+    }
+    (start..end).try_fold(0usize, |num, i| {
+        let d = data[i].is_ascii_digit().then(|| (data[i] - b'0') as usize)?;
+        num.checked_mul(10)?.checked_add(d)
+    })
+}
+
+// 需要遍历的分片数量。分片索引编码在flag及scan cursor中，超过上限时拒绝请求，不能只遍历部分分片
+#[inline]
+fn traverse_shards<P: RequestProcessor>(process: &P) -> Result<usize> {
+    match process.shards() {
+        shards if shards > MAX_SHARDS => Err(RedisError::ReqTooManyShards.into()),
+        shards => Ok(shards.max(1)),
+    }
+}
+
+// 返回给client的scan cursor中编码了分片索引：client cursor = 分片内的cursor << SHARD_BITS | 分片索引。
+// 把请求中的cursor替换为分片内的cursor，并指定请求发送的分片；cursor不合法时，请求不转发，直接返回异常。
+fn scan_request(
+    cmd: MemGuard,
+    cursor: Option<usize>,
+    shards: usize,
+    mut flag: Flag,
+    hash: i64,
+) -> HashedCommand {
+    let Some(cursor) = cursor.filter(|c| (c & MAX_SHARDS) < shards) else {
+        flag.set_noforward(true);
+        return HashedCommand::new(cmd, hash, flag);
+    };
+    let shard = cursor & MAX_SHARDS;
+    flag.set_shard(shard);
+    flag.set_key_count(shards as u16);
+
+    // *N\r\n$4\r\nscan\r\n$len\r\ncursor\r\n...，cursor在第5行
+    let start = cmd.skip_lf_cr(0, 3).expect("scan cursor");
+    let end = cmd.skip_lf_cr(start, 2).expect("scan cursor");
+    let backend = (cursor >> SHARD_BITS).to_string();
+    let mut data = Vec::with_capacity(cmd.len());
+    cmd.copy_to_vec_r(&mut data, 0..start);
+    data.push(b'$');
+    data.extend_from_slice(backend.len().to_string().as_bytes());
+    data.extend_from_slice(b"\r\n");
+    data.extend_from_slice(backend.as_bytes());
+    data.extend_from_slice(b"\r\n");
+    cmd.copy_to_vec_r(&mut data, end..);
+    HashedCommand::new(MemGuard::from_vec(data), hash, flag)
+}
+
+// 把scan响应中分片内的cursor替换为client的cursor：当前分片遍历完毕后，从下一个分片的0开始遍历，
+// 所有分片都遍历完毕后返回0
+fn write_scan_response<W: Writer>(req: &HashedCommand, rsp: &Command, w: &mut W) -> Result<()> {
+    // *2\r\n$len\r\ncursor\r\n*n\r\n...，cursor在第3行
+    let cursor = match rsp[0] {
+        b'*' => rsp.skip_lf_cr(0, 2).and_then(|start| {
+            let end = rsp.find(start, b'\r')?;
+            Some((cursor_num(rsp, start..end), end + CRLF_LEN))
+        }),
+        _ => None,
+    };
+    let (Some((cursor, oft)), Some(shard)) = (cursor, req.shard()) else {
+        return w.write_slice(rsp, 0);
+    };
+    // 分片内的cursor超出usize，或者左移后溢出，无法编码分片索引
+    let next = match cursor {
+        Some(0) if shard + 1 < req.key_count() as usize => shard + 1,
+        Some(0) => 0,
+        Some(c) if c.leading_zeros() >= SHARD_BITS as u32 => c << SHARD_BITS | shard,
+        _ => return w.write(CURSOR_OVERFLOW),
+    };
+    let next = next.to_string();
+    w.write(b"*2\r\n$")?;
+    w.write_str_num(next.len())?;
+    w.write(b"\r\n")?;
+    w.write(next.as_bytes())?;
+    w.write(b"\r\n")?;
+    w.write_slice(rsp, oft)
+}
+
+// 集合运算各分片返回的成员总数超过上限
+const TOO_MANY_MEMBERS: &[u8] = b"-ERR too many members for cross-shard set operation\r\n";
+
+// 按各分片smembers响应头中的成员数量 *N、~N 累加，超过上限时不需要再解析成员及等待其他分片
+fn members_exceeded<'a>(req: &HashedCommand, responses: impl Iterator<Item = &'a Command>) -> bool {
+    let mut total = 0;
+    for rsp in responses.filter(|rsp| matches!(rsp[0], b'*' | b'~')) {
+        let num = rsp.find(0, b'\r').and_then(|idx| rsp.try_str_num(1..idx));
+        total += num.unwrap_or(0);
+        if total > req.max_members() {
+            return true;
+        }
+    }
+    false
+}
+
+// 解析smembers响应中的成员：*num\r\n$len\r\nmember\r\n...，oft指向第一个成员
+fn members(rsp: &Command, num: usize, mut oft: usize) -> Option<Vec<Vec<u8>>> {
+    let mut members = Vec::with_capacity(num);
+    for _ in 0..num {
+        if oft >= rsp.len() || rsp[oft] != b'$' {
+            return None;
+        }
+        let end = rsp.find(oft, b'\r')?;
+        let len = rsp.try_str_num(oft + 1..end)?;
+        let start = end + CRLF_LEN;
+        let mut m = Vec::with_capacity(len);
+        rsp.copy_to_vec_r(&mut m, start..start + len);
+        members.push(m);
+        oft = start + len + CRLF_LEN;
+    }
+    Some(members)
+}
+
+// 按第一个集合中成员的顺序计算交集、差集；并集按成员出现的顺序
+fn set_op(op: CommandType, sets: Vec<Vec<Vec<u8>>>) -> Vec<Vec<u8>> {
+    use std::collections::HashSet;
+    let mut sets = sets.into_iter();
+    let first = sets.next().unwrap_or_default();
+    match op {
+        CommandType::SUnion => {
+            let mut seen = HashSet::new();
+            let mut union = Vec::new();
+            for m in first.into_iter().chain(sets.flatten()) {
+                if !seen.contains(&m) {
+                    seen.insert(m.clone());
+                    union.push(m);
+                }
+            }
+            union
+        }
+        _ => {
+            let others: Vec<HashSet<Vec<u8>>> = sets.map(|s| s.into_iter().collect()).collect();
+            let inter = op == CommandType::SInter;
+            first
+                .into_iter()
+                .filter(|m| match inter {
+                    true => others.iter().all(|s| s.contains(m)),
+                    false => !others.iter().any(|s| s.contains(m)),
+                })
+                .collect()
+        }
+    }
+}
+
 // tests only
 pub use packet::RequestContext;
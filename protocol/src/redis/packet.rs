@@ -1,9 +1,9 @@
 use super::{
-    command::{CommandHasher, CommandProperties, CommandType},
+    command::{CommandHasher, CommandProperties, CommandType, Commands},
     error::RedisError,
 };
 use crate::{
-    Flag, Result, StreamContext,
+    Acl, Flag, Result, StreamContext,
     error::Error,
     redis::{HandShakeStatus, command},
 };
@@ -18,15 +18,24 @@ pub(crate) const CRLF_LEN: usize = b"\r\n".len();
 pub struct RequestContext {
     pub bulk: u16,
     pub op_code: u16,
-    pub first: bool,      // 在multi-get请求中是否是第一个请求。
-    pub layer: u8,        // 请求的层次，目前只支持：master，all
-    pub sendto_all: bool, //发送到所有shard
-    //手动option，屏蔽需要对option repr的了解
-    pub is_reserved_hash: bool,
+    pub first: bool, // 在multi-get请求中是否是第一个请求。
+    pub layer: u8,   // 请求的层次，目前只支持：master，all
+    // 低位：发送到所有shard；次低位：是否通过hashkey等指定了hash，手动option，屏蔽需要对option repr的了解
+    pub reserved: u8,
+    // 连接级别的状态，整个连接有效，不随请求重置：
+    // [0..2): client通过hello协商的协议版本，0、2都是RESP2；[2]: 是否已认证；[3..6): 认证用户的Acl
+    pub conn: u8,
     //16
     pub reserved_hash: i64,
 }
 
+const SENDTO_ALL: u8 = 1;
+const RESERVED_HASH: u8 = 1 << 1;
+
+const RESP_MASK: u8 = 0b11;
+const AUTHED: u8 = 1 << 2;
+const ACL_SHIFT: u8 = 3;
+
 impl From<&mut StreamContext> for RequestContext {
     fn from(value: &mut StreamContext) -> Self {
         unsafe { std::mem::transmute(*value) }
@@ -140,14 +149,15 @@ impl<'a, S: crate::Stream> RequestPacket<'a, S> {
             if let Some(first_r) = self.data.find(self.oft, b'\r') {
                 debug_assert_eq!(self.data[self.oft], b'$', "{:?}", self);
                 // 路过CRLF_LEN个字节，通过命令获取op_code
-                let (op_code, idx) = CommandHasher::hash_slice(&*self.data, first_r + CRLF_LEN)?;
+                let (op_code, idx) = CommandHasher::<{ Commands::MAPPING_RANGE }>::hash_slice(&self.data, first_r + CRLF_LEN)?;
                 assert!(idx + CRLF_LEN <= self.data.len());
                 self.ctx.op_code = op_code;
                 // 第一次解析cmd需要对协议进行合法性校验
                 let cfg = command::get_cfg(op_code)?;
                 cfg.validate(self.bulk() as usize)?;
 
-                if cfg.need_reserved_hash && !(self.sendto_all() || self.ctx.is_reserved_hash) {
+                // 集合运算是否需要指定hash，取决于是否开启了mesh内合并，在拆分时校验
+                if cfg.need_reserved_hash && !cfg.set_op() && !self.hash_reserved() {
                     return Err(RedisError::ReqInvalid.into());
                 }
                 // check 命令长度
@@ -203,19 +213,26 @@ impl<'a, S: crate::Stream> RequestPacket<'a, S> {
     // 重置context，包括stream中的context
     #[inline]
     fn reset_context(&mut self) {
-        // 重置packet的ctx
-        self.ctx = Default::default();
+        // 重置packet的ctx，连接级别的状态需要保留
+        self.ctx = RequestContext {
+            conn: self.ctx.conn,
+            ..Default::default()
+        };
 
         // 重置stream的ctx
-        *self.stream.context() = Default::default();
+        *self.stream.context() = self.ctx.into();
     }
 
     // // 重置reserved hash，包括stream中的对应值
     #[inline]
     fn set_reserved_hash(&mut self, hash: i64) {
-        self.ctx.is_reserved_hash = true;
+        self.ctx.reserved |= RESERVED_HASH;
         self.ctx.reserved_hash = hash;
     }
+    #[inline]
+    fn is_reserved_hash(&self) -> bool {
+        self.ctx.reserved & RESERVED_HASH != 0
+    }
     // 更新reserved hash
     // #[inline]
     // pub(super) fn update_reserved_hash(&mut self, reserved_hash: i64) {
@@ -228,14 +245,41 @@ impl<'a, S: crate::Stream> RequestPacket<'a, S> {
     //     self.reserved_hash
     // }
 
+    // 是否通过hashkey、sendtoall等指定了请求发送的分片
+    #[inline]
+    pub(super) fn hash_reserved(&self) -> bool {
+        self.sendto_all() || self.is_reserved_hash()
+    }
+
     #[inline]
     pub(super) fn sendto_all(&self) -> bool {
-        self.ctx.sendto_all
+        self.ctx.reserved & SENDTO_ALL != 0
     }
 
     #[inline]
     pub(super) fn set_sendto_all(&mut self) {
-        self.ctx.sendto_all = true;
+        self.ctx.reserved |= SENDTO_ALL;
+    }
+
+    // hello 指定的协议版本，只支持2、3
+    #[inline]
+    pub(super) fn set_resp(&mut self, resp: u8) {
+        debug_assert!(resp == 2 || resp == 3, "resp:{}", resp);
+        self.ctx.conn = (self.ctx.conn & !RESP_MASK) | resp;
+    }
+    #[inline]
+    pub(super) fn resp3(&self) -> bool {
+        self.ctx.conn & RESP_MASK == 3
+    }
+    // 认证通过后，记录用户允许执行的指令类型
+    #[inline]
+    pub(super) fn set_acl(&mut self, acl: Acl) {
+        self.ctx.conn = (self.ctx.conn & RESP_MASK) | AUTHED | acl.bits() << ACL_SHIFT;
+    }
+    // 未认证时返回None
+    #[inline]
+    pub(super) fn acl(&self) -> Option<Acl> {
+        (self.ctx.conn & AUTHED != 0).then(|| Acl::from_bits(self.ctx.conn >> ACL_SHIFT))
     }
 
     #[inline]
@@ -243,7 +287,7 @@ impl<'a, S: crate::Stream> RequestPacket<'a, S> {
         if cfg.effect_on_next_req {
             let master_only = self.master_only();
             let sendto_all = self.sendto_all();
-            let is_reserved_hash = self.ctx.is_reserved_hash;
+            let is_reserved_hash = self.is_reserved_hash();
             let reserved_hash = self.ctx.reserved_hash;
             // 重置context、reserved-hash
             self.reset_context();
@@ -273,6 +317,9 @@ impl<'a, S: crate::Stream> RequestPacket<'a, S> {
         if self.sendto_all() {
             flag.set_sendto_all();
         }
+        if self.resp3() {
+            flag.set_resp3();
+        }
         flag
     }
 
@@ -282,7 +329,7 @@ impl<'a, S: crate::Stream> RequestPacket<'a, S> {
         if cfg.has_key {
             key = self.parse_key()?;
         }
-        let hash = if self.ctx.is_reserved_hash {
+        let hash = if self.is_reserved_hash() {
             self.ctx.reserved_hash
         } else {
             calculate_hash(alg, &key)
@@ -343,8 +390,9 @@ impl<'a, S: crate::Stream> RequestPacket<'a, S> {
         if sendto_all {
             self.set_sendto_all()
         }
-        self.ctx.is_reserved_hash = is_reserved_hash;
-        self.ctx.reserved_hash = reserved_hash;
+        if is_reserved_hash {
+            self.set_reserved_hash(reserved_hash);
+        }
         *self.stream.context() = self.ctx.into();
 
         // 设置packet的ctx到stream的ctx中，供下一个指令使用
@@ -419,12 +467,12 @@ impl std::ops::Deref for Packet {
 
 //整体解析原则，解析方保证解析完\r\n, oft移到\n+1, 即作为参数传入的oft不保证未溢出
 impl Packet {
-    // 调用方确保oft元素为'*'
+    // 调用方确保oft元素为'*'，或者RESP3的聚合类型'%' '~' '>' '|'
     // *num\r\n
     // oft移动到\r\n之后。 调用完该方法后，可能出现oft >= self.len()的情况
     #[inline]
     pub fn num_of_bulks(&self, oft: &mut usize) -> crate::Result<usize> {
-        debug_assert!(*oft < self.len() && self[*oft] == b'*');
+        debug_assert!(*oft < self.len() && b"*%~>|".contains(&self[*oft]));
         let mut n = 0;
         for i in *oft + 1..self.len() - 1 {
             if self[i] == b'\r' {
@@ -446,10 +494,10 @@ impl Packet {
         self.skip_string_inner(oft)?;
         Ok(())
     }
-    ///oft 需要是有效的，返回string的oft
+    ///oft 需要是有效的，返回string的oft。RESP3的verbatim string '='、blob error '!' 格式与'$'相同
     #[inline]
     fn skip_string_inner(&self, oft: &mut usize) -> Result<usize> {
-        debug_assert!(self[*oft] == b'$');
+        debug_assert!(matches!(self[*oft], b'$' | b'=' | b'!'));
         match self[*oft + 1] {
             b'-' => {
                 debug_assert!(self[*oft + 2] == b'1' && self[*oft + 3] == b'\r');
@@ -656,13 +704,22 @@ impl Packet {
     #[inline]
     pub fn skip_multibulks_inner(&self, oft: &mut usize, bulks: &mut u32) -> Result<()> {
         while *bulks > 0 {
-            self.check_onetoken(*oft)?;
+            // RESP3的null只有3个字节：_\r\n，其他类型至少4个字节
+            if *oft + 3 > self.len() || self.at(*oft) != b'_' {
+                self.check_onetoken(*oft)?;
+            }
             // 下面每种情况都确保了不会越界
             match self.at(*oft) {
-                b'*' => *bulks = *bulks + self.num_of_bulks(oft)? as u32,
+                // array，以及RESP3的set、push
+                b'*' | b'~' | b'>' => *bulks = *bulks + self.num_of_bulks(oft)? as u32,
+                // RESP3的map，每个元素是一对key、value
+                b'%' => *bulks += 2 * self.num_of_bulks(oft)? as u32,
+                // RESP3的attribute，附加在下一个元素之前，本身不算一个元素
+                b'|' => *bulks += 2 * self.num_of_bulks(oft)? as u32 + 1,
                 // 能完整解析才跳过当前字符串：num个字节 + "\r\n" 2个字节
-                b'$' => self.skip_string_inner(oft).map(|_| {})?,
-                b'+' | b':' => self.line(oft)?,
+                b'$' | b'=' | b'!' => self.skip_string_inner(oft).map(|_| {})?,
+                // 以及RESP3的null、double、boolean、big number
+                b'+' | b':' | b'-' | b'_' | b',' | b'#' | b'(' => self.line(oft)?,
                 _ => panic!("unsupport rsp:{:?}, pos: {}/{:?}", self, oft, bulks),
             }
             assert!(*bulks > 0);
@@ -0,0 +1,119 @@
+// 后端连接协商了RESP3后，响应中会出现RESP3特有的类型，未通过hello协商RESP3的client无法解析，
+// 需要转换为RESP2：
+//   map '%' => 2n个元素的array；set '~'、push '>' => array；null '_' => $-1；
+//   boolean '#' => 整数1、0；double ','、big number '(' => bulk string；
+//   verbatim string '=' => 去掉格式前缀'txt:'的bulk string；blob error '!' => 简单异常；
+//   attribute '|' => 直接丢弃。
+// 另外zrange等带withscores时，RESP3返回[member, score]组成的数组，RESP2是平铺的数组。
+use super::packet::{CRLF_LEN, Packet};
+use crate::{Result, Writer};
+use ds::RingSlice;
+
+// verbatim string的格式前缀，如 'txt:'、'mkd:'
+const VERBATIM_FMT_LEN: usize = 4;
+
+// 响应中是否包含RESP3特有的类型
+#[inline]
+pub(super) fn is_resp3(rsp: &RingSlice) -> bool {
+    match rsp[0] {
+        b'$' | b'+' | b'-' | b':' => false,
+        b'*' => {
+            let mut oft = 0;
+            while let Some((_, next)) = token(rsp, oft) {
+                if !matches!(rsp[oft], b'*' | b'$' | b'+' | b'-' | b':') {
+                    return true;
+                }
+                oft = next;
+            }
+            false
+        }
+        _ => true,
+    }
+}
+
+// 按token逐个转换，rsp是一个完整的响应。pairs为true时，把[member, score]组成的数组平铺
+pub(super) fn write_resp2<W: Writer>(rsp: &RingSlice, pairs: bool, w: &mut W) -> Result<()> {
+    let mut oft = 0;
+    let mut flatten = false;
+    if let (true, b'*', Some((end, next))) = (pairs, rsp[0], token(rsp, 0))
+        && next < rsp.len()
+        && rsp[next] == b'*'
+    {
+        let n = rsp.try_str_num(1..end).unwrap_or(0);
+        w.write_u8(b'*')?;
+        w.write_str_num(2 * n)?;
+        w.write(b"\r\n")?;
+        flatten = true;
+        oft = next;
+    }
+    while let Some((end, next)) = token(rsp, oft) {
+        match rsp[oft] {
+            b'*' if flatten => {}
+            b'%' => {
+                let n = rsp.try_str_num(oft + 1..end).unwrap_or(0);
+                w.write_u8(b'*')?;
+                w.write_str_num(2 * n)?;
+                w.write(b"\r\n")?;
+            }
+            b'~' | b'>' => {
+                w.write_u8(b'*')?;
+                rsp.copy_to(oft + 1..next, w)?;
+            }
+            b'_' => w.write(b"$-1\r\n")?,
+            b'#' => match rsp[oft + 1] {
+                b't' => w.write(b":1\r\n")?,
+                _ => w.write(b":0\r\n")?,
+            },
+            b',' | b'(' => {
+                w.write_u8(b'$')?;
+                w.write_str_num(end - oft - 1)?;
+                w.write(b"\r\n")?;
+                rsp.copy_to(oft + 1..next, w)?;
+            }
+            // =len\r\ntxt:data\r\n
+            b'=' => {
+                let len = rsp.try_str_num(oft + 1..end).unwrap_or(0);
+                let start = (end + CRLF_LEN + VERBATIM_FMT_LEN).min(next - CRLF_LEN);
+                w.write_u8(b'$')?;
+                w.write_str_num(len.saturating_sub(VERBATIM_FMT_LEN))?;
+                w.write(b"\r\n")?;
+                rsp.copy_to(start..next, w)?;
+            }
+            // !len\r\nERR msg\r\n
+            b'!' => {
+                w.write_u8(b'-')?;
+                rsp.copy_to(end + CRLF_LEN..next, w)?;
+            }
+            // |n\r\n后面是n对key、value，跳过后继续处理被附加属性的元素
+            b'|' => {
+                let data: Packet = (*rsp).into();
+                let mut bulks = 2 * rsp.try_str_num(oft + 1..end).unwrap_or(0) as u32;
+                let mut skip = end + CRLF_LEN;
+                data.skip_multibulks_inner(&mut skip, &mut bulks)?;
+                oft = skip;
+                continue;
+            }
+            _ => rsp.copy_to(oft..next, w)?,
+        }
+        oft = next;
+    }
+    Ok(())
+}
+
+// 返回当前token第一行'\r'的位置，以及下一个token的位置
+#[inline]
+fn token(rsp: &RingSlice, oft: usize) -> Option<(usize, usize)> {
+    if oft >= rsp.len() {
+        return None;
+    }
+    let end = rsp.find(oft, b'\r')?;
+    let next = match rsp[oft] {
+        b'$' | b'=' | b'!' => match rsp.try_str_num(oft + 1..end) {
+            Some(len) => end + CRLF_LEN + len + CRLF_LEN,
+            // $-1\r\n
+            None => end + CRLF_LEN,
+        },
+        _ => end + CRLF_LEN,
+    };
+    Some((end, next.min(rsp.len())))
+}
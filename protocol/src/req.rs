@@ -67,4 +67,17 @@ pub trait Request:
     fn retry_on_rsp_notok(&mut self, retry: bool);
     // 初始化quota
     fn quota(&mut self, quota: BackendQuota);
+    // 本次访问的副本，请求完成时更新副本的异常检测统计
+    fn outlier(&mut self, outlier: crate::outlier::Outlier);
+    // 允许对冲：请求超时前，由pipeline向下一个副本发送相同的请求
+    fn hedge(&mut self);
+    // 重试时上一次的响应，用于按响应进行重定向，如redis cluster的MOVED、ASK
+    fn last_response(&self) -> Option<&Command>;
+    // 收到MOVED、ASK响应时，由topo重定向
+    fn redirect(&mut self, redirect: bool);
+    // 请求之前附加了指令（如ASKING），这些指令的响应直接丢弃
+    fn prefix(&mut self, num: u8);
+    fn prefix_responses(&self) -> usize;
+    // 发起请求的client，同一个client的请求发送到后端的同一个连接，保证顺序
+    fn client(&self) -> usize;
 }
@@ -57,6 +57,34 @@ impl crate::Request for Request {
     fn quota(&mut self, quota: BackendQuota) {
         self.ctx().quota(quota);
     }
+    #[inline]
+    fn outlier(&mut self, outlier: crate::outlier::Outlier) {
+        self.ctx().outlier(outlier);
+    }
+    #[inline]
+    fn hedge(&mut self) {
+        self.ctx().set_hedge();
+    }
+    #[inline]
+    fn last_response(&self) -> Option<&Command> {
+        self.ctx().last_response()
+    }
+    #[inline]
+    fn redirect(&mut self, redirect: bool) {
+        self.ctx().redirect = redirect;
+    }
+    #[inline]
+    fn prefix(&mut self, num: u8) {
+        self.ctx().prefix = num;
+    }
+    #[inline]
+    fn prefix_responses(&self) -> usize {
+        self.ctx().prefix as usize
+    }
+    #[inline]
+    fn client(&self) -> usize {
+        self.ctx().client()
+    }
 }
 impl Request {
     #[inline]
@@ -65,7 +65,7 @@ pub(super) struct Commands {
 }
 impl Commands {
     /// 指令不多，2048应该够了
-    const MAPPING_RANGE: usize = 2048;
+    pub(super) const MAPPING_RANGE: usize = 2048;
     fn new() -> Self {
         Self {
             supported: array_init::array_init(|_| Default::default()),
@@ -97,7 +97,7 @@ impl Commands {
 
     #[inline]
     fn add_support(&mut self, mut c: CommandProperties) {
-        let idx = CommandHasher::hash_bytes(c.name.as_bytes()) as usize;
+        let idx = CommandHasher::<{ Self::MAPPING_RANGE }>::hash_bytes(c.name.as_bytes()) as usize;
         assert!(idx > 0 && idx < self.supported.len(), "idx:{}", idx);
         // 之前没有添加过。
         assert!(!self.supported[idx].supported);
@@ -54,6 +54,7 @@ impl Protocol for Vector {
     fn config(&self) -> crate::Config {
         crate::Config {
             need_auth: true,
+            tls_in_handshake: true,
             ..Default::default()
         }
     }
@@ -188,6 +189,7 @@ impl Vector {
             HandShakeStatus::Init => {
                 let mut client =
                     Client::from_user_pwd(option.username.clone(), option.token.clone());
+                client.ssl = option.tls.is_some();
                 packet.proc_handshake(&mut client)?;
                 packet.ctx().status = HandShakeStatus::InitialhHandshakeResponse;
                 Ok(HandShake::Continue)
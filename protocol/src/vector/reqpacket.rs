@@ -138,7 +138,7 @@ impl<'a, S: crate::Stream> RequestPacket<'a, S> {
         if let Some(first_r) = self.data.find(self.oft, b'\r') {
             debug_assert_eq!(self.data[self.oft], b'$', "{:?}", self);
             // 路过CRLF_LEN个字节，通过命令获取op_code
-            let (op_code, idx) = CommandHasher::hash_slice(&*self.data, first_r + CRLF_LEN)?;
+            let (op_code, idx) = CommandHasher::<{ command::Commands::MAPPING_RANGE }>::hash_slice(&self.data, first_r + CRLF_LEN)?;
             if idx + CRLF_LEN > self.data.len() {
                 return Err(crate::Error::ProtocolIncomplete(0));
             }
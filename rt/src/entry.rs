@@ -73,7 +73,10 @@ impl<T: TimeoutCheck + Sized + Unpin, F: Future<Output = Result<()>> + Unpin + R
         // 只要当前poll进入pending，就会触发持续refresh
         while ret.is_pending() {
             ready!(self.refresh_tick.poll_tick(cx));
-            let _ = self.inner.refresh()?;
+            if !self.inner.refresh()? {
+                // 不再需要运行，如服务已下线，直接进入close流程
+                return Poll::Ready(Ok(()));
+            }
         }
         ret.map(|r| Ok(r))
     }
@@ -9,3 +9,6 @@ pub use stream::*;
 
 mod timeout;
 pub use timeout::*;
+
+mod shutdown;
+pub use shutdown::*;
@@ -0,0 +1,110 @@
+// 优雅退出：收到SIGTERM、SIGINT后进入退出流程，停止侦听新的连接，
+// 已建立的连接处理完pending中的请求后关闭，所有连接关闭或者超过drain的截止时间后退出进程。
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::*};
+use std::task::{Context, Poll};
+
+use tokio::sync::{Notify, futures::Notified};
+
+// 进程的退出状态，由信号触发
+static GLOBAL: Shutdown = Shutdown::new();
+
+// 退出状态及尚未关闭的连接数。进程内使用GLOBAL，测试可以使用独立的实例
+pub struct Shutdown {
+    flag: AtomicBool,
+    notify: Notify,
+    conns: AtomicUsize,
+}
+
+impl Shutdown {
+    pub const fn new() -> Self {
+        Self {
+            flag: AtomicBool::new(false),
+            notify: Notify::const_new(),
+            conns: AtomicUsize::new(0),
+        }
+    }
+    // 进入退出流程，唤醒所有等待退出的任务。可重复调用
+    pub fn shutdown(&self) {
+        if !self.flag.swap(true, AcqRel) {
+            self.notify.notify_waiters();
+        }
+    }
+    #[inline]
+    pub fn shutting_down(&self) -> bool {
+        self.flag.load(Acquire)
+    }
+    // 等待进入退出流程
+    pub async fn wait(&self) {
+        // 先创建notified再检查状态，避免错过两者之间的notify
+        let notified = self.notify.notified();
+        if self.shutting_down() {
+            return;
+        }
+        notified.await
+    }
+    #[inline]
+    pub fn conns(&self) -> usize {
+        self.conns.load(Acquire)
+    }
+    #[inline]
+    pub fn drain(&'static self) -> Drain {
+        self.conns.fetch_add(1, AcqRel);
+        Drain {
+            shutdown: self,
+            notified: Box::pin(self.notify.notified()),
+        }
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[inline]
+pub fn shutdown() {
+    GLOBAL.shutdown()
+}
+#[inline]
+pub fn shutting_down() -> bool {
+    GLOBAL.shutting_down()
+}
+pub async fn wait_shutdown() {
+    GLOBAL.wait().await
+}
+#[inline]
+pub fn conns() -> usize {
+    GLOBAL.conns()
+}
+
+// 每个连接持有一个Drain，drop时连接数减1。
+// 在连接的poll中注册，进入退出流程时唤醒空闲的连接。
+pub struct Drain {
+    shutdown: &'static Shutdown,
+    notified: Pin<Box<Notified<'static>>>,
+}
+impl Default for Drain {
+    #[inline]
+    fn default() -> Self {
+        GLOBAL.drain()
+    }
+}
+impl Drain {
+    // Ready: 已进入退出流程
+    #[inline]
+    pub fn poll_shutdown(&mut self, cx: &mut Context) -> Poll<()> {
+        if self.shutdown.shutting_down() {
+            return Poll::Ready(());
+        }
+        self.notified.as_mut().poll(cx)
+    }
+}
+impl Drop for Drain {
+    #[inline]
+    fn drop(&mut self) {
+        self.shutdown.conns.fetch_sub(1, AcqRel);
+    }
+}
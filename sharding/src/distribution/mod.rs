@@ -58,12 +58,24 @@ const DIST_RANGE_SLOT_COUNT_DEFAULT: u64 = 256;
 use std::ops::Deref;
 impl Distribute {
     pub fn from<T: Deref<Target = str>>(distribution: &str, names: &[T]) -> Self {
+        Self::parse(distribution, names).unwrap_or_else(|default| {
+            log::warn!("'{}' is not valid , use modula instead", distribution);
+            println!("'{}' is not valid , use modula instead!", distribution);
+            default
+        })
+    }
+    // 不识别的distribution返回None，用于配置校验
+    pub fn try_from<T: Deref<Target = str>>(distribution: &str, names: &[T]) -> Option<Self> {
+        Self::parse(distribution, names).ok()
+    }
+    // 不识别的distribution返回Err，Err中为兼容老逻辑采用的modula
+    fn parse<T: Deref<Target = str>>(distribution: &str, names: &[T]) -> Result<Self, Self> {
         let dist = distribution.to_ascii_lowercase();
         let idx = dist.find('-');
         let name = &dist[..idx.unwrap_or(dist.len())];
         let num = idx.map(|i| dist[i + 1..].parse::<u64>().ok()).flatten();
 
-        match name {
+        Ok(match name {
             //DIST_PADDING => Self::Padding(Default::default()),
             "modula" => Self::Modula(Modula::from(names.len(), false)),
             "absmodula" => Self::Modula(Modula::from(names.len(), true)),
@@ -74,12 +86,8 @@ impl Distribute {
             "splitmod" => Self::SplitMod(SplitMod::from(num, names.len())),
             "slotmod" => Self::SlotMod(SlotMod::from(num, names.len())),
             "secmod" => Self::SecMod(SecMod::from(names.len())),
-            _ => {
-                log::warn!("'{}' is not valid , use modula instead", distribution);
-                println!("'{}' is not valid , use modula instead!", distribution);
-                Self::Modula(Modula::from(names.len(), false))
-            }
-        }
+            _ => return Err(Self::Modula(Modula::from(names.len(), false))),
+        })
     }
     // 适配mysql 动态shands
     // pub fn from_num(distribution: &str, num: usize) -> Self {
@@ -1,6 +1,7 @@
 pub mod bkdr;
 pub mod bkdrabscrc32;
 pub mod bkdrsub;
+pub mod crc16;
 pub mod crc32;
 pub mod crc32local;
 pub mod crc64;
@@ -14,6 +15,7 @@ pub mod rawsuffix;
 
 pub use bkdr::Bkdr;
 pub use bkdrabscrc32::BkdrAbsCrc32;
+pub use crc16::Crc16;
 pub use crc32::*;
 pub use crc32local::*;
 pub use lbcrc32local::LBCrc32localDelimiter;
@@ -85,6 +87,7 @@ pub enum Hasher {
     Crc32Abs(Crc32Abs), // crc32abs: 基于i32转换，然后直接取abs；其他走i64提升为正数
     Crc32AbsDelimiter(Crc32AbsDelimiter), // crc32abs: 基于i32转换，然后直接取abs，同时支持分隔符，格式：$start+$hashkey+$delimiter$
     Crc64(Crc64),       // Crc64 算法，对整个key做crc64计算
+    Crc16(Crc16),       // redis cluster的slot算法，支持hash tag，hash结果即slot
     Fnv1aF64(Fnv1aF64),
     Random(RandomHash), // random hash
     RawSuffix(RawSuffix),
@@ -113,12 +116,24 @@ impl Hasher {
         alg_lower
     }
     pub fn from(alg: &str) -> Self {
+        Self::parse(alg).unwrap_or_else(|default| {
+            log::error!("found unknown hash:{}, use {:?} instead", alg, default);
+            println!("found unknown hash:{}, use {:?} instead!", alg, default);
+            default
+        })
+    }
+    // 不识别的hash返回None，用于配置校验
+    pub fn try_from(alg: &str) -> Option<Self> {
+        Self::parse(alg).ok()
+    }
+    // 不识别的hash返回Err，Err中为兼容老逻辑采用的默认hash
+    fn parse(alg: &str) -> Result<Self, Self> {
         let alg_lower = Hasher::reconcreate_hash_name(alg);
         let alg_parts: Vec<&str> = alg_lower.split(HASHER_NAME_DELIMITER).collect();
 
         // 简单hash，即名字中没有"-"的hash，目前只有bkdr、raw、crc32
         if alg_parts.len() == 1 {
-            return match alg_parts[0] {
+            return Ok(match alg_parts[0] {
                 HASH_PADDING => Self::Padding(Default::default()),
                 "bkdr" => Self::Bkdr(Default::default()),
                 "bkdrsub" => Self::BkdrsubDelimiter(BkdrsubDelimiter::from('_' as u8)),
@@ -133,16 +148,13 @@ impl Hasher {
                 }
                 "crc32abs" => Self::Crc32Abs(Default::default()),
                 "crc64" => Self::Crc64(Default::default()),
+                "crc16" => Self::Crc16(Default::default()),
                 "random" => Self::Random(Default::default()),
                 "fnv1_32" => Self::Fnv1_32(Default::default()),
                 "fnv1a_64" => Self::Fnv1aF64(Default::default()),
-                _ => {
-                    // 默认采用mc的crc32-s hash
-                    log::error!("found unknown hash:{}, use crc32-short instead", alg);
-                    println!("found unknown hash:{}, use crc32-short instead!", alg);
-                    return Self::Crc32Short(Default::default());
-                }
-            };
+                // 默认采用mc的crc32-s hash
+                _ => return Err(Self::Crc32Short(Default::default())),
+            });
         }
 
         // 扩展hash，包括crc32扩展、crc32local扩展：
@@ -151,7 +163,7 @@ impl Hasher {
         //   如果业务有固定前缀，也可以支持，在hash name后加-xxx，xxx为前缀长度。
         // 2 crc32local 扩展hash，包括各种可扩展的分隔符，like： crc32-point, crc32-pound,crc32-underscore；
         debug_assert!(alg_parts.len() == 2 || alg_parts.len() == 3);
-        match alg_parts[0] {
+        Ok(match alg_parts[0] {
             "crc32" => match alg_parts[1] {
                 CRC32_EXT_SHORT => Self::Crc32Short(Default::default()),
                 CRC32_EXT_NUM => Self::Crc32Num(Crc32Num::from(alg_lower.as_str())),
@@ -165,11 +177,8 @@ impl Hasher {
                 _ => Self::Crc32localDelimiter(Crc32localDelimiter::from(alg_lower.as_str())),
             },
             "rawsuffix" => Self::RawSuffix(RawSuffix::from(alg_lower.as_str())),
-            _ => {
-                log::error!("found unknow hash: {} use crc32 instead", alg);
-                Self::Crc32(Default::default())
-            }
-        }
+            _ => return Err(Self::Crc32(Default::default())),
+        })
     }
 
     #[inline]
@@ -1,3 +1,4 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use ds::chan::mpsc::{channel, Sender, TrySendError};
@@ -5,6 +6,7 @@ use ds::chan::mpsc::{channel, Sender, TrySendError};
 use ds::Switcher;
 
 use crate::checker::BackendChecker;
+use crate::load::Load;
 use endpoint::{Endpoint, Timeout};
 use metrics::Path;
 use protocol::{Error, Protocol, Request, ResOption, Resource};
@@ -20,14 +22,31 @@ impl<R: Request, P: Protocol> From<(&str, P, Resource, &str, Timeout, ResOption)
             ResOption,
         ),
     ) -> Self {
-        let (tx, rx) = channel(256);
         let finish: Switcher = false.into();
         let init: Switcher = false.into();
-        let f = finish.clone();
         let path = Path::new(vec![rsrc.name(), service]);
-        let checker =
-            BackendChecker::from(addr, rx, f, init.clone(), parser, path, timeout, option);
-        rt::spawn(checker.start_check());
+        // 0与1相同，只建一个连接
+        let n = endpoint::validate::conns(option.conns);
+        let conns = (0..n)
+            .map(|idx| {
+                let (tx, rx) = channel(256);
+                let load: Arc<Load> = Default::default();
+                let checker = BackendChecker::from(
+                    addr,
+                    rx,
+                    finish.clone(),
+                    init.clone(),
+                    parser.clone(),
+                    path.clone(),
+                    timeout,
+                    option.clone(),
+                    (idx, n),
+                    load.clone(),
+                );
+                rt::spawn(checker.start_check());
+                Conn { tx, load }
+            })
+            .collect();
 
         let addr = addr.to_string();
         Backend {
@@ -35,7 +54,8 @@ impl<R: Request, P: Protocol> From<(&str, P, Resource, &str, Timeout, ResOption)
                 addr,
                 finish,
                 init,
-                tx,
+                conns,
+                cursor: AtomicUsize::new(0),
             }
             .into(),
         }
@@ -49,13 +69,51 @@ pub struct Backend<R> {
 
 pub struct BackendInner<R> {
     addr: String,
-    tx: Sender<R>,
+    // 每个连接由独立的checker维护，各自重连
+    conns: Vec<Conn<R>>,
+    // 固定的连接不可用时，选择连接的起始位置，轮询以打散pending相同的连接
+    cursor: AtomicUsize,
     // 实例销毁时，设置该值，通知checker，会议上check.
     finish: Switcher,
-    // 由checker设置，标识是否初始化完成。
+    // 由checker设置，标识是否初始化完成。任意一个连接初始化完成即可
     init: Switcher,
 }
 
+struct Conn<R> {
+    tx: Sender<R>,
+    // 未完成的请求数及耗时，由handler在请求完成时更新
+    load: Arc<Load>,
+}
+
+impl<R> BackendInner<R> {
+    // 同一个client的请求固定发送到同一个连接，避免pipeline中的请求在多个连接上乱序执行；
+    // 该连接不可用时，选择可用连接中inflight最少的一个；都不可用时返回起始位置的连接，由send返回错误
+    #[inline]
+    fn select(&self, client: usize) -> &Conn<R> {
+        let n = self.conns.len();
+        if n == 1 {
+            return &self.conns[0];
+        }
+        // client是waker的地址，低位是对齐的，先打散
+        let pinned = ((client as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32) as usize % n;
+        if self.conns[pinned].tx.get_enable() {
+            return &self.conns[pinned];
+        }
+        let start = self.cursor.fetch_add(1, Ordering::Relaxed) % n;
+        let mut idx = start;
+        let mut min = usize::MAX;
+        for i in 0..n {
+            let c = &self.conns[(start + i) % n];
+            let inflight = c.load.inflight();
+            if c.tx.get_enable() && inflight < min {
+                idx = (start + i) % n;
+                min = inflight;
+            }
+        }
+        &self.conns[idx]
+    }
+}
+
 impl<R> discovery::Inited for Backend<R> {
     // 已经连接上或者至少连接了一次
     #[inline]
@@ -74,7 +132,10 @@ impl<R: Request> Endpoint for Backend<R> {
     type Item = R;
     #[inline]
     fn send(&self, req: R) {
-        if let Err(e) = self.inner.tx.try_send(req) {
+        let conn = self.inner.select(req.client());
+        conn.load.incr();
+        if let Err(e) = conn.tx.try_send(req) {
+            conn.load.decr();
             match e {
                 TrySendError::Closed(r) => r.on_err(Error::ChanWriteClosed),
                 TrySendError::Full(r) => r.on_err(Error::ChanFull),
@@ -85,12 +146,21 @@ impl<R: Request> Endpoint for Backend<R> {
 
     #[inline]
     fn available(&self) -> bool {
-        self.inner.tx.get_enable()
+        self.inner.conns.iter().any(|c| c.tx.get_enable())
     }
     #[inline]
     fn addr(&self) -> &str {
         &self.inner.addr
     }
+    #[inline]
+    fn conns(&self) -> usize {
+        self.inner.conns.len()
+    }
+    // 多个连接时，client固定使用其中一个连接，代价取各连接的最小值
+    #[inline]
+    fn cost(&self) -> u64 {
+        self.inner.conns.iter().map(|c| c.load.cost()).min().unwrap_or(0)
+    }
     fn build_o<P: Protocol>(
         addr: &str,
         p: P,
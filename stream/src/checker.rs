@@ -2,14 +2,15 @@ use ds::time::{Duration, timeout};
 use rt::Cancel;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Poll, ready};
 
 use tokio::io::AsyncWrite;
-use tokio::net::TcpStream;
 
 use protocol::{Error, HandShake, Protocol, Request, ResOption, Result, Stream};
 
 use crate::handler::Handler;
+use crate::load::Load;
 use ds::Switcher;
 use ds::chan::mpsc::Receiver;
 use metrics::Path;
@@ -25,6 +26,9 @@ pub struct BackendChecker<P, Req> {
     timeout: endpoint::Timeout,
     path: Path,
     option: ResOption,
+    // 连接在backend中的序号及backend的连接总数
+    conn: (usize, usize),
+    load: Arc<Load>,
 }
 
 impl<P, Req> BackendChecker<P, Req> {
@@ -37,6 +41,8 @@ impl<P, Req> BackendChecker<P, Req> {
         path: Path,
         timeout: endpoint::Timeout,
         option: ResOption,
+        conn: (usize, usize),
+        load: Arc<Load>,
     ) -> Self {
         Self {
             addr: addr.to_string(),
@@ -47,6 +53,8 @@ impl<P, Req> BackendChecker<P, Req> {
             timeout,
             path,
             option,
+            conn,
+            load,
         }
     }
     pub(crate) async fn start_check(mut self)
@@ -59,10 +67,20 @@ impl<P, Req> BackendChecker<P, Req> {
         let mut be_conns = path_addr.qps("be_conn");
         let mut timeout = Path::base().qps("timeout");
         let mut m_timeout = path_addr.qps("timeout");
+        // 多个连接时，按连接统计重连及超时
+        let (idx, conns) = self.conn;
+        let mut conn_metrics = (conns > 1).then(|| {
+            let mut path = path_addr.clone();
+            path.push(format!("conn{}", idx));
+            (path.qps("be_conn"), path.qps("timeout"))
+        });
         let mut reconn = crate::reconn::ReconnPolicy::new();
         metrics::incr_task();
         while !self.finish.get() {
             be_conns += 1;
+            if let Some((be_conns, _)) = &mut conn_metrics {
+                *be_conns += 1;
+            }
             let stream = self.reconnect().await;
             if stream.is_none() {
                 // 连接失败，按策略sleep
@@ -77,7 +95,8 @@ impl<P, Req> BackendChecker<P, Req> {
             let mut stream = rt::Stream::from(stream.expect("not expected"));
             let rx = &mut self.rx;
 
-            if self.parser.config().need_auth && self.option.token.len() > 0 {
+            let need_auth = self.option.token.len() > 0 || self.option.resp3;
+            if self.parser.config().need_auth && need_auth {
                 let auth = Auth {
                     option: &mut self.option,
                     s: &mut stream,
@@ -102,7 +121,7 @@ impl<P, Req> BackendChecker<P, Req> {
             self.init.on();
             log::debug!("handler started:{:?} with: {}", self.path, self.addr);
             let p = self.parser.clone();
-            let handler = Handler::from(rx, stream, p, path_addr.clone());
+            let handler = Handler::from(rx, &self.load, stream, p, path_addr.clone());
             let handler = Entry::timeout(handler, Timeout::from(self.timeout.ms()));
             let ret = handler.await;
             log::info!(
@@ -117,6 +136,10 @@ impl<P, Req> BackendChecker<P, Req> {
                 Error::Timeout(_t) => {
                     m_timeout += 1;
                     timeout += 1;
+                    self.load.penalize(self.timeout.ms() as u64 * 1000);
+                    if let Some((_, timeout)) = &mut conn_metrics {
+                        *timeout += 1;
+                    }
                 }
                 Error::ChanReadClosed => {
                     debug_assert!(self.finish.get(), "channel closed but not finish");
@@ -134,21 +157,28 @@ impl<P, Req> BackendChecker<P, Req> {
         metrics::decr_task();
         log::info!("{:?} finished {}", path_addr, self.addr);
     }
-    async fn reconnect(&self) -> Option<TcpStream> {
-        timeout(Duration::from_secs(2), TcpStream::connect(&self.addr))
+    // 配置了TLS时，连接超时包含TLS握手的时间
+    async fn reconnect(&self) -> Option<net::Stream>
+    where
+        P: Protocol,
+    {
+        let tls = self.option.tls.as_ref();
+        let connect = async {
+            match self.parser.config().tls_in_handshake {
+                true => net::tls::connect_mysql(&self.addr, tls).await,
+                false => net::tls::connect(&self.addr, tls).await,
+            }
+        };
+        timeout(Duration::from_secs(2), connect)
             .await
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::TimedOut, e))
             .and_then(|x| x)
             .map_err(|_e| log::debug!("conn to {} err:{}", self.addr, _e))
             .ok()
-            .map(|s| {
-                let _ = s.set_nodelay(true);
-                s
-            })
     }
 }
 
-struct Auth<'a, P, S> {
+pub(crate) struct Auth<'a, P, S> {
     pub option: &'a mut ResOption,
     pub s: &'a mut S,
     pub parser: P,
@@ -17,6 +17,11 @@ impl CallbackContextPtr {
     pub fn build_request(&mut self) -> Request {
         Request::new(self.ptr)
     }
+    // 在释放前唯一标识一个请求
+    #[inline]
+    pub(crate) fn id(&self) -> usize {
+        self.ptr.as_ptr() as usize
+    }
     //需要在on_done时主动销毁self对象
     #[inline]
     pub(super) fn async_write_back<
@@ -0,0 +1,139 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+use tokio::io::AsyncWrite;
+
+use ds::MemGuard;
+use ds::time::{Duration, sleep, timeout};
+use protocol::{
+    Command, Error, HashedCommand, Protocol, Request as RequestTrait, ResOption, Result, Writer,
+};
+use tokio::time::{Instant, Sleep};
+
+use crate::{Request, checker::Auth};
+
+// 与共享的后端连接一致，建连超时包含TLS握手的时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+type Connect = Pin<Box<dyn Future<Output = std::io::Result<net::Stream>> + Send>>;
+
+// client独占的后端连接。订阅、事务等依赖连接状态的场景，不能复用分片共享的后端连接。
+// 连接在创建时异步建立，建连、认证完成前的数据先缓存下来。
+// 建连完成后，有请求等待响应时按后端的超时时间计时，超时后由调用方释放连接。
+pub(crate) struct Dedicated {
+    addr: String,
+    option: ResOption,
+    conn: Option<Connect>,
+    s: Option<rt::Stream<net::Stream>>,
+    authed: bool,
+    cached: Vec<u8>,
+    timeout: Duration,
+    // 开始计时时等待响应的请求数量，以及对应的timer
+    timer: Option<(usize, Pin<Box<Sleep>>)>,
+}
+
+impl Dedicated {
+    pub(crate) fn new(addr: String, option: ResOption, rsp_timeout: Duration) -> Self {
+        let to = addr.clone();
+        let tls = option.tls.clone();
+        let conn: Connect = Box::pin(async move {
+            timeout(CONNECT_TIMEOUT, net::tls::connect(&to, tls.as_ref()))
+                .await
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::TimedOut, e))
+                .and_then(|x| x)
+        });
+        Self {
+            addr,
+            option,
+            conn: Some(conn),
+            s: None,
+            authed: false,
+            cached: Vec::with_capacity(64),
+            timeout: rsp_timeout,
+            timer: None,
+        }
+    }
+    #[inline]
+    pub(crate) fn send(&mut self, req: &HashedCommand) {
+        req.copy_to_vec(&mut self.cached);
+    }
+    #[inline]
+    pub(crate) fn write(&mut self, data: &[u8]) {
+        self.cached.extend_from_slice(data);
+    }
+    // 建连、认证完成后，把缓存的数据发送到后端，返回可以读取响应的stream。
+    // waiting为等待响应的请求数量，超时未收到响应时返回Timeout
+    pub(crate) fn poll_ready<P: Protocol>(
+        &mut self,
+        cx: &mut Context,
+        parser: &P,
+        waiting: usize,
+    ) -> Poll<Result<&mut rt::Stream<net::Stream>>> {
+        if let Some(conn) = self.conn.as_mut() {
+            let s = ready!(conn.as_mut().poll(cx))?;
+            self.s = Some(rt::Stream::from(s));
+            self.conn = None;
+        }
+        self.poll_timeout(cx, waiting)?;
+        let s = self.s.as_mut().expect("connected");
+        if !self.authed {
+            let need_auth = !self.option.token.is_empty() || self.option.resp3;
+            if parser.config().need_auth && need_auth {
+                let mut auth = Auth {
+                    option: &mut self.option,
+                    s: &mut *s,
+                    parser: parser.clone(),
+                };
+                ready!(Pin::new(&mut auth).poll(cx))?;
+            }
+            self.authed = true;
+        }
+        if !self.cached.is_empty() {
+            s.write(&self.cached)?;
+            self.cached.clear();
+        }
+        ready!(Pin::new(&mut *s).poll_flush(cx))?;
+        Poll::Ready(Ok(s))
+    }
+    // 等待响应的请求数量变化（收到响应或发送了新的请求）时重新计时，没有等待的请求时不计时，
+    // 如订阅后等待推送的消息
+    fn poll_timeout(&mut self, cx: &mut Context, waiting: usize) -> Result<()> {
+        if waiting == 0 {
+            self.timer = None;
+            return Ok(());
+        }
+        let deadline = Instant::now() + self.timeout;
+        let (num, timer) = self
+            .timer
+            .get_or_insert_with(|| (waiting, Box::pin(sleep(self.timeout))));
+        if *num != waiting {
+            *num = waiting;
+            timer.as_mut().reset(deadline);
+        }
+        match timer.as_mut().poll(cx) {
+            Poll::Ready(()) => Err(Error::Timeout(self.timeout.as_millis() as u16)),
+            Poll::Pending => Ok(()),
+        }
+    }
+}
+
+// 本地构建响应
+#[inline]
+pub(crate) fn reply(req: Request, rsp: &[u8]) {
+    let ok = rsp[0] != b'-';
+    req.on_complete(Command::from(ok, MemGuard::from_vec(rsp.to_vec())));
+}
+
+impl std::fmt::Debug for Dedicated {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} authed:{} cached:{} timeout:{:?}",
+            self.addr,
+            self.authed,
+            self.cached.len(),
+            self.timeout
+        )
+    }
+}
@@ -12,9 +12,15 @@ use tokio::io::{AsyncRead, AsyncWrite};
 
 use metrics::{Metric, Path};
 
+use crate::load::Load;
+
 pub struct Handler<'r, Req, P, S> {
     data: &'r mut Receiver<Req>,
+    // 由backend在发送时增加未完成的请求数，请求完成（包括失败）时减少，同时更新耗时
+    load: &'r Load,
     pending: VecDeque<(Req, Instant)>,
+    // pending中第一个请求，已丢弃的前置指令响应数量
+    skipped: usize,
 
     s: S,
     parser: P,
@@ -60,14 +66,22 @@ where
     S: AsyncRead + AsyncWrite + Stream + Unpin,
     P: Protocol + Unpin,
 {
-    pub(crate) fn from(data: &'r mut Receiver<Req>, s: S, parser: P, path: Path) -> Self {
+    pub(crate) fn from(
+        data: &'r mut Receiver<Req>,
+        load: &'r Load,
+        s: S,
+        parser: P,
+        path: Path,
+    ) -> Self {
         data.enable();
         let name = path.clone();
         let rtt = path.rtt("req");
         let err = path.qps("be_err");
         Self {
             data,
+            load,
             pending: VecDeque::with_capacity(31),
+            skipped: 0,
             s,
             parser,
             rtt,
@@ -123,7 +137,10 @@ where
                 self.parser.on_sent(req.operation(), &mut self.host_metric);
                 match req.on_sent() {
                     Some(r) => self.pending.push_back((r, Instant::now())),
-                    None => self.num.rx(),
+                    None => {
+                        self.num.rx();
+                        self.done();
+                    }
                 }
             }
             // 发送完成后，清空buffer
@@ -143,16 +160,26 @@ where
                 if self.pending.len() == 0 {
                     panic!("unexpect response handler:{:?}", &self);
                 }
+                // 请求之前附加指令（如ASKING）的响应，直接丢弃
+                let (front, _) = self.pending.front().expect("front request");
+                if self.skipped < front.prefix_responses() {
+                    self.skipped += 1;
+                    continue;
+                }
+                self.skipped = 0;
                 let (req, start) = self.pending.pop_front().expect("take response");
                 self.num.rx();
                 // 统计请求耗时、异常响应
-                self.rtt += start.elapsed();
+                let elapsed = start.elapsed();
+                self.rtt += elapsed;
+                self.load.rtt(elapsed.as_micros() as u64);
                 if self.parser.metric_err(req.operation()) && !cmd.ok() {
                     self.err += 1;
                 }
 
                 self.parser.check(&*req, &cmd);
                 req.on_complete(cmd);
+                self.done();
                 continue;
             }
             if l == self.s.len() {
@@ -232,10 +259,12 @@ impl<'r, Req: Request, P: Protocol, S: AsyncRead + AsyncWrite + Unpin + Stream>
         // 有请求在队列中未发送。
         while let Poll::Ready(Some(req)) = self.data.poll_recv(&mut ctx) {
             req.on_err(Error::Pending);
+            self.done();
         }
         // 2. 有请求已经发送，但response未获取到
         while let Some((req, _)) = self.pending.pop_front() {
             req.on_err(Error::Waiting);
+            self.done();
         }
         // 3. cancel
         use rt::Cancel;
@@ -254,6 +283,14 @@ impl<'r, Req: Request, P: Protocol, S: AsyncRead + AsyncWrite + Unpin + Stream>
     }
 }
 
+impl<'r, Req, P, S> Handler<'r, Req, P, S> {
+    // 一个请求完成
+    #[inline(always)]
+    fn done(&self) {
+        self.load.decr();
+    }
+}
+
 use std::fmt::{self, Debug, Formatter};
 impl<'r, Req, P, S: Debug> Debug for Handler<'r, Req, P, S> {
     #[inline]
@@ -3,6 +3,7 @@ pub mod handler;
 pub mod pipeline;
 pub use protocol::callback::*;
 pub use protocol::request::*;
+pub mod load;
 mod reconn;
 
 mod context;
@@ -15,6 +16,9 @@ mod builder;
 pub use builder::*;
 
 pub(crate) mod checker;
+mod dedicated;
+mod subscribe;
+mod transaction;
 
 mod metric;
 pub use metric::StreamMetrics;
@@ -22,4 +26,4 @@ pub use metric::StreamMetrics;
 mod arena;
 
 mod topology;
-pub use topology::CheckedTopology;
+pub use topology::{CheckedTopology, TopologyCheck};
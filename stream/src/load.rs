@@ -0,0 +1,81 @@
+// 连接的负载：未完成的请求数，以及响应耗时的EWMA。
+// 用于在backend的多个连接间选择，以及ewma selector按访问代价在副本间选择。
+// EWMA按时间衰减（peak ewma）：耗时变大时立即生效，变小时按距上次更新的时间逐步衰减，
+// 长时间没有请求的连接代价趋近于0，会重新获得流量。
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering::*};
+
+// 衰减的时间常数
+const DECAY_MS: f64 = 2_000.0;
+// 还没有耗时数据，但已经有请求在等待响应时的代价
+const PENALTY: u64 = u64::MAX >> 16;
+
+// 进程启动后的毫秒数
+fn now_ms() -> u64 {
+    static START: OnceLock<std::time::Instant> = OnceLock::new();
+    START
+        .get_or_init(std::time::Instant::now)
+        .elapsed()
+        .as_millis() as u64
+}
+
+#[derive(Default)]
+pub struct Load {
+    inflight: AtomicUsize,
+    // 耗时的EWMA，微秒
+    ewma_us: AtomicU64,
+    // 最后一次更新ewma的时间
+    updated: AtomicU64,
+}
+
+impl Load {
+    #[inline]
+    pub fn incr(&self) {
+        self.inflight.fetch_add(1, Relaxed);
+    }
+    #[inline]
+    pub fn decr(&self) {
+        self.inflight.fetch_sub(1, Relaxed);
+    }
+    #[inline]
+    pub fn inflight(&self) -> usize {
+        self.inflight.load(Relaxed)
+    }
+    // 按距上次更新的时间衰减后的ewma
+    #[inline]
+    fn decayed(&self, now: u64) -> u64 {
+        let ewma = self.ewma_us.load(Relaxed);
+        let elapsed = now.saturating_sub(self.updated.load(Relaxed));
+        (ewma as f64 * (-(elapsed as f64) / DECAY_MS).exp()) as u64
+    }
+    // 一个请求的响应耗时。并发更新时的少量误差可以忽略
+    #[inline]
+    pub fn rtt(&self, us: u64) {
+        let now = now_ms();
+        let ewma = self.ewma_us.load(Relaxed);
+        let new = if us >= ewma {
+            us
+        } else {
+            let w = (-(now.saturating_sub(self.updated.load(Relaxed)) as f64) / DECAY_MS).exp();
+            (ewma as f64 * w + us as f64 * (1.0 - w)) as u64
+        };
+        self.ewma_us.store(new.max(1), Relaxed);
+        self.updated.store(now, Relaxed);
+    }
+    // 请求超时，按超时时间计算耗时
+    #[inline]
+    pub fn penalize(&self, us: u64) {
+        self.ewma_us.fetch_max(us, Relaxed);
+        self.updated.store(now_ms(), Relaxed);
+    }
+    // 访问代价：衰减后的耗时 * (未完成的请求数 + 1)
+    #[inline]
+    pub fn cost(&self) -> u64 {
+        let inflight = self.inflight() as u64;
+        let ewma = self.decayed(now_ms());
+        if ewma == 0 && inflight > 0 {
+            return PENALTY + inflight;
+        }
+        ewma.saturating_mul(inflight + 1)
+    }
+}
@@ -10,6 +10,7 @@ macro_rules! define_metrics {
             )+
             ops: [Metric; OPS.len()],
             rtt: Metric,
+            path: Path,
         }
         impl StreamMetrics {
             // 使用所有的metrics之前，需要先check是否已注册。
@@ -43,12 +44,17 @@ macro_rules! define_metrics {
                 // Metric操作是原子计数的，因此unsafe不会导致UB。
                 unsafe{self.rtt.as_mut()}
             }
+            #[inline]
+            pub fn path(&self) -> &Path {
+                &self.path
+            }
             pub fn new(path:&Path) -> Self {
                 let ops: [Metric; OPS.len()] =
                     array_init::array_init(|idx| path.rtt(OPS[idx].name()));
                 Self {
                     ops,
                     rtt: path.pop().rtt("cmd_all"),
+                    path: path.clone(),
                     $(
                         $(
                             $name: path.$t(stringify!($key)),
@@ -66,7 +72,7 @@ macro_rules! define_metrics {
 }
 
 define_metrics!(
-    qps:    tx-tx, rx-rx, err-err, cps-cps, kps-kps, conn-conn, key-key, nilconvert-nilconvert, inconsist-inconsist;
+    qps:    tx-tx, rx-rx, err-err, cps-cps, kps-kps, conn-conn, key-key, nilconvert-nilconvert, inconsist-inconsist, hedge-hedge, hedge_win-hedge_win;
     num:    conn_num-conn, read-read, write-write, invalid_cmd-invalid_cmd, unsupport_cmd-unsupport_cmd;
     rtt:    avg-avg;
     ratio:  cache-hit;
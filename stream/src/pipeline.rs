@@ -12,11 +12,13 @@ use crate::topology::TopologyCheck;
 use ds::{time::Instant, AtomicWaker};
 use endpoint::Topology;
 use protocol::Error::FlushOnClose;
-use protocol::{HashedCommand, Protocol, Result, Stream};
+use protocol::{HashedCommand, Protocol, Result, Stream, SubCmd, TxCmd};
 
 use crate::{
     arena::CallbackContextArena,
     context::{CallbackContextPtr, ResponseContext},
+    subscribe::Subscriber,
+    transaction::Transaction,
     CallbackContext, Request, StreamMetrics,
 };
 
@@ -45,6 +47,12 @@ where
         start_init: false,
         first: true, // 默认当前请求是第一个
         async_pending: VecDeque::new(),
+        sub: None,
+        tx: None,
+        drain: Default::default(),
+        hedges: VecDeque::new(),
+        discarded: VecDeque::new(),
+        hedge_timer: None,
 
         arena: CallbackContextArena::with_cache(32),
     };
@@ -67,6 +75,17 @@ pub struct CopyBidirectional<C, P, T> {
     first: bool, // 当前解析的请求是否是第一个。
 
     async_pending: VecDeque<CallbackContextPtr>, // 异步请求中的数量。
+    // 订阅模式下独占的后端连接，退订所有channel后释放
+    sub: Option<Box<Subscriber>>,
+    // 事务状态及其独占的后端连接，事务结束后释放
+    tx: Option<Box<Transaction>>,
+    // 进入退出流程后，不再接收新的请求，pending中的请求处理完成后关闭连接
+    drain: rt::Drain,
+    // 原请求的id及其对冲请求。原请求从pending中移除时，同时移除对应的对冲请求
+    hedges: VecDeque<(usize, CallbackContextPtr)>,
+    // 已被另一个请求的响应替代，但后端还未完成的请求，完成后释放
+    discarded: VecDeque<CallbackContextPtr>,
+    hedge_timer: Option<Pin<Box<tokio::time::Sleep>>>,
 
     arena: CallbackContextArena,
 }
@@ -82,14 +101,25 @@ where
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
         self.waker.register(cx.waker());
         self.process_async_pending();
+        self.process_discarded();
         loop {
+            let draining = self.drain.poll_shutdown(cx).is_ready();
             // 从client接收数据写入到buffer
-            let request = self.client.poll_recv(cx)?;
+            let request = match draining {
+                true => Poll::Pending,
+                false => self.client.poll_recv(cx)?,
+            };
             // 解析buffer中的请求，并且发送请求。
             self.parse_request()?;
+            // 读取事务独占连接上的响应
+            self.process_transaction(cx);
 
             // 把已经返回的response，写入到buffer中。
             self.process_pending()?;
+            // 超过对冲延迟仍未完成的读请求，发送对冲请求
+            self.process_hedge(cx);
+            // 订阅模式下，把后端推送的消息写入到buffer中。
+            self.process_subscribe(cx)?;
             let flush = self.poll_flush(cx)?;
 
             if self.pending.len() > 0 && !self.parser.config().pipeline {
@@ -99,6 +129,10 @@ where
             }
 
             ready!(flush);
+            // 已接收的请求都返回了响应，async_pending由close处理完成
+            if draining && self.pending.is_empty() {
+                return Poll::Ready(Ok(()));
+            }
             ready!(request);
         }
     }
@@ -116,14 +150,18 @@ where
             return Ok(());
         }
         // 解析请求，发送请求，并且注册回调
+        let mut parse_at = metrics::otlp::sample_next().then(Instant::now);
         let mut processor = Visitor {
             pending: &mut self.pending,
             waker: &mut self.waker,
             top: &self.top,
             first: &mut self.first,
             arena: &mut self.arena,
+            sub: &mut self.sub,
+            tx: &mut self.tx,
             retry_on_rsp_notok: self.parser.config().retry_on_rsp_notok,
             parser: &self.parser,
+            parse_at: &mut parse_at,
         };
 
         self.parser
@@ -154,9 +192,22 @@ where
                 self.start_init = true;
             }
             if !ctx.complete() {
-                break;
+                // 对冲请求先返回了正常的响应
+                if !self.hedge_won() {
+                    break;
+                }
+                continue;
+            }
+            // 需要合并响应的请求，所有子请求都完成后，一起写入client
+            if ctx.first() && !ctx.last() && self.parser.need_merge(ctx.request()) {
+                if !self.merge_ready() && !self.merge_early() {
+                    break;
+                }
+                self.process_merge()?;
+                continue;
             }
             let mut ctx = self.pending.pop_front().expect("front");
+            self.release_hedge(&ctx);
             let last = ctx.last();
             // 当前不是最后一个值。也优先写入cache
             (!last).then(|| self.client.cache(true));
@@ -171,6 +222,10 @@ where
             )?;
 
             let op = ctx.request().operation();
+            if let Some(trace) = ctx.take_trace() {
+                let ok = response.as_ref().is_some_and(|r| r.ok());
+                trace.record(self.metrics.path(), op.name(), ok);
+            }
             if let Some(rsp) = response {
                 let rsp_ok = rsp.ok();
                 if ctx.is_write_back() && rsp_ok {
@@ -197,6 +252,113 @@ where
         }
         Ok(())
     }
+    // 从第一个子请求到最后一个子请求是否都已完成
+    #[inline]
+    fn merge_ready(&self) -> bool {
+        for ctx in self.pending.iter() {
+            if !ctx.complete() {
+                return false;
+            }
+            if ctx.last() {
+                return true;
+            }
+        }
+        false
+    }
+    // 已完成的子请求已经可以确定合并的结果，如集合运算的成员数量超过上限
+    #[inline]
+    fn merge_early(&self) -> bool {
+        let mut responses = Vec::new();
+        for ctx in self.pending.iter() {
+            if ctx.complete() {
+                responses.extend(ctx.last_response());
+            }
+            if ctx.last() {
+                break;
+            }
+        }
+        let first = self.pending.front().expect("first");
+        self.parser.merge_early(first.request(), &responses)
+    }
+    // 合并所有子请求的响应，按一个请求统计耗时。
+    // 提前合并时，未完成的子请求在完成后释放，响应直接丢弃
+    fn process_merge(&mut self) -> Result<()> {
+        let mut ctxs = Vec::with_capacity(8);
+        while let Some(ctx) = self.pending.pop_front() {
+            let last = ctx.last();
+            self.release_hedge(&ctx);
+            ctxs.push(ctx);
+            if last {
+                break;
+            }
+        }
+        *self.metrics.key() += ctxs.len() as i64;
+        let done: Vec<_> = ctxs.iter().map(|ctx| ctx.complete()).collect();
+        let mut responses: Vec<_> = ctxs
+            .iter_mut()
+            .zip(&done)
+            .map(|(ctx, done)| done.then(|| ctx.take_response()).flatten())
+            .collect();
+        let op = ctxs[0].request().operation();
+        if self.parser.metric_err(op) {
+            *self.metrics.err() += responses.iter().flatten().filter(|r| !r.ok()).count() as i64;
+        }
+
+        self.parser.merge_response(
+            &mut ResponseContext::new(&mut ctxs[0], &self.metrics, |hash| self.top.shard_idx(hash)),
+            &mut responses,
+            &mut self.client,
+        )?;
+        for (ctx, rsp) in ctxs.iter_mut().zip(responses.iter()) {
+            if let Some(trace) = ctx.take_trace() {
+                let ok = rsp.as_ref().is_some_and(|r| r.ok());
+                trace.record(self.metrics.path(), op.name(), ok);
+            }
+        }
+        let undone = ctxs.into_iter().zip(done).filter(|(_, done)| !done);
+        self.discarded.extend(undone.map(|(ctx, _)| ctx));
+
+        let elapsed = self.start.elapsed();
+        *self.metrics.ops(op) += elapsed;
+        *self.metrics.rtt() += elapsed;
+        self.flush = true;
+        self.start_init = false;
+        Ok(())
+    }
+    // 订阅连接的确认消息回调给对应的请求；推送的消息在之前的请求响应都写完后才写入，保证响应的顺序
+    #[inline]
+    fn process_subscribe(&mut self, cx: &mut Context) -> Result<()> {
+        let Some(sub) = self.sub.as_mut() else {
+            return Ok(());
+        };
+        let front = self.pending.front().map(|ctx| ctx.id());
+        self.flush = true;
+        if let Poll::Ready(Err(e)) = sub.poll_relay(cx, &self.parser, &mut self.client, front) {
+            // 后端断连（如quit）前的响应尽量发送给client
+            let _ = Pin::new(&mut self.client).poll_flush(cx);
+            log::info!("+++ subscribe closed: {:?} {:?}", e, sub);
+            sub.on_err();
+            return Err(e);
+        }
+        // 所有channel都已退订，退出订阅模式
+        if sub.done() {
+            log::info!("+++ unsubscribed: {:?}", sub);
+            self.sub = None;
+        }
+        Ok(())
+    }
+    #[inline]
+    fn process_transaction(&mut self, cx: &mut Context) {
+        if let Some(tx) = self.tx.as_mut() {
+            if let Poll::Ready(Err(e)) = tx.poll_response(cx, &self.parser) {
+                log::warn!("+++ transaction conn err: {:?} {:?}", e, tx);
+                tx.on_err();
+            }
+            if tx.idle() {
+                self.tx = None;
+            }
+        }
+    }
     // 把response数据flush到client
     #[inline]
     fn poll_flush(&mut self, cx: &mut Context) -> Poll<Result<()>> {
@@ -206,6 +368,102 @@ where
         }
         Poll::Ready(Ok(()))
     }
+    // 对冲请求先完成并且响应正常时，替换掉pending中的原请求
+    #[inline]
+    fn hedge_won(&mut self) -> bool {
+        let Some(ctx) = self.pending.front_mut() else {
+            return false;
+        };
+        let Some(i) = self.hedges.iter().position(|(primary, hedge)| {
+            *primary == ctx.id() && hedge.complete() && hedge.last_response().is_some_and(|r| r.ok())
+        }) else {
+            return false;
+        };
+        let (_, mut hedge) = self.hedges.remove(i).expect("hedge");
+        std::mem::swap(ctx, &mut hedge);
+        self.discarded.push_back(hedge);
+        *self.metrics.hedge_win() += 1;
+        true
+    }
+    // 请求从pending中移除时：统计读请求的耗时；原请求先完成时，丢弃对冲请求
+    #[inline]
+    fn release_hedge(&mut self, ctx: &CallbackContextPtr) {
+        if let Some(hedge) = self.top.hedge().filter(|_| ctx.hedgeable()) {
+            hedge.record(ctx.start_at().elapsed().as_micros() as u64);
+        }
+        if let Some(i) = self.hedges.iter().position(|(primary, _)| *primary == ctx.id()) {
+            let (_, hedge) = self.hedges.remove(i).expect("hedge");
+            self.discarded.push_back(hedge);
+        }
+    }
+    // 超过对冲延迟仍未完成的读请求，按重试的路径向下一个副本发送一次相同的请求
+    fn process_hedge(&mut self, cx: &mut Context) {
+        let Some(hedge) = self.top.hedge() else {
+            return;
+        };
+        let Some(delay) = hedge.delay() else {
+            return;
+        };
+        let delay = delay.as_micros() as u64;
+        loop {
+            let mut wait = None;
+            for ctx in self.pending.iter() {
+                if ctx.complete() || !ctx.hedgeable() || ctx.hedged() {
+                    continue;
+                }
+                // 需要合并响应的子请求不对冲
+                if !(ctx.first() && ctx.last()) && self.parser.need_merge(ctx.request()) {
+                    continue;
+                }
+                let elapsed = ctx.start_at().elapsed().as_micros() as u64;
+                if elapsed < delay {
+                    let left = delay - elapsed;
+                    wait = Some(wait.map_or(left, |w: u64| w.min(left)));
+                    continue;
+                }
+                // 预算不足时，下次poll时再尝试
+                if !hedge.acquire() {
+                    continue;
+                }
+                let Some(new) = ctx.hedge() else {
+                    hedge.refund();
+                    continue;
+                };
+                let mut new = CallbackContextPtr::from(self.arena.alloc(new), &mut self.arena);
+                let req = new.build_request();
+                self.hedges.push_back((ctx.id(), new));
+                *self.metrics.hedge() += 1;
+                self.top.send(req);
+            }
+            let Some(wait) = wait else {
+                self.hedge_timer = None;
+                return;
+            };
+            let deadline = tokio::time::Instant::now() + std::time::Duration::from_micros(wait);
+            let timer = self
+                .hedge_timer
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+            timer.as_mut().reset(deadline);
+            if timer.as_mut().poll(cx).is_pending() {
+                return;
+            }
+        }
+    }
+    // 被替代的请求完成后释放，对冲过的原请求按完成时间统计耗时
+    #[inline]
+    fn process_discarded(&mut self) {
+        let hedge = self.top.hedge();
+        self.discarded.retain_mut(|ctx| {
+            if !ctx.complete() {
+                return true;
+            }
+            if let Some(hedge) = hedge.filter(|_| ctx.hedgeable()) {
+                hedge.record(ctx.start_at().elapsed().as_micros() as u64);
+            }
+            let _dropped = ctx.take_response();
+            false
+        });
+    }
     #[inline]
     fn process_async_pending(&mut self) {
         if self.async_pending.len() > 0 {
@@ -227,18 +485,45 @@ struct Visitor<'a, T, P> {
     parser: &'a P,
     first: &'a mut bool,
     arena: &'a mut CallbackContextArena,
+    sub: &'a mut Option<Box<Subscriber>>,
+    tx: &'a mut Option<Box<Transaction>>,
     retry_on_rsp_notok: bool,
+    // 当前请求被采样时，开始解析的时间
+    parse_at: &'a mut Option<Instant>,
 }
 
 impl<'a, T: Topology<Item = Request> + TopologyCheck, P: Protocol> protocol::RequestProcessor
     for Visitor<'a, T, P>
 {
     #[inline]
-    fn process(&mut self, cmd: HashedCommand, last: bool) {
+    fn process(&mut self, mut cmd: HashedCommand, last: bool) {
         let first = *self.first;
         // 如果当前是最后一个子请求，那下一个请求就是一个全新的请求。
         // 否则下一个请求是子请求。
         *self.first = last;
+        // 订阅类指令进入订阅模式，独占channel所在分片的后端连接
+        let sub_cmd = self.parser.sub_cmd(&cmd);
+        let subscribe = matches!(sub_cmd, SubCmd::Subscribe | SubCmd::PSubscribe);
+        if subscribe && self.sub.is_none() && !cmd.noforward() {
+            match self.top.dedicated_backend(cmd.hash()) {
+                Some((addr, option, to)) => {
+                    let shard = self.top.shard_idx(cmd.hash());
+                    *self.sub = Some(Box::new(Subscriber::new(addr, option, to.into(), shard)));
+                }
+                // 不支持订阅的资源，直接返回padding响应
+                None => {
+                    cmd.flag_mut().set_noforward(true);
+                }
+            }
+        }
+        // 认证失败、本地处理的指令不经过订阅连接，与其他请求一样按顺序返回
+        let in_sub = !cmd.noforward() && self.sub.as_ref().is_some_and(|s| s.accept(sub_cmd));
+        // multi之后的请求，以及watch、multi等事务指令，都由事务处理
+        let tx_cmd = self.parser.tx_cmd(&cmd);
+        let in_tx = match self.tx.as_ref() {
+            Some(tx) => tx.multi() || !matches!(tx_cmd, TxCmd::Other | TxCmd::Unsupported),
+            None => matches!(tx_cmd, TxCmd::Watch | TxCmd::Multi),
+        };
         let cb = self.top.callback();
         let req_op = cmd.operation();
         let ctx = self.arena.alloc(CallbackContext::new(
@@ -251,17 +536,55 @@ impl<'a, T: Topology<Item = Request> + TopologyCheck, P: Protocol> protocol::Req
             self.parser.max_tries(req_op),
         ));
         let mut ctx = CallbackContextPtr::from(ctx, self.arena);
+        let sampled = metrics::otlp::sampled();
+        if let Some(parse_at) = self.parse_at.take().filter(|_| sampled) {
+            ctx.trace(parse_at);
+        }
 
         // pendding 会move走ctx，所以提前把req给封装好
         let mut req: Request = ctx.build_request();
         self.pending.push_back(ctx);
 
         use protocol::req::Request as RequestTrait;
-        if req.noforward() {
+        if in_sub {
+            let id = self.pending.back().expect("pushed").id();
+            let sub = self.sub.as_mut().expect("sub");
+            sub.process(id, req, sub_cmd, self.top);
+        } else if in_tx {
+            let tx = self.tx.get_or_insert_with(Default::default);
+            tx.process(req, tx_cmd, self.top);
+        } else if req.noforward() {
             req.on_noforward();
         } else {
             self.top.send(req);
         }
+        // 下一个请求从此时开始解析，只在会被采样时取时间
+        *self.parse_at = metrics::otlp::sample_next().then(Instant::now);
+    }
+    #[inline]
+    fn shards(&self) -> usize {
+        self.top.shards()
+    }
+    #[inline]
+    fn max_set_members(&self) -> usize {
+        self.top.max_set_members()
+    }
+    #[inline]
+    fn require_auth(&self) -> bool {
+        self.top.require_auth()
+    }
+    #[inline]
+    fn resp3(&self) -> bool {
+        self.top.resp3()
+    }
+    #[inline]
+    fn auth(&self, user: &[u8], pass: &[u8]) -> Option<protocol::Acl> {
+        self.top.auth(user, pass)
+    }
+    #[inline]
+    fn route(&self, key: &[u8]) -> Option<String> {
+        let route = self.top.route(key);
+        (!route.is_null()).then(|| route.to_string())
     }
 }
 impl<C, P, T> Drop for CopyBidirectional<C, P, T> {
@@ -284,22 +607,39 @@ where
         self.waker.take();
         use rt::Cancel;
         self.client.cancel();
+        // 事务、订阅中等待响应的请求直接返回异常
+        if let Some(tx) = self.tx.as_mut() {
+            tx.on_err();
+        }
+        if let Some(sub) = self.sub.as_mut() {
+            sub.on_err();
+        }
         // 剔除已完成的请求
         while let Some(ctx) = self.pending.front_mut() {
             if !ctx.complete() {
                 break;
             }
             let mut ctx = self.pending.pop_front().expect("empty");
+            self.release_hedge(&ctx);
 
             // 如果已经有response记入到ctx，需要take走，保证rsp drop时状态的一致性
             let _dropped = ctx.take_response();
         }
         // 处理异步请求
         self.process_async_pending();
-        self.client.try_gc() && self.pending.len() == 0 && self.async_pending.len() == 0
+        self.process_discarded();
+        self.client.try_gc()
+            && self.pending.is_empty()
+            && self.async_pending.is_empty()
+            && self.discarded.is_empty()
     }
     #[inline]
     fn refresh(&mut self) -> Result<bool> {
+        // 服务已下线，关闭连接，释放对topology及后端连接的引用
+        if self.top.removed() {
+            log::info!("service removed, closing: {:?}", self);
+            return Ok(false);
+        }
         if self.top.refresh() {
             log::info!("topology refreshed: {:?}", self);
         }
@@ -313,12 +653,13 @@ impl<C: Debug, P, T> Debug for CopyBidirectional<C, P, T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "{} => pending:({},{}) frist => {:?} flush:{}  => {:?}",
+            "{} => pending:({},{}) frist => {:?} flush:{} sub:{:?} => {:?}",
             self.metrics.biz(),
             self.pending.len(),
             self.async_pending.len(),
             self.pending.get(0).map(|r| &**r),
             self.flush,
+            self.sub,
             self.client,
         )
     }
@@ -0,0 +1,215 @@
+use std::collections::{HashSet, VecDeque};
+use std::task::{Context, Poll, ready};
+
+use ds::RingSlice;
+use ds::time::Duration;
+use endpoint::Topology;
+use protocol::{AsyncBufRead, BufRead, Error, Protocol, ResOption, Result, SubCmd, Writer};
+
+use crate::{
+    Request,
+    dedicated::{Dedicated, reply},
+};
+
+// channel与订阅的分片不一致时返回给client的异常
+const ERR_CROSS_SHARD: &[u8] = b"-ERR channel not in subscribed shard\r\n";
+const ERR_SUBSCRIBED: &[u8] =
+    b"-ERR only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING / QUIT allowed in this context\r\n";
+const ERR_UNAVAILABLE: &[u8] = b"-ERR subscribe backend unavailable\r\n";
+
+// 订阅模式下client连接独占的后端连接：
+// 1. 由第一个订阅的channel决定分片，后续订阅的channel必须在同一个分片；pattern的分片由hashkey指定；
+// 2. 订阅类指令透传到该连接，确认消息按顺序回调给对应的请求，与其他请求的响应一起按请求顺序写回client；
+// 3. 未认证、无权限及本地处理的指令照常返回，其他指令直接返回异常；
+// 4. 后端推送的消息没有对应的请求，之前的请求响应都写完后，解析出完整的消息直接写回client；
+// 5. 所有channel、pattern都退订后退出订阅模式，之后的请求按正常流程处理。
+pub(crate) struct Subscriber {
+    shard: usize,
+    conn: Dedicated,
+    // 已订阅的channel、pattern，包含还未确认的
+    channels: HashSet<Vec<u8>>,
+    patterns: HashSet<Vec<u8>>,
+    // 已发送到后端、等待确认的请求
+    waiting: VecDeque<Waiting>,
+}
+
+struct Waiting {
+    // 请求在pending中的id
+    id: usize,
+    req: Request,
+    // 还需要的确认消息数量，不带参数的unsubscribe每个channel返回一个确认消息
+    left: usize,
+    rsp: Vec<u8>,
+}
+
+impl Subscriber {
+    pub(crate) fn new(addr: String, option: ResOption, timeout: Duration, shard: usize) -> Self {
+        log::info!("+++ subscribe to {} shard:{}", addr, shard);
+        Self {
+            shard,
+            conn: Dedicated::new(addr, option, timeout),
+            channels: HashSet::new(),
+            patterns: HashSet::new(),
+            waiting: VecDeque::new(),
+        }
+    }
+    // 已退订所有channel、pattern，只有订阅类指令继续由订阅连接处理
+    #[inline]
+    pub(crate) fn accept(&self, cmd: SubCmd) -> bool {
+        !self.closing() || cmd != SubCmd::Other
+    }
+    // 退订完成，且所有确认消息都已返回，可以退出订阅模式
+    #[inline]
+    pub(crate) fn done(&self) -> bool {
+        self.closing() && self.waiting.is_empty()
+    }
+    #[inline]
+    fn closing(&self) -> bool {
+        self.channels.is_empty() && self.patterns.is_empty()
+    }
+    pub(crate) fn process<T: Topology<Item = Request>>(
+        &mut self,
+        id: usize,
+        req: Request,
+        cmd: SubCmd,
+        top: &T,
+    ) {
+        match cmd {
+            SubCmd::Subscribe | SubCmd::PSubscribe if top.shard_idx(req.hash()) != self.shard => {
+                reply(req, ERR_CROSS_SHARD)
+            }
+            SubCmd::Subscribe => {
+                self.channels.extend(args(&req));
+                self.forward(id, req, 1);
+            }
+            SubCmd::PSubscribe => {
+                self.patterns.extend(args(&req));
+                self.forward(id, req, 1);
+            }
+            SubCmd::Unsubscribe => {
+                let n = unsubscribe(&mut self.channels, &req);
+                self.forward(id, req, n);
+            }
+            SubCmd::PUnsubscribe => {
+                let n = unsubscribe(&mut self.patterns, &req);
+                self.forward(id, req, n);
+            }
+            SubCmd::Other => reply(req, ERR_SUBSCRIBED),
+        }
+    }
+    fn forward(&mut self, id: usize, req: Request, left: usize) {
+        self.conn.send(&req);
+        let rsp = Vec::new();
+        self.waiting.push_back(Waiting { id, req, left, rsp });
+    }
+    // 把缓存的指令发送到后端，确认消息回调给对应的请求，推送的消息写入client。
+    // front为pending中第一个请求的id，推送的消息需要等之前的请求响应都写完。
+    // 只有出错时才会返回Ready，后端断连时返回Eof。
+    pub(crate) fn poll_relay<P, W>(
+        &mut self,
+        cx: &mut Context,
+        parser: &P,
+        client: &mut W,
+        front: Option<usize>,
+    ) -> Poll<Result<()>>
+    where
+        P: Protocol,
+        W: Writer,
+    {
+        let s = ready!(self.conn.poll_ready(cx, parser, self.waiting.len()))?;
+        loop {
+            let poll_read = s.poll_recv(cx)?;
+            while s.len() > 0 {
+                let push = is_push(&s.slice());
+                // 之前的请求完成时会唤醒
+                let waiting = self.waiting.front().map(|w| w.id);
+                if push && front.is_some() && front != waiting {
+                    return Poll::Pending;
+                }
+                let Some(msg) = parser.parse_response(s)? else {
+                    break;
+                };
+                if push {
+                    client.write_slice(&msg, 0)?;
+                    continue;
+                }
+                let Some(w) = self.waiting.front_mut() else {
+                    return Poll::Ready(Err(Error::UnexpectedData));
+                };
+                msg.copy_to_vec(&mut w.rsp);
+                w.left -= 1;
+                if w.left == 0 {
+                    let w = self.waiting.pop_front().expect("waiting");
+                    reply(w.req, &w.rsp);
+                }
+            }
+            ready!(poll_read);
+        }
+    }
+    // 订阅连接异常或确认消息超时，等待确认的请求返回异常
+    pub(crate) fn on_err(&mut self) {
+        while let Some(w) = self.waiting.pop_front() {
+            reply(w.req, ERR_UNAVAILABLE);
+        }
+    }
+}
+
+// 后端推送的消息：message、pmessage，其他的是订阅类指令的确认消息
+#[inline]
+fn is_push(data: &RingSlice) -> bool {
+    data.find_lf_cr(0).is_some_and(|i| {
+        data.start_with(i + 2, b"$7\r\nmessage\r\n") || data.start_with(i + 2, b"$8\r\npmessage\r\n")
+    })
+}
+
+// 退订指定的channel，不带参数时退订所有。返回后端确认消息的数量，没有任何订阅时也会返回一个
+fn unsubscribe(subscribed: &mut HashSet<Vec<u8>>, req: &Request) -> usize {
+    let args = args(req);
+    if args.is_empty() {
+        return subscribed.drain().count().max(1);
+    }
+    args.iter().for_each(|arg| {
+        subscribed.remove(arg);
+    });
+    args.len()
+}
+
+// 请求中除指令名外的所有参数，请求已通过协议校验
+fn args(req: &Request) -> Vec<Vec<u8>> {
+    let mut args = Vec::new();
+    // 跳过bulk num及指令名
+    let mut bulks = 0;
+    let mut oft = 0;
+    while let Some(lfcr) = req.find_lf_cr(oft) {
+        let len = req.try_str_num(oft + 1..lfcr).unwrap_or(0);
+        if bulks == 0 {
+            oft = lfcr + 2;
+        } else {
+            if bulks > 1 {
+                let mut arg = Vec::with_capacity(len);
+                req.sub_slice(lfcr + 2, len).copy_to_vec(&mut arg);
+                args.push(arg);
+            }
+            oft = lfcr + 2 + len + 2;
+        }
+        bulks += 1;
+        if oft >= req.len() {
+            break;
+        }
+    }
+    args
+}
+
+impl std::fmt::Debug for Subscriber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "subscribe => {:?} shard:{} channels:{} patterns:{} waiting:{}",
+            self.conn,
+            self.shard,
+            self.channels.len(),
+            self.patterns.len(),
+            self.waiting.len()
+        )
+    }
+}
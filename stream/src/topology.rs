@@ -1,6 +1,6 @@
 use discovery::TopologyReadGuard;
 use ds::ReadGuard;
-use endpoint::{Endpoint, Topology};
+use endpoint::{Endpoint, Timeout, Topology};
 use protocol::{
     callback::{Callback, CallbackPtr},
     request::Request,
@@ -10,6 +10,8 @@ use sharding::hash::{Hash, HashKey};
 pub trait TopologyCheck: Sized {
     fn refresh(&mut self) -> bool;
     fn callback(&self) -> CallbackPtr;
+    // 服务是否已下线
+    fn removed(&self) -> bool;
 }
 
 pub struct CheckedTopology<T> {
@@ -56,6 +58,10 @@ impl<T: Clone + Topology<Item = Request> + 'static> TopologyCheck for CheckedTop
     fn callback(&self) -> CallbackPtr {
         self.cb.clone()
     }
+    #[inline]
+    fn removed(&self) -> bool {
+        self.reader.removed()
+    }
 }
 
 impl<T: Endpoint> Endpoint for CheckedTopology<T> {
@@ -83,4 +89,36 @@ impl<T: Topology> Topology for CheckedTopology<T> {
     fn exp_sec(&self) -> u32 {
         self.top.exp_sec()
     }
+    #[inline]
+    fn dedicated_backend(&self, hash: i64) -> Option<(String, protocol::ResOption, Timeout)> {
+        self.top.dedicated_backend(hash)
+    }
+    #[inline]
+    fn shards(&self) -> usize {
+        self.top.shards()
+    }
+    #[inline]
+    fn max_set_members(&self) -> usize {
+        self.top.max_set_members()
+    }
+    #[inline]
+    fn require_auth(&self) -> bool {
+        self.top.require_auth()
+    }
+    #[inline]
+    fn resp3(&self) -> bool {
+        self.top.resp3()
+    }
+    #[inline]
+    fn auth(&self, user: &[u8], pass: &[u8]) -> Option<protocol::Acl> {
+        self.top.auth(user, pass)
+    }
+    #[inline]
+    fn route(&self, key: &[u8]) -> serde_json::Value {
+        self.top.route(key)
+    }
+    #[inline]
+    fn hedge(&self) -> Option<&endpoint::hedge::Hedge> {
+        self.top.hedge()
+    }
 }
@@ -0,0 +1,247 @@
+use std::collections::VecDeque;
+use std::task::{Context, Poll, ready};
+
+use ds::MemGuard;
+use endpoint::Topology;
+use protocol::{
+    AsyncBufRead, BufRead, Command, Error, Protocol, Request as RequestTrait, Result, TxCmd,
+};
+
+use crate::{
+    Request,
+    dedicated::{Dedicated, reply},
+};
+
+const OK: &[u8] = b"+OK\r\n";
+const QUEUED: &[u8] = b"+QUEUED\r\n";
+const EMPTY: &[u8] = b"*0\r\n";
+const MULTI: &[u8] = b"*1\r\n$5\r\nMULTI\r\n";
+const UNWATCH: &[u8] = b"*1\r\n$7\r\nUNWATCH\r\n";
+const ERR_CROSS_SHARD: &[u8] = b"-ERR transaction keys must be in the same shard\r\n";
+const ERR_UNSUPPORTED: &[u8] = b"-ERR multi-key command not supported in transaction\r\n";
+const ERR_NESTED: &[u8] = b"-ERR MULTI calls can not be nested\r\n";
+const ERR_WATCH_IN_MULTI: &[u8] = b"-ERR WATCH inside MULTI is not allowed\r\n";
+const ERR_EXECABORT: &[u8] = b"-EXECABORT Transaction discarded because of previous errors.\r\n";
+const ERR_UNAVAILABLE: &[u8] = b"-ERR transaction backend unavailable\r\n";
+
+// 单分片事务：
+// 1. watch、multi之后的第一个key确定事务所在分片，后续key必须在同一个分片（可以通过hashkey指定），否则事务被拒绝；
+// 2. watch 直接发送到分片master的独占连接；multi之后的指令在mesh内排队，本地返回+QUEUED；
+// 3. exec 时把 MULTI、排队的指令、EXEC 一起发送到独占连接，只把EXEC的响应返回给client。
+// 上一个事务的响应返回前（如pipeline中连续的事务），下一个事务沿用相同的分片。
+#[derive(Default)]
+pub(crate) struct Transaction {
+    // 事务所在的分片，以及用于定位后端的hash
+    shard: Option<(usize, i64)>,
+    conn: Option<Dedicated>,
+    multi: bool,
+    watched: bool,
+    // 排队过程中有指令被拒绝，exec时直接返回EXECABORT
+    aborted: bool,
+    queued: Vec<u8>,
+    queued_num: usize,
+    // 已发送到独占连接、等待响应的请求。None表示响应需要吞噬，如MULTI的+OK、排队指令的+QUEUED
+    pending: VecDeque<Option<Request>>,
+}
+
+impl Transaction {
+    #[inline]
+    pub(crate) fn multi(&self) -> bool {
+        self.multi
+    }
+    // 事务已结束，且所有响应都已返回，可以释放独占连接
+    #[inline]
+    pub(crate) fn idle(&self) -> bool {
+        !self.multi && !self.watched && self.pending.is_empty()
+    }
+    pub(crate) fn process<T: Topology<Item = Request>>(
+        &mut self,
+        mut req: Request,
+        cmd: TxCmd,
+        top: &T,
+    ) {
+        match cmd {
+            TxCmd::Watch if self.multi => reply(req, ERR_WATCH_IN_MULTI),
+            TxCmd::Watch => match self.pin(req.hash(), top) {
+                true => {
+                    self.watched = true;
+                    self.forward(req, top);
+                }
+                false => reply(req, ERR_CROSS_SHARD),
+            },
+            TxCmd::Multi if self.multi => reply(req, ERR_NESTED),
+            TxCmd::Multi => {
+                self.multi = true;
+                req.on_noforward();
+            }
+            TxCmd::Exec if self.multi => self.exec(req, top),
+            TxCmd::Discard if self.multi => {
+                self.discard();
+                reply(req, OK);
+            }
+            TxCmd::Unwatch if !self.multi => {
+                self.unwatch();
+                req.on_noforward();
+            }
+            // 与redis一致，事务内的unwatch、ping排队，在exec时执行
+            TxCmd::Unwatch | TxCmd::Keyless if self.multi => self.queue(req),
+            // 事务外的exec、discard，以及本地处理的指令，如ping
+            _ if !self.multi || req.noforward() => req.on_noforward(),
+            TxCmd::Unsupported => {
+                self.aborted = true;
+                reply(req, ERR_UNSUPPORTED);
+            }
+            _ => match self.pin(req.hash(), top) {
+                true => self.queue(req),
+                false => {
+                    self.aborted = true;
+                    reply(req, ERR_CROSS_SHARD);
+                }
+            },
+        }
+        // 事务已结束，下一个事务重新选择分片
+        if self.idle() {
+            self.shard = None;
+            self.conn = None;
+        }
+    }
+    // 读取独占连接上的响应，按发送顺序回调给对应的请求
+    pub(crate) fn poll_response<P: Protocol>(
+        &mut self,
+        cx: &mut Context,
+        parser: &P,
+    ) -> Poll<Result<()>> {
+        let Some(conn) = self.conn.as_mut() else {
+            return Poll::Ready(Ok(()));
+        };
+        let s = ready!(conn.poll_ready(cx, parser, self.pending.len()))?;
+        loop {
+            let poll_read = s.poll_recv(cx)?;
+            while s.len() > 0 {
+                let Some(rsp) = parser.parse_response(s)? else {
+                    break;
+                };
+                match self.pending.pop_front() {
+                    // 响应引用独占连接的buffer，事务结束后连接即被释放，需要复制出来
+                    Some(Some(req)) => {
+                        let mut data = Vec::with_capacity(rsp.len());
+                        rsp.copy_to_vec(&mut data);
+                        req.on_complete(Command::from(rsp.ok(), MemGuard::from_vec(data)));
+                    }
+                    Some(None) => {}
+                    None => return Poll::Ready(Err(Error::UnexpectedData)),
+                }
+            }
+            ready!(poll_read);
+        }
+    }
+    // 独占连接异常或响应超时，所有等待中的请求返回异常；watch状态丢失，当前事务只能放弃
+    pub(crate) fn on_err(&mut self) {
+        while let Some(req) = self.pending.pop_front() {
+            if let Some(req) = req {
+                reply(req, ERR_UNAVAILABLE);
+            }
+        }
+        self.conn = None;
+        if self.watched {
+            self.watched = false;
+            self.aborted = self.multi;
+        }
+    }
+    fn pin<T: Topology<Item = Request>>(&mut self, hash: i64, top: &T) -> bool {
+        let shard = top.shard_idx(hash);
+        match self.shard {
+            Some((pinned, _)) => pinned == shard,
+            None => {
+                self.shard = Some((shard, hash));
+                true
+            }
+        }
+    }
+    // 独占连接在首次发送时才建立
+    fn conn<T: Topology<Item = Request>>(&mut self, top: &T) -> Option<&mut Dedicated> {
+        if self.conn.is_none() {
+            let (_, hash) = self.shard?;
+            let (addr, option, to) = top.dedicated_backend(hash)?;
+            log::info!("+++ transaction to {}", addr);
+            self.conn = Some(Dedicated::new(addr, option, to.into()));
+        }
+        self.conn.as_mut()
+    }
+    fn forward<T: Topology<Item = Request>>(&mut self, req: Request, top: &T) {
+        match self.conn(top) {
+            Some(conn) => {
+                conn.send(&req);
+                self.pending.push_back(Some(req));
+            }
+            None => {
+                self.watched = false;
+                reply(req, ERR_UNAVAILABLE);
+            }
+        }
+    }
+    fn exec<T: Topology<Item = Request>>(&mut self, req: Request, top: &T) {
+        self.multi = false;
+        let queued = std::mem::take(&mut self.queued);
+        let num = std::mem::take(&mut self.queued_num);
+        if std::mem::take(&mut self.aborted) {
+            self.unwatch();
+            return reply(req, ERR_EXECABORT);
+        }
+        // 没有任何指令的空事务；只有ping等不带key的指令时，任选一个分片执行
+        if self.shard.is_none() {
+            if num == 0 {
+                return reply(req, EMPTY);
+            }
+            self.shard = Some((top.shard_idx(0), 0));
+        }
+        // exec之后，redis会自动unwatch
+        self.watched = false;
+        match self.conn(top) {
+            Some(conn) => {
+                conn.write(MULTI);
+                conn.write(&queued);
+                conn.send(&req);
+                (0..=num).for_each(|_| self.pending.push_back(None));
+                self.pending.push_back(Some(req));
+            }
+            None => reply(req, ERR_UNAVAILABLE),
+        }
+    }
+    fn queue(&mut self, req: Request) {
+        req.copy_to_vec(&mut self.queued);
+        self.queued_num += 1;
+        reply(req, QUEUED);
+    }
+    fn discard(&mut self) {
+        self.multi = false;
+        self.aborted = false;
+        self.queued.clear();
+        self.queued_num = 0;
+        self.unwatch();
+    }
+    fn unwatch(&mut self) {
+        if self.watched {
+            self.watched = false;
+            if let Some(conn) = self.conn.as_mut() {
+                conn.write(UNWATCH);
+                self.pending.push_back(None);
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Transaction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "transaction => {:?} shard:{:?} multi:{} watched:{} queued:{} pending:{}",
+            self.conn,
+            self.shard.map(|(s, _)| s),
+            self.multi,
+            self.watched,
+            self.queued_num,
+            self.pending.len()
+        )
+    }
+}
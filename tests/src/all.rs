@@ -2,13 +2,23 @@
 mod distribute;
 // mod hash_test;
 mod shard_test;
-//mod memcached_text;
+mod mc_bin;
+mod memcached_text;
 //mod mem;
 mod protocols;
 //mod queue;
 // mod redis;
 mod hash_test;
 mod redis;
+mod redis_pubsub;
+mod redis_transaction;
+mod redis_scan;
+mod redis_setop;
+mod redis_mkey;
+mod redis_resp3;
+mod redis_auth;
+mod redis_cluster;
+mod redis_route;
 mod ring_slice;
 mod size;
 //mod slice;
@@ -16,20 +26,31 @@ mod arena;
 mod asserts;
 mod layout;
 // mod mysql;
+mod backend_conns;
 mod bkdrsub;
 mod cfg_build;
+mod config_validate;
 mod cow;
 mod decrypt;
 mod discovery;
 mod dns;
+mod hedge;
+mod histogram;
 // mod ip;
 mod context;
 mod kv;
 mod mq;
 mod mysql_strategy;
 mod number;
+mod otlp;
+mod retire;
+mod outlier;
+mod pipeline_hook;
 mod proto_hook;
 mod ring_buffer;
 mod select;
 mod shard_checker;
+mod shutdown;
+mod tls;
 mod tx_buffer;
+mod upgrade;
@@ -0,0 +1,112 @@
+use discovery::Inited;
+use endpoint::{Endpoint, Timeout};
+use protocol::{Parser, ResOption, Resource};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use stream::{Backend, Request};
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+use crate::pipeline_hook::{self, cmd};
+
+fn backend(addr: &str, conns: usize) -> Backend<Request> {
+    let p = Parser::try_from("redis").expect("parser");
+    let option = ResOption {
+        conns,
+        ..Default::default()
+    };
+    let to = Timeout::new(500);
+    Backend::build_o(addr, p, Resource::Redis, "conns_test", to, option)
+}
+
+/// 每个后端按配置建立多个连接；0与1相同，超过上限时按上限建立
+#[test]
+fn conns_per_backend() {
+    metrics::tests::init_metrics_onlyfor_test();
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let l = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = l.local_addr().unwrap().to_string();
+
+        let b = backend(&addr, 3);
+        assert_eq!(b.conns(), 3);
+        let mut accepted = Vec::new();
+        for _ in 0..3 {
+            let (s, _) = l.accept().await.unwrap();
+            accepted.push(s);
+        }
+        while !b.available() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(b.inited());
+
+        // 不会建立多余的连接
+        let more = tokio::time::timeout(Duration::from_millis(200), l.accept()).await;
+        assert!(more.is_err());
+
+        assert_eq!(backend(&addr, 0).conns(), 1);
+        assert_eq!(backend(&addr, 100).conns(), 16);
+    });
+}
+
+/// 同一个client的请求固定发送到后端的同一个连接，pipeline中的请求不会在多个连接上乱序执行
+#[test]
+fn conns_pinned_per_client() {
+    pipeline_hook::run(async {
+        let l = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = l.local_addr().unwrap().to_string();
+        // 每个请求所在的后端连接
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let accepted = Arc::new(Mutex::new(0usize));
+        let (s, a) = (served.clone(), accepted.clone());
+        tokio::spawn(async move {
+            while let Ok((conn, _)) = l.accept().await {
+                let idx = {
+                    let mut a = a.lock().unwrap();
+                    *a += 1;
+                    *a
+                };
+                let served = s.clone();
+                tokio::spawn(async move {
+                    let (r, mut w) = conn.into_split();
+                    let mut r = BufReader::new(r);
+                    while let Some(args) = pipeline_hook::read_cmd(&mut r).await {
+                        if args[0].eq_ignore_ascii_case(b"hello") {
+                            let _ = w.write_all(b"-ERR unknown command 'HELLO'\r\n").await;
+                            continue;
+                        }
+                        served.lock().unwrap().push(idx);
+                        tokio::time::sleep(Duration::from_millis(2)).await;
+                        if w.write_all(b"+OK\r\n").await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        let cfg = format!(
+            "basic:\n  hash: crc32\n  distribution: modula\n  conns_per_backend: 4\nbackends:\n  - {addr},{addr}\n"
+        );
+        let service = pipeline_hook::redis_service("conns_pinned", &cfg).await;
+        // 主从各4个连接都建立之后再发送
+        while *accepted.lock().unwrap() < 8 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        for _ in 0..2 {
+            served.lock().unwrap().clear();
+            let mut client = service.connect().await;
+            let set = cmd(&["set", "k", "v"]);
+            for _ in 0..20 {
+                client.send(&set).await;
+            }
+            for _ in 0..20 {
+                client.expect(b"+OK\r\n").await;
+            }
+            let served = served.lock().unwrap();
+            assert_eq!(served.len(), 20);
+            assert!(served.iter().all(|idx| *idx == served[0]), "{:?}", served);
+        }
+    });
+}
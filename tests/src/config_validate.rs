@@ -0,0 +1,134 @@
+use endpoint::Validate;
+use endpoint::validate::ConfigError;
+use protocol::Parser;
+use sharding::{distribution::Distribute, hash::Hasher};
+use stream::{Backend, Request};
+
+type Topology = endpoint::TopologyProtocol<Backend<Request>, Parser>;
+
+fn validate(endpoint: &str, protocol: &str, cfg: &str) -> Result<(), ConfigError> {
+    let p = Parser::try_from(protocol).expect("parser");
+    let top: Topology = endpoint::TopologyProtocol::try_from(p, endpoint).expect("topology");
+    top.validate("test", cfg)
+}
+
+#[test]
+fn hash_distribution_names() {
+    assert!(Hasher::try_from("crc32-short").is_some());
+    assert!(Hasher::try_from("crc32-range-id").is_some());
+    assert!(Hasher::try_from("bkdr").is_some());
+    assert!(Hasher::try_from("crc33").is_none());
+    assert!(Hasher::try_from("crc33-short").is_none());
+
+    let shards = vec!["127.0.0.1:6379", "127.0.0.1:6380"];
+    assert!(Distribute::try_from("ketama", &shards).is_some());
+    assert!(Distribute::try_from("range-256", &shards).is_some());
+    assert!(Distribute::try_from("modulo", &shards).is_none());
+}
+
+#[test]
+fn redis_config() {
+    let cfg = "basic:\n  hash: crc32\n  distribution: modula\nbackends:\n  - 127.0.0.1:6379,127.0.0.1:6380\n";
+    assert_eq!(validate("rs", "redis", cfg), Ok(()));
+
+    let unknown_hash = cfg.replace("crc32", "crc33");
+    let e = validate("rs", "redis", &unknown_hash);
+    assert_eq!(e, Err(ConfigError::UnknownHash("crc33".to_string())));
+
+    let unknown_dist = cfg.replace("modula", "modulo");
+    let e = validate("rs", "redis", &unknown_dist);
+    assert_eq!(
+        e,
+        Err(ConfigError::UnknownDistribution("modulo".to_string()))
+    );
+
+    let range =
+        "basic:\n  distribution: range-256\nbackends:\n  - a:1,a:2\n  - b:1,b:2\n  - c:1,c:2\n";
+    let e = validate("rs", "redis", range);
+    assert!(matches!(
+        e,
+        Err(ConfigError::ShardMismatch { actual: 3, .. })
+    ));
+
+    let conns = cfg.replace("basic:\n", "basic:\n  conns_per_backend: 17\n");
+    assert!(matches!(
+        validate("rs", "redis", &conns),
+        Err(ConfigError::Invalid(_))
+    ));
+    let conns = cfg.replace("basic:\n", "basic:\n  conns_per_backend: 4\n");
+    assert_eq!(validate("rs", "redis", &conns), Ok(()));
+
+    let hedge = cfg.replace("basic:\n", "basic:\n  hedge_percentile: 100\n");
+    assert!(matches!(
+        validate("rs", "redis", &hedge),
+        Err(ConfigError::Invalid(_))
+    ));
+    let hedge = cfg.replace("basic:\n", "basic:\n  hedge_percentile: 95\n");
+    assert_eq!(validate("rs", "redis", &hedge), Ok(()));
+
+    assert_eq!(
+        validate("rs", "redis", "basic: {}\nbackends: []\n"),
+        Err(ConfigError::Empty("backends"))
+    );
+    assert!(matches!(
+        validate("rs", "redis", "backends: ["),
+        Err(ConfigError::Parse(_))
+    ));
+}
+
+#[test]
+fn kv_years() {
+    let cfg = "basic:\n  db_count: 32\nbackends:\n  2010-2015:\n    - a:1,a:2\n  2016-2020:\n    - b:1,b:2\n";
+    assert_eq!(validate("kv", "kv", cfg), Ok(()));
+
+    let overlap = cfg.replace("2016-2020", "2015-2020");
+    let e = validate("kv", "kv", &overlap);
+    assert_eq!(e, Err(ConfigError::Years("2015-2020".to_string())));
+
+    let gap = cfg.replace("2016-2020", "2017-2020");
+    assert!(matches!(
+        validate("kv", "kv", &gap),
+        Err(ConfigError::Years(_))
+    ));
+
+    // 各年的分片数需要一致
+    let shards = cfg.replace("    - b:1,b:2\n", "    - b:1,b:2\n    - c:1,c:2\n");
+    let e = validate("kv", "kv", &shards);
+    assert!(matches!(
+        e,
+        Err(ConfigError::ShardMismatch {
+            expected: 1,
+            actual: 2,
+            ..
+        })
+    ));
+
+    let db_count = cfg.replace("db_count: 32", "db_count: 0");
+    let e = validate("kv", "kv", &db_count);
+    assert!(matches!(
+        e,
+        Err(ConfigError::ShardMismatch { actual: 0, .. })
+    ));
+}
+
+/// cacheservice、phantomservice不按副本的访问代价选择，配置ewma时拒绝
+#[test]
+fn selector_unsupported() {
+    let cs = "hash: bkdr\ndistribution: ketama\nexptime: 0\nmaster: [127.0.0.1:11211]\n";
+    assert_eq!(validate("cs", "mc", cs), Ok(()));
+    let ewma = format!("{cs}selector: ewma\n");
+    assert!(matches!(
+        validate("cs", "mc", &ewma),
+        Err(ConfigError::Invalid(_))
+    ));
+
+    let ps = "basic:\n  distribution: range\nbackends:\n  - 127.0.0.1:6379\n";
+    assert_eq!(validate("ps", "phantom", ps), Ok(()));
+    let ewma = ps.replace("basic:\n", "basic:\n  selector: ewma\n");
+    assert!(matches!(
+        validate("ps", "phantom", &ewma),
+        Err(ConfigError::Invalid(_))
+    ));
+    let random = ps.replace("basic:\n", "basic:\n  selector: random\n");
+    assert_eq!(validate("ps", "phantom", &random), Ok(()));
+}
@@ -6,3 +6,50 @@ fn cpu_type() {
     assert!(cpu_type.is_ok());
     println!("cpu type: {}", cpu_type.unwrap());
 }
+
+/// 配置文件删除或者改名后，对应的服务在下一次scan时下线
+#[test]
+fn scan_removed() {
+    let dir = std::env::temp_dir().join(format!("breeze_scan_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = |name: &str| dir.join(name);
+    std::fs::write(file("svc1@redis:56810@rs"), "").unwrap();
+    std::fs::write(file("svc2@mc@cs"), "").unwrap();
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut listeners = context::ListenerIter::from(dir.to_str().unwrap().to_string());
+    let (added, removed, failed) = runtime.block_on(listeners.scan());
+    assert_eq!((added.len(), removed.len(), failed), (2, 0, 0));
+
+    std::fs::remove_file(file("svc1@redis:56810@rs")).unwrap();
+    std::fs::rename(file("svc2@mc@cs"), file("svc2@mc:56811@cs")).unwrap();
+    let (added, removed, failed) = runtime.block_on(listeners.scan());
+    let removed: Vec<String> = removed.iter().map(|q| q.name()).collect();
+    assert_eq!(removed.len(), 2);
+    assert!(removed.contains(&"svc1@redis:56810@rs".to_string()));
+    assert!(removed.contains(&"svc2@mc@cs".to_string()));
+    assert_eq!(added.len(), 1);
+    assert_eq!(added[0].name(), "svc2@mc:56811@cs");
+    assert_eq!(failed, 0);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// protocol中声明了tls的tcp侦听需要终结TLS，unix侦听忽略tls
+#[test]
+fn scan_tls() {
+    let dir = std::env::temp_dir().join(format!("breeze_scan_tls_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("svc1@redis:56820:tls@rs"), "").unwrap();
+    std::fs::write(dir.join("svc2@redis:56821@rs"), "").unwrap();
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut listeners = context::ListenerIter::from(dir.to_str().unwrap().to_string());
+    let (added, _, failed) = runtime.block_on(listeners.scan());
+    assert_eq!((added.len(), failed), (2, 0));
+    let tls = |service: &str| added.iter().find(|q| q.service() == service).unwrap().tls();
+    assert!(tls("svc1"));
+    assert!(!tls("svc2"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
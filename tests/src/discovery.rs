@@ -17,9 +17,10 @@ impl RandomLoad {
 }
 
 impl TopologyWrite for RandomLoad {
-    fn update(&mut self, _name: &str, cfg: &str) {
-        self.need_load = cfg.parse().unwrap();
+    fn update(&mut self, _name: &str, cfg: &str) -> Result<(), discovery::UpdateError> {
+        self.need_load = cfg.parse()?;
         self.count = 0;
+        Ok(())
     }
 
     fn need_load(&self) -> bool {
@@ -43,7 +44,7 @@ fn refresh() {
     assert!(!tx.need_load());
 
     //第一次没load成功，所以还是还是上一版top.need_load=0
-    tx.update(service, "2");
+    tx.update(service, "2").unwrap();
     assert_eq!(rx.get().need_load, 0);
     //第二次load成功
     assert!(tx.need_load());
@@ -52,7 +53,7 @@ fn refresh() {
     assert_eq!(rx.get().need_load, 2);
 
     //不需要load的场景
-    tx.update(service, "1");
+    tx.update(service, "1").unwrap();
     assert!(!tx.need_load());
     assert_eq!(rx.get().need_load, 1);
     //已经成功load后，load也会返回true
@@ -60,8 +61,8 @@ fn refresh() {
     assert_eq!(rx.get().need_load, 1);
 
     //并发更新，只有最后一个生效
-    tx.update(service, "2");
-    tx.update(service, "3");
+    tx.update(service, "2").unwrap();
+    tx.update(service, "3").unwrap();
     assert!(tx.need_load());
     assert_eq!(rx.get().need_load, 1);
     assert!(!tx.load());
@@ -71,3 +72,162 @@ fn refresh() {
     assert!(!tx.need_load());
     assert_eq!(rx.get().need_load, 3);
 }
+
+/// 被拒绝的配置不生效，按topology的服务名记录，之后有配置校验通过时清除
+#[test]
+fn rejected() {
+    let service = "config+cloud+redis+group:ns";
+    init_metrics_onlyfor_test();
+    let (mut tx, rx) = discovery::topology(RandomLoad::new(0), service);
+    tx.update("ns", "1").unwrap();
+    assert!(discovery::rejected(service).is_none());
+
+    assert!(tx.update("ns", "x").is_err());
+    assert_eq!(rx.get().need_load, 1);
+    let r = discovery::rejected(service).expect("rejected");
+    assert_eq!(r.cfg, "x");
+    assert!(!r.error.is_empty());
+    assert!(discovery::rejected("config+cloud+redis+other:ns").is_none());
+
+    tx.update("ns", "0").unwrap();
+    assert_eq!(rx.get().need_load, 0);
+    assert!(discovery::rejected(service).is_none());
+}
+
+/// file:// 从本地目录读取配置，mtime未变化时不重新读取
+#[test]
+fn local_discovery() {
+    use discovery::{Config, Discover, Discovery};
+    use std::time::{Duration, SystemTime};
+
+    let dir = std::env::temp_dir().join("breeze_local_discovery");
+    std::fs::create_dir_all(dir.join("3/config/redis")).unwrap();
+    let path = dir.join("3/config/redis/group");
+    std::fs::write(&path, "cfg1").unwrap();
+    let url = url::Url::parse(&format!("file://{}", dir.display())).unwrap();
+    let d = Discovery::from_url(&url);
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let name = "3/config/redis/group";
+        let sig = match d.get_service::<String>(name, "").await.unwrap() {
+            Config::Config(sig, cfg) => {
+                assert_eq!(cfg, "cfg1");
+                sig
+            }
+            c => panic!("unexpected: {:?}", c),
+        };
+        let c = d.get_service::<String>(name, &sig).await.unwrap();
+        assert!(matches!(c, Config::NotChanged));
+        let c = d.get_service::<String>("3/config/redis/none", "").await;
+        assert!(matches!(c.unwrap(), Config::NotFound));
+
+        std::fs::write(&path, "cfg2").unwrap();
+        let mtime = SystemTime::now() + Duration::from_secs(10);
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(mtime).unwrap();
+        match d.get_service::<String>(name, &sig).await.unwrap() {
+            Config::Config(new_sig, cfg) => {
+                assert_ne!(new_sig, sig);
+                assert_eq!(cfg, "cfg2");
+            }
+            c => panic!("unexpected: {:?}", c),
+        }
+    });
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// 快照先写临时文件再rename，历史版本最多保留history个
+#[test]
+fn snapshot_history() {
+    use discovery::snapshot::{Content, Snapshot};
+    let dir = std::env::temp_dir().join(format!("breeze_snapshot_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let snapshot = Snapshot::new(dir.to_str().unwrap().to_string(), 2);
+    let group = "config+cloud+redis+group";
+    let content = |sig: &str| Content {
+        sig: sig.to_string(),
+        group: "group".to_string(),
+        cfg: format!("cfg {sig}"),
+    };
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        assert!(snapshot.load(group, None).await.is_none());
+        snapshot.dump(group, &content("sig1")).await.unwrap();
+        let c = snapshot.load(group, None).await.unwrap();
+        assert_eq!((c.sig.as_str(), c.group.as_str()), ("sig1", "group"));
+        assert_eq!(c.cfg, "cfg sig1");
+        assert!(!dir.join(format!("{group}.tmp")).exists());
+
+        let versions = snapshot.versions(&format!("{group}:ns")).await;
+        assert_eq!(versions.len(), 1);
+
+        // 连续的dump各自保留一个版本，只保留最新的2个
+        snapshot.dump(group, &content("sig2")).await.unwrap();
+        snapshot.dump(group, &content("sig3")).await.unwrap();
+        let c = snapshot.load(group, None).await.unwrap();
+        assert_eq!(c.sig, "sig3");
+        let versions = snapshot.versions(group).await;
+        assert_eq!(versions.len(), 2);
+        assert!(versions[0] > versions[1]);
+        let c = snapshot.load(group, Some(versions[0])).await.unwrap();
+        assert_eq!(c.sig, "sig3");
+        let c = snapshot.load(group, Some(versions[1])).await.unwrap();
+        assert_eq!(c.sig, "sig2");
+    });
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// consul:// 响应中没有X-Consul-Index时，以内容的md5作为签名，内容不变时返回NotChanged
+#[test]
+fn consul_without_index() {
+    use discovery::{Config, Discover, Discovery};
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        // 模拟经过代理的consul，只返回内容，不返回X-Consul-Index
+        let cfg = Arc::new(Mutex::new("cfg1"));
+        let l = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = l.local_addr().unwrap();
+        let body = cfg.clone();
+        tokio::spawn(async move {
+            while let Ok((mut s, _)) = l.accept().await {
+                let mut buf = vec![0u8; 4096];
+                let _ = s.read(&mut buf).await;
+                let body = *body.lock().unwrap();
+                let rsp = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = s.write_all(rsp.as_bytes()).await;
+            }
+        });
+
+        let url = url::Url::parse(&format!("consul://{addr}/breeze")).unwrap();
+        let d = Discovery::from_url(&url);
+        let name = "3/config/redis/group";
+        let sig = match d.get_service::<String>(name, "").await.unwrap() {
+            Config::Config(sig, cfg) => {
+                assert_eq!(cfg, "cfg1");
+                sig
+            }
+            c => panic!("unexpected: {:?}", c),
+        };
+        assert!(!sig.is_empty());
+        let c = d.get_service::<String>(name, &sig).await.unwrap();
+        assert!(matches!(c, Config::NotChanged));
+
+        *cfg.lock().unwrap() = "cfg2";
+        match d.get_service::<String>(name, &sig).await.unwrap() {
+            Config::Config(new_sig, cfg) => {
+                assert_ne!(new_sig, sig);
+                assert_eq!(cfg, "cfg2");
+            }
+            c => panic!("unexpected: {:?}", c),
+        }
+    });
+}
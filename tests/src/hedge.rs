@@ -0,0 +1,138 @@
+use endpoint::Topology;
+use endpoint::hedge::{self, Hedge};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::pipeline_hook::{self, cmd};
+
+// 对冲的预算是全局的，用到预算的测试串行执行
+static SERIAL: Mutex<()> = Mutex::new(());
+
+#[test]
+fn hedge_disabled() {
+    assert!(Hedge::new(0).is_none());
+}
+
+/// 样本足够后，每秒按分位数更新一次对冲延迟；对冲次数不超过读请求数的budget%
+#[test]
+fn hedge_delay_budget() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    hedge::set_budget(10);
+    let h = Hedge::new(90).expect("hedge");
+    assert_eq!(h.delay(), None);
+    assert!(!h.acquire());
+
+    // 90%的请求耗时1ms，10%的请求耗时100ms
+    for i in 0..1000 {
+        let ms = if i % 10 == 0 { 100 } else { 1 };
+        h.record(ms * 1000);
+    }
+    // 跨过秒的边界后更新
+    std::thread::sleep(Duration::from_millis(1100));
+    h.record(1000);
+    let delay = h.delay().expect("delay");
+    assert!(
+        delay >= Duration::from_millis(1) && delay < Duration::from_millis(2),
+        "{:?}",
+        delay
+    );
+
+    let hedges = (0..200).filter(|_| h.acquire()).count();
+    assert!((95..=101).contains(&hedges), "{}", hedges);
+}
+
+// 两个从共享的模拟后端：w开头的key直接返回；
+// h开头的key第一次访问很慢，对冲请求很快返回；r开头的key第一次访问较快，对冲请求很慢
+fn replica(
+    seen: Arc<Mutex<HashMap<Vec<u8>, usize>>>,
+) -> impl Fn(&[Vec<u8>]) -> Option<(Duration, Vec<u8>)> {
+    move |args: &[Vec<u8>]| {
+        let key = args.get(1)?.clone();
+        let n = {
+            let mut seen = seen.lock().unwrap();
+            let n = seen.entry(key.clone()).or_default();
+            *n += 1;
+            *n
+        };
+        let slow = Duration::from_millis(300);
+        let rsp = match (key[0], n) {
+            (b'h', 1) => (slow, "slow"),
+            (b'h', _) => (Duration::ZERO, "fast"),
+            (b'r', 1) => (Duration::from_millis(20), "primary"),
+            (b'r', _) => (slow, "hedge"),
+            _ => (Duration::ZERO, "w"),
+        };
+        Some((
+            rsp.0,
+            format!("${}\r\n{}\r\n", rsp.1.len(), rsp.1).into_bytes(),
+        ))
+    }
+}
+
+/// 原请求超过对冲延迟未完成时，向另一个从发送对冲请求：
+/// 对冲请求先返回时使用对冲的响应（hedge_won），原请求先返回时丢弃对冲请求（release_hedge），
+/// 被替代的请求在之后完成时释放（discarded），不影响后续的请求
+#[test]
+fn hedge_pipeline() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    pipeline_hook::run(async {
+        hedge::set_budget(10);
+        let seen = Arc::new(Mutex::new(HashMap::new()));
+        let a = pipeline_hook::fake_redis(replica(seen.clone())).await;
+        let b = pipeline_hook::fake_redis(replica(seen.clone())).await;
+        let cfg = format!(
+            "basic:\n  hash: crc32\n  distribution: modula\n  hedge_percentile: 90\n  timeout_ms_slave: 2000\nbackends:\n  - {a},{a},{b}\n"
+        );
+        let service = pipeline_hook::redis_service("hedge_pipeline", &cfg).await;
+        let mut client = service.connect().await;
+
+        // 积累足够的样本及预算，跨过秒的边界后更新对冲延迟
+        for i in 0..200 {
+            client
+                .request(&cmd(&["get", &format!("w{i}")]), b"$1\r\nw\r\n")
+                .await;
+        }
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        client.request(&cmd(&["get", "w"]), b"$1\r\nw\r\n").await;
+        assert!(service.top().hedge().and_then(|h| h.delay()).is_some());
+
+        // 原请求先完成，对冲请求被丢弃
+        for i in 0..3 {
+            client
+                .request(&cmd(&["get", &format!("r{i}")]), b"$7\r\nprimary\r\n")
+                .await;
+        }
+        // 等待被丢弃的对冲请求完成，后端按连接顺序处理请求
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        // 对冲请求先完成，不需要等待慢的原请求
+        for i in 0..3 {
+            let start = Instant::now();
+            client
+                .request(&cmd(&["get", &format!("h{i}")]), b"$4\r\nfast\r\n")
+                .await;
+            assert!(
+                start.elapsed() < Duration::from_millis(250),
+                "{:?}",
+                start.elapsed()
+            );
+            // 等待慢的原请求完成，下一个请求的原请求不用排队
+            tokio::time::sleep(Duration::from_millis(350)).await;
+        }
+        // 每个请求最多对冲一次
+        let seen = seen.lock().unwrap().clone();
+        assert!(
+            (0..3).all(|i| seen[format!("r{i}").as_bytes()] == 2),
+            "{:?}",
+            seen
+        );
+        assert!(
+            (0..3).all(|i| seen[format!("h{i}").as_bytes()] == 2),
+            "{:?}",
+            seen
+        );
+
+        // 被替代的请求都完成之后，pipeline仍然正常处理请求
+        client.request(&cmd(&["get", "w"]), b"$1\r\nw\r\n").await;
+    });
+}
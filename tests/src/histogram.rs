@@ -0,0 +1,67 @@
+use metrics::Histogram;
+
+#[test]
+fn histogram_percentiles() {
+    let h = Histogram::default();
+    assert_eq!(h.take_percentiles(), None);
+    for us in 1..=1000 {
+        h.record(us);
+    }
+    // 合并到累计计数之前不可见
+    assert_eq!(h.count(), 0);
+    // 取所在桶的上界，误差不超过12.5%
+    let [p50, p99, p999] = h.take_percentiles().expect("percentiles");
+    assert_eq!(h.count(), 1000);
+    assert_eq!(h.sum_us(), 500500);
+    assert!((500..=563).contains(&p50), "{p50}");
+    assert!((990..=1114).contains(&p99), "{p99}");
+    assert!((p99..=1124).contains(&p999), "{p999}");
+    // 分位数只统计两次调用之间的请求
+    assert_eq!(h.take_percentiles(), None);
+    h.record(100_000);
+    let [p50, _, p999] = h.take_percentiles().expect("percentiles");
+    assert!((100_001..=112_500).contains(&p50), "{p50}");
+    assert_eq!(p50, p999);
+}
+
+// 累计分布按2的幂次输出，只增不减
+#[test]
+fn histogram_cumulative() {
+    let h = Histogram::default();
+    h.record(127);
+    h.record(128);
+    h.record(1 << 20);
+    h.record(u32::MAX as u64);
+    let _ = h.take_percentiles();
+    let les = h.cumulative();
+    assert_eq!(les[0], (128, 1));
+    assert_eq!(les[1], (256, 2));
+    assert_eq!(
+        les.iter().find(|(le, _)| *le == 1 << 20),
+        Some(&(1 << 20, 2))
+    );
+    assert_eq!(les.last(), Some(&(1 << 24, 3)));
+    assert!(les.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 <= w[1].1));
+
+    assert_eq!(h.take_percentiles(), None);
+    assert_eq!(h.cumulative(), les);
+    assert_eq!(h.count(), 4);
+}
+
+// 各线程分别记录，snapshot时合并
+#[test]
+fn histogram_threads() {
+    let h = std::sync::Arc::new(Histogram::default());
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let h = h.clone();
+            std::thread::spawn(move || (1..=1000).for_each(|us| h.record(us)))
+        })
+        .collect();
+    threads.into_iter().for_each(|t| t.join().expect("join"));
+    let [p50, _, _] = h.take_percentiles().expect("percentiles");
+    assert!((500..=563).contains(&p50), "{p50}");
+    assert_eq!(h.count(), 8000);
+    assert_eq!(h.sum_us(), 8 * 500500);
+    assert_eq!(h.take_percentiles(), None);
+}
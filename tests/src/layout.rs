@@ -45,9 +45,9 @@ fn checkout_basic() {
     assert_eq!(8, size_of::<metrics::Metric>());
     assert_eq!(64, size_of::<metrics::Item>());
     assert_eq!(1, size_of::<Parser>());
-    assert_eq!(56, size_of::<BackendInner<Request>>());
+    assert_eq!(72, size_of::<BackendInner<Request>>());
     assert_eq!(40, size_of::<CheckedTopology>());
-    assert_eq!(192, size_of::<stream::StreamMetrics>());
+    assert_eq!(232, size_of::<stream::StreamMetrics>());
     assert_eq!(24, size_of::<sharding::hash::Hasher>());
 }
 
@@ -60,7 +60,7 @@ fn check_layout_rx_buffer() {
 #[ignore]
 #[test]
 fn check_callback_ctx() {
-    assert_eq!(192, size_of::<CallbackContext>());
+    assert_eq!(224, size_of::<CallbackContext>());
     //assert_eq!(16, size_of::<protocol::callback::Context>());
 }
 //#[ignore]
@@ -86,7 +86,7 @@ fn check_topology() {
     assert_eq!(24, size_of::<sharding::hash::Hasher>());
     assert_eq!(976, size_of::<Topology>());
     assert_eq!(72, size_of::<CacheService>());
-    assert_eq!(96, size_of::<RedisService>());
+    assert_eq!(104, size_of::<RedisService>());
     assert_eq!(56, size_of::<PhantomService>());
 
     assert_eq!(152, size_of::<MsgQue>());
@@ -0,0 +1,147 @@
+use crate::proto_hook;
+use protocol::{memcache::MemcacheBinary, HashedCommand, Operation, Proto, RequestProcessor};
+
+struct MultiProcess {
+    reqs: Vec<(HashedCommand, bool)>,
+}
+
+impl RequestProcessor for MultiProcess {
+    fn process(&mut self, req: HashedCommand, last: bool) {
+        self.reqs.push((req, last));
+    }
+}
+
+// 构建一个二进制协议的请求
+fn packet(op: u8, extra: &[u8], key: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut p = vec![0x80, op];
+    p.extend_from_slice(&(key.len() as u16).to_be_bytes());
+    p.push(extra.len() as u8);
+    p.extend_from_slice(&[0, 0, 0]);
+    let body = extra.len() + key.len() + value.len();
+    p.extend_from_slice(&(body as u32).to_be_bytes());
+    p.extend_from_slice(&[0; 12]);
+    p.extend_from_slice(extra);
+    p.extend_from_slice(key);
+    p.extend_from_slice(value);
+    p
+}
+
+fn parse(data: Vec<u8>) -> MultiProcess {
+    let proto = MemcacheBinary;
+    let alg = &proto_hook::Alg {};
+    let mut process = MultiProcess { reqs: Vec::new() };
+    let mut stream = proto_hook::TestStream {
+        oft: 0,
+        ctx: Default::default(),
+        inner: data,
+    };
+    proto
+        .parse_request(&mut stream, alg, &mut process)
+        .expect("parse request");
+    process
+}
+
+/// incr/decr、append/prepend、replace、touch、gat 均按store处理，非quiet请求需要等待响应
+#[test]
+fn test_store_ops() {
+    let incr_extra = [0u8; 20];
+    let reqs = [
+        packet(0x05, &incr_extra, b"counter", b""), // incr
+        packet(0x06, &incr_extra, b"counter", b""), // decr
+        packet(0x0e, b"", b"key", b"tail"),         // append
+        packet(0x0f, b"", b"key", b"head"),         // prepend
+        packet(0x03, &[0u8; 8], b"key", b"v"),      // replace
+        packet(0x1c, &[0u8; 4], b"key", b""),       // touch
+        packet(0x1d, &[0u8; 4], b"key", b""),       // gat
+        packet(0x23, &[0u8; 4], b"key", b""),       // gatk
+    ];
+    for req in reqs {
+        let op = req[1];
+        let process = parse(req);
+        assert_eq!(process.reqs.len(), 1);
+        let (req, last) = &process.reqs[0];
+        assert!(last);
+        assert_eq!(req.operation(), Operation::Store, "op:{op:#x}");
+        assert!(!req.sentonly(), "op:{op:#x}");
+        assert!(!req.noforward(), "op:{op:#x}");
+    }
+}
+
+/// quiet的incr/append只发送，不等待响应
+#[test]
+fn test_quiet_store_ops() {
+    for op in [0x13u8, 0x15, 0x16, 0x19, 0x1a] {
+        let process = parse(packet(op, &[0u8; 8], b"key", b""));
+        let (req, _) = &process.reqs[0];
+        assert_eq!(req.operation(), Operation::Store, "op:{op:#x}");
+        assert!(req.sentonly(), "op:{op:#x}");
+    }
+}
+
+/// gatq与getq一样，作为multi-gat的非结尾请求，转换为gat发送
+#[test]
+fn test_multi_gat() {
+    let mut data = packet(0x1e, &[0u8; 4], b"key1", b"");
+    data.extend(packet(0x1e, &[0u8; 4], b"key2", b""));
+    data.extend(packet(0x1d, &[0u8; 4], b"key3", b""));
+    let process = parse(data);
+    assert_eq!(process.reqs.len(), 3);
+    for (i, (req, last)) in process.reqs.iter().enumerate() {
+        assert_eq!(*last, i == 2);
+        assert_eq!(req.at(1), 0x1d);
+        assert!(!req.sentonly());
+    }
+}
+
+/// 后端没有响应时，只有读请求返回NotFound，incr/decr/touch与其他写请求一样返回NotStored
+#[test]
+fn test_no_response() {
+    let proto = MemcacheBinary;
+    let reqs = [
+        (packet(0x00, b"", b"key", b""), 0x01),            // get
+        (packet(0x1d, &[0u8; 4], b"key", b""), 0x01),      // gat
+        (packet(0x05, &[0u8; 20], b"counter", b""), 0x05), // incr
+        (packet(0x06, &[0u8; 20], b"counter", b""), 0x05), // decr
+        (packet(0x1c, &[0u8; 4], b"key", b""), 0x05),      // touch
+        (packet(0x01, &[0u8; 8], b"key", b"v"), 0x05),     // set
+    ];
+    for (req, status) in reqs {
+        let op = req[1];
+        let (req, _) = parse(req).reqs.remove(0);
+        let mut ctx = proto_hook::TestCtx::new(req);
+        let mut w = proto_hook::TestStream {
+            oft: 0,
+            ctx: Default::default(),
+            inner: Vec::new(),
+        };
+        proto.write_response(&mut ctx, None, &mut w).expect("write");
+        assert_eq!(w.inner[1], op);
+        assert_eq!(
+            u16::from_be_bytes([w.inner[6], w.inner[7]]),
+            status,
+            "op:{op:#x}"
+        );
+    }
+}
+
+/// touch没有quiet指令，回写时保持touch，仍需等待响应
+#[test]
+fn test_touch_write_back() {
+    let proto = MemcacheBinary;
+    let (req, _) = parse(packet(0x1c, &[0u8; 4], b"key", b"")).reqs.remove(0);
+    let mut ctx = proto_hook::TestCtx::new(req);
+    let mut rsp = packet(0x1c, b"", b"", b"");
+    rsp[0] = 0x81;
+    let mut s = proto_hook::TestStream {
+        oft: 0,
+        ctx: Default::default(),
+        inner: rsp,
+    };
+    let rsp = proto
+        .parse_response(&mut s)
+        .expect("rsp")
+        .expect("complete");
+    assert!(proto.build_writeback_request(&mut ctx, &rsp, 0).is_none());
+    assert_eq!(ctx.req.at(1), 0x1c);
+    assert!(!ctx.req.sentonly());
+}
@@ -1,56 +1,235 @@
-#[cfg(test)]
-mod tests {
-    use ds::Slice;
-    use protocol::Protocol;
-
-    #[test]
-    fn test_parse_request() {
-        println!("begin");
-        let get_request = Slice::from("get key1\r\n".as_ref());
-        let get_not_supported_request = Slice::from("get key1 key2 key3 key4\r\n".as_ref());
-        let gets_request = Slice::from("gets key1 key2 key3 key4\r\n".as_ref());
-        let set_request = Slice::from("set key1 0 3600 6\r\nvalue1\r\n".as_ref());
-        let version_request = Slice::from("version\r\n".as_ref());
-
-        let parser = protocol::memcache::MemcacheText::new();
-        let get_parse_result = parser.parse_request(get_request);
-        let get_not_supported_parse_result = parser.parse_request(get_not_supported_request);
-        let gets_parse_result = parser.parse_request(gets_request);
-        let set_parse_result = parser.parse_request(set_request);
-        let version_parse_result = parser.parse_request(version_request);
-
-        let get = get_parse_result.unwrap().unwrap();
-        println!(
-            "get op = {}, keys.len = {}, keys[0] = {}",
-            get.operation().name(),
-            get.keys().len(),
-            String::from_utf8(get.keys()[0].to_vec()).unwrap()
-        );
-
-        println!(
-            "get not supported is_none: {}",
-            get_not_supported_parse_result.unwrap().is_none()
-        );
-
-        let gets = gets_parse_result.unwrap().unwrap();
-        println!(
-            "gets op = {}, keys.len = {}, keys[0] = {}, keys[1] = {}, keys[2] = {}, keys[3] = {}",
-            gets.operation().name(),
-            gets.keys().len(),
-            String::from_utf8(gets.keys()[0].to_vec()).unwrap(),
-            String::from_utf8(gets.keys()[1].to_vec()).unwrap(),
-            String::from_utf8(gets.keys()[2].to_vec()).unwrap(),
-            String::from_utf8(gets.keys()[3].to_vec()).unwrap()
-        );
-
-        let set = set_parse_result.unwrap().unwrap();
-        println!(
-            "set op = {}, keys[0] = {}",
-            set.operation().name(),
-            String::from_utf8(set.keys()[0].to_vec()).unwrap()
-        );
-
-        let version = version_parse_result.unwrap().unwrap();
-        println!("version op = {}", version.operation().name());
+use crate::proto_hook;
+use protocol::{
+    memcache::MemcacheText, BufRead, Command, HashedCommand, Operation, Proto, RequestProcessor,
+};
+
+struct MultiProcess {
+    reqs: Vec<(HashedCommand, bool)>,
+}
+
+impl RequestProcessor for MultiProcess {
+    fn process(&mut self, req: HashedCommand, last: bool) {
+        self.reqs.push((req, last));
+    }
+}
+
+fn stream(data: &[u8]) -> proto_hook::TestStream {
+    proto_hook::TestStream {
+        oft: 0,
+        ctx: Default::default(),
+        inner: data.to_vec(),
+    }
+}
+
+fn parse(data: &[u8]) -> (MultiProcess, proto_hook::TestStream) {
+    let proto = MemcacheText;
+    let alg = &proto_hook::Alg {};
+    let mut process = MultiProcess { reqs: Vec::new() };
+    let mut stream = stream(data);
+    proto
+        .parse_request(&mut stream, alg, &mut process)
+        .expect("parse request");
+    (process, stream)
+}
+
+/// multi-key get 拆分为多个单key的子请求，只有最后一个子请求的last为true
+#[test]
+fn test_multi_get_split() {
+    let (process, stream) = parse(b"get key1 key2 key3\r\n");
+    assert_eq!(stream.len(), 0);
+    assert_eq!(process.reqs.len(), 3);
+    for (i, (req, last)) in process.reqs.iter().enumerate() {
+        assert!(req.equal(format!("get key{}\r\n", i + 1).as_bytes()), "{req:?}");
+        assert_eq!(req.operation(), Operation::MGet);
+        assert_eq!(*last, i == 2);
+        assert!(!req.noforward());
+    }
+
+    let (process, _) = parse(b"gets key1 key2\r\n");
+    assert_eq!(process.reqs.len(), 2);
+    assert!(process.reqs[0].0.equal(b"gets key1\r\n"));
+    assert!(process.reqs[1].0.equal(b"gets key2\r\n"));
+    assert!(process.reqs.iter().all(|(r, _)| r.operation() == Operation::Gets));
+
+    // 单key请求原样透传
+    let (process, _) = parse(b"get key1\r\n");
+    assert_eq!(process.reqs.len(), 1);
+    assert!(process.reqs[0].1);
+    assert!(process.reqs[0].0.equal(b"get key1\r\n"));
+    assert_eq!(process.reqs[0].0.operation(), Operation::Get);
+}
+
+/// 请求以任意长度到达
+#[test]
+fn test_req_reenter() {
+    let reqs = b"set key1 0 3600 6\r\nvalue1\r\ndelete key1 noreply\r\nincr key2 1\r\ncas key3 0 0 1 99\r\na\r\n";
+    let proto = MemcacheText;
+    let alg = &proto_hook::Alg {};
+    for i in 0..reqs.len() {
+        let mut process = MultiProcess { reqs: Vec::new() };
+        let (part, _) = reqs.split_at(i);
+        let mut stream = stream(part);
+        proto
+            .parse_request(&mut stream, alg, &mut process)
+            .expect("parse request");
+        let parsed: usize = process.reqs.iter().map(|(r, _)| r.len()).sum();
+        assert_eq!(stream.len(), part.len() - parsed);
+    }
+
+    let (process, stream) = parse(reqs);
+    assert_eq!(stream.len(), 0);
+    assert_eq!(process.reqs.len(), 4);
+    let (set, _) = &process.reqs[0];
+    assert!(set.equal(b"set key1 0 3600 6\r\nvalue1\r\n"));
+    assert_eq!(set.operation(), Operation::Store);
+    assert!(!set.sentonly());
+    let (del, _) = &process.reqs[1];
+    assert!(del.sentonly());
+    assert!(process.reqs[2].0.equal(b"incr key2 1\r\n"));
+    assert!(process.reqs[3].0.equal(b"cas key3 0 0 1 99\r\na\r\n"));
+}
+
+#[test]
+fn test_meta() {
+    let (process, _) = parse(b"version\r\nquit\r\n");
+    assert_eq!(process.reqs.len(), 2);
+    assert!(process
+        .reqs
+        .iter()
+        .all(|(r, _)| r.noforward() && r.operation() == Operation::Meta));
+}
+
+/// 按完整的指令名匹配，前缀相同的指令也返回ERROR，不转发
+#[test]
+fn test_unknown_cmd() {
+    let proto = MemcacheText;
+    let reqs = b"unknown key\r\ndelet k\r\ntouchy k 0\r\nversionx\r\ngetk k\r\nge\r\n\r\ndelete k\r\n";
+    let (process, left) = parse(reqs);
+    assert_eq!(left.len(), 0);
+    assert_eq!(process.reqs.len(), 8);
+    for (req, last) in process.reqs.into_iter().take(7) {
+        assert!(req.noforward() && last, "{req:?}");
+        let mut ctx = proto_hook::TestCtx::new(req);
+        let mut w = stream(b"");
+        proto.write_response(&mut ctx, None, &mut w).expect("write");
+        assert_eq!(w.inner, b"ERROR\r\n".to_vec());
+    }
+    let (process, _) = parse(b"delete k\r\ntouch k 0\r\n");
+    assert!(process.reqs.iter().all(|(r, _)| !r.noforward()));
+}
+
+#[test]
+fn test_parse_response() {
+    let proto = MemcacheText;
+    let cases: [(&[u8], bool); 7] = [
+        (b"VALUE key1 0 6\r\nvalue1\r\nEND\r\n", true),
+        (b"END\r\n", false),
+        (b"STORED\r\n", true),
+        (b"NOT_STORED\r\n", false),
+        (b"NOT_FOUND\r\n", true),
+        (b"EXISTS\r\n", false),
+        (b"12\r\n", true),
+    ];
+    for (rsp, ok) in cases {
+        let mut s = stream(rsp);
+        let cmd = proto.parse_response(&mut s).expect("rsp").expect("complete");
+        assert_eq!(cmd.ok(), ok, "{:?}", rsp);
+        assert!(cmd.equal(rsp));
+        assert_eq!(s.len(), 0);
+    }
+
+    // 数据不完整时等待
+    let rsp = b"VALUE key1 0 6 100\r\nvalue1\r\nEND\r\n";
+    for i in 0..rsp.len() {
+        let mut s = stream(&rsp[..i]);
+        assert!(proto.parse_response(&mut s).expect("rsp").is_none());
+    }
+}
+
+/// 子请求的响应只回写VALUE部分，最后一个子请求补上END
+#[test]
+fn test_write_response() {
+    let proto = MemcacheText;
+    let (process, _) = parse(b"get key1 key2 key3\r\n");
+    let rsps: [&[u8]; 3] = [
+        b"VALUE key1 0 1\r\na\r\nEND\r\n",
+        b"END\r\n",
+        b"VALUE key3 0 1\r\nc\r\nEND\r\n",
+    ];
+    let mut w = stream(b"");
+    for ((req, _), rsp) in process.reqs.into_iter().zip(rsps) {
+        let mut ctx = proto_hook::TestCtx::new(req);
+        let mut rsp = proto.parse_response(&mut stream(rsp)).unwrap().unwrap();
+        proto
+            .write_response(&mut ctx, Some(&mut rsp), &mut w)
+            .expect("write");
+    }
+    assert_eq!(
+        w.inner,
+        b"VALUE key1 0 1\r\na\r\nVALUE key3 0 1\r\nc\r\nEND\r\n".to_vec()
+    );
+}
+
+#[test]
+fn test_write_back() {
+    let proto = MemcacheText;
+    let (mut process, _) = parse(b"get key1\r\n");
+    let (req, _) = process.reqs.remove(0);
+    let mut ctx = proto_hook::TestCtx::new(req);
+    let rsp: Command = proto
+        .parse_response(&mut stream(b"VALUE key1 5 6 100\r\nvalue1\r\nEND\r\n"))
+        .unwrap()
+        .unwrap();
+    let wb = proto
+        .build_writeback_request(&mut ctx, &rsp, 3600)
+        .expect("write back");
+    assert!(wb.equal(b"set key1 5 3600 6 noreply\r\nvalue1\r\n"), "{wb:?}");
+    assert!(wb.sentonly());
+    assert_eq!(wb.operation(), Operation::Store);
+
+    let (mut process, _) = parse(b"cas key1 5 0 6 100\r\nvalue1\r\n");
+    let (req, _) = process.reqs.remove(0);
+    let mut ctx = proto_hook::TestCtx::new(req);
+    let rsp = proto
+        .parse_response(&mut stream(b"STORED\r\n"))
+        .unwrap()
+        .unwrap();
+    let wb = proto
+        .build_writeback_request(&mut ctx, &rsp, 0)
+        .expect("write back");
+    assert!(wb.equal(b"set key1 5 0 6 noreply\r\nvalue1\r\n"), "{wb:?}");
+    assert!(wb.sentonly());
+}
+
+/// 后端没有响应时返回SERVER_ERROR，key超长时不转发，直接返回CLIENT_ERROR
+#[test]
+fn test_no_response_and_bad_key() {
+    let proto = MemcacheText;
+    let (process, _) = parse(b"set key1 0 0 1\r\na\r\ndelete key1\r\n");
+    for (req, _) in process.reqs {
+        let mut ctx = proto_hook::TestCtx::new(req);
+        let mut w = stream(b"");
+        proto.write_response(&mut ctx, None, &mut w).expect("write");
+        assert_eq!(w.inner, b"SERVER_ERROR no response\r\n".to_vec());
+    }
+
+    let long = "k".repeat(251);
+    let reqs = format!(
+        "get key1 {long}\r\nset {long} 0 0 1\r\na\r\ndelete {long}\r\nget key1\r\n"
+    );
+    let (process, stream_left) = parse(reqs.as_bytes());
+    assert_eq!(stream_left.len(), 0);
+    assert_eq!(process.reqs.len(), 4);
+    for (req, last) in &process.reqs[..3] {
+        assert!(req.noforward() && *last, "{req:?}");
+    }
+    assert!(!process.reqs[3].0.noforward());
+    for (req, _) in process.reqs.into_iter().take(3) {
+        let mut ctx = proto_hook::TestCtx::new(req);
+        let mut w = stream(b"");
+        proto.write_response(&mut ctx, None, &mut w).expect("write");
+        assert_eq!(w.inner, b"CLIENT_ERROR bad command line format\r\n".to_vec());
     }
+    let (process, _) = parse(format!("get {}\r\n", "k".repeat(250)).as_bytes());
+    assert!(!process.reqs[0].0.noforward());
 }
@@ -0,0 +1,45 @@
+use ds::time::Instant;
+use metrics::{Path, otlp, snapshot::Records};
+
+fn count(buf: &[u8], s: &str) -> usize {
+    buf.windows(s.len()).filter(|w| *w == s.as_bytes()).count()
+}
+
+/// 按比例采样，每个请求编码为一个root span及四个阶段的span
+#[test]
+fn otlp_spans() {
+    assert!(!otlp::enabled());
+    assert!(!otlp::sampled());
+    assert_eq!(otlp::export_spans(), None);
+
+    otlp::init(&[("pool", "test_pool"), ("idc", "")], 0.5);
+    assert!(otlp::enabled());
+    let sampled = (0..100).filter(|_| otlp::sampled()).count();
+    assert_eq!(sampled, 50);
+    // 只有调用sampled才计数
+    assert!(otlp::sample_next());
+    assert!(otlp::sample_next());
+    assert!(otlp::sampled());
+    assert!(!otlp::sample_next());
+    // 每个线程单独计数
+    let other = std::thread::spawn(|| otlp::sample_next() && otlp::sampled());
+    assert!(other.join().expect("join"));
+    assert!(!otlp::sampled());
+
+    let path = Path::new(vec!["mc", "ns1"]);
+    let start = Instant::now();
+    otlp::record_span(&path, "get", true, [start; 5]);
+    otlp::record_span(&path, "set", false, [start; 5]);
+    let buf = otlp::export_spans().expect("spans");
+    assert_eq!(count(&buf, "test_pool"), 1);
+    assert_eq!(count(&buf, "request"), 2);
+    for stage in ["parse", "topology.send", "backend", "write_response"] {
+        assert_eq!(count(&buf, stage), 2, "{}", stage);
+    }
+    assert_eq!(count(&buf, "ns1"), 2);
+    // 已导出的span被清空
+    assert_eq!(otlp::export_spans(), None);
+    // 指标来自每个周期的snapshot，没有指标时也带上资源属性
+    let buf = otlp::export_metrics(&Records::default()).expect("metrics");
+    assert_eq!(count(&buf, "test_pool"), 1);
+}
@@ -0,0 +1,156 @@
+use ds::time::{Duration, Instant};
+use metrics::tests::init_metrics_onlyfor_test;
+use protocol::outlier::Outlier;
+
+fn group(n: usize) -> Vec<Outlier> {
+    let addrs: Vec<String> = (0..n).map(|i| format!("127.0.0.{}:6379", i + 1)).collect();
+    Outlier::group(addrs.iter().map(|a| a.as_str()))
+}
+
+/// 窗口内失败率超过一半时摘除，请求数不足时不判断
+#[test]
+fn outlier_errors() {
+    init_metrics_onlyfor_test();
+    let replicas = group(4);
+    let o = &replicas[0];
+    for _ in 0..19 {
+        o.record(Instant::now(), false);
+    }
+    assert!(!o.ejected());
+    o.record(Instant::now(), false);
+    assert!(o.ejected());
+    assert!(!o.available());
+
+    // 失败率未超过阈值的不摘除
+    let o = &replicas[1];
+    for i in 0..40 {
+        o.record(Instant::now(), i % 3 != 0);
+    }
+    assert!(!o.ejected());
+    assert!(replicas[1].available());
+}
+
+/// 同一组中被摘除的副本不超过一半
+#[test]
+fn outlier_max_ejected() {
+    init_metrics_onlyfor_test();
+    let replicas = group(3);
+    for o in &replicas {
+        for _ in 0..20 {
+            o.record(Instant::now(), false);
+        }
+    }
+    assert_eq!(replicas.iter().filter(|o| o.ejected()).count(), 1);
+
+    // 只有一个副本时不摘除
+    let single = group(1);
+    for _ in 0..40 {
+        single[0].record(Instant::now(), false);
+    }
+    assert!(!single[0].ejected());
+}
+
+/// 平均耗时超过同组其他副本的3倍时摘除
+#[test]
+fn outlier_slow() {
+    init_metrics_onlyfor_test();
+    let replicas = group(3);
+    for _ in 0..20 {
+        replicas[1].record(Instant::now(), true);
+        replicas[2].record(Instant::now(), true);
+    }
+    let starts: Vec<Instant> = (0..20).map(|_| Instant::now()).collect();
+    std::thread::sleep(Duration::from_millis(15));
+    for start in starts {
+        replicas[0].record(start, true);
+    }
+    // 成功请求只在切换窗口时检查，失败请求会立即检查
+    replicas[0].record(Instant::now(), false);
+    assert!(replicas[0].ejected());
+    assert!(!replicas[1].ejected());
+}
+
+fn eject(o: &Outlier) {
+    for _ in 0..20 {
+        o.record(Instant::now(), false);
+    }
+    assert!(o.ejected());
+}
+
+/// 摘除到期后只放行一个探测请求，摘除前发出的请求不会恢复副本，探测成功后恢复
+#[test]
+fn outlier_probe_restore() {
+    init_metrics_onlyfor_test();
+    let replicas = group(4);
+    let o = &replicas[0];
+    // 摘除前发出、摘除后才完成的请求
+    let before = Instant::now();
+    eject(o);
+    assert!(o.ejected_ms() > 9_000);
+    assert!(!o.available());
+
+    o.expire_onlyfor_test();
+    std::thread::sleep(Duration::from_millis(5));
+    assert!(o.available());
+    let probe = Instant::now();
+    // 探测未完成时，不再放行其他请求
+    assert!(!o.available());
+
+    o.record(before, true);
+    assert!(o.ejected());
+    o.record(probe, true);
+    assert!(!o.ejected());
+    assert!(o.available());
+}
+
+/// 探测失败时继续摘除，摘除时间按连续摘除的次数翻倍
+#[test]
+fn outlier_probe_backoff() {
+    init_metrics_onlyfor_test();
+    let replicas = group(4);
+    let o = &replicas[0];
+    eject(o);
+    let first = o.ejected_ms();
+    assert!(first > 9_000 && first <= 10_000, "{}", first);
+
+    for expected in [20_000, 40_000] {
+        o.expire_onlyfor_test();
+        assert!(o.available());
+        o.record(Instant::now(), false);
+        let ms = o.ejected_ms();
+        assert!(
+            ms > expected - 1_000 && ms <= expected,
+            "{} {}",
+            ms,
+            expected
+        );
+    }
+
+    // 恢复后再次摘除，继续按之前的次数计算
+    o.expire_onlyfor_test();
+    assert!(o.available());
+    o.record(Instant::now(), true);
+    assert!(!o.ejected());
+    eject(o);
+    assert!(o.ejected_ms() > 79_000);
+}
+
+/// 副本列表变化时，地址相同的副本保留摘除状态
+#[test]
+fn outlier_regroup() {
+    init_metrics_onlyfor_test();
+    let replicas = group(4);
+    eject(&replicas[1]);
+    let addrs = ["127.0.0.2:6379", "127.0.0.5:6379", "127.0.0.3:6379"];
+    let regrouped = Outlier::regroup(&replicas, addrs.into_iter());
+    drop(replicas);
+    assert!(regrouped[0].ejected());
+    assert!(regrouped[0].ejected_ms() > 9_000);
+    assert!(!regrouped[1].ejected());
+    // 继承的摘除计入新分组的摘除数量：3个副本最多摘除1个
+    eject(&regrouped[0]);
+    for _ in 0..20 {
+        regrouped[2].record(Instant::now(), false);
+    }
+    assert!(!regrouped[2].ejected());
+}
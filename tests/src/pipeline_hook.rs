@@ -0,0 +1,262 @@
+// 端到端测试：模拟的redis后端 + 真实的topology、pipeline，client通过本地tcp连接访问
+use std::sync::{Arc, Once};
+use std::time::Duration;
+
+use discovery::{TopologyReadGuard, TopologyWrite, TopologyWriteGuard};
+use endpoint::{Endpoint, Timeout};
+use metrics::Path;
+use protocol::{Acl, Parser, ResOption, callback::CallbackPtr};
+use sharding::hash::{Hash, HashKey};
+use stream::{Backend, CheckedTopology, Request, StreamMetrics, TopologyCheck};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+pub(crate) type Topology = endpoint::TopologyProtocol<Backend<Request>, Parser>;
+
+// dns缓存是进程内全局的，只启动一次
+fn start_dns() {
+    static DNS: Once = Once::new();
+    DNS.call_once(|| {
+        let refresher = discovery::dns::start_dns_resolver_refresher();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("runtime");
+            rt.block_on(refresher);
+        });
+    });
+}
+
+// 模拟的redis后端。handler根据请求的参数返回响应前的延迟及响应内容，返回None时不响应
+pub(crate) async fn fake_redis<F>(handler: F) -> String
+where
+    F: Fn(&[Vec<u8>]) -> Option<(Duration, Vec<u8>)> + Send + Sync + 'static,
+{
+    let l = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let addr = l.local_addr().expect("addr").to_string();
+    let handler = Arc::new(handler);
+    tokio::spawn(async move {
+        while let Ok((s, _)) = l.accept().await {
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let (r, mut w) = s.into_split();
+                let mut r = BufReader::new(r);
+                while let Some(args) = read_cmd(&mut r).await {
+                    // 后端不支持RESP3，连接继续使用RESP2
+                    if args[0].eq_ignore_ascii_case(b"hello") {
+                        let _ = w.write_all(b"-ERR unknown command 'HELLO'\r\n").await;
+                        continue;
+                    }
+                    if let Some((delay, rsp)) = handler(&args) {
+                        tokio::time::sleep(delay).await;
+                        if w.write_all(&rsp).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+    });
+    addr
+}
+
+pub(crate) async fn read_cmd<R: AsyncBufReadExt + Unpin>(r: &mut R) -> Option<Vec<Vec<u8>>> {
+    let mut line = String::new();
+    r.read_line(&mut line).await.ok().filter(|n| *n > 0)?;
+    let n: usize = line.trim_end().strip_prefix('*')?.parse().ok()?;
+    let mut args = Vec::with_capacity(n);
+    for _ in 0..n {
+        line.clear();
+        r.read_line(&mut line).await.ok()?;
+        let len: usize = line.trim_end().strip_prefix('$')?.parse().ok()?;
+        let mut arg = vec![0u8; len + 2];
+        r.read_exact(&mut arg).await.ok()?;
+        arg.truncate(len);
+        args.push(arg);
+    }
+    Some(args)
+}
+
+pub(crate) struct Service {
+    _tx: TopologyWriteGuard<Topology>,
+    rx: TopologyReadGuard<Topology>,
+    metrics: Arc<StreamMetrics>,
+}
+
+// 按配置构建redis服务，等待所有后端连接建立
+pub(crate) async fn redis_service(name: &str, cfg: &str) -> Service {
+    service("rs", name, cfg).await
+}
+
+// endpoint为资源类型，如rs、rc
+pub(crate) async fn service(endpoint: &str, name: &str, cfg: &str) -> Service {
+    metrics::tests::start_register_onlyfor_test();
+    start_dns();
+    let p = Parser::try_from("redis").expect("parser");
+    let top: Topology = endpoint::TopologyProtocol::try_from(p, endpoint).expect("topology");
+    let (mut tx, rx) = discovery::topology(top, name);
+    tx.update(name, cfg).expect("cfg");
+    for _ in 0..500 {
+        if tx.need_load() {
+            tx.load();
+        }
+        if rx.inited() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(rx.inited(), "service not inited");
+    let mut metrics = StreamMetrics::new(&Path::new(vec!["redis", name]));
+    while !metrics.check_registered() {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let metrics = Arc::new(metrics);
+    Service {
+        _tx: tx,
+        rx,
+        metrics,
+    }
+}
+
+impl Service {
+    pub(crate) fn top(&self) -> ds::ReadGuard<Topology> {
+        self.rx.get()
+    }
+    // 建立一个client连接，服务端由pipeline处理
+    pub(crate) async fn connect(&self) -> Client {
+        self.connect_with(CheckedTopology::from(self.rx.clone()))
+            .await
+    }
+    // 建立一个需要认证的client连接：用户default/pw可以执行所有指令，reader/r只能执行读指令。
+    // 配置中的用户密码是加密的，解密依赖启动参数，测试中由topology的包装提供认证
+    pub(crate) async fn connect_authed(&self) -> Client {
+        self.connect_with(Authed(CheckedTopology::from(self.rx.clone())))
+            .await
+    }
+    async fn connect_with<T>(&self, top: T) -> Client
+    where
+        T: endpoint::Topology<Item = Request> + TopologyCheck + Unpin + 'static,
+    {
+        let l = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let client = TcpStream::connect(l.local_addr().expect("addr"))
+            .await
+            .expect("connect");
+        let (server, _) = l.accept().await.expect("accept");
+        let parser = Parser::try_from("redis").expect("parser");
+        let metrics = self.metrics.clone();
+        tokio::spawn(async move {
+            let server = rt::Stream::from(server);
+            let _ = stream::pipeline::copy_bidirectional(top, metrics, server, parser).await;
+        });
+        Client(client)
+    }
+}
+
+// 除认证外，其他都由实际的topology处理
+struct Authed(CheckedTopology<Topology>);
+
+impl Endpoint for Authed {
+    type Item = Request;
+    fn send(&self, req: Request) {
+        self.0.send(req)
+    }
+    fn shard_idx(&self, hash: i64) -> usize {
+        self.0.shard_idx(hash)
+    }
+}
+
+impl Hash for Authed {
+    fn hash<K: HashKey>(&self, k: &K) -> i64 {
+        self.0.hash(k)
+    }
+}
+
+impl endpoint::Topology for Authed {
+    fn exp_sec(&self) -> u32 {
+        self.0.exp_sec()
+    }
+    fn dedicated_backend(&self, hash: i64) -> Option<(String, ResOption, Timeout)> {
+        self.0.dedicated_backend(hash)
+    }
+    fn shards(&self) -> usize {
+        self.0.shards()
+    }
+    fn max_set_members(&self) -> usize {
+        self.0.max_set_members()
+    }
+    fn require_auth(&self) -> bool {
+        true
+    }
+    fn resp3(&self) -> bool {
+        self.0.resp3()
+    }
+    fn auth(&self, user: &[u8], pass: &[u8]) -> Option<Acl> {
+        match (user, pass) {
+            (b"default", b"pw") => Some(Acl::ALL),
+            (b"reader", b"r") => Some(Acl::READ),
+            _ => None,
+        }
+    }
+    fn hedge(&self) -> Option<&endpoint::hedge::Hedge> {
+        self.0.hedge()
+    }
+}
+
+impl TopologyCheck for Authed {
+    fn refresh(&mut self) -> bool {
+        self.0.refresh()
+    }
+    fn callback(&self) -> CallbackPtr {
+        self.0.callback()
+    }
+    fn removed(&self) -> bool {
+        self.0.removed()
+    }
+}
+
+pub(crate) struct Client(TcpStream);
+
+impl Client {
+    pub(crate) async fn send(&mut self, req: &[u8]) {
+        self.0.write_all(req).await.expect("send");
+    }
+    // 按顺序读取期望的响应，超时或不一致时失败
+    pub(crate) async fn expect(&mut self, rsp: &[u8]) {
+        let mut buf = vec![0u8; rsp.len()];
+        let read = tokio::time::timeout(Duration::from_secs(3), self.0.read_exact(&mut buf)).await;
+        assert!(
+            read.is_ok(),
+            "timeout, expected:{:?}",
+            String::from_utf8_lossy(rsp)
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            String::from_utf8_lossy(rsp),
+            "unexpected response"
+        );
+    }
+    pub(crate) async fn request(&mut self, req: &[u8], rsp: &[u8]) {
+        self.send(req).await;
+        self.expect(rsp).await;
+    }
+}
+
+// 在多线程runtime中运行测试
+pub(crate) fn run<F: std::future::Future>(f: F) -> F::Output {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()
+        .expect("runtime")
+        .block_on(f)
+}
+
+// 构建redis请求
+pub(crate) fn cmd(args: &[&str]) -> Vec<u8> {
+    let mut req = format!("*{}\r\n", args.len());
+    for arg in args {
+        req += &format!("${}\r\n{}\r\n", arg.len(), arg);
+    }
+    req.into_bytes()
+}
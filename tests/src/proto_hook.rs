@@ -59,7 +59,7 @@ impl Metric<TestMetricItem> for TestMetric {
 
 impl Commander<TestMetric, TestMetricItem> for TestCtx {
     fn request_mut(&mut self) -> &mut HashedCommand {
-        todo!()
+        &mut self.req
     }
 
     fn request(&self) -> &HashedCommand {
@@ -163,6 +163,10 @@ impl RequestProcessor for Process {
         self.reqs.push(req);
         assert!(last)
     }
+    // 后端开启了RESP3
+    fn resp3(&self) -> bool {
+        true
+    }
 }
 
 pub(crate) struct Alg {}
@@ -0,0 +1,132 @@
+use crate::proto_hook;
+use protocol::{Acl, HashedCommand, Proto, RedisFlager, RequestProcessor, TxCmd, redis::Redis};
+use sharding::hash::Hasher;
+
+struct Process {
+    reqs: Vec<HashedCommand>,
+}
+
+impl RequestProcessor for Process {
+    fn process(&mut self, req: HashedCommand, _last: bool) {
+        self.reqs.push(req);
+    }
+    fn require_auth(&self) -> bool {
+        true
+    }
+    fn resp3(&self) -> bool {
+        true
+    }
+    fn auth(&self, user: &[u8], pass: &[u8]) -> Option<Acl> {
+        match (user, pass) {
+            (b"default", b"pw") => Some(Acl::ALL),
+            (b"reader", b"r") => Some(Acl::READ.union(Acl::META)),
+            _ => None,
+        }
+    }
+}
+
+fn parse(data: &[u8]) -> Vec<HashedCommand> {
+    let alg = Hasher::from("crc32");
+    let mut process = Process { reqs: Vec::new() };
+    let mut stream = proto_hook::TestStream {
+        oft: 0,
+        ctx: Default::default(),
+        inner: data.to_vec(),
+    };
+    Redis
+        .parse_request(&mut stream, &alg, &mut process)
+        .expect("parse request");
+    process.reqs
+}
+
+fn cmd(args: &[&str]) -> Vec<u8> {
+    let mut data = format!("*{}\r\n", args.len());
+    for arg in args {
+        data += &format!("${}\r\n{}\r\n", arg.len(), arg);
+    }
+    data.into_bytes()
+}
+
+fn write(req: HashedCommand) -> Vec<u8> {
+    let mut ctx = proto_hook::TestCtx::new(req);
+    let mut w = proto_hook::TestStream {
+        oft: 0,
+        ctx: Default::default(),
+        inner: Vec::new(),
+    };
+    Redis.write_response(&mut ctx, None, &mut w).expect("write");
+    w.inner
+}
+
+const NOAUTH: &[u8] = b"-NOAUTH Authentication required.\r\n";
+const NOPERM: &[u8] = b"-NOPERM this user has no permissions to run this command\r\n";
+const WRONGPASS: &[u8] = b"-WRONGPASS invalid username-password pair or user is disabled.\r\n";
+
+/// 认证前只能执行auth、hello、quit，认证失败时保持未认证状态
+#[test]
+fn test_auth() {
+    let data = [
+        cmd(&["get", "k"]),
+        cmd(&["ping"]),
+        cmd(&["multi"]),
+        cmd(&["subscribe", "ch"]),
+        cmd(&["auth", "wrong"]),
+        cmd(&["get", "k"]),
+        cmd(&["auth", "pw"]),
+        cmd(&["set", "k", "v"]),
+    ]
+    .concat();
+    let reqs = parse(&data);
+    assert_eq!(reqs.len(), 8);
+    assert!(reqs[..6].iter().all(|r| r.noforward()));
+    assert_eq!(Redis.tx_cmd(&reqs[2]), TxCmd::Other);
+    assert!(!Redis.subscribe(&reqs[3]));
+    assert!(!reqs[7].noforward());
+
+    let rsps: Vec<Vec<u8>> = reqs.into_iter().take(7).map(write).collect();
+    let expect: [&[u8]; 7] = [
+        NOAUTH, NOAUTH, NOAUTH, NOAUTH, WRONGPASS, NOAUTH, b"+OK\r\n",
+    ];
+    assert_eq!(rsps, expect);
+}
+
+/// 按用户的acl校验指令类型，multi-key指令整体拒绝
+#[test]
+fn test_acl() {
+    let data = [
+        cmd(&["auth", "reader", "r"]),
+        cmd(&["get", "k"]),
+        cmd(&["mget", "k1", "k2"]),
+        cmd(&["ping"]),
+        cmd(&["set", "k", "v"]),
+        cmd(&["del", "k1", "k2"]),
+    ]
+    .concat();
+    let reqs = parse(&data);
+    assert_eq!(reqs.len(), 7);
+    assert!(reqs[1..4].iter().all(|r| r.rejected() == 0));
+    assert!(!reqs[1].noforward() && !reqs[2].noforward());
+    assert_eq!(write(reqs.into_iter().nth(5).expect("set")), NOPERM);
+    let reqs = parse(&data);
+    assert!(reqs[6].equal(&cmd(&["del", "k1", "k2"])));
+    assert_eq!(write(reqs.into_iter().nth(6).expect("del")), NOPERM);
+}
+
+/// hello 可以同时认证和切换协议版本，认证失败时不切换
+#[test]
+fn test_hello_auth() {
+    let data = [
+        cmd(&["hello", "3"]),
+        cmd(&["hello", "3", "AUTH", "reader", "x"]),
+        cmd(&["hello", "3", "auth", "reader", "r", "setname", "c"]),
+        cmd(&["get", "k"]),
+    ]
+    .concat();
+    let reqs = parse(&data);
+    let resp3: Vec<bool> = reqs.iter().map(|r| r.resp3()).collect();
+    assert_eq!(resp3, [false, false, true, true]);
+    assert!(!reqs[3].noforward());
+    let rsps: Vec<Vec<u8>> = reqs.into_iter().take(3).map(write).collect();
+    let expect: [&[u8]; 3] = [NOAUTH, WRONGPASS, b"%1\r\n$5\r\nproto\r\n:3\r\n"];
+    assert_eq!(rsps, expect);
+}
@@ -0,0 +1,173 @@
+use crate::pipeline_hook::{self, cmd};
+use crate::proto_hook;
+use ds::RingSlice;
+use protocol::{
+    Proto,
+    redis::{
+        Redis,
+        cluster::{self, Redirect, SlotRange},
+    },
+};
+use sharding::hash::{Hash, Hasher};
+use std::sync::{
+    Arc, OnceLock,
+    atomic::{AtomicUsize, Ordering::Relaxed},
+};
+use std::time::Duration;
+
+/// crc16对16384取模，支持hash tag
+#[test]
+fn test_crc16_slot() {
+    let hasher = Hasher::from("crc16");
+    let slot = |key: &str| hasher.hash(&key.as_bytes());
+    assert_eq!(slot("123456789"), 0x31c3);
+    assert_eq!(slot("foo"), 12182);
+    assert_eq!(slot("bar"), 5061);
+    assert_eq!(slot("{user1000}.following"), slot("{user1000}.followers"));
+    assert_eq!(slot("{user1000}.following"), slot("user1000"));
+    // 第一个"{"之后的第一个"}"，括号内为空时使用整个key
+    assert_eq!(slot("foo{bar}{zap}"), slot("bar"));
+    assert_eq!(slot("foo{{bar}}zap"), slot("{bar"));
+    assert_ne!(slot("foo{}{bar}"), slot("bar"));
+    assert_eq!(slot("foo{bar"), slot("foo{bar"));
+}
+
+#[test]
+fn test_redirect() {
+    let parse = |data: &[u8]| Redirect::parse(&RingSlice::from_slice(data));
+    assert_eq!(
+        parse(b"-MOVED 3999 127.0.0.1:6381\r\n"),
+        Some(Redirect {
+            ask: false,
+            slot: 3999,
+            addr: "127.0.0.1:6381".to_string(),
+        })
+    );
+    assert_eq!(
+        parse(b"-ASK 12182 10.0.0.2:7000\r\n"),
+        Some(Redirect {
+            ask: true,
+            slot: 12182,
+            addr: "10.0.0.2:7000".to_string(),
+        })
+    );
+    assert_eq!(parse(b"-ERR unknown command\r\n"), None);
+    assert_eq!(parse(b"$5\r\nMOVED\r\n"), None);
+
+    // 重定向的响应与其他异常响应一样是ok的，不计入后端异常，由redis cluster的topo按响应重试
+    let mut s = proto_hook::TestStream {
+        oft: 0,
+        ctx: Default::default(),
+        inner: b"-MOVED 3999 127.0.0.1:6381\r\n-ERR x\r\n".to_vec(),
+    };
+    let moved = Redis.parse_response(&mut s).expect("rsp").expect("moved");
+    assert!(moved.ok());
+    let err = Redis.parse_response(&mut s).expect("rsp").expect("err");
+    assert!(err.ok());
+}
+
+#[test]
+fn test_parse_slots() {
+    let data = b"*2\r\n\
+        *4\r\n:0\r\n:5460\r\n*3\r\n$9\r\n127.0.0.1\r\n:7000\r\n$3\r\nid1\r\n*3\r\n$9\r\n127.0.0.1\r\n:7003\r\n$3\r\nid4\r\n\
+        *3\r\n:5461\r\n:16383\r\n*4\r\n$9\r\n127.0.0.1\r\n:7001\r\n$3\r\nid2\r\n*2\r\n$8\r\nhostname\r\n$2\r\nh2\r\n";
+    let slots = cluster::parse_slots(data).expect("slots");
+    assert_eq!(
+        slots,
+        [
+            SlotRange {
+                start: 0,
+                end: 5460,
+                nodes: vec!["127.0.0.1:7000".to_string(), "127.0.0.1:7003".to_string()],
+            },
+            SlotRange {
+                start: 5461,
+                end: 16383,
+                nodes: vec!["127.0.0.1:7001".to_string()],
+            },
+        ]
+    );
+    // 数据不完整
+    for len in [0, 3, 20, data.len() - 1] {
+        assert!(matches!(
+            cluster::parse_slots(&data[..len]),
+            Err(protocol::Error::ProtocolIncomplete(_))
+        ));
+    }
+    assert!(cluster::parse_slots(b"-ERR This instance has cluster support disabled\r\n").is_err());
+}
+
+// 两个节点各负责一半的slot
+fn cluster_slots(a: &str, b: &str) -> Vec<u8> {
+    let node = |addr: &str| {
+        let (ip, port) = addr.split_once(':').expect("addr");
+        format!("*2\r\n${}\r\n{}\r\n:{}\r\n", ip.len(), ip, port)
+    };
+    format!(
+        "*2\r\n*3\r\n:0\r\n:8191\r\n{}*3\r\n:8192\r\n:16383\r\n{}",
+        node(a),
+        node(b)
+    )
+    .into_bytes()
+}
+
+/// MOVED、ASK按响应重定向，ASK先发送ASKING且其响应不返回给client；
+/// 重定向到未知节点、其他异常响应都不重试，直接返回给client
+#[test]
+fn test_cluster_redirect() {
+    pipeline_hook::run(async {
+        let slot = |key: &[u8]| Hasher::from("crc16").hash(&key);
+        // 所有key都在节点a负责的slot
+        for key in ["k", "bar", "{k}x", "{k}e"] {
+            assert!(slot(key.as_bytes()) < 8192, "{}", key);
+        }
+        let rsp = |data: &[u8]| Some((Duration::ZERO, data.to_vec()));
+        let b = pipeline_hook::fake_redis(move |args| {
+            match (args[0].to_ascii_lowercase().as_slice(), args.get(1)) {
+                (b"asking", _) => rsp(b"+OK\r\n"),
+                (b"get", Some(k)) if k == b"k" => rsp(b"$2\r\nvk\r\n"),
+                (b"get", Some(k)) if k == b"bar" => rsp(b"$4\r\nvbar\r\n"),
+                _ => rsp(b"-ERR unexpected\r\n"),
+            }
+        })
+        .await;
+        let a_addr = Arc::new(OnceLock::<String>::new());
+        let gets = Arc::new(AtomicUsize::new(0));
+        let (me, to_b, counter) = (a_addr.clone(), b.clone(), gets.clone());
+        let a = pipeline_hook::fake_redis(move |args| {
+            if args[0].eq_ignore_ascii_case(b"cluster") {
+                let a = me.get().expect("addr a");
+                return Some((Duration::ZERO, cluster_slots(a, &to_b)));
+            }
+            counter.fetch_add(1, Relaxed);
+            let redirect = |ty: &str, addr: &str| {
+                let rsp = format!("-{} {} {}\r\n", ty, slot(&args[1]), addr);
+                Some((Duration::ZERO, rsp.into_bytes()))
+            };
+            match args[1].as_slice() {
+                b"k" => redirect("ASK", &to_b),
+                b"bar" => redirect("MOVED", &to_b),
+                b"{k}x" => redirect("MOVED", "127.0.0.1:1"),
+                _ => rsp(b"-ERR wrongtype\r\n"),
+            }
+        })
+        .await;
+        a_addr.set(a.clone()).expect("set addr a");
+
+        let cfg = format!("basic:\n  hash: crc16\nbackends:\n  - {a}\n");
+        let service = pipeline_hook::service("rc", "cluster_redirect", &cfg).await;
+        let mut client = service.connect().await;
+        client.request(&cmd(&["get", "k"]), b"$2\r\nvk\r\n").await;
+        client
+            .request(&cmd(&["get", "bar"]), b"$4\r\nvbar\r\n")
+            .await;
+        let moved = format!("-MOVED {} 127.0.0.1:1\r\n", slot(b"{k}x"));
+        client
+            .request(&cmd(&["get", "{k}x"]), moved.as_bytes())
+            .await;
+        client
+            .request(&cmd(&["get", "{k}e"]), b"-ERR wrongtype\r\n")
+            .await;
+        assert_eq!(gets.load(Relaxed), 4);
+    });
+}
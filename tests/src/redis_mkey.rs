@@ -0,0 +1,74 @@
+use crate::proto_hook;
+use protocol::{Command, HashedCommand, Proto, RedisFlager, RequestProcessor, redis::Redis};
+use sharding::hash::Hasher;
+
+struct Process {
+    reqs: Vec<(HashedCommand, bool)>,
+}
+
+impl RequestProcessor for Process {
+    fn process(&mut self, req: HashedCommand, last: bool) {
+        self.reqs.push((req, last));
+    }
+}
+
+fn stream(data: &[u8]) -> proto_hook::TestStream {
+    proto_hook::TestStream {
+        oft: 0,
+        ctx: Default::default(),
+        inner: data.to_vec(),
+    }
+}
+
+fn parse(data: &[u8]) -> Vec<(HashedCommand, bool)> {
+    let alg = Hasher::from("crc32");
+    let mut process = Process { reqs: Vec::new() };
+    Redis
+        .parse_request(&mut stream(data), &alg, &mut process)
+        .expect("parse request");
+    process.reqs
+}
+
+/// del、exists、unlink、touch 按key拆分，每个key发送到各自的分片
+#[test]
+fn test_mkey_count_split() {
+    for cmd in ["del", "exists", "unlink", "touch"] {
+        let req = format!(
+            "*3\r\n${}\r\n{}\r\n$2\r\nk1\r\n$2\r\nk2\r\n",
+            cmd.len(),
+            cmd
+        );
+        let reqs = parse(req.as_bytes());
+        assert_eq!(reqs.len(), 2, "{cmd}");
+        for (i, (req, last)) in reqs.iter().enumerate() {
+            let expect = format!("*2\r\n${}\r\n{}\r\n$2\r\nk{}\r\n", cmd.len(), cmd, i + 1);
+            assert!(req.equal(expect.as_bytes()), "{req:?}");
+            assert_eq!(req.mkey_first(), i == 0);
+            assert_eq!(*last, i == 1);
+            assert!(Redis.need_merge(req));
+        }
+        assert_eq!(reqs[0].0.key_count(), 2);
+        assert_ne!(reqs[0].0.hash(), reqs[1].0.hash());
+    }
+}
+
+/// 各key的整数响应累加成一个响应，任何一个key失败都返回异常
+#[test]
+fn test_mkey_count_merge() {
+    let merge = |rsps: &[&[u8]]| {
+        let (req, _) = parse(b"*3\r\n$3\r\ndel\r\n$2\r\nk1\r\n$2\r\nk2\r\n").remove(0);
+        let mut ctx = proto_hook::TestCtx::new(req);
+        let mut responses: Vec<Option<Command>> = rsps
+            .iter()
+            .map(|r| Redis.parse_response(&mut stream(r)).expect("rsp"))
+            .collect();
+        let mut w = stream(b"");
+        Redis
+            .merge_response(&mut ctx, &mut responses, &mut w)
+            .expect("merge");
+        w.inner
+    };
+    assert_eq!(merge(&[b":1\r\n", b":1\r\n"]), b":2\r\n");
+    assert_eq!(merge(&[b":0\r\n", b":1\r\n"]), b":1\r\n");
+    assert_eq!(merge(&[b":1\r\n", b"-ERR busy\r\n"]), b"-ERR busy\r\n");
+}
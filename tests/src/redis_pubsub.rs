@@ -0,0 +1,205 @@
+use crate::pipeline_hook::{self, cmd};
+use crate::proto_hook;
+use protocol::{redis::Redis, HashedCommand, Operation, Proto, RequestProcessor};
+use sharding::hash::Hasher;
+
+struct Process {
+    reqs: Vec<(HashedCommand, bool)>,
+}
+
+impl RequestProcessor for Process {
+    fn process(&mut self, req: HashedCommand, last: bool) {
+        self.reqs.push((req, last));
+    }
+}
+
+fn parse(data: &[u8]) -> Vec<(HashedCommand, bool)> {
+    let alg = Hasher::from("crc32");
+    let mut process = Process { reqs: Vec::new() };
+    let mut stream = proto_hook::TestStream {
+        oft: 0,
+        ctx: Default::default(),
+        inner: data.to_vec(),
+    };
+    Redis
+        .parse_request(&mut stream, &alg, &mut process)
+        .expect("parse request");
+    process.reqs
+}
+
+/// subscribe 按channel拆分，每个channel独立计算hash
+#[test]
+fn test_subscribe_split() {
+    let reqs = parse(b"*3\r\n$9\r\nsubscribe\r\n$2\r\nc1\r\n$2\r\nc2\r\n");
+    assert_eq!(reqs.len(), 2);
+    for (i, (req, last)) in reqs.iter().enumerate() {
+        let expect = format!("*2\r\n$9\r\nsubscribe\r\n$2\r\nc{}\r\n", i + 1);
+        assert!(req.equal(expect.as_bytes()), "{req:?}");
+        assert!(Redis.subscribe(req));
+        assert!(!req.noforward());
+        assert_eq!(*last, i == 1);
+    }
+
+    // pattern 需要通过hashkey指定路由的key
+    let mut stream = proto_hook::TestStream {
+        oft: 0,
+        ctx: Default::default(),
+        inner: b"*2\r\n$10\r\npsubscribe\r\n$3\r\nc.*\r\n".to_vec(),
+    };
+    let mut process = Process { reqs: Vec::new() };
+    let alg = Hasher::from("crc32");
+    assert!(
+        Redis
+            .parse_request(&mut stream, &alg, &mut process)
+            .is_err()
+    );
+    let reqs =
+        parse(b"*2\r\n$8\r\nhashkeyq\r\n$2\r\nc1\r\n*2\r\n$10\r\npsubscribe\r\n$3\r\nc.*\r\n");
+    assert_eq!(reqs.len(), 1);
+    assert!(Redis.subscribe(&reqs[0].0));
+    assert_eq!(
+        reqs[0].0.hash(),
+        parse(b"*2\r\n$9\r\nsubscribe\r\n$2\r\nc1\r\n")[0].0.hash()
+    );
+
+    // unsubscribe 不带channel时也是合法请求，且不会进入订阅模式
+    let reqs = parse(b"*1\r\n$11\r\nunsubscribe\r\n");
+    assert_eq!(reqs.len(), 1);
+    assert!(!Redis.subscribe(&reqs[0].0));
+}
+
+/// publish 与 subscribe 同一个channel的hash一致，才能路由到同一个分片
+#[test]
+fn test_publish_hash() {
+    let sub = parse(b"*2\r\n$9\r\nsubscribe\r\n$7\r\nchannel\r\n");
+    let publish = parse(b"*3\r\n$7\r\npublish\r\n$7\r\nchannel\r\n$3\r\nmsg\r\n");
+    assert_eq!(publish.len(), 1);
+    let (req, _) = &publish[0];
+    assert!(!Redis.subscribe(req));
+    assert_eq!(req.operation(), Operation::Store);
+    assert_eq!(req.hash(), sub[0].0.hash());
+
+    let other = parse(b"*3\r\n$7\r\npublish\r\n$8\r\nchannel2\r\n$3\r\nmsg\r\n");
+    assert_ne!(other[0].0.hash(), req.hash());
+}
+
+// 订阅类指令的确认消息，subscribe之后紧跟一条推送的消息
+fn pubsub(args: &[Vec<u8>]) -> Option<(std::time::Duration, Vec<u8>)> {
+    let cmd = String::from_utf8_lossy(&args[0]).to_lowercase();
+    let rsp = match cmd.as_str() {
+        "get" => b"$1\r\nv\r\n".to_vec(),
+        "subscribe" | "psubscribe" | "unsubscribe" => {
+            let arg = String::from_utf8_lossy(&args[1]);
+            let n = (cmd != "unsubscribe") as usize;
+            let mut rsp = format!(
+                "*3\r\n${}\r\n{}\r\n${}\r\n{}\r\n:{}\r\n",
+                cmd.len(),
+                cmd,
+                arg.len(),
+                arg,
+                n
+            );
+            if cmd == "subscribe" {
+                rsp += &format!(
+                    "*3\r\n$7\r\nmessage\r\n${}\r\n{}\r\n$2\r\nhi\r\n",
+                    arg.len(),
+                    arg
+                );
+            }
+            rsp.into_bytes()
+        }
+        _ => b"-ERR unexpected\r\n".to_vec(),
+    };
+    Some((std::time::Duration::ZERO, rsp))
+}
+
+/// 订阅模式下：非订阅类指令按顺序返回异常，推送的消息在之前的响应之后写回；
+/// 退订所有channel后退出订阅模式，之后的请求正常转发
+#[test]
+fn test_subscribe_mode() {
+    pipeline_hook::run(async {
+        let addr = pipeline_hook::fake_redis(pubsub).await;
+        let cfg = format!(
+            "basic:\n  hash: crc32\n  distribution: modula\nbackends:\n  - {addr},{addr}\n"
+        );
+        let service = pipeline_hook::redis_service("pubsub", &cfg).await;
+        let mut client = service.connect().await;
+
+        let get = cmd(&["get", "k"]);
+        client.request(&get, b"$1\r\nv\r\n").await;
+
+        let mut reqs = cmd(&["subscribe", "c1"]);
+        reqs.extend(&get);
+        reqs.extend(cmd(&["ping"]));
+        client.send(&reqs).await;
+        client
+            .expect(b"*3\r\n$9\r\nsubscribe\r\n$2\r\nc1\r\n:1\r\n")
+            .await;
+        client
+            .expect(b"-ERR only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING / QUIT allowed in this context\r\n")
+            .await;
+        client.expect(b"+PONG\r\n").await;
+        client
+            .expect(b"*3\r\n$7\r\nmessage\r\n$2\r\nc1\r\n$2\r\nhi\r\n")
+            .await;
+
+        // 退订后，get在unsubscribe的确认消息之后返回
+        let mut reqs = cmd(&["unsubscribe", "c1"]);
+        reqs.extend(&get);
+        client.send(&reqs).await;
+        client
+            .expect(b"*3\r\n$11\r\nunsubscribe\r\n$2\r\nc1\r\n:0\r\n")
+            .await;
+        client.expect(b"$1\r\nv\r\n").await;
+    });
+}
+
+/// 订阅模式下，未认证、无权限的指令不经过订阅连接，按顺序返回认证异常
+#[test]
+fn test_subscribe_denied() {
+    pipeline_hook::run(async {
+        let addr = pipeline_hook::fake_redis(pubsub).await;
+        let cfg = format!(
+            "basic:\n  hash: crc32\n  distribution: modula\nbackends:\n  - {addr},{addr}\n"
+        );
+        let service = pipeline_hook::redis_service("pubsub_denied", &cfg).await;
+        let mut client = service.connect_authed().await;
+
+        // 未认证时subscribe被拒绝，不进入订阅模式
+        let get = cmd(&["get", "k"]);
+        let mut reqs = cmd(&["subscribe", "c1"]);
+        reqs.extend(&get);
+        client.send(&reqs).await;
+        client.expect(b"-NOAUTH Authentication required.\r\n").await;
+        client.expect(b"-NOAUTH Authentication required.\r\n").await;
+
+        // 只读用户订阅后，写指令返回无权限，读指令返回订阅模式的异常
+        client
+            .request(&cmd(&["auth", "reader", "r"]), b"+OK\r\n")
+            .await;
+        let mut reqs = cmd(&["subscribe", "c1"]);
+        reqs.extend(cmd(&["set", "k", "v"]));
+        reqs.extend(&get);
+        client.send(&reqs).await;
+        client
+            .expect(b"*3\r\n$9\r\nsubscribe\r\n$2\r\nc1\r\n:1\r\n")
+            .await;
+        client
+            .expect(b"-NOPERM this user has no permissions to run this command\r\n")
+            .await;
+        client
+            .expect(b"-ERR only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING / QUIT allowed in this context\r\n")
+            .await;
+        client
+            .expect(b"*3\r\n$7\r\nmessage\r\n$2\r\nc1\r\n$2\r\nhi\r\n")
+            .await;
+
+        client
+            .request(
+                &cmd(&["unsubscribe", "c1"]),
+                b"*3\r\n$11\r\nunsubscribe\r\n$2\r\nc1\r\n:0\r\n",
+            )
+            .await;
+        client.request(&get, b"$1\r\nv\r\n").await;
+    });
+}
@@ -0,0 +1,181 @@
+use crate::proto_hook;
+use protocol::{
+    Command, HandShake, HashedCommand, Proto, RedisFlager, RequestProcessor, ResOption,
+    redis::Redis,
+};
+use sharding::hash::Hasher;
+
+fn stream(data: &[u8]) -> proto_hook::TestStream {
+    proto_hook::TestStream {
+        oft: 0,
+        ctx: Default::default(),
+        inner: data.to_vec(),
+    }
+}
+
+fn parse(data: &[u8]) -> Vec<HashedCommand> {
+    let alg = Hasher::from("crc32");
+    let mut process = proto_hook::Process { reqs: Vec::new() };
+    Redis
+        .parse_request(&mut stream(data), &alg, &mut process)
+        .expect("parse request");
+    process.reqs
+}
+
+fn rsp(data: &[u8]) -> Command {
+    let mut s = stream(data);
+    let rsp = Redis
+        .parse_response(&mut s)
+        .expect("rsp")
+        .expect("complete");
+    assert_eq!(rsp.len(), data.len(), "{:?}", rsp);
+    rsp
+}
+
+fn write(req: HashedCommand, response: Option<&[u8]>) -> Vec<u8> {
+    let mut ctx = proto_hook::TestCtx::new(req);
+    let mut w = stream(b"");
+    let mut response = response.map(rsp);
+    Redis
+        .write_response(&mut ctx, response.as_mut(), &mut w)
+        .expect("write");
+    w.inner
+}
+
+/// hello 协商的协议版本对连接后续的请求都有效
+#[test]
+fn test_hello() {
+    let reqs = parse(
+        b"*2\r\n$5\r\nhello\r\n$1\r\n3\r\n*2\r\n$3\r\nget\r\n$1\r\nk\r\n*1\r\n$5\r\nhello\r\n*2\r\n$5\r\nhello\r\n$1\r\n4\r\n*2\r\n$5\r\nhello\r\n$1\r\n2\r\n*2\r\n$3\r\nget\r\n$1\r\nk\r\n",
+    );
+    let resp3: Vec<bool> = reqs.iter().map(|r| r.resp3()).collect();
+    assert_eq!(resp3, [true, true, true, true, false, false]);
+    let rsps: Vec<Vec<u8>> = reqs
+        .into_iter()
+        .filter(|r| r.noforward())
+        .map(|r| write(r, None))
+        .collect();
+    let resp3: &[u8] = b"%1\r\n$5\r\nproto\r\n:3\r\n";
+    let resp2: &[u8] = b"*2\r\n$5\r\nproto\r\n:2\r\n";
+    let noproto: &[u8] = b"-NOPROTO sorry, this protocol version is not supported\r\n";
+    assert_eq!(rsps, [resp3, resp3, noproto, resp2]);
+}
+
+// 后端未开启RESP3
+struct Resp2(Vec<HashedCommand>);
+
+impl RequestProcessor for Resp2 {
+    fn process(&mut self, req: HashedCommand, _last: bool) {
+        self.0.push(req);
+    }
+}
+
+/// 后端未开启RESP3时，hello 3返回NOPROTO，连接保持RESP2
+#[test]
+fn test_hello_resp3_disabled() {
+    let alg = Hasher::from("crc32");
+    let mut process = Resp2(Vec::new());
+    let data = b"*2\r\n$5\r\nhello\r\n$1\r\n3\r\n*2\r\n$3\r\nget\r\n$1\r\nk\r\n*2\r\n$5\r\nhello\r\n$1\r\n2\r\n";
+    Redis
+        .parse_request(&mut stream(data), &alg, &mut process)
+        .expect("parse request");
+    let reqs = process.0;
+    assert!(reqs.iter().all(|r| !r.resp3()));
+    let rsps: Vec<Vec<u8>> = reqs
+        .into_iter()
+        .filter(|r| r.noforward())
+        .map(|r| write(r, None))
+        .collect();
+    let resp2: &[u8] = b"*2\r\n$5\r\nproto\r\n:2\r\n";
+    let noproto: &[u8] = b"-NOPROTO sorry, this protocol version is not supported\r\n";
+    assert_eq!(rsps, [noproto, resp2]);
+}
+
+/// RESP3 的响应类型
+#[test]
+fn test_parse_resp3() {
+    let cases: [&[u8]; 10] = [
+        b"%2\r\n$1\r\na\r\n:1\r\n$1\r\nb\r\n_\r\n",
+        b"~2\r\n$1\r\na\r\n$1\r\nb\r\n",
+        b",3.14\r\n",
+        b"_\r\n",
+        b"#t\r\n",
+        b"(3492890328409238509324850943850943825024385\r\n",
+        b"=15\r\ntxt:Some string\r\n",
+        b"!21\r\nSYNTAX invalid syntax\r\n",
+        b">3\r\n$7\r\nmessage\r\n$2\r\nch\r\n$2\r\nhi\r\n",
+        b"|1\r\n+key\r\n,0.5\r\n*2\r\n:1\r\n#f\r\n",
+    ];
+    for data in cases {
+        rsp(data);
+    }
+    // 数据不完整
+    let mut s = stream(b"%2\r\n$1\r\na\r\n:1\r\n$1\r\nb\r\n");
+    assert!(Redis.parse_response(&mut s).expect("rsp").is_none());
+}
+
+/// 未协商RESP3的client，响应转换为RESP2
+#[test]
+fn test_downgrade() {
+    let get = b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n";
+    let hgetall = b"*2\r\n$7\r\nhgetall\r\n$1\r\nk\r\n";
+    let zrange = b"*5\r\n$6\r\nzrange\r\n$1\r\nk\r\n$1\r\n0\r\n$2\r\n-1\r\n$10\r\nwithscores\r\n";
+    let cases: [(&[u8], &[u8], &[u8]); 8] = [
+        (get, b"_\r\n", b"$-1\r\n"),
+        (get, b"$1\r\na\r\n", b"$1\r\na\r\n"),
+        (
+            hgetall,
+            b"%2\r\n$1\r\na\r\n$1\r\n1\r\n$1\r\nb\r\n$1\r\n2\r\n",
+            b"*4\r\n$1\r\na\r\n$1\r\n1\r\n$1\r\nb\r\n$1\r\n2\r\n",
+        ),
+        (
+            zrange,
+            b"*2\r\n*2\r\n$1\r\na\r\n,1\r\n*2\r\n$1\r\nb\r\n,2.5\r\n",
+            b"*4\r\n$1\r\na\r\n$1\r\n1\r\n$1\r\nb\r\n$3\r\n2.5\r\n",
+        ),
+        (get, b"#f\r\n", b":0\r\n"),
+        (
+            get,
+            b"=15\r\ntxt:Some string\r\n",
+            b"$11\r\nSome string\r\n",
+        ),
+        (
+            get,
+            b"!21\r\nSYNTAX invalid syntax\r\n",
+            b"-SYNTAX invalid syntax\r\n",
+        ),
+        (
+            get,
+            b"|1\r\n+key\r\n,0.5\r\n~1\r\n(12\r\n",
+            b"*1\r\n$2\r\n12\r\n",
+        ),
+    ];
+    for (req, backend, expect) in cases {
+        let req = parse(req).remove(0);
+        assert_eq!(write(req, Some(backend)), expect);
+    }
+
+    // 协商了RESP3的client，原样返回
+    let map = b"%1\r\n$1\r\na\r\n$1\r\n1\r\n";
+    let mut req = b"*2\r\n$5\r\nhello\r\n$1\r\n3\r\n".to_vec();
+    req.extend_from_slice(hgetall);
+    let req = parse(&req).remove(1);
+    assert_eq!(write(req, Some(map)), map);
+}
+
+/// 后端连接认证后，通过hello 3协商RESP3，不支持时继续使用RESP2
+#[test]
+fn test_handshake_hello() {
+    let mut option = ResOption {
+        resp3: true,
+        ..Default::default()
+    };
+    let mut s = stream(b"");
+    let hs = Redis.handshake(&mut s, &mut option).expect("hello");
+    assert!(matches!(hs, HandShake::Continue));
+    assert_eq!(s.inner, b"*2\r\n$5\r\nHELLO\r\n$1\r\n3\r\n");
+
+    s.inner = b"-ERR unknown command 'HELLO'\r\n".to_vec();
+    let hs = Redis.handshake(&mut s, &mut option).expect("hello");
+    assert!(matches!(hs, HandShake::Success));
+}
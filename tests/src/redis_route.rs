@@ -0,0 +1,55 @@
+use crate::proto_hook;
+use protocol::{HashedCommand, Proto, RequestProcessor, redis::Redis};
+use sharding::hash::Hasher;
+
+struct Process {
+    route: bool,
+    reqs: Vec<HashedCommand>,
+}
+
+impl RequestProcessor for Process {
+    fn process(&mut self, req: HashedCommand, _last: bool) {
+        self.reqs.push(req);
+    }
+    fn route(&self, key: &[u8]) -> Option<String> {
+        self.route
+            .then(|| format!(r#"{{"key":"{}"}}"#, String::from_utf8_lossy(key)))
+    }
+}
+
+fn stream(data: &[u8]) -> proto_hook::TestStream {
+    proto_hook::TestStream {
+        oft: 0,
+        ctx: Default::default(),
+        inner: data.to_vec(),
+    }
+}
+
+fn keyroute(route: bool) -> Vec<u8> {
+    let alg = Hasher::from("crc32");
+    let mut process = Process {
+        route,
+        reqs: Vec::new(),
+    };
+    Redis
+        .parse_request(
+            &mut stream(b"*2\r\n$8\r\nkeyroute\r\n$3\r\nfoo\r\n"),
+            &alg,
+            &mut process,
+        )
+        .expect("parse request");
+    assert_eq!(process.reqs.len(), 1);
+    let req = process.reqs.pop().expect("req");
+    assert!(req.noforward());
+    let mut ctx = proto_hook::TestCtx::new(req);
+    let mut w = stream(b"");
+    Redis.write_response(&mut ctx, None, &mut w).expect("write");
+    w.inner
+}
+
+/// keyroute 不转发，直接返回topology计算的路由信息，不支持时返回nil
+#[test]
+fn test_keyroute() {
+    assert_eq!(keyroute(true), b"$13\r\n{\"key\":\"foo\"}\r\n");
+    assert_eq!(keyroute(false), b"$-1\r\n");
+}
@@ -0,0 +1,208 @@
+use crate::proto_hook;
+use protocol::{Command, Error, HashedCommand, Proto, RedisFlager, RequestProcessor, redis::Redis};
+use sharding::hash::Hasher;
+
+struct Process {
+    shards: usize,
+    reqs: Vec<(HashedCommand, bool)>,
+}
+
+impl RequestProcessor for Process {
+    fn process(&mut self, req: HashedCommand, last: bool) {
+        self.reqs.push((req, last));
+    }
+    fn shards(&self) -> usize {
+        self.shards
+    }
+}
+
+fn stream(data: &[u8]) -> proto_hook::TestStream {
+    proto_hook::TestStream {
+        oft: 0,
+        ctx: Default::default(),
+        inner: data.to_vec(),
+    }
+}
+
+fn parse(data: &[u8], shards: usize) -> Vec<(HashedCommand, bool)> {
+    let alg = Hasher::from("crc32");
+    let mut process = Process {
+        shards,
+        reqs: Vec::new(),
+    };
+    Redis
+        .parse_request(&mut stream(data), &alg, &mut process)
+        .expect("parse request");
+    process.reqs
+}
+
+fn rsp(data: &[u8]) -> Command {
+    Redis
+        .parse_response(&mut stream(data))
+        .expect("rsp")
+        .expect("complete")
+}
+
+/// dbsize、keys 拆分到所有分片
+#[test]
+fn test_fanout_split() {
+    let reqs = parse(b"*1\r\n$6\r\ndbsize\r\n", 3);
+    assert_eq!(reqs.len(), 3);
+    for (i, (req, last)) in reqs.iter().enumerate() {
+        assert!(req.equal(b"*1\r\n$6\r\ndbsize\r\n"));
+        assert_eq!(req.shard(), Some(i));
+        assert_eq!(req.mkey_first(), i == 0);
+        assert_eq!(*last, i == 2);
+        assert!(Redis.need_merge(req));
+    }
+    assert_eq!(reqs[0].0.key_count(), 3);
+
+    let reqs = parse(b"*2\r\n$4\r\nkeys\r\n$1\r\n*\r\n", 2);
+    assert_eq!(reqs.len(), 2);
+    assert!(
+        reqs.iter()
+            .all(|(r, _)| r.equal(b"*2\r\n$4\r\nkeys\r\n$1\r\n*\r\n"))
+    );
+
+    // randomkey 不需要拆分
+    let reqs = parse(b"*1\r\n$9\r\nrandomkey\r\n", 3);
+    assert_eq!(reqs.len(), 1);
+    assert_eq!(reqs[0].0.shard(), None);
+}
+
+/// 分片数量超过上限时，不能只遍历部分分片，返回异常
+#[test]
+fn test_too_many_shards() {
+    let reqs = parse(b"*1\r\n$6\r\ndbsize\r\n", 4095);
+    assert_eq!(reqs.len(), 4095);
+    assert_eq!(reqs[4094].0.shard(), Some(4094));
+
+    let alg = Hasher::from("crc32");
+    for data in [
+        &b"*1\r\n$6\r\ndbsize\r\n"[..],
+        b"*2\r\n$4\r\nkeys\r\n$1\r\n*\r\n",
+        b"*2\r\n$4\r\nscan\r\n$1\r\n0\r\n",
+    ] {
+        let mut process = Process {
+            shards: 4096,
+            reqs: Vec::new(),
+        };
+        let ret = Redis.parse_request(&mut stream(data), &alg, &mut process);
+        match ret {
+            Err(Error::FlushOnClose(rsp)) => {
+                assert_eq!(&*rsp, b"-ERR too many shards to traverse\r\n")
+            }
+            ret => panic!("unexpected: {:?}", ret),
+        }
+        assert!(process.reqs.is_empty());
+    }
+}
+
+/// client cursor = 分片内的cursor << 12 | 分片索引
+#[test]
+fn test_scan_cursor() {
+    let reqs = parse(b"*2\r\n$4\r\nscan\r\n$1\r\n0\r\n", 3);
+    assert_eq!(reqs.len(), 1);
+    let req = &reqs[0].0;
+    assert!(req.equal(b"*2\r\n$4\r\nscan\r\n$1\r\n0\r\n"));
+    assert_eq!(req.shard(), Some(0));
+    assert!(!req.noforward());
+
+    let cursor = (17 << 12 | 2).to_string();
+    let data = format!(
+        "*4\r\n$4\r\nscan\r\n${}\r\n{}\r\n$5\r\nmatch\r\n$2\r\nk*\r\n",
+        cursor.len(),
+        cursor
+    );
+    let reqs = parse(data.as_bytes(), 3);
+    let req = &reqs[0].0;
+    assert!(req.equal(b"*4\r\n$4\r\nscan\r\n$2\r\n17\r\n$5\r\nmatch\r\n$2\r\nk*\r\n"));
+    assert_eq!(req.shard(), Some(2));
+
+    // 分片不存在、cursor不是数字或超出范围，直接返回异常
+    for cursor in ["3", "abc", "", "99999999999999999999999"] {
+        let data = format!("*2\r\n$4\r\nscan\r\n${}\r\n{}\r\n", cursor.len(), cursor);
+        let reqs = parse(data.as_bytes(), 3);
+        let req = reqs.into_iter().next().expect("req").0;
+        assert!(req.noforward());
+        let mut ctx = proto_hook::TestCtx::new(req);
+        let mut w = stream(b"");
+        Redis.write_response(&mut ctx, None, &mut w).expect("write");
+        assert_eq!(w.inner, b"-ERR invalid cursor\r\n");
+    }
+}
+
+/// 分片内遍历完毕后切换到下一个分片，所有分片遍历完毕后返回0
+#[test]
+fn test_scan_response() {
+    let cases: [(&[u8], &[u8], &[u8]); 3] = [
+        (
+            b"*2\r\n$4\r\nscan\r\n$1\r\n1\r\n",
+            b"*2\r\n$2\r\n10\r\n*1\r\n$1\r\na\r\n",
+            b"*2\r\n$5\r\n40961\r\n*1\r\n$1\r\na\r\n",
+        ),
+        (
+            b"*2\r\n$4\r\nscan\r\n$1\r\n1\r\n",
+            b"*2\r\n$1\r\n0\r\n*0\r\n",
+            b"*2\r\n$1\r\n2\r\n*0\r\n",
+        ),
+        (
+            b"*2\r\n$4\r\nscan\r\n$1\r\n2\r\n",
+            b"*2\r\n$1\r\n0\r\n*1\r\n$1\r\nb\r\n",
+            b"*2\r\n$1\r\n0\r\n*1\r\n$1\r\nb\r\n",
+        ),
+    ];
+    // 分片内的cursor左移后溢出，无法编码分片索引
+    let overflow = format!("*2\r\n$20\r\n{}\r\n*0\r\n", u64::MAX);
+    let cases = cases.into_iter().chain([(
+        &b"*2\r\n$4\r\nscan\r\n$1\r\n1\r\n"[..],
+        overflow.as_bytes(),
+        &b"-ERR backend cursor too large to encode shard\r\n"[..],
+    )]);
+    for (req, backend, expect) in cases {
+        let req = parse(req, 3).into_iter().next().expect("req").0;
+        let mut ctx = proto_hook::TestCtx::new(req);
+        let mut w = stream(b"");
+        Redis
+            .write_response(&mut ctx, Some(&mut rsp(backend)), &mut w)
+            .expect("write");
+        assert_eq!(w.inner, expect);
+    }
+}
+
+#[test]
+fn test_merge_response() {
+    let merge = |req: &[u8], rsps: &[&[u8]]| {
+        let req = parse(req, rsps.len()).into_iter().next().expect("req").0;
+        let mut ctx = proto_hook::TestCtx::new(req);
+        let mut responses: Vec<_> = rsps.iter().map(|r| Some(rsp(r))).collect();
+        let mut w = stream(b"");
+        Redis
+            .merge_response(&mut ctx, &mut responses, &mut w)
+            .expect("merge");
+        w.inner
+    };
+    let dbsize = b"*1\r\n$6\r\ndbsize\r\n";
+    assert_eq!(
+        merge(dbsize, &[b":3\r\n", b":0\r\n", b":12\r\n"]),
+        b":15\r\n"
+    );
+    // 任何一个分片失败，都返回异常
+    assert_eq!(
+        merge(dbsize, &[b":3\r\n", b"-ERR busy\r\n"]),
+        b"-ERR busy\r\n"
+    );
+
+    let keys = b"*2\r\n$4\r\nkeys\r\n$1\r\n*\r\n";
+    assert_eq!(
+        merge(
+            keys,
+            &[
+                b"*1\r\n$1\r\na\r\n",
+                b"*0\r\n",
+                b"*2\r\n$1\r\nb\r\n$1\r\nc\r\n"
+            ]
+        ),
+        b"*3\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n"
+    );
+}
@@ -0,0 +1,168 @@
+use crate::proto_hook;
+use protocol::{Command, HashedCommand, Proto, RequestProcessor, TxCmd, redis::Redis};
+use sharding::hash::Hasher;
+
+struct Process {
+    max_set_members: usize,
+    reqs: Vec<HashedCommand>,
+}
+
+impl RequestProcessor for Process {
+    fn process(&mut self, req: HashedCommand, _last: bool) {
+        self.reqs.push(req);
+    }
+    fn max_set_members(&self) -> usize {
+        self.max_set_members
+    }
+}
+
+fn stream(data: &[u8]) -> proto_hook::TestStream {
+    proto_hook::TestStream {
+        oft: 0,
+        ctx: Default::default(),
+        inner: data.to_vec(),
+    }
+}
+
+fn parse(data: &[u8], max_set_members: usize) -> protocol::Result<Vec<HashedCommand>> {
+    let alg = Hasher::from("crc32");
+    let mut process = Process {
+        max_set_members,
+        reqs: Vec::new(),
+    };
+    Redis.parse_request(&mut stream(data), &alg, &mut process)?;
+    Ok(process.reqs)
+}
+
+/// 未指定分片时按key拆分成smembers；未开启mesh内合并时必须通过hashkey指定分片
+#[test]
+fn test_setop_split() {
+    let sinter = b"*3\r\n$6\r\nsinter\r\n$2\r\nk1\r\n$2\r\nk2\r\n";
+    let reqs = parse(sinter, 100).expect("parse");
+    assert_eq!(reqs.len(), 2);
+    assert!(reqs[0].equal(b"*2\r\n$8\r\nsmembers\r\n$2\r\nk1\r\n"));
+    assert!(reqs[1].equal(b"*2\r\n$8\r\nsmembers\r\n$2\r\nk2\r\n"));
+    assert_ne!(reqs[0].hash(), reqs[1].hash());
+    assert!(reqs.iter().all(|r| Redis.need_merge(r)));
+    assert!(reqs.iter().all(|r| Redis.tx_cmd(r) == TxCmd::Unsupported));
+
+    assert!(parse(sinter, 0).is_err());
+
+    // 通过hashkey指定分片时，原样发送
+    let pinned =
+        b"*2\r\n$8\r\nhashkeyq\r\n$2\r\nk1\r\n*3\r\n$6\r\nsinter\r\n$2\r\nk1\r\n$2\r\nk2\r\n";
+    for max in [0, 100] {
+        let reqs = parse(pinned, max).expect("parse");
+        assert_eq!(reqs.len(), 1);
+        assert!(reqs[0].equal(&pinned[26..]));
+        assert_eq!(Redis.tx_cmd(&reqs[0]), TxCmd::Other);
+    }
+}
+
+#[test]
+fn test_setop_merge() {
+    let merge = |cmd: &str, max: usize, rsps: &[&[u8]]| {
+        let mut req = format!("*{}\r\n${}\r\n{}\r\n", rsps.len() + 1, cmd.len(), cmd);
+        (0..rsps.len()).for_each(|i| req += &format!("$2\r\nk{}\r\n", i));
+        let req = parse(req.as_bytes(), max).expect("parse").remove(0);
+        let mut ctx = proto_hook::TestCtx::new(req);
+        let mut responses: Vec<Option<Command>> = rsps
+            .iter()
+            .map(|r| Redis.parse_response(&mut stream(r)).expect("rsp"))
+            .collect();
+        let mut w = stream(b"");
+        Redis
+            .merge_response(&mut ctx, &mut responses, &mut w)
+            .expect("merge");
+        w.inner
+    };
+    let rsps: [&[u8]; 3] = [
+        b"*3\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n",
+        b"*2\r\n$1\r\nc\r\n$1\r\nb\r\n",
+        b"*2\r\n$1\r\nb\r\n$1\r\nd\r\n",
+    ];
+    assert_eq!(merge("sinter", 100, &rsps), b"*1\r\n$1\r\nb\r\n");
+    assert_eq!(
+        merge("sunion", 100, &rsps),
+        b"*4\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n$1\r\nd\r\n"
+    );
+    assert_eq!(merge("sdiff", 100, &rsps), b"*1\r\n$1\r\na\r\n");
+    // key不存在时是空集合
+    assert_eq!(merge("sinter", 100, &[rsps[0], b"*0\r\n"]), b"*0\r\n");
+
+    // 成员总数超过上限
+    assert_eq!(
+        merge("sunion", 6, &rsps),
+        b"-ERR too many members for cross-shard set operation\r\n"
+    );
+    // 类型不对时返回分片的异常
+    let wrongtype: &[u8] =
+        b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n";
+    assert_eq!(merge("sdiff", 100, &[rsps[0], wrongtype]), wrongtype);
+}
+
+/// 已完成的分片响应头中的成员数量超过上限时，不再等待其他分片
+#[test]
+fn test_setop_merge_early() {
+    let req = b"*3\r\n$6\r\nsunion\r\n$2\r\nk1\r\n$2\r\nk2\r\n";
+    let big = Redis
+        .parse_response(&mut stream(b"*3\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n"))
+        .expect("rsp")
+        .expect("complete");
+    let first = parse(req, 3).expect("parse").remove(0);
+    assert!(!Redis.merge_early(&first, &[&big]));
+    let first = parse(req, 2).expect("parse").remove(0);
+    assert!(Redis.merge_early(&first, &[&big]));
+
+    // 未完成的分片响应为None
+    let mut ctx = proto_hook::TestCtx::new(first);
+    let mut w = stream(b"");
+    Redis
+        .merge_response(&mut ctx, &mut [Some(big), None], &mut w)
+        .expect("merge");
+    assert_eq!(
+        w.inner,
+        b"-ERR too many members for cross-shard set operation\r\n"
+    );
+}
+
+/// 单个分片的成员数量超过上限时，不等待响应慢的分片，直接返回异常
+#[test]
+fn test_setop_exceeded_one_shard() {
+    use crate::pipeline_hook::{self, cmd};
+    use std::time::Duration;
+    pipeline_hook::run(async {
+        let handler = |args: &[Vec<u8>]| match args[1].as_slice() {
+            b"big" => Some((
+                Duration::ZERO,
+                b"*3\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n".to_vec(),
+            )),
+            _ => Some((Duration::from_secs(2), b"*0\r\n".to_vec())),
+        };
+        let (a, b) = (
+            pipeline_hook::fake_redis(handler).await,
+            pipeline_hook::fake_redis(handler).await,
+        );
+        let cfg = format!(
+            "basic:\n  hash: crc32\n  distribution: modula\n  max_set_members: 2\n  timeout_ms_master: 3000\n  timeout_ms_slave: 3000\nbackends:\n  - {a},{a}\n  - {b},{b}\n"
+        );
+        let service = pipeline_hook::redis_service("setop_exceeded", &cfg).await;
+        let mut client = service.connect().await;
+
+        let start = std::time::Instant::now();
+        client
+            .request(
+                &cmd(&["sunion", "big", "slow"]),
+                b"-ERR too many members for cross-shard set operation\r\n",
+            )
+            .await;
+        assert!(start.elapsed() < Duration::from_secs(1));
+        // 响应慢的分片返回后被丢弃，不影响之后的请求
+        client
+            .request(
+                &cmd(&["smembers", "big"]),
+                b"*3\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n",
+            )
+            .await;
+    });
+}
@@ -0,0 +1,177 @@
+use crate::pipeline_hook::{self, cmd};
+use crate::proto_hook;
+use protocol::{redis::Redis, HashedCommand, Proto, RequestProcessor, TxCmd};
+use sharding::hash::Hasher;
+
+struct Process {
+    reqs: Vec<HashedCommand>,
+}
+
+impl RequestProcessor for Process {
+    fn process(&mut self, req: HashedCommand, _last: bool) {
+        self.reqs.push(req);
+    }
+}
+
+fn parse(data: &[u8]) -> Vec<HashedCommand> {
+    let alg = Hasher::from("crc32");
+    let mut process = Process { reqs: Vec::new() };
+    let mut stream = proto_hook::TestStream {
+        oft: 0,
+        ctx: Default::default(),
+        inner: data.to_vec(),
+    };
+    Redis
+        .parse_request(&mut stream, &alg, &mut process)
+        .expect("parse request");
+    process.reqs
+}
+
+fn tx_cmds(data: &[u8]) -> Vec<TxCmd> {
+    parse(data).iter().map(|r| Redis.tx_cmd(r)).collect()
+}
+
+#[test]
+fn test_tx_cmd() {
+    let reqs = b"*1\r\n$5\r\nMULTI\r\n*2\r\n$3\r\nget\r\n$1\r\na\r\n*1\r\n$4\r\nexec\r\n*1\r\n$7\r\ndiscard\r\n*1\r\n$7\r\nunwatch\r\n";
+    assert_eq!(
+        tx_cmds(reqs),
+        [
+            TxCmd::Multi,
+            TxCmd::Other,
+            TxCmd::Exec,
+            TxCmd::Discard,
+            TxCmd::Unwatch
+        ]
+    );
+    assert_eq!(tx_cmds(b"*1\r\n$4\r\nping\r\n"), [TxCmd::Keyless]);
+    // 事务外的multi、exec等都在本地响应
+    assert!(parse(reqs)
+        .iter()
+        .filter(|r| Redis.tx_cmd(r) != TxCmd::Other)
+        .all(|r| r.noforward()));
+}
+
+/// watch 按key拆分，每个key单独校验分片
+#[test]
+fn test_watch_split() {
+    let reqs = parse(b"*3\r\n$5\r\nwatch\r\n$2\r\nk1\r\n$2\r\nk2\r\n");
+    assert_eq!(reqs.len(), 2);
+    assert!(reqs[0].equal(b"*2\r\n$5\r\nwatch\r\n$2\r\nk1\r\n"));
+    assert!(reqs[1].equal(b"*2\r\n$5\r\nwatch\r\n$2\r\nk2\r\n"));
+    assert!(reqs.iter().all(|r| Redis.tx_cmd(r) == TxCmd::Watch && !r.noforward()));
+    assert_ne!(reqs[0].hash(), reqs[1].hash());
+}
+
+/// 只有单key的multi-key指令可以在事务中排队
+#[test]
+fn test_multi_key_in_tx() {
+    assert_eq!(tx_cmds(b"*2\r\n$3\r\ndel\r\n$1\r\na\r\n"), [TxCmd::Other]);
+    assert_eq!(
+        tx_cmds(b"*3\r\n$4\r\nmset\r\n$1\r\na\r\n$1\r\n1\r\n"),
+        [TxCmd::Other]
+    );
+    assert_eq!(
+        tx_cmds(b"*3\r\n$3\r\ndel\r\n$1\r\na\r\n$1\r\nb\r\n"),
+        [TxCmd::Unsupported, TxCmd::Unsupported]
+    );
+    assert_eq!(
+        tx_cmds(b"*2\r\n$4\r\nmget\r\n$1\r\na\r\n"),
+        [TxCmd::Unsupported]
+    );
+}
+
+/// 事务内的ping、unwatch与其他指令一样排队，exec时发送到后端执行
+#[test]
+fn test_keyless_in_multi() {
+    use std::sync::{Arc, Mutex};
+    pipeline_hook::run(async {
+        // 后端收到的事务内指令，exec时按顺序返回各自的响应
+        let queued = Arc::new(Mutex::new(Vec::new()));
+        let sent = queued.clone();
+        let addr = pipeline_hook::fake_redis(move |args| {
+            let cmd = String::from_utf8_lossy(&args[0]).to_lowercase();
+            let mut queued = sent.lock().expect("lock");
+            let rsp = match cmd.as_str() {
+                "multi" => "+OK\r\n".to_string(),
+                "exec" => {
+                    let rsps: Vec<&str> = queued
+                        .drain(..)
+                        .map(|c| if c == "ping" { "+PONG\r\n" } else { "+OK\r\n" })
+                        .collect();
+                    format!("*{}\r\n{}", rsps.len(), rsps.concat())
+                }
+                _ => {
+                    queued.push(cmd);
+                    "+QUEUED\r\n".to_string()
+                }
+            };
+            Some((std::time::Duration::ZERO, rsp.into_bytes()))
+        })
+        .await;
+        let cfg = format!(
+            "basic:\n  hash: crc32\n  distribution: modula\nbackends:\n  - {addr},{addr}\n"
+        );
+        let service = pipeline_hook::redis_service("tx_keyless", &cfg).await;
+        let mut client = service.connect().await;
+
+        let (multi, exec) = (cmd(&["multi"]), cmd(&["exec"]));
+        client.request(&multi, b"+OK\r\n").await;
+        client.request(&cmd(&["ping"]), b"+QUEUED\r\n").await;
+        client.request(&cmd(&["unwatch"]), b"+QUEUED\r\n").await;
+        client
+            .request(&cmd(&["set", "k", "v"]), b"+QUEUED\r\n")
+            .await;
+        client
+            .request(&exec, b"*3\r\n+PONG\r\n+OK\r\n+OK\r\n")
+            .await;
+
+        // 只有ping的事务
+        client.request(&multi, b"+OK\r\n").await;
+        client.request(&cmd(&["ping"]), b"+QUEUED\r\n").await;
+        client.request(&exec, b"*1\r\n+PONG\r\n").await;
+
+        // 事务外的ping本地响应
+        client.request(&cmd(&["ping"]), b"+PONG\r\n").await;
+    });
+}
+
+/// 独占连接上的响应超过后端超时时间未返回，释放连接并返回异常，之后的事务重新建连
+#[test]
+fn test_tx_timeout() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    pipeline_hook::run(async {
+        // 第一个exec不响应
+        let stalled = Arc::new(AtomicBool::new(false));
+        let addr = pipeline_hook::fake_redis(move |args| {
+            let cmd = String::from_utf8_lossy(&args[0]).to_lowercase();
+            let rsp: &[u8] = match cmd.as_str() {
+                "multi" => b"+OK\r\n",
+                "exec" if !stalled.swap(true, Ordering::Relaxed) => return None,
+                "exec" => b"*1\r\n+OK\r\n",
+                _ => b"+QUEUED\r\n",
+            };
+            Some((std::time::Duration::ZERO, rsp.to_vec()))
+        })
+        .await;
+        let cfg = format!(
+            "basic:\n  hash: crc32\n  distribution: modula\n  timeout_ms_master: 100\nbackends:\n  - {addr},{addr}\n"
+        );
+        let service = pipeline_hook::redis_service("tx_timeout", &cfg).await;
+        let mut client = service.connect().await;
+
+        let (multi, exec, set) = (cmd(&["multi"]), cmd(&["exec"]), cmd(&["set", "k", "v"]));
+        client.request(&multi, b"+OK\r\n").await;
+        client.request(&set, b"+QUEUED\r\n").await;
+        let start = std::time::Instant::now();
+        client
+            .request(&exec, b"-ERR transaction backend unavailable\r\n")
+            .await;
+        assert!(start.elapsed() >= std::time::Duration::from_millis(100));
+
+        client.request(&multi, b"+OK\r\n").await;
+        client.request(&set, b"+QUEUED\r\n").await;
+        client.request(&exec, b"*1\r\n+OK\r\n").await;
+    });
+}
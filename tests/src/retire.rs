@@ -0,0 +1,35 @@
+use metrics::{Path, tests::live_onlyfor_test as live};
+
+/// 服务下线后metric不再输出。下线尚未处理完成时重新上线的服务，其metric不受之前下线的影响
+#[test]
+fn unregister_then_readd() {
+    metrics::tests::start_register_onlyfor_test();
+    let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    rt.block_on(async {
+        let path = Path::new(vec!["retire_test", "ns_readd"]);
+        let mut num = path.num("conn");
+        metrics::flush().await;
+        assert!(num.check_registered());
+        assert_eq!(live(&path), 1);
+
+        metrics::unregister(&path);
+        metrics::flush().await;
+        assert_eq!(live(&path), 0);
+
+        // 下线后重新上线
+        let _num = path.num("conn");
+        metrics::flush().await;
+        assert_eq!(live(&path), 1);
+
+        // 重新上线时，之前的下线还未处理完成
+        metrics::unregister(&path);
+        let _num = path.num("conn");
+        metrics::flush().await;
+        assert_eq!(live(&path), 1);
+
+        // 其他path的下线不影响
+        metrics::unregister(&Path::new(vec!["retire_test", "ns_other"]));
+        metrics::flush().await;
+        assert_eq!(live(&path), 1);
+    });
+}
@@ -1,8 +1,12 @@
 use discovery::distance::Addr;
-use endpoint::{select::Distance, Endpoint};
+use endpoint::{
+    select::{Distance, Selector},
+    Endpoint,
+};
 struct TBackend {
     addr: String,
     available: bool,
+    cost: u64,
 }
 
 impl Addr for TBackend {
@@ -20,11 +24,22 @@ impl Endpoint for TBackend {
     fn send(&self, _req: Self::Item) {
         todo!()
     }
+    fn cost(&self) -> u64 {
+        self.cost
+    }
 }
 
 impl TBackend {
     fn new(addr: String, available: bool) -> Self {
-        Self { addr, available }
+        Self {
+            addr,
+            available,
+            cost: 0,
+        }
+    }
+    fn with_cost(mut self, cost: u64) -> Self {
+        self.cost = cost;
+        self
     }
 }
 
@@ -145,3 +160,98 @@ fn select_all_local_all_noava() {
     assert_eq!(shards.select_next_idx(0, 2), 1);
     assert_eq!(shards.select_next_idx(1, 3), 2);
 }
+
+//被异常检测摘除的副本不会被选到
+#[test]
+fn select_skip_ejected() {
+    metrics::tests::init_metrics_onlyfor_test();
+    let mut shards = Distance::new();
+    shards.update(
+        vec![
+            TBackend::new("127.0.0.1".to_string(), true),
+            TBackend::new("127.0.0.2".to_string(), true),
+            TBackend::new("127.0.0.3".to_string(), true),
+            TBackend::new("127.0.0.4".to_string(), true),
+        ],
+        4,
+        false,
+    );
+    for _ in 0..20 {
+        shards.outlier(1).record(ds::time::Instant::now(), false);
+    }
+    assert!(shards.outlier(1).ejected());
+    assert_eq!(shards.ejected().count(), 1);
+    for _ in 0..4096 {
+        assert_ne!(shards.select_idx(), 1);
+    }
+    assert_eq!(shards.select_next_idx(0, 1), 2);
+}
+
+//ewma：随机选两个副本，访问代价大的不会被选到，其他副本都能分到流量
+#[test]
+fn select_ewma() {
+    assert_eq!(Selector::from("ewma"), Selector::Ewma);
+    assert_eq!(Selector::from("timeslice"), Selector::ByDistance);
+    let shards = Distance::with_selector(
+        vec![
+            TBackend::new("127.0.0.1".to_string(), true).with_cost(10),
+            TBackend::new("127.0.0.2".to_string(), true).with_cost(20),
+            TBackend::new("127.0.0.3".to_string(), true).with_cost(1000),
+        ],
+        "ewma",
+        false,
+    );
+    assert!(shards.quota().is_none());
+    let mut hits = std::collections::HashMap::new();
+    for _ in 0..4096 {
+        let (_, b) = shards.unsafe_select();
+        *hits.entry(b.addr.clone()).or_insert(0) += 1;
+    }
+    assert_eq!(hits.get("127.0.0.3"), None);
+    // 代价最小的副本，与其他任一副本比较时都会被选中
+    assert!(hits["127.0.0.1"] > hits["127.0.0.2"]);
+}
+
+/// 耗时变大时立即生效，之后按时间衰减
+#[test]
+fn load_decay() {
+    use stream::load::Load;
+    let load = Load::default();
+    assert_eq!(load.cost(), 0);
+    load.rtt(10_000);
+    assert!(
+        load.cost() > 9_900 && load.cost() <= 10_000,
+        "{}",
+        load.cost()
+    );
+    // 耗时变小时，按距上次更新的时间逐步衰减，而不是立即变小
+    load.rtt(1_000);
+    assert!(load.cost() > 9_000, "{}", load.cost());
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    // 2秒的时间常数，200ms后约衰减到90%
+    let decayed = load.cost();
+    assert!(decayed > 5_000 && decayed < 9_700, "{}", decayed);
+    load.rtt(50_000);
+    assert!(load.cost() > 49_000, "{}", load.cost());
+    // 未完成的请求越多，代价越大
+    load.incr();
+    load.incr();
+    assert!(load.cost() > 2 * 49_000 * 3 / 2, "{}", load.cost());
+}
+
+/// 超时按超时时间计算耗时，只会调大ewma；没有耗时数据但有请求在等待时，代价很大
+#[test]
+fn load_penalize() {
+    use stream::load::Load;
+    let load = Load::default();
+    load.incr();
+    assert!(load.cost() > 1 << 40, "{}", load.cost());
+    load.decr();
+    assert_eq!(load.cost(), 0);
+
+    load.rtt(1_000);
+    load.penalize(500_000);
+    assert!(load.cost() > 490_000, "{}", load.cost());
+    load.penalize(1_000);
+    assert!(load.cost() > 490_000, "{}", load.cost());
+}
@@ -0,0 +1,32 @@
+use std::task::{Context, Poll, Waker};
+
+/// 进入退出流程后，已创建和新创建的Drain都能感知到，drop后连接数归零。
+/// 使用独立的实例，不影响进程内其他测试的连接
+#[test]
+fn drain_on_shutdown() {
+    static SHUTDOWN: rt::Shutdown = rt::Shutdown::new();
+    let mut cx = Context::from_waker(Waker::noop());
+
+    let mut before = SHUTDOWN.drain();
+    assert_eq!(SHUTDOWN.conns(), 1);
+    assert!(!SHUTDOWN.shutting_down());
+    assert_eq!(before.poll_shutdown(&mut cx), Poll::Pending);
+
+    // 等待中的任务被唤醒
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let waiting = runtime.spawn(SHUTDOWN.wait());
+    SHUTDOWN.shutdown();
+    assert!(SHUTDOWN.shutting_down());
+    assert!(!rt::shutting_down());
+    assert_eq!(before.poll_shutdown(&mut cx), Poll::Ready(()));
+    let mut after = SHUTDOWN.drain();
+    assert_eq!(after.poll_shutdown(&mut cx), Poll::Ready(()));
+    assert_eq!(SHUTDOWN.conns(), 2);
+
+    runtime.block_on(waiting).unwrap();
+    runtime.block_on(SHUTDOWN.wait());
+
+    drop(before);
+    drop(after);
+    assert_eq!(SHUTDOWN.conns(), 0);
+}
@@ -0,0 +1,140 @@
+use net::tls::{Acceptor, TlsOption};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+// 测试证书：ca签发的localhost、127.0.0.1的证书
+const CA: &str = "tls_certs/ca.pem";
+const CERT: &str = "tls_certs/server.pem";
+const KEY: &str = "tls_certs/server.key";
+
+fn option(sni: &str) -> TlsOption {
+    TlsOption {
+        ca: CA.to_string(),
+        sni: sni.to_string(),
+        ..Default::default()
+    }
+}
+
+/// 侦听端终结TLS，后端连接按ca校验证书，sni为空时使用地址中的host
+#[test]
+fn tls_echo() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let l = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = l.local_addr().unwrap().to_string();
+        let acceptor = Acceptor::new(CERT, KEY).unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (s, _) = l.accept().await.unwrap();
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let Ok(mut s) = acceptor.accept(net::Stream::Tcp(s)).await else {
+                        return;
+                    };
+                    assert!(matches!(s, net::Stream::Tls(_)));
+                    let mut buf = [0u8; 4];
+                    s.read_exact(&mut buf).await.unwrap();
+                    s.write_all(&buf).await.unwrap();
+                });
+            }
+        });
+
+        for sni in ["", "localhost"] {
+            let mut s = net::tls::connect(&addr, Some(&option(sni))).await.unwrap();
+            assert!(matches!(s, net::Stream::Tls(_)));
+            s.write_all(b"ping").await.unwrap();
+            let mut buf = [0u8; 4];
+            s.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+        }
+
+        // 证书中没有的名字，校验失败
+        assert!(
+            net::tls::connect(&addr, Some(&option("breeze.invalid")))
+                .await
+                .is_err()
+        );
+        // ca不存在
+        let mut o = option("");
+        o.ca = "tls_certs/not_exists.pem".to_string();
+        assert!(net::tls::connect(&addr, Some(&o)).await.is_err());
+        // 不配置tls时是明文连接
+        let s = net::tls::connect(&addr, None).await.unwrap();
+        assert!(matches!(s, net::Stream::Tcp(_)));
+    });
+}
+
+#[test]
+fn tls_acceptor_invalid() {
+    assert!(Acceptor::new(CERT, "tls_certs/not_exists.key").is_err());
+    // 私钥文件中没有key
+    assert!(Acceptor::new(CERT, CERT).is_err());
+}
+
+// mysql greeting：protocol 10，server version，connection id，auth data，filler，caps低16位，charset，status，caps高16位
+fn greeting(caps: u32) -> Vec<u8> {
+    let mut payload = vec![10u8];
+    payload.extend_from_slice(b"8.0.0\0");
+    payload.extend_from_slice(&[1, 0, 0, 0]);
+    payload.extend_from_slice(&[b'a'; 8]);
+    payload.push(0);
+    payload.extend_from_slice(&(caps as u16).to_le_bytes());
+    payload.extend_from_slice(&[45, 2, 0]);
+    payload.extend_from_slice(&((caps >> 16) as u16).to_le_bytes());
+    let mut packet = (payload.len() as u32).to_le_bytes().to_vec();
+    packet[3] = 0;
+    packet.extend_from_slice(&payload);
+    packet
+}
+
+/// mysql在协议握手中通过SSLRequest协商TLS，greeting按seq 1交给上层，之后的数据走TLS
+#[test]
+fn tls_mysql_ssl_request() {
+    const SSL: u32 = 0x800;
+    const CAPS: u32 = 0x0000_8200 | SSL;
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let l = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = l.local_addr().unwrap().to_string();
+        let acceptor = Acceptor::new(CERT, KEY).unwrap();
+        tokio::spawn(async move {
+            for caps in [CAPS, CAPS & !SSL] {
+                let (mut s, _) = l.accept().await.unwrap();
+                s.write_all(&greeting(caps)).await.unwrap();
+                if caps & SSL == 0 {
+                    continue;
+                }
+                let mut req = [0u8; 36];
+                s.read_exact(&mut req).await.unwrap();
+                assert_eq!(&req[..4], &[32, 0, 0, 1]);
+                let flags = u32::from_le_bytes([req[4], req[5], req[6], req[7]]);
+                assert_ne!(flags & SSL, 0);
+                let mut s = acceptor.accept(net::Stream::Tcp(s)).await.unwrap();
+                let mut buf = [0u8; 4];
+                s.read_exact(&mut buf).await.unwrap();
+                s.write_all(&buf).await.unwrap();
+            }
+        });
+
+        let mut s = net::tls::connect_mysql(&addr, Some(&option("")))
+            .await
+            .unwrap();
+        assert!(matches!(s, net::Stream::Tls(_)));
+        let expected = greeting(CAPS);
+        let mut got = vec![0u8; expected.len()];
+        s.read_exact(&mut got).await.unwrap();
+        assert_eq!(got[3], 1);
+        assert_eq!(&got[4..], &expected[4..]);
+        s.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        s.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+
+        // 后端不支持ssl
+        assert!(
+            net::tls::connect_mysql(&addr, Some(&option("")))
+                .await
+                .is_err()
+        );
+    });
+}
@@ -0,0 +1,49 @@
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::os::unix::net::UnixStream;
+
+use net::upgrade::Upgrade;
+
+/// 旧进程把侦听的fd交给新进程后，新进程直接使用，旧的listener关闭后仍可以accept。
+/// 新旧进程使用独立的实例，不受进程内其他侦听的影响
+#[test]
+fn handover_listeners() {
+    let (old, new) = (Upgrade::new(), Upgrade::new());
+    let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = l.local_addr().unwrap();
+    old.register("tcp@127.0.0.1:0".to_string(), l.as_raw_fd());
+    assert!(!new.inherited());
+
+    std::thread::scope(|s| {
+        let (a, b) = UnixStream::pair().unwrap();
+        let handover = s.spawn(|| old.handover(a));
+        assert_eq!(new.inherit(b).unwrap(), 1);
+        assert_eq!(handover.join().unwrap().unwrap(), 1);
+    });
+    assert!(new.inherited());
+    old.unregister(l.as_raw_fd());
+    drop(l);
+
+    assert!(new.take("tcp@127.0.0.1:1").is_none());
+    let fd = new.take("tcp@127.0.0.1:0").unwrap();
+    let l = std::net::TcpListener::from(fd);
+    assert_eq!(l.local_addr().unwrap(), addr);
+    let _client = std::net::TcpStream::connect(addr).unwrap();
+    assert!(l.accept().is_ok());
+}
+
+/// 没有发送升级请求的连接（如探测控制socket是否在使用）不会触发交接
+#[test]
+fn handover_requires_request() {
+    let old = Upgrade::new();
+    let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    old.register("tcp@127.0.0.1:0".to_string(), l.as_raw_fd());
+
+    let (a, b) = UnixStream::pair().unwrap();
+    drop(b);
+    assert!(old.handover(a).is_err());
+
+    let (a, mut b) = UnixStream::pair().unwrap();
+    b.write_all(b"upgrad\n!").unwrap();
+    assert!(old.handover(a).is_err());
+}